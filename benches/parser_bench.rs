@@ -0,0 +1,95 @@
+//! Parser throughput on large generated documents
+//!
+//! Run with `cargo bench`. Each case is parsed repeatedly for roughly
+//! `TARGET_DURATION` and reported as time per parse and MB/s.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use wtf::parser::MarkdownParser;
+
+// Benchmark parameters
+const TARGET_SIZE: usize = 512 * 1024;
+const TARGET_DURATION: Duration = Duration::from_secs(2);
+const WARMUP_ITERATIONS: usize = 3;
+const BYTES_PER_MB: f64 = 1024.0 * 1024.0;
+
+fn main() {
+    let cases = [
+        ("prose", prose_document()),
+        ("inline-heavy", inline_heavy_document()),
+        ("reference (many sections)", reference_document()),
+        ("escapes", escaped_document()),
+    ];
+
+    println!(
+        "{:<28} {:>10} {:>12} {:>10}",
+        "document", "size", "per parse", "MB/s"
+    );
+    for (name, source) in &cases {
+        let per_parse = time_parse(source);
+        let throughput = source.len() as f64 / BYTES_PER_MB / per_parse.as_secs_f64();
+        println!(
+            "{:<28} {:>8}KB {:>10.2}ms {:>10.1}",
+            name,
+            source.len() / 1024,
+            per_parse.as_secs_f64() * 1000.0,
+            throughput
+        );
+    }
+}
+
+/// Average wall time of one parse of `source`
+fn time_parse(source: &str) -> Duration {
+    for _ in 0..WARMUP_ITERATIONS {
+        black_box(MarkdownParser::parse(black_box(source)).unwrap());
+    }
+
+    let start = Instant::now();
+    let mut iterations = 0u32;
+    while start.elapsed() < TARGET_DURATION {
+        black_box(MarkdownParser::parse(black_box(source)).unwrap());
+        iterations += 1;
+    }
+    start.elapsed() / iterations
+}
+
+/// Repeats `chunk` (with a counter substituted for `{}`) up to TARGET_SIZE
+fn repeat_to_size(chunk: &str) -> String {
+    let mut output = String::with_capacity(TARGET_SIZE + chunk.len() * 2);
+    let mut index = 0;
+    while output.len() < TARGET_SIZE {
+        output.push_str(&chunk.replace("{}", &index.to_string()));
+        index += 1;
+    }
+    output
+}
+
+/// Long paragraphs of plain text under a handful of headings
+fn prose_document() -> String {
+    let paragraph = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do \
+                     eiusmod tempor incididunt ut labore et dolore magna aliqua. Ut enim ad \
+                     minim veniam, quis nostrud exercitation ullamco laboris nisi ut aliquip.\n\
+                     Duis aute irure dolor in reprehenderit in voluptate velit esse cillum \
+                     dolore eu fugiat nulla pariatur.\n\n";
+    repeat_to_size(&format!("# Chapter {{}}\n\n{}", paragraph.repeat(20)))
+}
+
+/// Dense emphasis and links, as in generated reference tables
+fn inline_heavy_document() -> String {
+    repeat_to_size(
+        "**field_{}** *(optional)* see [the docs](/reference/{}) and **[nested *link*](#{})**\n",
+    )
+}
+
+/// Deeply nested sections with short bodies and images
+fn reference_document() -> String {
+    repeat_to_size(
+        "# Module {}\n\nOverview.\n\n## Function {}\n\nReturns **a value**.\n\n\
+         ### Example {}\n\n![diagram {}](/img/{}.png)\n\n#### Notes\n\nSee [above](#{}).\n\n",
+    )
+}
+
+/// Text full of backslash escapes (forces owned text nodes)
+fn escaped_document() -> String {
+    repeat_to_size("Use \\*literal\\* stars and \\[brackets\\] in line {}.\n")
+}
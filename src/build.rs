@@ -0,0 +1,557 @@
+use crate::build_cache::{BuildCache, CachedPage, content_hash, file_hash};
+use crate::layout::is_layout_file;
+use crate::router::generate_sitemap_footer;
+use crate::server::{Server, ServerConfig, ServerError};
+use crate::variables::SITE_CONFIG_FILENAME;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Output layout
+const INDEX_FILENAME: &str = "index.html";
+const NOT_FOUND_OUTPUT: &str = "404.html";
+
+// Content tree
+const MD_EXTENSION: &str = "md";
+const NOTFOUND_FILENAME: &str = "404.md";
+const HIDDEN_PREFIX: char = '.';
+const PATH_SEPARATOR: char = '/';
+const ROOT_URL_PATH: &str = "/";
+
+// ============================================================================
+// ERROR HANDLING
+// ============================================================================
+
+#[derive(Debug)]
+pub enum BuildError {
+    /// The server couldn't be set up (content root or emoji map)
+    ServerError(ServerError),
+    /// A route failed to render, as a 500 would when served
+    RenderError { route: String, source: ServerError },
+    /// Reading the content tree or writing the output failed
+    IoError { path: PathBuf, source: String },
+    /// The output directory can't hold the site
+    InvalidOutput { path: PathBuf, reason: String },
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::ServerError(error) => write!(f, "{}", error),
+            BuildError::RenderError { route, source } => {
+                write!(f, "Error rendering {}: {}", route, source)
+            }
+            BuildError::IoError { path, source } => {
+                write!(f, "IO error on {}: {}", path.display(), source)
+            }
+            BuildError::InvalidOutput { path, reason } => {
+                write!(f, "Invalid output directory {}: {}", path.display(), reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+impl From<ServerError> for BuildError {
+    fn from(error: ServerError) -> Self {
+        BuildError::ServerError(error)
+    }
+}
+
+// ============================================================================
+// SITE BUILDER
+// ============================================================================
+
+/// What a build wrote
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BuildSummary {
+    /// Routes rendered to `index.html` files
+    pub pages: usize,
+    /// `404.html` error pages
+    pub not_found_pages: usize,
+    /// Static files copied as-is
+    pub assets: usize,
+    /// Pages and error pages left as the last build wrote them, as nothing
+    /// they're rendered from changed
+    pub unchanged: usize,
+}
+
+/// Renders a site into a directory any static file server can host
+///
+/// Every route the router resolves is requested from a `Server` and its
+/// response written to `<route>/index.html`, so built pages are exactly
+/// what `wtf serve` sends, sitemap footer included. This covers pages the
+/// sitemap doesn't list, such as those in a directory without an index
+/// page.
+///
+/// Error pages go to `404.html`: one at the top, and one in each directory
+/// with its own `404.md`. Each is the server's response to a missing page
+/// in that directory; deeper directories fall back to the nearest
+/// ancestor's, as `Router::resolve_404` does.
+///
+/// Every other file (images, stylesheets, ...) is copied unchanged, except
+/// `_site.conf`, layouts and hidden files and directories. Existing files in the
+/// output directory are overwritten but never deleted.
+///
+/// Builds are incremental: a cache in the output directory records the
+/// content hash of every file each page was rendered from (its markdown,
+/// includes, sized images and layout). Pages whose files are all unchanged
+/// aren't rendered again, unless something every page depends on changed:
+/// the set of routes, assets and layouts, the sitemap, `_site.conf` or the
+/// emoji settings. `with_force` ignores the cache.
+///
+/// # Example
+/// ```no_run
+/// use wtf::build::SiteBuilder;
+/// use wtf::server::ServerConfig;
+/// use std::path::Path;
+///
+/// let builder = SiteBuilder::new(ServerConfig::new("content".into()))?;
+/// let summary = builder.build(Path::new("public"))?;
+/// println!("{} pages", summary.pages);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct SiteBuilder {
+    server: Server,
+    config: ServerConfig,
+    force: bool,
+}
+
+/// The files of a content tree, sorted into what a build does with them
+#[derive(Default)]
+struct SiteFiles {
+    /// Routes to render
+    routes: Vec<String>,
+    /// URL paths of directories with their own error page (`""` for the top)
+    not_found_dirs: Vec<String>,
+    /// Files to copy, with their path relative to the content root
+    assets: Vec<(PathBuf, PathBuf)>,
+    /// File names of the layouts in the content root
+    layouts: Vec<String>,
+}
+
+impl SiteBuilder {
+    /// Creates a builder for the content tree a server config describes
+    ///
+    /// # Returns
+    /// * `Ok(SiteBuilder)` - Ready to build
+    /// * `Err(BuildError)` - If the content root or emoji map is invalid
+    pub fn new(config: ServerConfig) -> Result<Self, BuildError> {
+        Ok(Self {
+            server: Server::new(config.clone())?,
+            config,
+            force: false,
+        })
+    }
+
+    /// Renders every page, even those the build cache says are up to date
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Builds the site into a directory, creating it if needed
+    ///
+    /// # Arguments
+    /// * `output` - Directory to write the site to; may be inside the
+    ///   content root, which is then skipped when scanning
+    ///
+    /// # Returns
+    /// * `Ok(BuildSummary)` - Counts of what was written
+    /// * `Err(BuildError)` - If a page fails to render or a file can't be
+    ///   read or written
+    pub fn build(&self, output: &Path) -> Result<BuildSummary, BuildError> {
+        fs::create_dir_all(output).map_err(|e| io_error(output, e))?;
+        let output = output.canonicalize().map_err(|e| io_error(output, e))?;
+        let content_root = self.server.router().content_root();
+        if output == content_root {
+            return Err(BuildError::InvalidOutput {
+                path: output,
+                reason: "it is the content root".to_string(),
+            });
+        }
+
+        let mut site = SiteFiles {
+            not_found_dirs: vec![String::new()],
+            ..SiteFiles::default()
+        };
+        self.scan(content_root, "", &output, &mut site)?;
+
+        // Drop the cache before writing anything: if this build fails, the
+        // next one mustn't trust records of pages it may have overwritten
+        let site_hash = self.site_hash(&site)?;
+        let previous = if self.force {
+            None
+        } else {
+            BuildCache::load(&output).filter(|cache| cache.site == site_hash)
+        };
+        BuildCache::remove(&output).map_err(|e| io_error(&output, e))?;
+
+        for (source, relative) in &site.assets {
+            let target = output.join(relative);
+            if !self.force && is_copy_current(source, &target) {
+                continue;
+            }
+            create_parent(&target)?;
+            fs::copy(source, &target).map_err(|e| io_error(source, e))?;
+        }
+
+        let not_found_pages = site.not_found_dirs.iter().map(|dir| {
+            let url_path = format!("{}{}{}", dir, PATH_SEPARATOR, NOT_FOUND_OUTPUT);
+            let target = PathBuf::from(url_path.trim_start_matches(PATH_SEPARATOR));
+            (url_path, target)
+        });
+        let pages = site
+            .routes
+            .iter()
+            .map(|route| (route.clone(), route_output(route)))
+            .chain(not_found_pages);
+
+        let mut cache = BuildCache::new(site_hash);
+        let mut unchanged = 0;
+        for (url_path, target) in pages {
+            let target = output.join(target);
+            if let Some(page) = previous.as_ref().and_then(|cache| cache.get(&url_path))
+                && target.is_file()
+                && page.is_fresh()
+            {
+                cache.insert(&url_path, page.clone());
+                unchanged += 1;
+                continue;
+            }
+
+            let page = self.server.render_request(&url_path).map_err(|source| {
+                BuildError::RenderError {
+                    route: url_path.clone(),
+                    source,
+                }
+            })?;
+            write_file(&target, &page.html)?;
+
+            let files = page.source.iter().chain(&page.dependencies);
+            if let Some(record) = CachedPage::record(files.map(PathBuf::as_path)) {
+                cache.insert(&url_path, record);
+            }
+        }
+        cache.write(&output).map_err(|e| io_error(&output, e))?;
+
+        Ok(BuildSummary {
+            pages: site.routes.len(),
+            not_found_pages: site.not_found_dirs.len(),
+            assets: site.assets.len(),
+            unchanged,
+        })
+    }
+
+    /// Hashes everything every page depends on, so that a change to any of
+    /// it invalidates the whole build cache
+    fn site_hash(&self, site: &SiteFiles) -> Result<u64, BuildError> {
+        let sitemap = self
+            .server
+            .router()
+            .build_sitemap()
+            .map_err(ServerError::from)?;
+        let content_root = self.server.router().content_root();
+
+        let mut inputs = vec![
+            env!("CARGO_PKG_VERSION").to_string(),
+            site.routes.join("\n"),
+            site.not_found_dirs.join("\n"),
+            // An image appearing can change the size of a page's <img>
+            site.assets
+                .iter()
+                .map(|(_, relative)| relative.display().to_string())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            // A page whose layout was missing used the built-in theme
+            site.layouts.join("\n"),
+            generate_sitemap_footer(&sitemap, None),
+            self.config.emoji.to_string(),
+        ];
+        let shared_files = [
+            Some(content_root.join(SITE_CONFIG_FILENAME)),
+            self.config.emoji_map.clone(),
+        ];
+        for file in shared_files.into_iter().flatten() {
+            inputs.push(format!("{:?}", file_hash(&file)));
+        }
+
+        Ok(content_hash(inputs.join("\0").as_bytes()))
+    }
+
+    /// Sorts the files under a directory into routes, error pages and
+    /// assets, in name order
+    ///
+    /// # Arguments
+    /// * `dir` - Directory to scan
+    /// * `url_prefix` - URL path of the directory (`""` for the content root)
+    /// * `output` - Build output directory, skipped if found
+    /// * `site` - Where to record what was found
+    fn scan(
+        &self,
+        dir: &Path,
+        url_prefix: &str,
+        output: &Path,
+        site: &mut SiteFiles,
+    ) -> Result<(), BuildError> {
+        let mut entries = fs::read_dir(dir)
+            .and_then(|entries| entries.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| io_error(dir, e))?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        let content_root = self.server.router().content_root();
+        for entry in entries {
+            let path = entry.path();
+            let file_name = entry.file_name();
+            let name = file_name.to_string_lossy();
+            if name.starts_with(HIDDEN_PREFIX) {
+                continue;
+            }
+
+            if path.is_dir() {
+                if path
+                    .canonicalize()
+                    .is_ok_and(|canonical| canonical == output)
+                {
+                    continue;
+                }
+                let sub_prefix = format!("{}{}{}", url_prefix, PATH_SEPARATOR, name);
+                self.scan(&path, &sub_prefix, output, site)?;
+            } else if path.extension().is_some_and(|ext| ext == MD_EXTENSION) {
+                // The top-level error page is always built
+                if name == NOTFOUND_FILENAME && !url_prefix.is_empty() {
+                    site.not_found_dirs.push(url_prefix.to_string());
+                }
+                // Partials and shadowed files have no route
+                if let Some(route) = self.server.router().route_for_file(&path) {
+                    site.routes.push(route);
+                }
+            } else if let Ok(relative) = path.strip_prefix(content_root) {
+                if relative == Path::new(SITE_CONFIG_FILENAME) {
+                    continue;
+                }
+                if url_prefix.is_empty() && is_layout_file(&name) {
+                    site.layouts.push(name.into_owned());
+                    continue;
+                }
+                site.assets.push((path.clone(), relative.to_path_buf()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether a copied asset still matches its source: same size, and not
+/// older (copying sets the copy's modification time)
+fn is_copy_current(source: &Path, target: &Path) -> bool {
+    let (Ok(source), Ok(target)) = (fs::metadata(source), fs::metadata(target)) else {
+        return false;
+    };
+    match (source.modified(), target.modified()) {
+        (Ok(source_modified), Ok(target_modified)) => {
+            source.len() == target.len() && target_modified >= source_modified
+        }
+        _ => false,
+    }
+}
+
+/// The file a route is written to, relative to the output directory
+fn route_output(route: &str) -> PathBuf {
+    if route == ROOT_URL_PATH {
+        PathBuf::from(INDEX_FILENAME)
+    } else {
+        Path::new(route.trim_start_matches(PATH_SEPARATOR)).join(INDEX_FILENAME)
+    }
+}
+
+fn write_file(path: &Path, contents: &[u8]) -> Result<(), BuildError> {
+    create_parent(path)?;
+    fs::write(path, contents).map_err(|e| io_error(path, e))
+}
+
+fn create_parent(path: &Path) -> Result<(), BuildError> {
+    match path.parent() {
+        Some(parent) => fs::create_dir_all(parent).map_err(|e| io_error(parent, e)),
+        None => Ok(()),
+    }
+}
+
+fn io_error(path: &Path, error: io::Error) -> BuildError {
+    BuildError::IoError {
+        path: path.to_path_buf(),
+        source: error.to_string(),
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_site() -> tempfile::TempDir {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::write(
+            root.join("root.md"),
+            "# Welcome\n\nSee the [guide](guide/guide.md).",
+        )
+        .unwrap();
+        fs::write(root.join("_site.conf"), "name: Handbook\n").unwrap();
+        fs::write(root.join("logo.png"), [0x89, b'P', b'N', b'G']).unwrap();
+        fs::create_dir(root.join("guide")).unwrap();
+        fs::write(root.join("guide/guide.md"), "# Guide").unwrap();
+        fs::write(root.join("guide/intro.md"), "# Intro").unwrap();
+        fs::write(root.join("guide/404.md"), "# Not in the guide").unwrap();
+        // No index page, so the sitemap leaves these out
+        fs::create_dir(root.join("notes")).unwrap();
+        fs::write(root.join("notes/todo.md"), "# Todo").unwrap();
+        fs::create_dir(root.join(".git")).unwrap();
+        fs::write(root.join(".git/HEAD"), "ref").unwrap();
+        temp_dir
+    }
+
+    fn served(root: &Path, url_path: &str) -> Vec<u8> {
+        let server = Server::new(ServerConfig::new(root.to_path_buf())).unwrap();
+        server.handle_request(url_path).unwrap().1
+    }
+
+    #[test]
+    fn test_route_output() {
+        assert_eq!(route_output("/"), PathBuf::from("index.html"));
+        assert_eq!(
+            route_output("/guide/intro"),
+            PathBuf::from("guide/intro/index.html")
+        );
+    }
+
+    #[test]
+    fn test_build_matches_server() {
+        let site = create_site();
+        let root = site.path();
+        let output = tempfile::tempdir().unwrap();
+
+        let builder = SiteBuilder::new(ServerConfig::new(root.to_path_buf())).unwrap();
+        let summary = builder.build(output.path()).unwrap();
+        assert_eq!(
+            summary,
+            BuildSummary {
+                // /, /guide, /guide/404, /guide/intro, /notes/todo
+                pages: 5,
+                not_found_pages: 2,
+                assets: 1,
+                unchanged: 0,
+            }
+        );
+
+        let out = output.path();
+        for (file, url_path) in [
+            ("index.html", "/"),
+            ("guide/index.html", "/guide"),
+            ("guide/intro/index.html", "/guide/intro"),
+            ("notes/todo/index.html", "/notes/todo"),
+            ("404.html", "/missing"),
+            ("guide/404.html", "/guide/missing"),
+        ] {
+            assert_eq!(fs::read(out.join(file)).unwrap(), served(root, url_path));
+        }
+
+        assert_eq!(fs::read(out.join("logo.png")).unwrap(), b"\x89PNG");
+        assert!(!out.join("_site.conf").exists());
+        assert!(!out.join(".git").exists());
+    }
+
+    #[test]
+    fn test_build_links_nested_page_images_from_root() {
+        let site = create_site();
+        let root = site.path();
+        fs::write(root.join("guide/pic.png"), "png").unwrap();
+        fs::write(
+            root.join("guide/intro.md"),
+            "# Intro\n\n![Diagram](pic.png)",
+        )
+        .unwrap();
+        let output = tempfile::tempdir().unwrap();
+
+        let builder = SiteBuilder::new(ServerConfig::new(root.to_path_buf())).unwrap();
+        builder.build(output.path()).unwrap();
+
+        // Written to guide/intro/index.html, where `pic.png` would miss
+        let intro = fs::read_to_string(output.path().join("guide/intro/index.html")).unwrap();
+        assert!(intro.contains("<img src=\"/guide/pic.png\" alt=\"Diagram\""));
+        assert!(output.path().join("guide/pic.png").is_file());
+    }
+
+    #[test]
+    fn test_build_skips_output_inside_content_root() {
+        let site = create_site();
+        let root = site.path();
+        let output = root.join("public");
+
+        let builder = SiteBuilder::new(ServerConfig::new(root.to_path_buf())).unwrap();
+        builder.build(&output).unwrap();
+        // A second build doesn't pick up the first one's files
+        let summary = builder.build(&output).unwrap();
+        assert_eq!(summary.assets, 1);
+        assert!(!output.join("public").exists());
+
+        let result = builder.build(root);
+        assert!(matches!(result, Err(BuildError::InvalidOutput { .. })));
+    }
+
+    #[test]
+    fn test_incremental_build_renders_only_changed_pages() {
+        let site = create_site();
+        let root = site.path();
+        fs::create_dir(root.join("shared")).unwrap();
+        fs::write(root.join("shared/note.md"), "Old note").unwrap();
+        fs::write(
+            root.join("guide/intro.md"),
+            "# Intro\n\n{{include \"shared/note.md\"}}",
+        )
+        .unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let out = output.path();
+
+        let builder = SiteBuilder::new(ServerConfig::new(root.to_path_buf())).unwrap();
+        assert_eq!(builder.build(out).unwrap().unchanged, 0);
+        // 6 pages (shared/note is a route too) and 2 error pages
+        assert_eq!(builder.build(out).unwrap().unchanged, 8);
+
+        // Only the page including the note depends on it
+        fs::write(root.join("shared/note.md"), "New note").unwrap();
+        assert_eq!(builder.build(out).unwrap().unchanged, 6);
+        let intro = fs::read(out.join("guide/intro/index.html")).unwrap();
+        assert!(String::from_utf8(intro).unwrap().contains("New note"));
+        assert_eq!(
+            fs::read(out.join("guide/intro/index.html")).unwrap(),
+            served(root, "/guide/intro")
+        );
+
+        // A deleted output is rendered again
+        fs::remove_file(out.join("index.html")).unwrap();
+        assert_eq!(builder.build(out).unwrap().unchanged, 7);
+
+        // A new page changes every sitemap footer
+        fs::write(root.join("guide/faq.md"), "# FAQ").unwrap();
+        assert_eq!(builder.build(out).unwrap().unchanged, 0);
+        assert_eq!(fs::read(out.join("index.html")).unwrap(), served(root, "/"));
+
+        // Every page is in the site layout, which isn't published
+        fs::write(root.join("_layout.html"), "<main>{{content}}</main>").unwrap();
+        assert_eq!(builder.build(out).unwrap().unchanged, 0);
+        fs::write(root.join("_layout.html"), "<div>{{content}}</div>").unwrap();
+        assert_eq!(builder.build(out).unwrap().unchanged, 0);
+        assert_eq!(fs::read(out.join("index.html")).unwrap(), served(root, "/"));
+        assert!(served(root, "/").starts_with(b"<div><h1>Welcome</h1>"));
+        assert!(!out.join("_layout.html").exists());
+
+        let builder = builder.with_force(true);
+        assert_eq!(builder.build(out).unwrap().unchanged, 0);
+    }
+}
@@ -0,0 +1,243 @@
+use crate::variables::format_date;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Cache file
+const CACHE_FILENAME: &str = ".wtf-build-cache";
+const CACHE_HEADER: &str = "wtf-build-cache 1";
+const SITE_KEYWORD: &str = "site";
+const PAGE_KEYWORD: &str = "page";
+const UPDATED_KEYWORD: &str = "updated";
+const FILE_KEYWORD: &str = "file";
+
+// FNV-1a, 64-bit
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// What a page was last rendered from
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CachedPage {
+    /// Modification date of the first file, the page's own markdown (for
+    /// `page.updated`)
+    pub(crate) updated: Option<String>,
+    /// Every file read, with its content hash
+    pub(crate) files: Vec<(PathBuf, u64)>,
+}
+
+impl CachedPage {
+    /// Records the current state of the files a page was rendered from
+    ///
+    /// # Arguments
+    /// * `files` - The page's markdown file first, then its other
+    ///   dependencies
+    ///
+    /// # Returns
+    /// * `None` - If a file can no longer be read, so the record would be
+    ///   wrong
+    pub(crate) fn record<'a>(files: impl IntoIterator<Item = &'a Path>) -> Option<Self> {
+        let files = files
+            .into_iter()
+            .map(|file| Some((file.to_path_buf(), file_hash(file)?)))
+            .collect::<Option<Vec<_>>>()?;
+        let updated = files.first().and_then(|(file, _)| modified_date(file));
+        Some(Self { updated, files })
+    }
+
+    /// Whether every file is unchanged since the page was recorded
+    pub(crate) fn is_fresh(&self) -> bool {
+        let updated = self.files.first().and_then(|(file, _)| modified_date(file));
+        updated == self.updated
+            && self
+                .files
+                .iter()
+                .all(|(file, hash)| file_hash(file) == Some(*hash))
+    }
+}
+
+/// Records what each page of a static build was rendered from
+///
+/// Kept in the output directory as a line-based text file. `site` is a
+/// hash of everything every page depends on (the sitemap, site config,
+/// ...); a cache recorded with another one is stale as a whole.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct BuildCache {
+    pub(crate) site: u64,
+    pages: HashMap<String, CachedPage>,
+}
+
+impl BuildCache {
+    pub(crate) fn new(site: u64) -> Self {
+        Self {
+            site,
+            pages: HashMap::new(),
+        }
+    }
+
+    /// Reads the cache a previous build left in an output directory
+    ///
+    /// # Returns
+    /// * `None` - If there's no cache, or it can't be read or parsed
+    pub(crate) fn load(output: &Path) -> Option<Self> {
+        let text = fs::read_to_string(output.join(CACHE_FILENAME)).ok()?;
+        Self::parse(&text)
+    }
+
+    /// Deletes the cache from an output directory, if there is one
+    pub(crate) fn remove(output: &Path) -> io::Result<()> {
+        match fs::remove_file(output.join(CACHE_FILENAME)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes the cache into an output directory
+    pub(crate) fn write(&self, output: &Path) -> io::Result<()> {
+        fs::write(output.join(CACHE_FILENAME), self.to_text())
+    }
+
+    pub(crate) fn get(&self, url_path: &str) -> Option<&CachedPage> {
+        self.pages.get(url_path)
+    }
+
+    pub(crate) fn insert(&mut self, url_path: &str, page: CachedPage) {
+        self.pages.insert(url_path.to_string(), page);
+    }
+
+    fn to_text(&self) -> String {
+        let mut url_paths: Vec<&String> = self.pages.keys().collect();
+        url_paths.sort();
+
+        let mut text = format!("{}\n{} {:016x}\n", CACHE_HEADER, SITE_KEYWORD, self.site);
+        for url_path in url_paths {
+            let page = &self.pages[url_path];
+            // Paths come last on their lines, so they may contain spaces
+            text.push_str(&format!("{} {}\n", PAGE_KEYWORD, url_path));
+            if let Some(updated) = &page.updated {
+                text.push_str(&format!("{} {}\n", UPDATED_KEYWORD, updated));
+            }
+            for (file, hash) in &page.files {
+                text.push_str(&format!(
+                    "{} {:016x} {}\n",
+                    FILE_KEYWORD,
+                    hash,
+                    file.display()
+                ));
+            }
+        }
+        text
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        let mut lines = text.lines();
+        if lines.next()? != CACHE_HEADER {
+            return None;
+        }
+        let site = lines.next()?.strip_prefix(SITE_KEYWORD)?.trim_start();
+        let mut cache = Self::new(u64::from_str_radix(site, 16).ok()?);
+
+        let mut current: Option<(String, CachedPage)> = None;
+        for line in lines {
+            let (keyword, rest) = line.split_once(' ')?;
+            match keyword {
+                PAGE_KEYWORD => {
+                    if let Some((url_path, page)) = current.take() {
+                        cache.insert(&url_path, page);
+                    }
+                    let page = CachedPage {
+                        updated: None,
+                        files: Vec::new(),
+                    };
+                    current = Some((rest.to_string(), page));
+                }
+                UPDATED_KEYWORD => current.as_mut()?.1.updated = Some(rest.to_string()),
+                FILE_KEYWORD => {
+                    let (hash, file) = rest.split_once(' ')?;
+                    let hash = u64::from_str_radix(hash, 16).ok()?;
+                    current.as_mut()?.1.files.push((PathBuf::from(file), hash));
+                }
+                _ => return None,
+            }
+        }
+        if let Some((url_path, page)) = current {
+            cache.insert(&url_path, page);
+        }
+
+        Some(cache)
+    }
+}
+
+/// FNV-1a hash of some bytes
+///
+/// Fast and stable across runs and platforms, which is all change
+/// detection needs; it's not meant to resist deliberate collisions.
+pub(crate) fn content_hash(data: &[u8]) -> u64 {
+    data.iter().fold(FNV_OFFSET_BASIS, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Content hash of a file, or `None` if it can't be read
+pub(crate) fn file_hash(file: &Path) -> Option<u64> {
+    fs::read(file).ok().map(|data| content_hash(&data))
+}
+
+/// A file's modification date as `page.updated` shows it
+fn modified_date(file: &Path) -> Option<String> {
+    fs::metadata(file)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .map(format_date)
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_hash() {
+        assert_eq!(content_hash(b""), FNV_OFFSET_BASIS);
+        assert_eq!(content_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_ne!(content_hash(b"ab"), content_hash(b"ba"));
+    }
+
+    #[test]
+    fn test_cache_round_trip_and_freshness() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let page_file = temp_dir.path().join("my page.md");
+        fs::write(&page_file, "# Hi").unwrap();
+
+        let mut cache = BuildCache::new(42);
+        let page = CachedPage::record([page_file.as_path()]).unwrap();
+        assert!(page.updated.is_some());
+        cache.insert("/my page", page);
+        cache.insert("/404.html", CachedPage::record([]).unwrap());
+        cache.write(temp_dir.path()).unwrap();
+
+        let loaded = BuildCache::load(temp_dir.path()).unwrap();
+        assert_eq!(loaded, cache);
+        assert!(loaded.get("/my page").unwrap().is_fresh());
+
+        fs::write(&page_file, "# Hello").unwrap();
+        assert!(!loaded.get("/my page").unwrap().is_fresh());
+        fs::remove_file(&page_file).unwrap();
+        assert!(CachedPage::record([page_file.as_path()]).is_none());
+
+        BuildCache::remove(temp_dir.path()).unwrap();
+        BuildCache::remove(temp_dir.path()).unwrap();
+        assert!(BuildCache::load(temp_dir.path()).is_none());
+    }
+
+    #[test]
+    fn test_parse_rejects_other_formats() {
+        assert!(BuildCache::parse("").is_none());
+        assert!(BuildCache::parse("wtf-build-cache 2\nsite 0\n").is_none());
+        assert!(BuildCache::parse("wtf-build-cache 1\nsite 0\nfile 0 /x\n").is_none());
+        assert!(BuildCache::parse("wtf-build-cache 1\nsite 0\n").is_some());
+    }
+}
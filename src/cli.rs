@@ -1,17 +1,68 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
 // Constants for help text
 const ABOUT_TEXT: &str = "Directory-based markdown website server and renderer";
 const SERVE_ABOUT: &str = "Start the markdown website server";
-const RENDER_ABOUT: &str = "Render a markdown file to HTML";
+const RENDER_ABOUT: &str = "Render a markdown file to HTML (or another format)";
+const VIEW_ABOUT: &str = "Print the page served at a route, styled for the terminal";
+const INDEX_ABOUT: &str = "Print a Gemini or Gopher index page listing every page in the site";
+const EXPORT_ABOUT: &str = "Export the whole site as a single file";
+const EPUB_ABOUT: &str = "Package every page, in sitemap order, as an EPUB 3 book";
+const SINGLE_PAGE_ABOUT: &str =
+    "Combine every page, in sitemap order, into one self-contained HTML file";
+const BUILD_ABOUT: &str = "Render every page into a directory for any static file server";
+const FMT_ABOUT: &str = "Rewrite markdown files in canonical form";
 const PATH_HELP: &str = "Directory containing markdown files";
 const HOST_HELP: &str = "Host address to bind to";
 const PORT_HELP: &str = "Port to listen on";
+const ROUTE_HELP: &str = "Route of the page to view (e.g. / or /docs/setup)";
 const FILE_HELP: &str = "Markdown file to render";
+const FORMAT_HELP: &str = "Output format (json requires the 'serde' feature)";
+const ROOT_HELP: &str =
+    "Content root for includes and _site.conf variables [default: the file's directory]";
+const EMOJI_HELP: &str = "Expand :shortcode: emoji in page text";
+const EMOJI_MAP_HELP: &str = "File with custom 'shortcode = emoji' mappings (implies --emoji)";
+const XHTML_HELP: &str = "Close void elements XHTML-style (<br />)";
+const PRETTY_HELP: &str = "Indent HTML output, one block per line";
+const SECTIONS_HELP: &str = "Wrap each section in a <section> element";
+const HEADING_OFFSET_HELP: &str = "Shift heading levels down (1 renders # as <h2>), capped at <h6>";
+const CLASS_PREFIX_HELP: &str =
+    "Give every generated element a class of PREFIX plus its tag name (md- gives md-p)";
+const EXTERNAL_NEW_TAB_HELP: &str = "Open external (http/https) links in a new tab";
+const EXTERNAL_NOOPENER_HELP: &str = "Add rel=\"noopener noreferrer\" to external links";
+const EXTERNAL_CLASS_HELP: &str = "Add CLASS to external links";
+const FIGURES_HELP: &str = "Render images as <figure> with the alt text as caption";
+const LAZY_IMAGES_HELP: &str = "Add loading=\"lazy\" and decoding=\"async\" to images";
+const IMAGE_DIMENSIONS_HELP: &str =
+    "Add width/height to local images (PNG, JPEG, GIF, WebP) under the content root";
+const HIGHLIGHT_HELP: &str =
+    "Highlight code blocks (Rust, TOML, YAML, JSON, shell, Python) with tok-* classes";
+const WIDTH_HELP: &str = "Wrap text and terminal output at N columns (0 disables wrapping) [default: 80, or the terminal width for term]";
+const VIEW_WIDTH_HELP: &str =
+    "Wrap at N columns (0 disables wrapping) [default: the terminal width, $COLUMNS, or 80]";
+const GOPHER_HOST_HELP: &str = "Host name Gopher menu links point at";
+const GOPHER_PORT_HELP: &str = "Port Gopher menu links point at";
+const MAN_SECTION_HELP: &str = "Manual section for the .TH header (man output)";
+const STANDALONE_HELP: &str = "Wrap LaTeX output in a complete document with its preamble";
+const INDEX_FORMAT_HELP: &str = "Index page format";
+const BUILD_SOURCE_HELP: &str = "Directory containing markdown files";
+const BUILD_OUTPUT_HELP: &str = "Directory to write the site to (created if missing)";
+const FORCE_HELP: &str = "Render every page, even those unchanged since the last build";
+const OUTPUT_HELP: &str = "File to write";
+const TITLE_HELP: &str = "Title [default: the content directory's name]";
+const CSS_HELP: &str = "Stylesheet to embed after the built-in one";
+const LANGUAGE_HELP: &str = "Language tag for the book's metadata";
+const FMT_PATHS_HELP: &str = "Markdown files or directories (searched recursively for .md files)";
+const CHECK_HELP: &str =
+    "List files that aren't formatted instead of rewriting them (exit 1 if any)";
 
 const DEFAULT_HOST: &str = "0.0.0.0";
 const DEFAULT_PORT: &str = "8080";
+const DEFAULT_GOPHER_HOST: &str = "localhost";
+const DEFAULT_GOPHER_PORT: &str = "70";
+const DEFAULT_MAN_SECTION: &str = "1";
+const DEFAULT_LANGUAGE: &str = "en";
 
 /// Directory-based markdown website server and renderer
 #[derive(Parser, Debug)]
@@ -29,9 +80,29 @@ pub enum Commands {
     #[command(about = SERVE_ABOUT)]
     Serve(ServeArgs),
 
-    /// Render a markdown file to HTML
+    /// Render a markdown file to HTML (or another format)
     #[command(about = RENDER_ABOUT)]
     Render(RenderArgs),
+
+    /// Print the page served at a route, styled for the terminal
+    #[command(about = VIEW_ABOUT)]
+    View(ViewArgs),
+
+    /// Print a Gemini or Gopher index page listing every page in the site
+    #[command(about = INDEX_ABOUT)]
+    Index(IndexArgs),
+
+    /// Export the whole site as a single file
+    #[command(about = EXPORT_ABOUT)]
+    Export(ExportArgs),
+
+    /// Render every page into a static directory
+    #[command(about = BUILD_ABOUT)]
+    Build(BuildArgs),
+
+    /// Rewrite markdown files in canonical form
+    #[command(about = FMT_ABOUT)]
+    Fmt(FmtArgs),
 }
 
 #[derive(Parser, Debug)]
@@ -47,6 +118,14 @@ pub struct ServeArgs {
     /// Port to listen on
     #[arg(long, short = 'p', default_value = DEFAULT_PORT, help = PORT_HELP)]
     pub port: u16,
+
+    /// Expand :shortcode: emoji in page text
+    #[arg(long, help = EMOJI_HELP)]
+    pub emoji: bool,
+
+    /// File with custom emoji mappings
+    #[arg(long, value_name = "FILE", help = EMOJI_MAP_HELP)]
+    pub emoji_map: Option<PathBuf>,
 }
 
 #[derive(Parser, Debug)]
@@ -54,4 +133,272 @@ pub struct RenderArgs {
     /// Markdown file to render
     #[arg(value_name = "FILE", help = FILE_HELP)]
     pub file: PathBuf,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = RenderFormat::Html, help = FORMAT_HELP)]
+    pub format: RenderFormat,
+
+    /// Content root for includes and site variables
+    #[arg(long, value_name = "DIR", help = ROOT_HELP)]
+    pub root: Option<PathBuf>,
+
+    /// Expand :shortcode: emoji in page text
+    #[arg(long, help = EMOJI_HELP)]
+    pub emoji: bool,
+
+    /// File with custom emoji mappings
+    #[arg(long, value_name = "FILE", help = EMOJI_MAP_HELP)]
+    pub emoji_map: Option<PathBuf>,
+
+    /// XHTML-style void elements (HTML output)
+    #[arg(long, help = XHTML_HELP)]
+    pub xhtml: bool,
+
+    /// Pretty-printed output (HTML output)
+    #[arg(long, help = PRETTY_HELP)]
+    pub pretty: bool,
+
+    /// Wrap sections in <section> elements (HTML output)
+    #[arg(long, help = SECTIONS_HELP)]
+    pub sections: bool,
+
+    /// Heading level offset (HTML output)
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 0,
+        value_parser = clap::value_parser!(u8).range(0..=5),
+        help = HEADING_OFFSET_HELP
+    )]
+    pub heading_offset: u8,
+
+    /// Class prefix for generated elements (HTML output)
+    #[arg(long, value_name = "PREFIX", help = CLASS_PREFIX_HELP)]
+    pub class_prefix: Option<String>,
+
+    /// Open external links in a new tab (HTML output)
+    #[arg(long, help = EXTERNAL_NEW_TAB_HELP)]
+    pub external_new_tab: bool,
+
+    /// Add rel="noopener noreferrer" to external links (HTML output)
+    #[arg(long, help = EXTERNAL_NOOPENER_HELP)]
+    pub external_noopener: bool,
+
+    /// Class for external links (HTML output)
+    #[arg(long, value_name = "CLASS", help = EXTERNAL_CLASS_HELP)]
+    pub external_class: Option<String>,
+
+    /// Images as captioned figures (HTML output)
+    #[arg(long, help = FIGURES_HELP)]
+    pub figures: bool,
+
+    /// Lazy-loaded images (HTML output)
+    #[arg(long, help = LAZY_IMAGES_HELP)]
+    pub lazy_images: bool,
+
+    /// Sizes for local images (HTML output)
+    #[arg(long, help = IMAGE_DIMENSIONS_HELP)]
+    pub image_dimensions: bool,
+
+    /// Syntax highlighting for code blocks (HTML output)
+    #[arg(long, help = HIGHLIGHT_HELP)]
+    pub highlight: bool,
+
+    /// Wrap column (text and terminal output)
+    #[arg(long, value_name = "N", help = WIDTH_HELP)]
+    pub width: Option<usize>,
+
+    /// Host for Gopher menu links (Gopher output)
+    #[arg(long, value_name = "HOST", default_value = DEFAULT_GOPHER_HOST, help = GOPHER_HOST_HELP)]
+    pub gopher_host: String,
+
+    /// Port for Gopher menu links (Gopher output)
+    #[arg(long, value_name = "PORT", default_value = DEFAULT_GOPHER_PORT, help = GOPHER_PORT_HELP)]
+    pub gopher_port: u16,
+
+    /// Manual section (man output)
+    #[arg(long, value_name = "SECTION", default_value = DEFAULT_MAN_SECTION, help = MAN_SECTION_HELP)]
+    pub man_section: String,
+
+    /// Complete document instead of a fragment (LaTeX output)
+    #[arg(long, help = STANDALONE_HELP)]
+    pub standalone: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct ViewArgs {
+    /// Route of the page to view
+    #[arg(value_name = "ROUTE", help = ROUTE_HELP)]
+    pub route: String,
+
+    /// Directory containing markdown files [default: current directory]
+    #[arg(value_name = "PATH", help = PATH_HELP)]
+    pub path: Option<PathBuf>,
+
+    /// Wrap column
+    #[arg(long, value_name = "N", help = VIEW_WIDTH_HELP)]
+    pub width: Option<usize>,
+
+    /// Expand :shortcode: emoji in page text
+    #[arg(long, help = EMOJI_HELP)]
+    pub emoji: bool,
+
+    /// File with custom emoji mappings
+    #[arg(long, value_name = "FILE", help = EMOJI_MAP_HELP)]
+    pub emoji_map: Option<PathBuf>,
+}
+
+#[derive(Parser, Debug)]
+pub struct IndexArgs {
+    /// Directory containing markdown files [default: current directory]
+    #[arg(value_name = "PATH", help = PATH_HELP)]
+    pub path: Option<PathBuf>,
+
+    /// Index page format
+    #[arg(long, value_enum, default_value_t = IndexFormat::Gemtext, help = INDEX_FORMAT_HELP)]
+    pub format: IndexFormat,
+
+    /// Host for Gopher menu links
+    #[arg(long, value_name = "HOST", default_value = DEFAULT_GOPHER_HOST, help = GOPHER_HOST_HELP)]
+    pub gopher_host: String,
+
+    /// Port for Gopher menu links
+    #[arg(long, value_name = "PORT", default_value = DEFAULT_GOPHER_PORT, help = GOPHER_PORT_HELP)]
+    pub gopher_port: u16,
+}
+
+#[derive(Parser, Debug)]
+pub struct ExportArgs {
+    #[command(subcommand)]
+    pub format: ExportFormat,
+}
+
+/// Formats for the 'export' subcommand
+#[derive(Subcommand, Debug)]
+pub enum ExportFormat {
+    /// Package every page as an EPUB 3 book
+    #[command(about = EPUB_ABOUT)]
+    Epub(EpubArgs),
+
+    /// Combine every page into one HTML file
+    #[command(about = SINGLE_PAGE_ABOUT)]
+    SinglePage(SinglePageArgs),
+}
+
+#[derive(Parser, Debug)]
+pub struct EpubArgs {
+    /// Directory containing markdown files [default: current directory]
+    #[arg(value_name = "PATH", help = PATH_HELP)]
+    pub path: Option<PathBuf>,
+
+    /// File to write
+    #[arg(long, short = 'o', value_name = "FILE", help = OUTPUT_HELP)]
+    pub output: PathBuf,
+
+    /// Book title
+    #[arg(long, help = TITLE_HELP)]
+    pub title: Option<String>,
+
+    /// Language tag
+    #[arg(long, default_value = DEFAULT_LANGUAGE, help = LANGUAGE_HELP)]
+    pub language: String,
+
+    /// Expand :shortcode: emoji in page text
+    #[arg(long, help = EMOJI_HELP)]
+    pub emoji: bool,
+
+    /// File with custom emoji mappings
+    #[arg(long, value_name = "FILE", help = EMOJI_MAP_HELP)]
+    pub emoji_map: Option<PathBuf>,
+}
+
+#[derive(Parser, Debug)]
+pub struct SinglePageArgs {
+    /// Directory containing markdown files [default: current directory]
+    #[arg(value_name = "PATH", help = PATH_HELP)]
+    pub path: Option<PathBuf>,
+
+    /// File to write
+    #[arg(long, short = 'o', value_name = "FILE", help = OUTPUT_HELP)]
+    pub output: PathBuf,
+
+    /// Document title
+    #[arg(long, help = TITLE_HELP)]
+    pub title: Option<String>,
+
+    /// Extra stylesheet to embed
+    #[arg(long, value_name = "FILE", help = CSS_HELP)]
+    pub css: Option<PathBuf>,
+
+    /// Expand :shortcode: emoji in page text
+    #[arg(long, help = EMOJI_HELP)]
+    pub emoji: bool,
+
+    /// File with custom emoji mappings
+    #[arg(long, value_name = "FILE", help = EMOJI_MAP_HELP)]
+    pub emoji_map: Option<PathBuf>,
+}
+
+#[derive(Parser, Debug)]
+pub struct BuildArgs {
+    /// Directory containing markdown files
+    #[arg(value_name = "SRC", help = BUILD_SOURCE_HELP)]
+    pub source: PathBuf,
+
+    /// Directory to write the site to
+    #[arg(value_name = "OUT", help = BUILD_OUTPUT_HELP)]
+    pub output: PathBuf,
+
+    /// Ignore the build cache
+    #[arg(long, help = FORCE_HELP)]
+    pub force: bool,
+
+    /// Expand :shortcode: emoji in page text
+    #[arg(long, help = EMOJI_HELP)]
+    pub emoji: bool,
+
+    /// File with custom emoji mappings
+    #[arg(long, value_name = "FILE", help = EMOJI_MAP_HELP)]
+    pub emoji_map: Option<PathBuf>,
+}
+
+#[derive(Parser, Debug)]
+pub struct FmtArgs {
+    /// Markdown files or directories to format
+    #[arg(value_name = "PATH", required = true, help = FMT_PATHS_HELP)]
+    pub paths: Vec<PathBuf>,
+
+    /// Only check formatting, don't rewrite files
+    #[arg(long, help = CHECK_HELP)]
+    pub check: bool,
+}
+
+/// Output formats for the 'render' subcommand
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderFormat {
+    /// Minified HTML fragment
+    Html,
+    /// Parsed document tree as JSON
+    Json,
+    /// Plain text, word-wrapped (see --width)
+    Text,
+    /// ANSI-styled text for reading in a terminal
+    Term,
+    /// Gemtext for Gemini capsules
+    Gemtext,
+    /// Gopher map (see --gopher-host and --gopher-port)
+    Gopher,
+    /// roff man page titled after the file (see --man-section)
+    Man,
+    /// LaTeX fragment (see --standalone)
+    Latex,
+}
+
+/// Output formats for the 'index' subcommand
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexFormat {
+    /// Gemtext page of link lines
+    Gemtext,
+    /// Gopher menu
+    Gopher,
 }
@@ -0,0 +1,931 @@
+use crate::parser::{Document, InlineNode};
+use crate::visit::{self, VisitorMut};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Constants
+const SHORTCODE_DELIM: char = ':';
+const MAPPING_SEPARATOR: char = '=';
+const MAPPING_COMMENT: char = '#';
+
+// ============================================================================
+// ERROR HANDLING
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EmojiError {
+    IoError { path: PathBuf, message: String },
+    InvalidMapping { line: usize, reason: String },
+}
+
+impl fmt::Display for EmojiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmojiError::IoError { path, message } => {
+                write!(
+                    f,
+                    "IO error reading emoji map '{}': {}",
+                    path.display(),
+                    message
+                )
+            }
+            EmojiError::InvalidMapping { line, reason } => {
+                write!(f, "Invalid emoji mapping on line {}: {}", line, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EmojiError {}
+
+// ============================================================================
+// EMOJI EXPANDER
+// ============================================================================
+
+/// Replaces GitHub-style emoji shortcodes (`:warning:`) with Unicode emoji
+///
+/// Shortcodes are looked up in a custom mapping first (team-specific codes,
+/// loaded from a file) and then in the bundled GitHub table. Unknown
+/// shortcodes are left untouched, so text like `10:30:00` survives as-is.
+///
+/// Only `InlineNode::Text` nodes are rewritten; link URLs and image sources
+/// are never modified.
+#[derive(Debug, Clone, Default)]
+pub struct EmojiExpander {
+    custom: HashMap<String, String>,
+}
+
+impl EmojiExpander {
+    /// Creates an expander that only knows the bundled GitHub shortcodes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads additional shortcodes from a mapping file
+    ///
+    /// The file contains one `shortcode = replacement` pair per line.
+    /// Shortcodes may be written with or without surrounding colons, blank
+    /// lines and lines starting with `#` are ignored. Custom entries take
+    /// precedence over the bundled table.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the mapping file
+    ///
+    /// # Returns
+    /// * `Ok(EmojiExpander)` - Expander with the custom mappings added
+    /// * `Err(EmojiError)` - If the file can't be read or a line is malformed
+    pub fn with_custom_map(mut self, path: &Path) -> Result<Self, EmojiError> {
+        let content = fs::read_to_string(path).map_err(|e| EmojiError::IoError {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })?;
+
+        self.custom.extend(parse_mapping(&content)?);
+        Ok(self)
+    }
+
+    /// Adds a single custom shortcode mapping
+    pub fn with_mapping(mut self, shortcode: &str, replacement: &str) -> Self {
+        self.custom
+            .insert(shortcode.to_string(), replacement.to_string());
+        self
+    }
+
+    /// Looks up the replacement for a shortcode (without colons)
+    pub fn lookup(&self, shortcode: &str) -> Option<&str> {
+        if let Some(custom) = self.custom.get(shortcode) {
+            return Some(custom);
+        }
+
+        EMOJI_TABLE
+            .binary_search_by(|(name, _)| (*name).cmp(shortcode))
+            .ok()
+            .map(|index| EMOJI_TABLE[index].1)
+    }
+
+    /// Expands every known shortcode in a string
+    pub fn expand_text(&self, text: &str) -> String {
+        let mut output = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(open) = rest.find(SHORTCODE_DELIM) {
+            output.push_str(&rest[..open]);
+            let after_open = &rest[open + 1..];
+
+            let Some(close) = after_open.find(SHORTCODE_DELIM) else {
+                rest = &rest[open..];
+                break;
+            };
+
+            let candidate = &after_open[..close];
+            match self.lookup_candidate(candidate) {
+                Some(emoji) => {
+                    output.push_str(emoji);
+                    rest = &after_open[close + 1..];
+                }
+                None => {
+                    // Keep the colon and retry from the closing one, so
+                    // "at 10:30 :tada:" still finds ":tada:"
+                    output.push(SHORTCODE_DELIM);
+                    rest = after_open;
+                }
+            }
+        }
+
+        output.push_str(rest);
+        output
+    }
+
+    /// Expands shortcodes in every text node of a document, in place
+    pub fn expand_document(&self, document: &mut Document) {
+        ShortcodePass { expander: self }.visit_document_mut(document);
+    }
+
+    fn lookup_candidate(&self, candidate: &str) -> Option<&str> {
+        if !is_shortcode_name(candidate) {
+            return None;
+        }
+        self.lookup(candidate)
+    }
+}
+
+/// `VisitorMut` pass applying an `EmojiExpander` to text nodes
+struct ShortcodePass<'a> {
+    expander: &'a EmojiExpander,
+}
+
+impl VisitorMut for ShortcodePass<'_> {
+    fn visit_inline_mut(&mut self, node: &mut InlineNode) {
+        if let InlineNode::Text(text) = node
+            && text.contains(SHORTCODE_DELIM)
+        {
+            *text = self.expander.expand_text(text).into();
+        }
+        visit::walk_inline_mut(self, node);
+    }
+}
+
+/// Checks whether a string is a plausible shortcode name
+///
+/// GitHub shortcodes use lowercase letters, digits, `_`, `+` and `-`
+/// (e.g. `white_check_mark`, `+1`, `e-mail`)
+fn is_shortcode_name(candidate: &str) -> bool {
+    !candidate.is_empty()
+        && candidate.chars().all(|ch| {
+            ch.is_ascii_lowercase() || ch.is_ascii_digit() || matches!(ch, '_' | '+' | '-')
+        })
+}
+
+/// Parses the contents of a custom mapping file
+fn parse_mapping(content: &str) -> Result<HashMap<String, String>, EmojiError> {
+    let mut mapping = HashMap::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with(MAPPING_COMMENT) {
+            continue;
+        }
+
+        let Some((shortcode, replacement)) = line.split_once(MAPPING_SEPARATOR) else {
+            return Err(EmojiError::InvalidMapping {
+                line: line_number,
+                reason: format!("expected 'shortcode {} replacement'", MAPPING_SEPARATOR),
+            });
+        };
+
+        let shortcode = shortcode
+            .trim()
+            .trim_start_matches(SHORTCODE_DELIM)
+            .trim_end_matches(SHORTCODE_DELIM);
+        let replacement = replacement.trim();
+
+        if !is_shortcode_name(shortcode) {
+            return Err(EmojiError::InvalidMapping {
+                line: line_number,
+                reason: format!("invalid shortcode '{}'", shortcode),
+            });
+        }
+
+        if replacement.is_empty() {
+            return Err(EmojiError::InvalidMapping {
+                line: line_number,
+                reason: format!("missing replacement for '{}'", shortcode),
+            });
+        }
+
+        mapping.insert(shortcode.to_string(), replacement.to_string());
+    }
+
+    Ok(mapping)
+}
+
+// ============================================================================
+// BUNDLED SHORTCODE TABLE
+// ============================================================================
+
+// Subset of GitHub's gemoji shortcodes, sorted by name for binary search
+const EMOJI_TABLE: &[(&str, &str)] = &[
+    ("+1", "👍"),
+    ("-1", "👎"),
+    ("100", "💯"),
+    ("1234", "🔢"),
+    ("8ball", "🎱"),
+    ("a", "🅰️"),
+    ("ab", "🆎"),
+    ("abacus", "🧮"),
+    ("abc", "🔤"),
+    ("airplane", "✈️"),
+    ("alarm_clock", "⏰"),
+    ("alien", "👽"),
+    ("ambulance", "🚑"),
+    ("anchor", "⚓"),
+    ("angel", "👼"),
+    ("anger", "💢"),
+    ("angry", "😠"),
+    ("anguished", "😧"),
+    ("ant", "🐜"),
+    ("apple", "🍎"),
+    ("arrow_backward", "◀️"),
+    ("arrow_down", "⬇️"),
+    ("arrow_forward", "▶️"),
+    ("arrow_heading_down", "⤵️"),
+    ("arrow_heading_up", "⤴️"),
+    ("arrow_left", "⬅️"),
+    ("arrow_right", "➡️"),
+    ("arrow_right_hook", "↪️"),
+    ("arrow_up", "⬆️"),
+    ("arrows_clockwise", "🔃"),
+    ("arrows_counterclockwise", "🔄"),
+    ("art", "🎨"),
+    ("astonished", "😲"),
+    ("atom_symbol", "⚛️"),
+    ("b", "🅱️"),
+    ("baby", "👶"),
+    ("back", "🔙"),
+    ("balloon", "🎈"),
+    ("ballot_box_with_check", "☑️"),
+    ("banana", "🍌"),
+    ("bangbang", "‼️"),
+    ("bank", "🏦"),
+    ("bar_chart", "📊"),
+    ("baseball", "⚾"),
+    ("basketball", "🏀"),
+    ("bat", "🦇"),
+    ("battery", "🔋"),
+    ("bear", "🐻"),
+    ("beer", "🍺"),
+    ("beers", "🍻"),
+    ("beginner", "🔰"),
+    ("bell", "🔔"),
+    ("bike", "🚲"),
+    ("bird", "🐦"),
+    ("birthday", "🎂"),
+    ("black_circle", "⚫"),
+    ("black_flag", "🏴"),
+    ("black_heart", "🖤"),
+    ("black_square_button", "🔲"),
+    ("blue_heart", "💙"),
+    ("blush", "😊"),
+    ("bomb", "💣"),
+    ("book", "📖"),
+    ("bookmark", "🔖"),
+    ("books", "📚"),
+    ("boom", "💥"),
+    ("bouquet", "💐"),
+    ("bow", "🙇"),
+    ("brain", "🧠"),
+    ("bread", "🍞"),
+    ("briefcase", "💼"),
+    ("broken_heart", "💔"),
+    ("bug", "🐛"),
+    ("bulb", "💡"),
+    ("bus", "🚌"),
+    ("butterfly", "🦋"),
+    ("cake", "🍰"),
+    ("calendar", "📆"),
+    ("calling", "📲"),
+    ("camera", "📷"),
+    ("camera_flash", "📸"),
+    ("candle", "🕯️"),
+    ("card_index", "📇"),
+    ("cat", "🐱"),
+    ("cd", "💿"),
+    ("chains", "⛓️"),
+    ("chart", "💹"),
+    ("chart_with_downwards_trend", "📉"),
+    ("chart_with_upwards_trend", "📈"),
+    ("checkered_flag", "🏁"),
+    ("cherries", "🍒"),
+    ("chicken", "🐔"),
+    ("children_crossing", "🚸"),
+    ("christmas_tree", "🎄"),
+    ("cl", "🆑"),
+    ("clap", "👏"),
+    ("clipboard", "📋"),
+    ("clock1", "🕐"),
+    ("closed_book", "📕"),
+    ("closed_lock_with_key", "🔐"),
+    ("cloud", "☁️"),
+    ("clown_face", "🤡"),
+    ("coffee", "☕"),
+    ("cold_sweat", "😰"),
+    ("collision", "💥"),
+    ("computer", "💻"),
+    ("confetti_ball", "🎊"),
+    ("confounded", "😖"),
+    ("confused", "😕"),
+    ("construction", "🚧"),
+    ("construction_worker", "👷"),
+    ("cookie", "🍪"),
+    ("cool", "🆒"),
+    ("cop", "👮"),
+    ("copyright", "©️"),
+    ("cow", "🐮"),
+    ("crab", "🦀"),
+    ("crayon", "🖍️"),
+    ("credit_card", "💳"),
+    ("crescent_moon", "🌙"),
+    ("crossed_fingers", "🤞"),
+    ("crown", "👑"),
+    ("cry", "😢"),
+    ("crying_cat_face", "😿"),
+    ("crystal_ball", "🔮"),
+    ("cupid", "💘"),
+    ("dart", "🎯"),
+    ("dash", "💨"),
+    ("date", "📅"),
+    ("desktop_computer", "🖥️"),
+    ("disappointed", "😞"),
+    ("dizzy", "💫"),
+    ("dizzy_face", "😵"),
+    ("dna", "🧬"),
+    ("dog", "🐶"),
+    ("dollar", "💵"),
+    ("dolphin", "🐬"),
+    ("door", "🚪"),
+    ("doughnut", "🍩"),
+    ("dragon", "🐉"),
+    ("dress", "👗"),
+    ("droplet", "💧"),
+    ("dvd", "📀"),
+    ("e-mail", "📧"),
+    ("ear", "👂"),
+    ("earth_africa", "🌍"),
+    ("earth_americas", "🌎"),
+    ("earth_asia", "🌏"),
+    ("egg", "🥚"),
+    ("eggplant", "🍆"),
+    ("eight", "8️⃣"),
+    ("eight_pointed_black_star", "✴️"),
+    ("eight_spoked_asterisk", "✳️"),
+    ("electric_plug", "🔌"),
+    ("elephant", "🐘"),
+    ("email", "📧"),
+    ("end", "🔚"),
+    ("envelope", "✉️"),
+    ("euro", "💶"),
+    ("exclamation", "❗"),
+    ("expressionless", "😑"),
+    ("eyes", "👀"),
+    ("facepunch", "👊"),
+    ("factory", "🏭"),
+    ("fast_forward", "⏩"),
+    ("fearful", "😨"),
+    ("file_cabinet", "🗄️"),
+    ("file_folder", "📁"),
+    ("film_strip", "🎞️"),
+    ("fire", "🔥"),
+    ("fire_engine", "🚒"),
+    ("fireworks", "🎆"),
+    ("fish", "🐟"),
+    ("fist", "✊"),
+    ("five", "5️⃣"),
+    ("flags", "🎏"),
+    ("flashlight", "🔦"),
+    ("fleur_de_lis", "⚜️"),
+    ("floppy_disk", "💾"),
+    ("flushed", "😳"),
+    ("fog", "🌫️"),
+    ("football", "🏈"),
+    ("footprints", "👣"),
+    ("fork_and_knife", "🍴"),
+    ("fountain_pen", "🖋️"),
+    ("four", "4️⃣"),
+    ("four_leaf_clover", "🍀"),
+    ("fox_face", "🦊"),
+    ("free", "🆓"),
+    ("fries", "🍟"),
+    ("frog", "🐸"),
+    ("frowning", "😦"),
+    ("fuelpump", "⛽"),
+    ("full_moon", "🌕"),
+    ("game_die", "🎲"),
+    ("gear", "⚙️"),
+    ("gem", "💎"),
+    ("ghost", "👻"),
+    ("gift", "🎁"),
+    ("gift_heart", "💝"),
+    ("globe_with_meridians", "🌐"),
+    ("goat", "🐐"),
+    ("golf", "⛳"),
+    ("grapes", "🍇"),
+    ("green_apple", "🍏"),
+    ("green_book", "📗"),
+    ("green_heart", "💚"),
+    ("grey_exclamation", "❕"),
+    ("grey_question", "❔"),
+    ("grimacing", "😬"),
+    ("grin", "😁"),
+    ("grinning", "😀"),
+    ("guitar", "🎸"),
+    ("hamburger", "🍔"),
+    ("hammer", "🔨"),
+    ("hammer_and_pick", "⚒️"),
+    ("hammer_and_wrench", "🛠️"),
+    ("hand", "✋"),
+    ("handshake", "🤝"),
+    ("hankey", "💩"),
+    ("hash", "#️⃣"),
+    ("hatched_chick", "🐥"),
+    ("headphones", "🎧"),
+    ("hear_no_evil", "🙉"),
+    ("heart", "❤️"),
+    ("heart_eyes", "😍"),
+    ("heartbeat", "💓"),
+    ("heavy_check_mark", "✔️"),
+    ("heavy_division_sign", "➗"),
+    ("heavy_dollar_sign", "💲"),
+    ("heavy_exclamation_mark", "❗"),
+    ("heavy_minus_sign", "➖"),
+    ("heavy_multiplication_x", "✖️"),
+    ("heavy_plus_sign", "➕"),
+    ("helicopter", "🚁"),
+    ("hibiscus", "🌺"),
+    ("high_brightness", "🔆"),
+    ("hocho", "🔪"),
+    ("honey_pot", "🍯"),
+    ("horse", "🐴"),
+    ("hospital", "🏥"),
+    ("hotel", "🏨"),
+    ("hourglass", "⌛"),
+    ("hourglass_flowing_sand", "⏳"),
+    ("house", "🏠"),
+    ("hugs", "🤗"),
+    ("hushed", "😯"),
+    ("ice_cream", "🍨"),
+    ("icecream", "🍦"),
+    ("id", "🆔"),
+    ("imp", "👿"),
+    ("inbox_tray", "📥"),
+    ("incoming_envelope", "📨"),
+    ("infinity", "♾️"),
+    ("information_source", "ℹ️"),
+    ("innocent", "😇"),
+    ("interrobang", "⁉️"),
+    ("iphone", "📱"),
+    ("jack_o_lantern", "🎃"),
+    ("japanese_goblin", "👺"),
+    ("japanese_ogre", "👹"),
+    ("jeans", "👖"),
+    ("joy", "😂"),
+    ("joy_cat", "😹"),
+    ("key", "🔑"),
+    ("keyboard", "⌨️"),
+    ("kiss", "💋"),
+    ("kissing", "😗"),
+    ("kissing_heart", "😘"),
+    ("koala", "🐨"),
+    ("label", "🏷️"),
+    ("ladybug", "🐞"),
+    ("laptop", "💻"),
+    ("large_blue_circle", "🔵"),
+    ("large_blue_diamond", "🔷"),
+    ("large_orange_diamond", "🔶"),
+    ("laughing", "😆"),
+    ("leaves", "🍃"),
+    ("ledger", "📒"),
+    ("leftwards_arrow_with_hook", "↩️"),
+    ("lemon", "🍋"),
+    ("link", "🔗"),
+    ("lion", "🦁"),
+    ("lips", "👄"),
+    ("lipstick", "💄"),
+    ("lock", "🔒"),
+    ("lock_with_ink_pen", "🔏"),
+    ("lollipop", "🍭"),
+    ("loud_sound", "🔊"),
+    ("loudspeaker", "📢"),
+    ("love_letter", "💌"),
+    ("low_brightness", "🔅"),
+    ("m", "Ⓜ️"),
+    ("mag", "🔍"),
+    ("mag_right", "🔎"),
+    ("mailbox", "📫"),
+    ("mailbox_with_mail", "📬"),
+    ("man", "👨"),
+    ("mask", "😷"),
+    ("medal_sports", "🏅"),
+    ("mega", "📣"),
+    ("memo", "📝"),
+    ("microphone", "🎤"),
+    ("microscope", "🔬"),
+    ("minidisc", "💽"),
+    ("moneybag", "💰"),
+    ("monkey", "🐒"),
+    ("monkey_face", "🐵"),
+    ("mortar_board", "🎓"),
+    ("mouse", "🐭"),
+    ("movie_camera", "🎥"),
+    ("muscle", "💪"),
+    ("mushroom", "🍄"),
+    ("musical_note", "🎵"),
+    ("mute", "🔇"),
+    ("nail_care", "💅"),
+    ("necktie", "👔"),
+    ("negative_squared_cross_mark", "❎"),
+    ("nerd_face", "🤓"),
+    ("neutral_face", "😐"),
+    ("new", "🆕"),
+    ("new_moon", "🌑"),
+    ("newspaper", "📰"),
+    ("ng", "🆖"),
+    ("nine", "9️⃣"),
+    ("no_bell", "🔕"),
+    ("no_entry", "⛔"),
+    ("no_entry_sign", "🚫"),
+    ("no_good", "🙅"),
+    ("no_mouth", "😶"),
+    ("nose", "👃"),
+    ("notebook", "📓"),
+    ("notes", "🎶"),
+    ("nut_and_bolt", "🔩"),
+    ("o", "⭕"),
+    ("o2", "🅾️"),
+    ("ocean", "🌊"),
+    ("octopus", "🐙"),
+    ("ok", "🆗"),
+    ("ok_hand", "👌"),
+    ("ok_woman", "🙆"),
+    ("old_key", "🗝️"),
+    ("on", "🔛"),
+    ("one", "1️⃣"),
+    ("open_book", "📖"),
+    ("open_file_folder", "📂"),
+    ("open_hands", "👐"),
+    ("open_mouth", "😮"),
+    ("orange", "🍊"),
+    ("orange_book", "📙"),
+    ("orange_heart", "🧡"),
+    ("outbox_tray", "📤"),
+    ("owl", "🦉"),
+    ("ox", "🐂"),
+    ("package", "📦"),
+    ("page_facing_up", "📄"),
+    ("page_with_curl", "📃"),
+    ("pager", "📟"),
+    ("paintbrush", "🖌️"),
+    ("palm_tree", "🌴"),
+    ("panda_face", "🐼"),
+    ("paperclip", "📎"),
+    ("paperclips", "🖇️"),
+    ("parking", "🅿️"),
+    ("part_alternation_mark", "〽️"),
+    ("partly_sunny", "⛅"),
+    ("passport_control", "🛂"),
+    ("paw_prints", "🐾"),
+    ("peach", "🍑"),
+    ("pear", "🍐"),
+    ("pen", "🖊️"),
+    ("pencil", "📝"),
+    ("pencil2", "✏️"),
+    ("penguin", "🐧"),
+    ("pensive", "😔"),
+    ("persevere", "😣"),
+    ("phone", "☎️"),
+    ("pick", "⛏️"),
+    ("pig", "🐷"),
+    ("pill", "💊"),
+    ("pineapple", "🍍"),
+    ("pizza", "🍕"),
+    ("point_down", "👇"),
+    ("point_left", "👈"),
+    ("point_right", "👉"),
+    ("point_up", "☝️"),
+    ("point_up_2", "👆"),
+    ("poop", "💩"),
+    ("popcorn", "🍿"),
+    ("postbox", "📮"),
+    ("potato", "🥔"),
+    ("pouting_cat", "😾"),
+    ("pray", "🙏"),
+    ("printer", "🖨️"),
+    ("purple_heart", "💜"),
+    ("pushpin", "📌"),
+    ("question", "❓"),
+    ("rabbit", "🐰"),
+    ("racehorse", "🐎"),
+    ("radio", "📻"),
+    ("radio_button", "🔘"),
+    ("rage", "😡"),
+    ("rainbow", "🌈"),
+    ("rainbow_flag", "🏳️‍🌈"),
+    ("raised_hand", "✋"),
+    ("raised_hands", "🙌"),
+    ("ram", "🐏"),
+    ("recycle", "♻️"),
+    ("red_circle", "🔴"),
+    ("registered", "®️"),
+    ("relaxed", "☺️"),
+    ("relieved", "😌"),
+    ("repeat", "🔁"),
+    ("revolving_hearts", "💞"),
+    ("rewind", "⏪"),
+    ("ribbon", "🎀"),
+    ("rice", "🍚"),
+    ("ring", "💍"),
+    ("robot", "🤖"),
+    ("rocket", "🚀"),
+    ("rofl", "🤣"),
+    ("roll_eyes", "🙄"),
+    ("rooster", "🐓"),
+    ("rose", "🌹"),
+    ("rotating_light", "🚨"),
+    ("round_pushpin", "📍"),
+    ("runner", "🏃"),
+    ("running", "🏃"),
+    ("sake", "🍶"),
+    ("sandwich", "🥪"),
+    ("santa", "🎅"),
+    ("satellite", "📡"),
+    ("satisfied", "😆"),
+    ("saxophone", "🎷"),
+    ("school", "🏫"),
+    ("scissors", "✂️"),
+    ("scream", "😱"),
+    ("scroll", "📜"),
+    ("see_no_evil", "🙈"),
+    ("seedling", "🌱"),
+    ("seven", "7️⃣"),
+    ("shamrock", "☘️"),
+    ("shark", "🦈"),
+    ("sheep", "🐑"),
+    ("shield", "🛡️"),
+    ("ship", "🚢"),
+    ("shirt", "👕"),
+    ("shushing_face", "🤫"),
+    ("signal_strength", "📶"),
+    ("six", "6️⃣"),
+    ("skull", "💀"),
+    ("sleeping", "😴"),
+    ("sleepy", "😪"),
+    ("slightly_frowning_face", "🙁"),
+    ("slightly_smiling_face", "🙂"),
+    ("small_blue_diamond", "🔹"),
+    ("small_orange_diamond", "🔸"),
+    ("small_red_triangle", "🔺"),
+    ("small_red_triangle_down", "🔻"),
+    ("smile", "😄"),
+    ("smile_cat", "😸"),
+    ("smiley", "😃"),
+    ("smiley_cat", "😺"),
+    ("smiling_imp", "😈"),
+    ("smirk", "😏"),
+    ("smoking", "🚬"),
+    ("snail", "🐌"),
+    ("snake", "🐍"),
+    ("sneezing_face", "🤧"),
+    ("snowflake", "❄️"),
+    ("snowman", "⛄"),
+    ("sob", "😭"),
+    ("soccer", "⚽"),
+    ("soon", "🔜"),
+    ("sos", "🆘"),
+    ("sound", "🔉"),
+    ("space_invader", "👾"),
+    ("sparkle", "❇️"),
+    ("sparkles", "✨"),
+    ("sparkling_heart", "💖"),
+    ("speak_no_evil", "🙊"),
+    ("speaker", "🔈"),
+    ("speech_balloon", "💬"),
+    ("spider", "🕷️"),
+    ("spiral_calendar", "🗓️"),
+    ("spiral_notepad", "🗒️"),
+    ("star", "⭐"),
+    ("star2", "🌟"),
+    ("stars", "🌠"),
+    ("stop_sign", "🛑"),
+    ("stopwatch", "⏱️"),
+    ("straight_ruler", "📏"),
+    ("strawberry", "🍓"),
+    ("stuck_out_tongue", "😛"),
+    ("stuck_out_tongue_closed_eyes", "😝"),
+    ("stuck_out_tongue_winking_eye", "😜"),
+    ("sun_with_face", "🌞"),
+    ("sunflower", "🌻"),
+    ("sunglasses", "😎"),
+    ("sunny", "☀️"),
+    ("sunrise", "🌅"),
+    ("sushi", "🍣"),
+    ("sweat", "😓"),
+    ("sweat_drops", "💦"),
+    ("sweat_smile", "😅"),
+    ("syringe", "💉"),
+    ("taco", "🌮"),
+    ("tada", "🎉"),
+    ("tangerine", "🍊"),
+    ("taxi", "🚕"),
+    ("tea", "🍵"),
+    ("telephone", "☎️"),
+    ("telescope", "🔭"),
+    ("tennis", "🎾"),
+    ("tent", "⛺"),
+    ("test_tube", "🧪"),
+    ("thermometer", "🌡️"),
+    ("thinking", "🤔"),
+    ("thought_balloon", "💭"),
+    ("three", "3️⃣"),
+    ("thumbsdown", "👎"),
+    ("thumbsup", "👍"),
+    ("ticket", "🎫"),
+    ("tiger", "🐯"),
+    ("timer_clock", "⏲️"),
+    ("tired_face", "😫"),
+    ("tm", "™️"),
+    ("toilet", "🚽"),
+    ("tomato", "🍅"),
+    ("tongue", "👅"),
+    ("toolbox", "🧰"),
+    ("top", "🔝"),
+    ("tophat", "🎩"),
+    ("tractor", "🚜"),
+    ("traffic_light", "🚥"),
+    ("train", "🚋"),
+    ("tram", "🚊"),
+    ("triangular_flag_on_post", "🚩"),
+    ("triangular_ruler", "📐"),
+    ("trident", "🔱"),
+    ("triumph", "😤"),
+    ("trophy", "🏆"),
+    ("truck", "🚚"),
+    ("trumpet", "🎺"),
+    ("tulip", "🌷"),
+    ("turtle", "🐢"),
+    ("tv", "📺"),
+    ("two", "2️⃣"),
+    ("two_hearts", "💕"),
+    ("umbrella", "☔"),
+    ("unamused", "😒"),
+    ("unicorn", "🦄"),
+    ("unlock", "🔓"),
+    ("up", "🆙"),
+    ("upside_down_face", "🙃"),
+    ("v", "✌️"),
+    ("vertical_traffic_light", "🚦"),
+    ("video_camera", "📹"),
+    ("video_game", "🎮"),
+    ("violin", "🎻"),
+    ("volcano", "🌋"),
+    ("vs", "🆚"),
+    ("warning", "⚠️"),
+    ("wastebasket", "🗑️"),
+    ("watch", "⌚"),
+    ("watermelon", "🍉"),
+    ("wave", "👋"),
+    ("wavy_dash", "〰️"),
+    ("weary", "😩"),
+    ("whale", "🐳"),
+    ("wheelchair", "♿"),
+    ("white_check_mark", "✅"),
+    ("white_circle", "⚪"),
+    ("white_flag", "🏳️"),
+    ("white_flower", "💮"),
+    ("white_heart", "🤍"),
+    ("white_square_button", "🔳"),
+    ("wind_chime", "🎐"),
+    ("wine_glass", "🍷"),
+    ("wink", "😉"),
+    ("wolf", "🐺"),
+    ("woman", "👩"),
+    ("worried", "😟"),
+    ("wrench", "🔧"),
+    ("x", "❌"),
+    ("yellow_heart", "💛"),
+    ("yum", "😋"),
+    ("zap", "⚡"),
+    ("zero", "0️⃣"),
+    ("zipper_mouth_face", "🤐"),
+    ("zzz", "💤"),
+];
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{BlockNode, MarkdownParser};
+
+    #[test]
+    fn test_table_is_sorted() {
+        assert!(EMOJI_TABLE.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn test_expand_known_shortcodes() {
+        let expander = EmojiExpander::new();
+        assert_eq!(
+            expander.expand_text(":warning: disk almost full :white_check_mark:"),
+            "⚠️ disk almost full ✅"
+        );
+        assert_eq!(expander.expand_text(":+1::-1:"), "👍👎");
+    }
+
+    #[test]
+    fn test_unknown_shortcodes_untouched() {
+        let expander = EmojiExpander::new();
+        assert_eq!(expander.expand_text(":not_an_emoji:"), ":not_an_emoji:");
+        assert_eq!(expander.expand_text("Ratio 1:2"), "Ratio 1:2");
+        assert_eq!(expander.expand_text("at 10:30 :tada:"), "at 10:30 🎉");
+        assert_eq!(expander.expand_text(":Warning:"), ":Warning:");
+    }
+
+    #[test]
+    fn test_custom_mapping_overrides_table() {
+        let expander = EmojiExpander::new()
+            .with_mapping("shipit", "🐿️")
+            .with_mapping("warning", "(!)");
+        assert_eq!(expander.expand_text(":shipit: :warning:"), "🐿️ (!)");
+    }
+
+    #[test]
+    fn test_parse_mapping() {
+        let mapping = parse_mapping("# team codes\n\n:shipit: = 🐿️\noncall=📟\n").unwrap();
+        assert_eq!(mapping.get("shipit").map(String::as_str), Some("🐿️"));
+        assert_eq!(mapping.get("oncall").map(String::as_str), Some("📟"));
+    }
+
+    #[test]
+    fn test_parse_mapping_errors() {
+        assert!(matches!(
+            parse_mapping("shipit"),
+            Err(EmojiError::InvalidMapping { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse_mapping("ok = 👌\nShip It = 🐿️"),
+            Err(EmojiError::InvalidMapping { line: 2, .. })
+        ));
+        assert!(matches!(
+            parse_mapping("shipit ="),
+            Err(EmojiError::InvalidMapping { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_custom_map_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let map_path = temp_dir.path().join("emoji.map");
+        fs::write(&map_path, "oncall = 📟\n").unwrap();
+
+        let expander = EmojiExpander::new().with_custom_map(&map_path).unwrap();
+        assert_eq!(expander.lookup("oncall"), Some("📟"));
+        assert_eq!(expander.lookup("fire"), Some("🔥"));
+
+        let missing = EmojiExpander::new().with_custom_map(&temp_dir.path().join("missing"));
+        assert!(matches!(missing, Err(EmojiError::IoError { .. })));
+    }
+
+    #[test]
+    fn test_expand_document_skips_urls() {
+        let mut doc = MarkdownParser::parse(
+            "# Status :fire:\n\n**:warning:** see [docs :book:](http://x/:fire:)",
+        )
+        .unwrap();
+        EmojiExpander::new().expand_document(&mut doc);
+
+        let section = &doc.sections()[0];
+        assert_eq!(section.title(), &[InlineNode::Text("Status 🔥".into())]);
+
+        match &section.content()[0] {
+            BlockNode::Paragraph(inlines) => {
+                assert_eq!(
+                    inlines[0],
+                    InlineNode::Bold(vec![InlineNode::Text("⚠️".into())])
+                );
+                assert_eq!(
+                    inlines[2],
+                    InlineNode::Link {
+                        text: vec![InlineNode::Text("docs 📖".into())],
+                        url: "http://x/:fire:".into(),
+                    }
+                );
+            }
+            _ => panic!("Expected Paragraph"),
+        }
+    }
+}
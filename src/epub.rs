@@ -0,0 +1,483 @@
+use crate::export::{
+    ExportError, ExportPage, UrlRewriter, collect_pages, escape_xml, image_media_type, page_link,
+};
+use crate::html_writer::{HtmlConfig, HtmlWriter};
+use crate::router::SitemapEntry;
+use crate::server::{Server, ServerConfig, document_title};
+use crate::variables::format_date;
+use crate::visit::VisitorMut;
+use crate::zip::{ZipWriter, crc32};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+// Package layout
+const MIMETYPE_PATH: &str = "mimetype";
+const MIMETYPE: &str = "application/epub+zip";
+const CONTAINER_PATH: &str = "META-INF/container.xml";
+const CONTENT_DIR: &str = "OEBPS/";
+const PACKAGE_FILE: &str = "content.opf";
+const NAV_FILE: &str = "nav.xhtml";
+const PAGE_PREFIX: &str = "page-";
+const PAGE_EXTENSION: &str = ".xhtml";
+const IMAGE_DIR: &str = "images/";
+const IMAGE_PREFIX: &str = "image-";
+
+const MEDIA_TYPE_XHTML: &str = "application/xhtml+xml";
+
+// Metadata defaults
+const DEFAULT_LANGUAGE: &str = "en";
+const DEFAULT_TITLE: &str = "Untitled";
+const IDENTIFIER_PREFIX: &str = "urn:wtf:";
+const NAV_HEADING: &str = "Contents";
+
+const CONTAINER_XML: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n\
+<rootfiles>\n\
+<rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/>\n\
+</rootfiles>\n\
+</container>\n";
+
+const XML_DECLARATION: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+
+// ============================================================================
+// EPUB EXPORTER
+// ============================================================================
+
+/// Packages a whole content tree as an EPUB 3 book
+///
+/// Pages are the book's chapters, in sitemap order (depth first), each
+/// rendered as the server would render it (front matter, includes,
+/// variables, emoji) to an XHTML content document. The navigation document
+/// mirrors the sitemap's nesting.
+///
+/// Links between pages point at the chapters instead of routes, and local
+/// images are packaged with the book; links to anything else are left as
+/// written.
+///
+/// # Example
+/// ```no_run
+/// use wtf::epub::EpubExporter;
+/// use wtf::server::ServerConfig;
+/// use std::fs::File;
+///
+/// let exporter = EpubExporter::new(ServerConfig::new("content".into()))?
+///     .with_title("Handbook");
+/// exporter.write_epub(File::create("handbook.epub")?)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct EpubExporter {
+    server: Server,
+    title: String,
+    language: String,
+}
+
+/// A page of the book and where it's packaged
+struct Chapter {
+    page: ExportPage,
+    /// Path of the content document relative to the package file
+    href: String,
+}
+
+/// Images packaged so far, by canonical source path
+#[derive(Default)]
+struct ImageManifest {
+    hrefs: HashMap<PathBuf, String>,
+    /// Package-relative path, source file and media type, in packaging order
+    items: Vec<(String, PathBuf, &'static str)>,
+}
+
+impl EpubExporter {
+    /// Creates an exporter for the content tree a server config describes
+    ///
+    /// The book is titled after the content root directory until
+    /// `with_title` is used.
+    ///
+    /// # Returns
+    /// * `Ok(EpubExporter)` - Ready to export
+    /// * `Err(ExportError)` - If the content root or emoji map is invalid
+    pub fn new(config: ServerConfig) -> Result<Self, ExportError> {
+        let server = Server::new(config)?;
+        let title = server
+            .router()
+            .content_root()
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| DEFAULT_TITLE.to_string());
+
+        Ok(Self {
+            server,
+            title,
+            language: DEFAULT_LANGUAGE.to_string(),
+        })
+    }
+
+    /// Sets the book title
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// Sets the book's language tag (default `en`)
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = language.into();
+        self
+    }
+
+    /// Renders every page and writes the packaged book
+    ///
+    /// # Returns
+    /// * `Ok(())` - The complete EPUB was written
+    /// * `Err(ExportError)` - If a page or image can't be read or writing fails
+    pub fn write_epub<W: Write>(&self, writer: W) -> Result<(), ExportError> {
+        let sitemap = self.server.router().build_sitemap()?;
+        let chapters: Vec<Chapter> = collect_pages(self.server.router(), &sitemap)?
+            .into_iter()
+            .enumerate()
+            .map(|(i, page)| Chapter {
+                page,
+                href: format!("{}{}{}", PAGE_PREFIX, i + 1, PAGE_EXTENSION),
+            })
+            .collect();
+        let chapter_hrefs: HashMap<&str, &str> = chapters
+            .iter()
+            .map(|chapter| (chapter.page.route.as_str(), chapter.href.as_str()))
+            .collect();
+
+        let mut zip = ZipWriter::new(writer);
+        // The mimetype must come first, stored, for readers to sniff it
+        zip.add_stored(MIMETYPE_PATH, MIMETYPE.as_bytes())?;
+        zip.add_stored(CONTAINER_PATH, CONTAINER_XML.as_bytes())?;
+
+        let mut images = ImageManifest::default();
+        let mut titles = HashMap::new();
+        for chapter in &chapters {
+            let page = &chapter.page;
+            let mut document = self.server.load_page(&page.file, &page.route)?;
+            let title = document_title(&document).unwrap_or_else(|| page.name.clone());
+
+            // Links between pages lead to chapters; local images are packaged
+            let links = self.server.router().link_rewriter(&page.file);
+            UrlRewriter {
+                link: |url: &str| {
+                    let (route, fragment) = page_link(&links, url)?;
+                    let href = chapter_hrefs.get(route.as_str())?;
+                    Some(format!("{}{}", href, fragment))
+                },
+                image: |url: &str| images.add(links.local_file(url)?),
+            }
+            .visit_document_mut(&mut document);
+
+            let body = HtmlWriter::with_config(epub_html_config())
+                .write_html(&document)
+                .map_err(|e| ExportError::PageError {
+                    path: page.file.clone(),
+                    source: e.to_string(),
+                })?;
+            let xhtml = self.content_document(&title, &body);
+            zip.add_stored(
+                &format!("{}{}", CONTENT_DIR, chapter.href),
+                xhtml.as_bytes(),
+            )?;
+            titles.insert(page.route.as_str(), title);
+        }
+
+        for (href, file, _) in &images.items {
+            let data = fs::read(file).map_err(|e| ExportError::IoError {
+                path: file.clone(),
+                source: e.to_string(),
+            })?;
+            zip.add_stored(&format!("{}{}", CONTENT_DIR, href), &data)?;
+        }
+
+        let nav = self.nav_document(&sitemap, &chapter_hrefs, &titles);
+        zip.add_stored(&format!("{}{}", CONTENT_DIR, NAV_FILE), nav.as_bytes())?;
+        let package = self.package_document(&chapters, &images);
+        zip.add_stored(
+            &format!("{}{}", CONTENT_DIR, PACKAGE_FILE),
+            package.as_bytes(),
+        )?;
+
+        zip.finish()?;
+        Ok(())
+    }
+
+    /// Wraps a rendered page body in an XHTML content document
+    fn content_document(&self, title: &str, body: &str) -> String {
+        format!(
+            "{}<!DOCTYPE html>\n\
+             <html xmlns=\"http://www.w3.org/1999/xhtml\" lang=\"{lang}\" xml:lang=\"{lang}\">\n\
+             <head><meta charset=\"utf-8\"/><title>{}</title></head>\n\
+             <body>\n{}</body>\n</html>\n",
+            XML_DECLARATION,
+            escape_xml(title),
+            body,
+            lang = escape_xml(&self.language)
+        )
+    }
+
+    /// Builds the navigation document, a table of contents nested like the
+    /// sitemap
+    fn nav_document(
+        &self,
+        sitemap: &[SitemapEntry],
+        hrefs: &HashMap<&str, &str>,
+        titles: &HashMap<&str, String>,
+    ) -> String {
+        let mut list = String::new();
+        write_nav_list(&mut list, sitemap, hrefs, titles);
+        format!(
+            "{}<!DOCTYPE html>\n\
+             <html xmlns=\"http://www.w3.org/1999/xhtml\" \
+             xmlns:epub=\"http://www.idpf.org/2007/ops\" lang=\"{lang}\" xml:lang=\"{lang}\">\n\
+             <head><meta charset=\"utf-8\"/><title>{title}</title></head>\n\
+             <body>\n<nav epub:type=\"toc\" id=\"toc\">\n<h1>{}</h1>\n{}</nav>\n</body>\n</html>\n",
+            XML_DECLARATION,
+            NAV_HEADING,
+            list,
+            title = escape_xml(&self.title),
+            lang = escape_xml(&self.language)
+        )
+    }
+
+    /// Builds the package document: metadata, manifest and reading order
+    fn package_document(&self, chapters: &[Chapter], images: &ImageManifest) -> String {
+        let routes: Vec<&str> = chapters.iter().map(|c| c.page.route.as_str()).collect();
+        let identifier = format!(
+            "{}{:08x}",
+            IDENTIFIER_PREFIX,
+            crc32(format!("{}\n{}", self.title, routes.join("\n")).as_bytes())
+        );
+        let modified = format!("{}T00:00:00Z", format_date(SystemTime::now()));
+
+        let mut manifest = format!(
+            "<item id=\"nav\" href=\"{}\" media-type=\"{}\" properties=\"nav\"/>\n",
+            NAV_FILE, MEDIA_TYPE_XHTML
+        );
+        let mut spine = String::new();
+        for (i, chapter) in chapters.iter().enumerate() {
+            manifest.push_str(&format!(
+                "<item id=\"page-{}\" href=\"{}\" media-type=\"{}\"/>\n",
+                i + 1,
+                chapter.href,
+                MEDIA_TYPE_XHTML
+            ));
+            spine.push_str(&format!("<itemref idref=\"page-{}\"/>\n", i + 1));
+        }
+        for (i, (href, _, media_type)) in images.items.iter().enumerate() {
+            manifest.push_str(&format!(
+                "<item id=\"image-{}\" href=\"{}\" media-type=\"{}\"/>\n",
+                i + 1,
+                href,
+                media_type
+            ));
+        }
+
+        format!(
+            "{}<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" \
+             unique-identifier=\"book-id\">\n\
+             <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n\
+             <dc:identifier id=\"book-id\">{}</dc:identifier>\n\
+             <dc:title>{}</dc:title>\n\
+             <dc:language>{}</dc:language>\n\
+             <meta property=\"dcterms:modified\">{}</meta>\n\
+             </metadata>\n\
+             <manifest>\n{}</manifest>\n\
+             <spine>\n{}</spine>\n\
+             </package>\n",
+            XML_DECLARATION,
+            identifier,
+            escape_xml(&self.title),
+            escape_xml(&self.language),
+            modified,
+            manifest,
+            spine
+        )
+    }
+}
+
+/// HTML options for chapters: XHTML syntax, captioned figures and
+/// highlighted code, as close to the served pages as a reader allows
+fn epub_html_config() -> HtmlConfig {
+    HtmlConfig::new()
+        .with_xhtml(true)
+        .with_figures(true)
+        .with_highlight(true)
+}
+
+/// Writes a nested `<ol>` of chapter links for sitemap entries
+fn write_nav_list(
+    out: &mut String,
+    entries: &[SitemapEntry],
+    hrefs: &HashMap<&str, &str>,
+    titles: &HashMap<&str, String>,
+) {
+    out.push_str("<ol>\n");
+    for entry in entries {
+        let route = entry.url_path.as_str();
+        let label = escape_xml(titles.get(route).unwrap_or(&entry.name));
+        match hrefs.get(route) {
+            Some(href) => out.push_str(&format!("<li><a href=\"{}\">{}</a>", href, label)),
+            // Navigation entries need a label even without a page
+            None => out.push_str(&format!("<li><span>{}</span>", label)),
+        }
+        if !entry.children.is_empty() {
+            out.push('\n');
+            write_nav_list(out, &entry.children, hrefs, titles);
+        }
+        out.push_str("</li>\n");
+    }
+    out.push_str("</ol>\n");
+}
+
+impl ImageManifest {
+    /// Adds a local image to the book (once per file)
+    ///
+    /// # Returns
+    /// * `Some(String)` - The image's path in the package
+    /// * `None` - If it isn't a format EPUB readers support
+    fn add(&mut self, file: PathBuf) -> Option<String> {
+        if let Some(href) = self.hrefs.get(&file) {
+            return Some(href.clone());
+        }
+
+        let (extension, media_type) = image_media_type(&file)?;
+        let href = format!(
+            "{}{}{}.{}",
+            IMAGE_DIR,
+            IMAGE_PREFIX,
+            self.items.len() + 1,
+            extension
+        );
+        self.hrefs.insert(file.clone(), href.clone());
+        self.items.push((href.clone(), file, media_type));
+        Some(href)
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// Reads the stored entries of an archive written by `ZipWriter`
+    fn entries(bytes: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut entries = Vec::new();
+        let mut at = 0;
+        while bytes[at..].starts_with(&[0x50, 0x4b, 0x03, 0x04]) {
+            let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]) as usize;
+            let size = u32::from_le_bytes([
+                bytes[at + 22],
+                bytes[at + 23],
+                bytes[at + 24],
+                bytes[at + 25],
+            ]) as usize;
+            let name_len = u16_at(at + 26);
+            let name_start = at + 30;
+            let data_start = name_start + name_len;
+            let name = String::from_utf8(bytes[name_start..data_start].to_vec()).unwrap();
+            entries.push((name, bytes[data_start..data_start + size].to_vec()));
+            at = data_start + size;
+        }
+        entries
+    }
+
+    fn entry<'a>(entries: &'a [(String, Vec<u8>)], name: &str) -> &'a str {
+        let (_, data) = entries.iter().find(|(n, _)| n == name).unwrap();
+        std::str::from_utf8(data).unwrap()
+    }
+
+    fn write_site(root: &Path) {
+        fs::create_dir(root.join("guide")).unwrap();
+        fs::write(
+            root.join("root.md"),
+            "# Welcome\nSee the [guide](guide/guide.md#start).",
+        )
+        .unwrap();
+        fs::write(
+            root.join("guide/guide.md"),
+            "# Guide & more\n![Logo](logo.png)\n\nBack [home](/) or [away](https://x.org).",
+        )
+        .unwrap();
+        fs::write(root.join("guide/intro.md"), "No heading here").unwrap();
+        fs::write(root.join("guide/logo.png"), b"\x89PNG fake").unwrap();
+    }
+
+    #[test]
+    fn test_package_structure() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        write_site(temp_dir.path());
+
+        let mut bytes = Vec::new();
+        EpubExporter::new(ServerConfig::new(temp_dir.path().to_path_buf()))
+            .unwrap()
+            .with_title("Hand & Book")
+            .with_language("de")
+            .write_epub(&mut bytes)
+            .unwrap();
+        let entries = entries(&bytes);
+
+        let names: Vec<&str> = entries.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "mimetype",
+                "META-INF/container.xml",
+                "OEBPS/page-1.xhtml",
+                "OEBPS/page-2.xhtml",
+                "OEBPS/page-3.xhtml",
+                "OEBPS/images/image-1.png",
+                "OEBPS/nav.xhtml",
+                "OEBPS/content.opf",
+            ]
+        );
+        assert_eq!(entry(&entries, "mimetype"), "application/epub+zip");
+
+        let package = entry(&entries, "OEBPS/content.opf");
+        assert!(package.contains("<dc:title>Hand &amp; Book</dc:title>"));
+        assert!(package.contains("<dc:language>de</dc:language>"));
+        assert!(package.contains(
+            "<item id=\"image-1\" href=\"images/image-1.png\" media-type=\"image/png\"/>"
+        ));
+        assert!(package.contains(
+            "<spine>\n<itemref idref=\"page-1\"/>\n<itemref idref=\"page-2\"/>\n\
+             <itemref idref=\"page-3\"/>\n</spine>"
+        ));
+    }
+
+    #[test]
+    fn test_chapters_and_nav() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        write_site(temp_dir.path());
+
+        let mut bytes = Vec::new();
+        EpubExporter::new(ServerConfig::new(temp_dir.path().to_path_buf()))
+            .unwrap()
+            .write_epub(&mut bytes)
+            .unwrap();
+        let entries = entries(&bytes);
+
+        let root = entry(&entries, "OEBPS/page-1.xhtml");
+        assert!(root.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE html>"));
+        assert!(root.contains("<title>Welcome</title>"));
+        assert!(root.contains("<a href=\"page-2.xhtml#start\">guide</a>"));
+
+        let guide = entry(&entries, "OEBPS/page-2.xhtml");
+        assert!(guide.contains("src=\"images/image-1.png\""));
+        assert!(guide.contains("<a href=\"page-1.xhtml\">home</a>"));
+        assert!(guide.contains("<a href=\"https://x.org\">away</a>"));
+
+        let nav = entry(&entries, "OEBPS/nav.xhtml");
+        assert!(nav.contains(
+            "<ol>\n<li><a href=\"page-1.xhtml\">Welcome</a></li>\n\
+             <li><a href=\"page-2.xhtml\">Guide &amp; more</a>\n\
+             <ol>\n<li><a href=\"page-3.xhtml\">intro</a></li>\n</ol>\n</li>\n</ol>\n"
+        ));
+    }
+}
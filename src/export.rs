@@ -0,0 +1,280 @@
+use crate::parser::{BlockNode, InlineNode};
+use crate::router::{LinkRewriter, ResolvedPath, Router, RouterError, SitemapEntry};
+use crate::server::ServerError;
+use crate::visit::{self, VisitorMut};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Shared by the whole-site exports (EPUB, single page)
+
+const PATH_SEPARATOR: char = '/';
+const FRAGMENT_SEPARATOR: char = '#';
+const QUERY_SEPARATOR: char = '?';
+
+/// Image formats exports embed, by lower-case file extension
+const IMAGE_MEDIA_TYPES: [(&str, &str); 6] = [
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("svg", "image/svg+xml"),
+];
+
+// ============================================================================
+// ERROR HANDLING
+// ============================================================================
+
+/// Errors from the whole-site exports (`EpubExporter`, `SinglePageExporter`)
+#[derive(Debug)]
+pub enum ExportError {
+    /// The site couldn't be set up (e.g. an invalid emoji map)
+    ServerError(ServerError),
+    RouterError(RouterError),
+    /// A page couldn't be loaded or rendered
+    PageError {
+        path: PathBuf,
+        source: String,
+    },
+    /// An image couldn't be read
+    IoError {
+        path: PathBuf,
+        source: String,
+    },
+    /// The output writer failed
+    WriteError(String),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::ServerError(error) => write!(f, "{}", error),
+            ExportError::RouterError(error) => write!(f, "Router error: {}", error),
+            ExportError::PageError { path, source } => {
+                write!(f, "Error rendering {}: {}", path.display(), source)
+            }
+            ExportError::IoError { path, source } => {
+                write!(f, "IO error reading {}: {}", path.display(), source)
+            }
+            ExportError::WriteError(message) => write!(f, "Write error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+impl From<RouterError> for ExportError {
+    fn from(error: RouterError) -> Self {
+        ExportError::RouterError(error)
+    }
+}
+
+impl From<ServerError> for ExportError {
+    fn from(error: ServerError) -> Self {
+        let page_error = |path, source: &dyn fmt::Display| ExportError::PageError {
+            path,
+            source: source.to_string(),
+        };
+        match error {
+            ServerError::RouterError { source } => ExportError::RouterError(source),
+            ServerError::IoError { path, source } => ExportError::PageError { path, source },
+            ServerError::ParseError { path, source }
+            | ServerError::VariableError { path, source } => page_error(path, &source),
+            ServerError::IncludeError { path, source } => page_error(path, &source),
+            ServerError::LayoutError { path, source } => page_error(path, &source),
+            ServerError::RenderError { path, source } => page_error(path, &source),
+            ServerError::BindError { .. } | ServerError::EmojiError { .. } => {
+                ExportError::ServerError(error)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for ExportError {
+    fn from(error: io::Error) -> Self {
+        ExportError::WriteError(error.to_string())
+    }
+}
+
+// ============================================================================
+// PAGES
+// ============================================================================
+
+/// A page of the site, in the order exports present it
+pub(crate) struct ExportPage {
+    pub(crate) route: String,
+    /// Sitemap name, for pages without an H1 title
+    pub(crate) name: String,
+    pub(crate) file: PathBuf,
+    /// Nesting depth in the sitemap (0 for top-level pages)
+    pub(crate) depth: usize,
+}
+
+/// Lists every page in sitemap order, depth first
+///
+/// Entries no route serves (e.g. a directory without an index page) are
+/// skipped, but their children are kept.
+pub(crate) fn collect_pages(
+    router: &Router,
+    entries: &[SitemapEntry],
+) -> Result<Vec<ExportPage>, RouterError> {
+    let mut pages = Vec::new();
+    collect_pages_at(router, entries, 0, &mut pages)?;
+    Ok(pages)
+}
+
+fn collect_pages_at(
+    router: &Router,
+    entries: &[SitemapEntry],
+    depth: usize,
+    pages: &mut Vec<ExportPage>,
+) -> Result<(), RouterError> {
+    for entry in entries {
+        if let ResolvedPath::Found(file) = router.resolve_path(&entry.url_path)? {
+            pages.push(ExportPage {
+                route: entry.url_path.clone(),
+                name: entry.name.clone(),
+                file,
+                depth,
+            });
+        }
+        collect_pages_at(router, &entry.children, depth + 1, pages)?;
+    }
+    Ok(())
+}
+
+/// Splits a link into the route it leads to and its fragment (`#...`, or
+/// empty), for links to `.md` files and to routes (`/docs`)
+///
+/// Queries are dropped; they mean nothing once pages are exported.
+pub(crate) fn page_link(links: &LinkRewriter, url: &str) -> Option<(String, String)> {
+    let route = links.rewrite(url).unwrap_or_else(|| url.to_string());
+    let (path, fragment) = match route.find(FRAGMENT_SEPARATOR) {
+        Some(index) => route.split_at(index),
+        None => (route.as_str(), ""),
+    };
+    let path = path.split(QUERY_SEPARATOR).next().unwrap_or(path);
+    // `//host/...` is protocol-relative, i.e. another site
+    if !path.starts_with(PATH_SEPARATOR) || path.starts_with("//") {
+        return None;
+    }
+    Some((path.to_string(), fragment.to_string()))
+}
+
+/// The lower-case extension and media type of an image file exports embed
+pub(crate) fn image_media_type(file: &Path) -> Option<(String, &'static str)> {
+    let extension = file.extension()?.to_str()?.to_ascii_lowercase();
+    IMAGE_MEDIA_TYPES
+        .iter()
+        .find(|(known, _)| *known == extension)
+        .map(|(_, media_type)| (extension, *media_type))
+}
+
+/// Replaces link and image URLs throughout a document
+///
+/// Each closure returns the new URL, or `None` to leave the URL as written.
+pub(crate) struct UrlRewriter<L, I> {
+    pub(crate) link: L,
+    pub(crate) image: I,
+}
+
+impl<L, I> VisitorMut for UrlRewriter<L, I>
+where
+    L: FnMut(&str) -> Option<String>,
+    I: FnMut(&str) -> Option<String>,
+{
+    fn visit_block_mut(&mut self, block: &mut BlockNode) {
+        if let BlockNode::Image { url, .. } = block
+            && let Some(new_url) = (self.image)(url)
+        {
+            *url = new_url.into();
+        }
+        visit::walk_block_mut(self, block);
+    }
+
+    fn visit_inline_mut(&mut self, node: &mut InlineNode) {
+        if let InlineNode::Link { url, .. } = node
+            && let Some(new_url) = (self.link)(url)
+        {
+            *url = new_url.into();
+        }
+        visit::walk_inline_mut(self, node);
+    }
+}
+
+/// Escapes text for HTML/XML content and attribute values
+pub(crate) fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variables::SiteConfigError;
+    use std::fs;
+
+    #[test]
+    fn test_page_link() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join("guide")).unwrap();
+        fs::write(root.join("guide/guide.md"), "# Guide").unwrap();
+        fs::write(root.join("guide/intro.md"), "# Intro").unwrap();
+        let router = Router::new(root.to_path_buf()).unwrap();
+        let links = router.link_rewriter(&root.join("guide/guide.md"));
+        let link = |url: &str| page_link(&links, url);
+        let route = |path: &str, fragment: &str| Some((path.to_string(), fragment.to_string()));
+
+        assert_eq!(link("intro.md"), route("/guide/intro", ""));
+        assert_eq!(link("intro.md#setup"), route("/guide/intro", "#setup"));
+        assert_eq!(link("intro.md?v=2#setup"), route("/guide/intro", "#setup"));
+        assert_eq!(link("/guide?tab=1"), route("/guide", ""));
+        assert_eq!(link("/missing#x"), route("/missing", "#x"));
+
+        // Not pages of this site
+        assert_eq!(link("//example.com/guide"), None);
+        assert_eq!(link("https://example.com/guide"), None);
+        assert_eq!(link("#local"), None);
+        assert_eq!(link("pic.png"), None);
+    }
+
+    #[test]
+    fn test_image_media_type() {
+        assert_eq!(
+            image_media_type(Path::new("a/photo.JPG")),
+            Some(("jpg".to_string(), "image/jpeg"))
+        );
+        assert_eq!(
+            image_media_type(Path::new("icon.svg")),
+            Some(("svg".to_string(), "image/svg+xml"))
+        );
+        assert_eq!(image_media_type(Path::new("notes.txt")), None);
+        assert_eq!(image_media_type(Path::new("png")), None);
+    }
+
+    #[test]
+    fn test_server_errors_keep_their_kind() {
+        let path = PathBuf::from("site/_layout.html");
+        let error = ExportError::from(ServerError::LayoutError {
+            path: path.clone(),
+            source: crate::layout::LayoutError::MissingContent,
+        });
+        assert!(matches!(&error, ExportError::PageError { path: p, .. } if *p == path));
+        assert!(!error.to_string().starts_with("Write error"));
+
+        let config_error = SiteConfigError::IoError {
+            path: path.clone(),
+            source: "denied".to_string(),
+        };
+        let error = ExportError::from(ServerError::from(config_error));
+        assert!(matches!(error, ExportError::PageError { .. }));
+    }
+}
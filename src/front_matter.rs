@@ -0,0 +1,200 @@
+use crate::parser::ParseError;
+
+// Constants
+const FRONT_MATTER_DELIM: &str = "---";
+const KEY_VALUE_SEPARATOR: char = ':';
+const COMMENT_CHAR: char = '#';
+const DOUBLE_QUOTE: char = '"';
+const SINGLE_QUOTE: char = '\'';
+
+// ============================================================================
+// FRONT MATTER
+// ============================================================================
+
+/// Simple `key: value` metadata, as found in page front matter
+///
+/// Front matter is an optional block at the very top of a page, delimited by
+/// `---` lines:
+///
+/// ```text
+/// ---
+/// title: Release notes
+/// owner: "Platform team"
+/// ---
+/// # Release notes
+/// ```
+///
+/// Only flat `key: value` pairs are supported. Values may be wrapped in single
+/// or double quotes, blank lines and `#` comments are ignored. Entries keep
+/// their original order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrontMatter {
+    entries: Vec<(String, String)>,
+}
+
+impl FrontMatter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the value for a key, if present
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns all entries in their original order
+    pub fn entries(&self) -> &[(String, String)] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Parses a block of `key: value` lines
+    ///
+    /// Later entries replace earlier ones with the same key.
+    ///
+    /// # Returns
+    /// * `Ok(FrontMatter)` - The parsed entries
+    /// * `Err(ParseError::InvalidFrontMatter)` - If a line isn't a valid pair
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut front_matter = Self::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with(COMMENT_CHAR) {
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once(KEY_VALUE_SEPARATOR) else {
+                return Err(ParseError::InvalidFrontMatter {
+                    line: line_number,
+                    reason: format!("expected 'key{} value'", KEY_VALUE_SEPARATOR),
+                });
+            };
+
+            let key = key.trim();
+            if key.is_empty() || key.contains(char::is_whitespace) {
+                return Err(ParseError::InvalidFrontMatter {
+                    line: line_number,
+                    reason: format!("invalid key '{}'", key),
+                });
+            }
+
+            front_matter.insert(key, unquote(value.trim()));
+        }
+
+        Ok(front_matter)
+    }
+
+    fn insert(&mut self, key: &str, value: &str) {
+        match self.entries.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value.to_string(),
+            None => self.entries.push((key.to_string(), value.to_string())),
+        }
+    }
+}
+
+/// Splits optional front matter off the top of a markdown source
+///
+/// Front matter is only recognised when the very first line is `---` and a
+/// closing `---` line follows; otherwise the whole source is returned as the
+/// body with empty front matter.
+///
+/// # Returns
+/// * `Ok((FrontMatter, body))` - Parsed front matter and the remaining markdown
+/// * `Err(ParseError::InvalidFrontMatter)` - If the front matter is malformed
+pub fn split_front_matter(source: &str) -> Result<(FrontMatter, &str), ParseError> {
+    let Some(first_line_end) = source.find('\n') else {
+        return Ok((FrontMatter::new(), source));
+    };
+
+    if source[..first_line_end].trim_end() != FRONT_MATTER_DELIM {
+        return Ok((FrontMatter::new(), source));
+    }
+
+    let block_start = first_line_end + 1;
+    let mut offset = block_start;
+
+    for line in source[block_start..].split_inclusive('\n') {
+        if line.trim_end() == FRONT_MATTER_DELIM {
+            let front_matter = FrontMatter::parse(&source[block_start..offset])?;
+            return Ok((front_matter, &source[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+
+    // No closing delimiter - not front matter after all
+    Ok((FrontMatter::new(), source))
+}
+
+/// Strips one pair of matching surrounding quotes from a value
+fn unquote(value: &str) -> &str {
+    for quote in [DOUBLE_QUOTE, SINGLE_QUOTE] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|v| v.strip_suffix(quote))
+        {
+            return inner;
+        }
+    }
+    value
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_front_matter() {
+        let source = "---\ntitle: Notes\nowner: \"Platform: core\"\n---\n# Notes\n";
+        let (front_matter, body) = split_front_matter(source).unwrap();
+        assert_eq!(front_matter.get("title"), Some("Notes"));
+        assert_eq!(front_matter.get("owner"), Some("Platform: core"));
+        assert_eq!(body, "# Notes\n");
+    }
+
+    #[test]
+    fn test_no_front_matter() {
+        let (front_matter, body) = split_front_matter("# Title\n---\n").unwrap();
+        assert!(front_matter.is_empty());
+        assert_eq!(body, "# Title\n---\n");
+
+        // Opening delimiter without a closing one is just text
+        let (front_matter, body) = split_front_matter("---\ntitle: x\n").unwrap();
+        assert!(front_matter.is_empty());
+        assert_eq!(body, "---\ntitle: x\n");
+    }
+
+    #[test]
+    fn test_parse_comments_and_overrides() {
+        let front_matter = FrontMatter::parse("# comment\n\nname: a\nname: 'b'\n").unwrap();
+        assert_eq!(front_matter.entries().len(), 1);
+        assert_eq!(front_matter.get("name"), Some("b"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            FrontMatter::parse("ok: 1\nnot a pair"),
+            Err(ParseError::InvalidFrontMatter {
+                line: 2,
+                reason: "expected 'key: value'".to_string(),
+            })
+        );
+        assert!(matches!(
+            FrontMatter::parse("two words: x"),
+            Err(ParseError::InvalidFrontMatter { line: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,363 @@
+use crate::parser::{Document, Event, ParseError, Tag, TagEnd};
+use crate::renderer::{self, RenderError, Renderer};
+use crate::router::{LinkRewriter, SitemapEntry};
+use std::cell::RefCell;
+use std::fmt;
+use std::io;
+
+// Line types
+const HEADING_CHAR: char = '#';
+const MAX_HEADING_LEVEL: u8 = 3;
+const LINK_LINE: &str = "=>";
+const PREFORMAT_TOGGLE: &str = "```";
+const LINE_MARKERS: [&str; 5] = ["=>", "#", "* ", ">", "```"];
+
+const NEWLINE: char = '\n';
+const SPACE: char = ' ';
+
+// Index page built from the sitemap
+const INDEX_TITLE: &str = "Index";
+const INDEX_NAME_SEPARATOR: &str = " / ";
+
+/// Gemtext writer for publishing pages to Gemini capsules
+///
+/// Gemtext is line-based, with no inline markup:
+/// - Headings as `#`, `##` or `###` lines (deeper levels are capped at `###`)
+/// - Paragraphs as plain lines (Gemini clients wrap them), one per line
+///   break; bold and italic lose their markers
+/// - Links hoisted to `=> url text` lines after the block they appear in
+/// - Images as `=> url alt text` lines
+/// - Code blocks between ```` ``` ```` toggles, with the info string as
+///   alt text
+///
+/// Gemtext has no escapes, so text lines that would read as another line
+/// type (`=>`, `#`, `* `, `>`, ```` ``` ````) are indented by a space.
+///
+/// # Example
+/// ```
+/// use wtf::gemtext_writer::GemtextWriter;
+/// use wtf::parser::MarkdownParser;
+///
+/// let doc = MarkdownParser::parse("#### Deep\nSee [docs](/docs) **now**").unwrap();
+/// let text = GemtextWriter::new().write_gemtext(&doc).unwrap();
+/// assert_eq!(text, "### Deep\n\nSee docs now\n=> /docs docs\n");
+/// ```
+pub struct GemtextWriter {
+    links: Option<LinkRewriter>,
+    state: RefCell<GemtextState>,
+}
+
+/// The block being collected and the links to hoist after it
+#[derive(Default)]
+struct GemtextState {
+    block: String,
+    /// URL and start offset in `block` of each open link
+    open_links: Vec<(String, usize)>,
+    /// URL and text of each finished link in the block
+    links: Vec<(String, String)>,
+    started: bool,
+}
+
+impl GemtextWriter {
+    pub fn new() -> Self {
+        Self {
+            links: None,
+            state: RefCell::new(GemtextState::default()),
+        }
+    }
+
+    /// Rewrites links to markdown files as route URLs (see
+    /// `HtmlWriter::with_link_rewriter`)
+    pub fn with_link_rewriter(mut self, links: LinkRewriter) -> Self {
+        self.links = Some(links);
+        self
+    }
+
+    /// Converts a parsed markdown document to Gemtext
+    ///
+    /// # Returns
+    /// * `Ok(String)` - The Gemtext, ending in a newline unless it's empty
+    /// * `Err(RenderError)` - If writing fails
+    pub fn write_gemtext(&self, document: &Document) -> Result<String, RenderError> {
+        renderer::render_to_string(self, document)
+    }
+
+    /// Streams the Gemtext for a document into a writer
+    ///
+    /// # Returns
+    /// * `Err(RenderError)` - If the writer returns an error
+    pub fn write_gemtext_to<W: io::Write>(
+        &self,
+        document: &Document,
+        writer: W,
+    ) -> Result<(), RenderError> {
+        renderer::render_to_io(self, document, writer)
+    }
+
+    /// Renders an event stream (e.g. `MarkdownParser::events`) to Gemtext
+    ///
+    /// # Returns
+    /// * `Ok(String)` - The same Gemtext as `write_gemtext` on the events' tree
+    /// * `Err(RenderError)` - For the first parse error in the stream
+    pub fn write_gemtext_events<'a, I>(&self, events: I) -> Result<String, RenderError>
+    where
+        I: IntoIterator<Item = Result<Event<'a>, ParseError>>,
+    {
+        renderer::render_events_to_string(self, events)
+    }
+
+    /// Builds a Gemini index page listing every page in a sitemap
+    ///
+    /// Gemtext has no nested lists, so the hierarchy is flattened into one
+    /// link line per page, named by its path (`home / about`).
+    ///
+    /// # Arguments
+    /// * `entries` - Top-level entries from `Router::build_sitemap`
+    pub fn write_sitemap(&self, entries: &[SitemapEntry]) -> String {
+        let mut output = format!("{} {}{}{}", HEADING_CHAR, INDEX_TITLE, NEWLINE, NEWLINE);
+        write_sitemap_links(&mut output, entries, "");
+        output
+    }
+
+    /// Writes a finished block, separated from the previous one by a blank
+    /// line and followed by its hoisted links
+    fn write_block(&self, out: &mut dyn fmt::Write, text: &str) -> fmt::Result {
+        let mut state = self.state.borrow_mut();
+        if state.started {
+            out.write_char(NEWLINE)?;
+        }
+        state.started = true;
+        out.write_str(text)?;
+        out.write_char(NEWLINE)?;
+
+        for (url, text) in state.links.drain(..) {
+            write_link_line(out, &url, &text)?;
+        }
+        Ok(())
+    }
+
+    fn rewrite_url(&self, url: &str) -> String {
+        self.links
+            .as_ref()
+            .and_then(|links| links.rewrite(url))
+            .unwrap_or_else(|| url.to_string())
+    }
+}
+
+impl Default for GemtextWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer for GemtextWriter {
+    type Error = RenderError;
+
+    fn begin(&self, _out: &mut dyn fmt::Write) -> Result<(), RenderError> {
+        self.state.replace(GemtextState::default());
+        Ok(())
+    }
+
+    fn start(&self, _out: &mut dyn fmt::Write, tag: &Tag) -> Result<(), RenderError> {
+        let mut state = self.state.borrow_mut();
+        match tag {
+            Tag::Heading(_) | Tag::Paragraph => state.block.clear(),
+            Tag::Link(url) => {
+                let url = self.rewrite_url(url);
+                let start = state.block.len();
+                state.open_links.push((url, start));
+            }
+            Tag::Section(_) | Tag::Bold | Tag::Italic => {}
+        }
+        Ok(())
+    }
+
+    fn end(&self, out: &mut dyn fmt::Write, tag: TagEnd) -> Result<(), RenderError> {
+        let block = match tag {
+            TagEnd::Heading(level) => {
+                let title = std::mem::take(&mut self.state.borrow_mut().block);
+                let marker: String =
+                    std::iter::repeat_n(HEADING_CHAR, usize::from(level.min(MAX_HEADING_LEVEL)))
+                        .collect();
+                format!("{}{}{}", marker, SPACE, title)
+            }
+            TagEnd::Paragraph => {
+                let block = std::mem::take(&mut self.state.borrow_mut().block);
+                block
+                    .split(NEWLINE)
+                    .map(guard_line)
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            TagEnd::Link => {
+                let mut state = self.state.borrow_mut();
+                if let Some((url, start)) = state.open_links.pop() {
+                    let text = state.block[start..].replace(NEWLINE, " ");
+                    state.links.push((url, text));
+                }
+                return Ok(());
+            }
+            TagEnd::Section(_) | TagEnd::Bold | TagEnd::Italic => return Ok(()),
+        };
+        Ok(self.write_block(out, &block)?)
+    }
+
+    fn text(&self, _out: &mut dyn fmt::Write, text: &str) -> Result<(), RenderError> {
+        self.state.borrow_mut().block.push_str(text);
+        Ok(())
+    }
+
+    fn line_break(&self, _out: &mut dyn fmt::Write) -> Result<(), RenderError> {
+        self.state.borrow_mut().block.push(NEWLINE);
+        Ok(())
+    }
+
+    fn image(
+        &self,
+        out: &mut dyn fmt::Write,
+        alt_text: &str,
+        url: &str,
+    ) -> Result<(), RenderError> {
+        let mut line = String::new();
+        write_link_line(&mut line, url, alt_text)?;
+        Ok(self.write_block(out, line.trim_end_matches(NEWLINE))?)
+    }
+
+    fn code_block(
+        &self,
+        out: &mut dyn fmt::Write,
+        info: &str,
+        code: &str,
+    ) -> Result<(), RenderError> {
+        let mut block = format!("{}{}{}", PREFORMAT_TOGGLE, info, NEWLINE);
+        for line in code.split(NEWLINE) {
+            // A toggle inside the code would end preformatting early
+            if line.starts_with(PREFORMAT_TOGGLE) {
+                block.push(SPACE);
+            }
+            block.push_str(line);
+            block.push(NEWLINE);
+        }
+        block.push_str(PREFORMAT_TOGGLE);
+        Ok(self.write_block(out, &block)?)
+    }
+}
+
+/// Writes `=> url text` (or `=> url` for empty text)
+fn write_link_line(out: &mut dyn fmt::Write, url: &str, text: &str) -> fmt::Result {
+    write!(out, "{}{}{}", LINK_LINE, SPACE, url)?;
+    if !text.is_empty() {
+        write!(out, "{}{}", SPACE, text)?;
+    }
+    out.write_char(NEWLINE)
+}
+
+/// Indents a text line by a space if it would otherwise be read as a link,
+/// heading, list item, quote or preformatting toggle
+fn guard_line(line: &str) -> String {
+    if LINE_MARKERS.iter().any(|marker| line.starts_with(marker)) {
+        format!("{}{}", SPACE, line)
+    } else {
+        line.to_string()
+    }
+}
+
+/// Writes one link line per entry, depth first, named by the path of names
+fn write_sitemap_links(output: &mut String, entries: &[SitemapEntry], parent: &str) {
+    for entry in entries {
+        let name = if parent.is_empty() {
+            entry.name.clone()
+        } else {
+            format!("{}{}{}", parent, INDEX_NAME_SEPARATOR, entry.name)
+        };
+        // Writing to a String can't fail
+        let _ = write_link_line(output, &entry.url_path, &name);
+        write_sitemap_links(output, &entry.children, &name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::MarkdownParser;
+
+    fn gemtext(source: &str) -> String {
+        GemtextWriter::new()
+            .write_gemtext(&MarkdownParser::parse(source).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_blocks() {
+        assert_eq!(
+            gemtext(
+                "Intro *a* **b**\nline two\n# One\n## Two\n### Three\n###### Six\n![A cat](cat.png)\n![](x.png)"
+            ),
+            "Intro a b\nline two\n\n# One\n\n## Two\n\n### Three\n\n### Six\n\n\
+             => cat.png A cat\n\n=> x.png\n"
+        );
+        assert_eq!(gemtext(""), "");
+    }
+
+    #[test]
+    fn test_links_hoisted_after_block() {
+        assert_eq!(
+            gemtext("Read [the *docs*](/docs) or [](https://x.org).\n\n# [Home](/)"),
+            "Read the docs or .\n=> /docs the docs\n=> https://x.org\n\n# Home\n=> / Home\n"
+        );
+    }
+
+    #[test]
+    fn test_line_type_guards() {
+        assert_eq!(
+            gemtext("\\# not\n=> nor\n> this\n\\* or this\n*fine*"),
+            " # not\n => nor\n > this\n * or this\nfine\n"
+        );
+        assert_eq!(
+            gemtext("````rust\nlet x = 1;\n```\n````"),
+            "```rust\nlet x = 1;\n ```\n```\n"
+        );
+    }
+
+    #[test]
+    fn test_link_rewriter() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir(root.join("docs")).unwrap();
+        std::fs::write(root.join("docs/docs.md"), "").unwrap();
+        std::fs::write(root.join("root.md"), "").unwrap();
+
+        let router = crate::router::Router::new(root.to_path_buf()).unwrap();
+        let writer =
+            GemtextWriter::new().with_link_rewriter(router.link_rewriter(&root.join("root.md")));
+        let doc = MarkdownParser::parse("[Docs](docs/docs.md#setup)").unwrap();
+        assert_eq!(
+            writer.write_gemtext(&doc).unwrap(),
+            "Docs\n=> /docs#setup Docs\n"
+        );
+    }
+
+    #[test]
+    fn test_write_sitemap() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir(root.join("home")).unwrap();
+        std::fs::write(root.join("root.md"), "").unwrap();
+        std::fs::write(root.join("home/home.md"), "").unwrap();
+        std::fs::write(root.join("home/about.md"), "").unwrap();
+
+        let sitemap = crate::router::Router::new(root.to_path_buf())
+            .unwrap()
+            .build_sitemap()
+            .unwrap();
+        assert_eq!(
+            GemtextWriter::new().write_sitemap(&sitemap),
+            "# Index\n\n=> / root\n=> /home home\n=> /home/about home / about\n"
+        );
+    }
+
+    #[test]
+    fn test_events_render_like_tree() {
+        renderer::assert_events_render_like_tree(&GemtextWriter::new(), renderer::EVENTS_FIXTURE);
+    }
+}
@@ -0,0 +1,499 @@
+use crate::parser::{Document, Event, ParseError, Tag, TagEnd};
+use crate::renderer::{self, RenderError, Renderer};
+use crate::router::{LinkRewriter, SitemapEntry};
+use crate::text_writer::wrap;
+use std::cell::RefCell;
+use std::fmt;
+use std::io;
+
+// Layout
+const DEFAULT_WIDTH: usize = 70;
+const CODE_INDENT: &str = "    ";
+const NEWLINE: char = '\n';
+const SPACE: char = ' ';
+const UNDERLINE_TITLE: char = '=';
+const UNDERLINE_SUBTITLE: char = '-';
+
+// Menu lines: `<type><display>\t<selector>\t<host>\t<port>\r\n`
+const FIELD_SEPARATOR: char = '\t';
+const LINE_END: &str = "\r\n";
+const MENU_END: &str = ".\r\n";
+const URL_SELECTOR_PREFIX: &str = "URL:";
+const URL_SCHEME_SEPARATOR: &str = "://";
+
+// Item types
+const TYPE_INFO: char = 'i';
+const TYPE_MENU: char = '1';
+const TYPE_URL: char = 'h';
+const TYPE_IMAGE: char = 'I';
+const TYPE_GIF: char = 'g';
+
+// Informational lines point nowhere
+const INFO_HOST: &str = "error.host";
+const INFO_PORT: u16 = 1;
+
+// Index menu built from the sitemap
+const INDEX_TITLE: &str = "Index";
+const INDEX_INDENT: &str = "  ";
+
+/// Gopher map writer for publishing pages as Gopher menus
+///
+/// Every line of the page becomes a menu item:
+/// - Text as `i` (informational) items: headings underlined like
+///   `TextWriter`, paragraphs wrapped at the configured width, code blocks
+///   indented by four spaces
+/// - Links hoisted to items after the block they appear in: local routes
+///   as `1` (menu) items on this server, other URLs as `h` items with a
+///   `URL:` selector
+/// - Images as `I` (or `g` for GIFs) items, or `h` items when remote
+/// - A blank info item between blocks and a `.` line at the end
+///
+/// Tabs in text would split an item's fields, so they become spaces.
+///
+/// # Example
+/// ```
+/// use wtf::gopher_writer::GopherWriter;
+/// use wtf::parser::MarkdownParser;
+///
+/// let doc = MarkdownParser::parse("See [docs](/docs)").unwrap();
+/// let map = GopherWriter::new("example.org", 70).write_gophermap(&doc).unwrap();
+/// assert_eq!(
+///     map,
+///     "iSee docs\t\terror.host\t1\r\n1docs\t/docs\texample.org\t70\r\n.\r\n"
+/// );
+/// ```
+pub struct GopherWriter {
+    host: String,
+    port: u16,
+    width: usize,
+    links: Option<LinkRewriter>,
+    state: RefCell<GopherState>,
+}
+
+/// The block being collected and the links to hoist after it
+#[derive(Default)]
+struct GopherState {
+    block: String,
+    /// URL and start offset in `block` of each open link
+    open_links: Vec<(String, usize)>,
+    /// URL and text of each finished link in the block
+    links: Vec<(String, String)>,
+    started: bool,
+}
+
+impl GopherWriter {
+    /// Creates a writer whose menu links point at the given server
+    ///
+    /// # Arguments
+    /// * `host` - Host name Gopher clients connect to for local pages
+    /// * `port` - Port of that server
+    pub fn new(host: impl Into<String>, port: u16) -> Self {
+        Self {
+            host: host.into(),
+            port,
+            width: DEFAULT_WIDTH,
+            links: None,
+            state: RefCell::new(GopherState::default()),
+        }
+    }
+
+    /// Sets the column paragraphs wrap at (default 70, 0 disables wrapping)
+    pub fn with_width(mut self, width: usize) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Rewrites links to markdown files as route selectors (see
+    /// `HtmlWriter::with_link_rewriter`), and links to other local files
+    /// as selectors from the site root
+    ///
+    /// Gopher selectors are absolute, so without a rewriter relative links
+    /// and images are written as given.
+    pub fn with_link_rewriter(mut self, links: LinkRewriter) -> Self {
+        self.links = Some(links);
+        self
+    }
+
+    /// Converts a parsed markdown document to a Gopher map
+    ///
+    /// # Returns
+    /// * `Ok(String)` - The menu, with CRLF line endings and a final `.` line
+    /// * `Err(RenderError)` - If writing fails
+    pub fn write_gophermap(&self, document: &Document) -> Result<String, RenderError> {
+        renderer::render_to_string(self, document)
+    }
+
+    /// Streams the Gopher map for a document into a writer
+    ///
+    /// # Returns
+    /// * `Err(RenderError)` - If the writer returns an error
+    pub fn write_gophermap_to<W: io::Write>(
+        &self,
+        document: &Document,
+        writer: W,
+    ) -> Result<(), RenderError> {
+        renderer::render_to_io(self, document, writer)
+    }
+
+    /// Renders an event stream (e.g. `MarkdownParser::events`) to a Gopher map
+    ///
+    /// # Returns
+    /// * `Ok(String)` - The same menu as `write_gophermap` on the events' tree
+    /// * `Err(RenderError)` - For the first parse error in the stream
+    pub fn write_gophermap_events<'a, I>(&self, events: I) -> Result<String, RenderError>
+    where
+        I: IntoIterator<Item = Result<Event<'a>, ParseError>>,
+    {
+        renderer::render_events_to_string(self, events)
+    }
+
+    /// Builds a Gopher index menu listing every page in a sitemap
+    ///
+    /// Nested pages are indented by two spaces per level.
+    ///
+    /// # Arguments
+    /// * `entries` - Top-level entries from `Router::build_sitemap`
+    pub fn write_sitemap(&self, entries: &[SitemapEntry]) -> String {
+        let mut output = String::new();
+        // Writing to a String can't fail
+        let _ = self
+            .write_info(&mut output, INDEX_TITLE)
+            .and_then(|_| self.write_info(&mut output, ""))
+            .and_then(|_| self.write_sitemap_items(&mut output, entries, 0));
+        output.push_str(MENU_END);
+        output
+    }
+
+    fn write_sitemap_items(
+        &self,
+        out: &mut dyn fmt::Write,
+        entries: &[SitemapEntry],
+        depth: usize,
+    ) -> fmt::Result {
+        for entry in entries {
+            let display = format!("{}{}", INDEX_INDENT.repeat(depth), entry.name);
+            self.write_item(out, TYPE_MENU, &display, &entry.url_path)?;
+            self.write_sitemap_items(out, &entry.children, depth + 1)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a finished block as info lines, separated from the previous
+    /// block by a blank info line and followed by its hoisted links
+    fn write_block(&self, out: &mut dyn fmt::Write, lines: &str) -> fmt::Result {
+        let links = {
+            let mut state = self.state.borrow_mut();
+            let separate = state.started;
+            state.started = true;
+            if separate {
+                self.write_info(out, "")?;
+            }
+            std::mem::take(&mut state.links)
+        };
+        for line in lines.split(NEWLINE) {
+            self.write_info(out, line)?;
+        }
+        for (url, text) in links {
+            self.write_link(out, &url, &text, TYPE_MENU)?;
+        }
+        Ok(())
+    }
+
+    fn write_info(&self, out: &mut dyn fmt::Write, text: &str) -> fmt::Result {
+        write_line(out, TYPE_INFO, text, "", INFO_HOST, INFO_PORT)
+    }
+
+    /// Writes a link item: `local_type` on this server for local routes,
+    /// an `h` item with a `URL:` selector for anything with a scheme
+    fn write_link(
+        &self,
+        out: &mut dyn fmt::Write,
+        url: &str,
+        text: &str,
+        local_type: char,
+    ) -> fmt::Result {
+        let display = if text.is_empty() { url } else { text };
+        if url.contains(URL_SCHEME_SEPARATOR) {
+            let selector = format!("{}{}", URL_SELECTOR_PREFIX, url);
+            self.write_item(out, TYPE_URL, display, &selector)
+        } else {
+            self.write_item(out, local_type, display, url)
+        }
+    }
+
+    fn write_item(
+        &self,
+        out: &mut dyn fmt::Write,
+        item_type: char,
+        display: &str,
+        selector: &str,
+    ) -> fmt::Result {
+        write_line(out, item_type, display, selector, &self.host, self.port)
+    }
+
+    /// Selector for a local link: its route for a page, its path from the
+    /// site root for any other file
+    fn rewrite_url(&self, url: &str) -> String {
+        self.links
+            .as_ref()
+            .and_then(|links| links.rewrite(url).or_else(|| links.rewrite_file(url)))
+            .unwrap_or_else(|| url.to_string())
+    }
+}
+
+impl Renderer for GopherWriter {
+    type Error = RenderError;
+
+    fn begin(&self, _out: &mut dyn fmt::Write) -> Result<(), RenderError> {
+        self.state.replace(GopherState::default());
+        Ok(())
+    }
+
+    fn finish(&self, out: &mut dyn fmt::Write) -> Result<(), RenderError> {
+        Ok(out.write_str(MENU_END)?)
+    }
+
+    fn start(&self, _out: &mut dyn fmt::Write, tag: &Tag) -> Result<(), RenderError> {
+        let mut state = self.state.borrow_mut();
+        match tag {
+            Tag::Heading(_) | Tag::Paragraph => state.block.clear(),
+            Tag::Link(url) => {
+                let url = self.rewrite_url(url);
+                let start = state.block.len();
+                state.open_links.push((url, start));
+            }
+            Tag::Section(_) | Tag::Bold | Tag::Italic => {}
+        }
+        Ok(())
+    }
+
+    fn end(&self, out: &mut dyn fmt::Write, tag: TagEnd) -> Result<(), RenderError> {
+        let block = match tag {
+            TagEnd::Heading(level) => {
+                let title = std::mem::take(&mut self.state.borrow_mut().block);
+                let underline_char = if level == 1 {
+                    UNDERLINE_TITLE
+                } else {
+                    UNDERLINE_SUBTITLE
+                };
+                let underline: String =
+                    std::iter::repeat_n(underline_char, title.chars().count()).collect();
+                format!("{}{}{}", title, NEWLINE, underline)
+            }
+            TagEnd::Paragraph => {
+                let block = std::mem::take(&mut self.state.borrow_mut().block);
+                wrap(&block, self.width)
+            }
+            TagEnd::Link => {
+                let mut state = self.state.borrow_mut();
+                if let Some((url, start)) = state.open_links.pop() {
+                    let text = state.block[start..].replace(NEWLINE, " ");
+                    state.links.push((url, text));
+                }
+                return Ok(());
+            }
+            TagEnd::Section(_) | TagEnd::Bold | TagEnd::Italic => return Ok(()),
+        };
+        Ok(self.write_block(out, &block)?)
+    }
+
+    fn text(&self, _out: &mut dyn fmt::Write, text: &str) -> Result<(), RenderError> {
+        self.state.borrow_mut().block.push_str(text);
+        Ok(())
+    }
+
+    fn line_break(&self, _out: &mut dyn fmt::Write) -> Result<(), RenderError> {
+        self.state.borrow_mut().block.push(NEWLINE);
+        Ok(())
+    }
+
+    fn image(
+        &self,
+        out: &mut dyn fmt::Write,
+        alt_text: &str,
+        url: &str,
+    ) -> Result<(), RenderError> {
+        {
+            let mut state = self.state.borrow_mut();
+            if state.started {
+                self.write_info(out, "")?;
+            }
+            state.started = true;
+        }
+        let item_type = if url.to_ascii_lowercase().ends_with(".gif") {
+            TYPE_GIF
+        } else {
+            TYPE_IMAGE
+        };
+        Ok(self.write_link(out, &self.rewrite_url(url), alt_text, item_type)?)
+    }
+
+    fn code_block(
+        &self,
+        out: &mut dyn fmt::Write,
+        _info: &str,
+        code: &str,
+    ) -> Result<(), RenderError> {
+        let indented: Vec<String> = code
+            .split(NEWLINE)
+            .map(|line| format!("{}{}", CODE_INDENT, line))
+            .collect();
+        Ok(self.write_block(out, &indented.join("\n"))?)
+    }
+}
+
+/// Writes one menu line, replacing characters that would break its fields
+fn write_line(
+    out: &mut dyn fmt::Write,
+    item_type: char,
+    display: &str,
+    selector: &str,
+    host: &str,
+    port: u16,
+) -> fmt::Result {
+    write!(
+        out,
+        "{}{}{}{}{}{}{}{}{}",
+        item_type,
+        sanitize_field(display),
+        FIELD_SEPARATOR,
+        sanitize_field(selector),
+        FIELD_SEPARATOR,
+        host,
+        FIELD_SEPARATOR,
+        port,
+        LINE_END
+    )
+}
+
+fn sanitize_field(field: &str) -> String {
+    field
+        .chars()
+        .filter(|&ch| ch != '\r')
+        .map(|ch| {
+            if ch == FIELD_SEPARATOR || ch == NEWLINE {
+                SPACE
+            } else {
+                ch
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::MarkdownParser;
+
+    fn gophermap(source: &str) -> String {
+        GopherWriter::new("localhost", 7070)
+            .write_gophermap(&MarkdownParser::parse(source).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_blocks() {
+        assert_eq!(
+            gophermap("# Title\n## Sub\nSome *text*\n```\nfn main() {}\n```"),
+            "iTitle\t\terror.host\t1\r\n\
+             i=====\t\terror.host\t1\r\n\
+             i\t\terror.host\t1\r\n\
+             iSub\t\terror.host\t1\r\n\
+             i---\t\terror.host\t1\r\n\
+             i\t\terror.host\t1\r\n\
+             iSome text\t\terror.host\t1\r\n\
+             i\t\terror.host\t1\r\n\
+             i    fn main() {}\t\terror.host\t1\r\n\
+             .\r\n"
+        );
+        assert_eq!(gophermap(""), ".\r\n");
+    }
+
+    #[test]
+    fn test_links_and_images() {
+        assert_eq!(
+            gophermap(
+                "Go [home](/) or [](https://x.org)\n\n![Cat](/cat.png)\n\n![](https://x.org/a.GIF)"
+            ),
+            "iGo home or\t\terror.host\t1\r\n\
+             1home\t/\tlocalhost\t7070\r\n\
+             hhttps://x.org\tURL:https://x.org\tlocalhost\t7070\r\n\
+             i\t\terror.host\t1\r\n\
+             ICat\t/cat.png\tlocalhost\t7070\r\n\
+             i\t\terror.host\t1\r\n\
+             hhttps://x.org/a.GIF\tURL:https://x.org/a.GIF\tlocalhost\t7070\r\n\
+             .\r\n"
+        );
+    }
+
+    #[test]
+    fn test_link_rewriter_makes_selectors_absolute() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir(root.join("guide")).unwrap();
+        std::fs::write(root.join("guide/intro.md"), "").unwrap();
+        std::fs::write(root.join("guide/pic.png"), "").unwrap();
+        std::fs::write(root.join("notes.txt"), "").unwrap();
+        std::fs::write(root.join("root.md"), "").unwrap();
+
+        let router = crate::router::Router::new(root.to_path_buf()).unwrap();
+        let writer = GopherWriter::new("localhost", 70)
+            .with_link_rewriter(router.link_rewriter(&root.join("guide/intro.md")));
+        let doc =
+            MarkdownParser::parse("[Home](../root.md) [notes](../notes.txt)\n\n![Pic](pic.png)")
+                .unwrap();
+        assert_eq!(
+            writer.write_gophermap(&doc).unwrap(),
+            "iHome notes\t\terror.host\t1\r\n\
+             1Home\t/\tlocalhost\t70\r\n\
+             1notes\t/notes.txt\tlocalhost\t70\r\n\
+             i\t\terror.host\t1\r\n\
+             IPic\t/guide/pic.png\tlocalhost\t70\r\n\
+             .\r\n"
+        );
+    }
+
+    #[test]
+    fn test_wrap_and_tabs() {
+        let writer = GopherWriter::new("h", 70).with_width(10);
+        let doc = MarkdownParser::parse("one two three\tfour").unwrap();
+        assert_eq!(
+            writer.write_gophermap(&doc).unwrap(),
+            "ione two\t\terror.host\t1\r\n\
+             ithree four\t\terror.host\t1\r\n\
+             .\r\n"
+        );
+    }
+
+    #[test]
+    fn test_write_sitemap() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir(root.join("home")).unwrap();
+        std::fs::write(root.join("root.md"), "").unwrap();
+        std::fs::write(root.join("home/home.md"), "").unwrap();
+        std::fs::write(root.join("home/about.md"), "").unwrap();
+
+        let sitemap = crate::router::Router::new(root.to_path_buf())
+            .unwrap()
+            .build_sitemap()
+            .unwrap();
+        assert_eq!(
+            GopherWriter::new("localhost", 70).write_sitemap(&sitemap),
+            "iIndex\t\terror.host\t1\r\n\
+             i\t\terror.host\t1\r\n\
+             1root\t/\tlocalhost\t70\r\n\
+             1home\t/home\tlocalhost\t70\r\n\
+             1  about\t/home/about\tlocalhost\t70\r\n\
+             .\r\n"
+        );
+    }
+
+    #[test]
+    fn test_events_render_like_tree() {
+        renderer::assert_events_render_like_tree(
+            &GopherWriter::new("localhost", 70),
+            renderer::EVENTS_FIXTURE,
+        );
+    }
+}
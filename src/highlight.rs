@@ -0,0 +1,1218 @@
+//! Server-side syntax highlighting for code blocks
+//!
+//! `highlight` splits code into tokens for a `Language` named by a code
+//! block's info string (` ```rust `). Classified tokens are written by
+//! `HtmlWriter` as `<span class="tok-...">`, so a theme styles highlighted
+//! code with CSS alone, no client-side JavaScript. The classes are shared by
+//! every language. With `HtmlConfig::class_prefix` they're prefixed like
+//! every class, after the span's own (`<span class="md-span md-tok-...">`):
+//!
+//! | Class            | Tokens                                              |
+//! |------------------|-----------------------------------------------------|
+//! | `tok-keyword`    | Keywords (`fn`, `def`, `if`, `done`, ...)           |
+//! | `tok-type`       | Type names, capitalized names and Rust lifetimes    |
+//! | `tok-function`   | Called and defined functions                        |
+//! | `tok-macro`      | Rust macro invocations (`println!`)                 |
+//! | `tok-string`     | String and character literals                       |
+//! | `tok-number`     | Numeric literals                                    |
+//! | `tok-literal`    | `true`/`false`/`null`/`None`, `ALL_CAPS` constants  |
+//! | `tok-comment`    | Comments                                            |
+//! | `tok-key`        | Keys in JSON objects, TOML tables and YAML mappings |
+//! | `tok-variable`   | Shell variables, YAML anchors and aliases           |
+//! | `tok-attribute`  | Rust attributes, Python decorators                  |
+//! | `tok-section`    | TOML table headers                                  |
+//!
+//! The tokenisers are deliberately shallow (one pass, no parsing), so
+//! unusual code may be classified loosely but is never lost: the token
+//! texts always concatenate back to the input.
+
+// Characters and markers shared by several languages
+const UNDERSCORE: u8 = b'_';
+const NEWLINE: u8 = b'\n';
+const ESCAPE: u8 = b'\\';
+const DOUBLE_QUOTE: u8 = b'"';
+const SINGLE_QUOTE: u8 = b'\'';
+const OPEN_PAREN: u8 = b'(';
+const OPEN_BRACKET: u8 = b'[';
+const CLOSE_BRACKET: u8 = b']';
+const CLOSE_BRACE: u8 = b'}';
+const TRIPLE_QUOTE_LEN: usize = 3;
+
+// Rust
+const RUST_ATTRIBUTE: &str = "#[";
+const RUST_INNER_ATTRIBUTE: &str = "#![";
+const RUST_MACRO_BANG: u8 = b'!';
+const RUST_NOT_EQUAL: &str = "!=";
+const RUST_BYTE_PREFIX: u8 = b'b';
+const RUST_RAW_PREFIX: u8 = b'r';
+const RUST_RAW_HASH: u8 = b'#';
+
+// Python
+const PYTHON_DECORATOR: u8 = b'@';
+const PYTHON_STRING_PREFIXES: &[u8] = b"rRbBfFuU";
+const PYTHON_MAX_PREFIX_LEN: usize = 2;
+
+// Shell
+const SHELL_VARIABLE: u8 = b'$';
+const SHELL_BRACED_VARIABLE: &str = "${";
+const SHELL_SPECIAL_VARIABLES: &[u8] = b"@#?*$!-";
+
+// TOML and YAML
+const TOML_TABLE: u8 = b'[';
+const YAML_ANCHOR: u8 = b'&';
+const YAML_ALIAS: u8 = b'*';
+const YAML_KEY_SEPARATOR: u8 = b':';
+const YAML_SEQUENCE_ITEM: u8 = b'-';
+const YAML_COMMENT: &str = "#";
+const YAML_SCALAR_STARTS: &[u8] = b":-[{,?";
+
+/// Separates the language from the rest of an info string (`rust,ignore`)
+const INFO_SEPARATORS: [char; 1] = [','];
+
+// ============================================================================
+// LANGUAGES
+// ============================================================================
+
+/// A language `highlight` can tokenise
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    Toml,
+    Yaml,
+    Json,
+    Shell,
+    Python,
+}
+
+impl Language {
+    /// Picks the language named by a code block's info string
+    ///
+    /// # Arguments
+    /// * `info` - The text after the opening fence; its first word (before
+    ///   whitespace or a comma) names the language, case-insensitively
+    ///
+    /// # Returns
+    /// * `Some(Language)` - For a known name or alias (`rs`, `yml`, `sh`,
+    ///   `bash`, `zsh`, `py`, ...)
+    /// * `None` - For an unknown or missing language
+    pub fn from_info(info: &str) -> Option<Self> {
+        let name = language_name(info)?.to_ascii_lowercase();
+        match name.as_str() {
+            "rust" | "rs" => Some(Language::Rust),
+            "toml" => Some(Language::Toml),
+            "yaml" | "yml" => Some(Language::Yaml),
+            "json" => Some(Language::Json),
+            "shell" | "sh" | "bash" | "zsh" => Some(Language::Shell),
+            "python" | "py" | "python3" => Some(Language::Python),
+            _ => None,
+        }
+    }
+
+    fn syntax(self) -> &'static Syntax {
+        match self {
+            Language::Rust => &RUST,
+            Language::Toml => &TOML,
+            Language::Yaml => &YAML,
+            Language::Json => &JSON,
+            Language::Shell => &SHELL,
+            Language::Python => &PYTHON,
+        }
+    }
+}
+
+/// The language word of a code block's info string, as written
+///
+/// # Returns
+/// * `Some(&str)` - The first word (`rust` for `rust,ignore extra`)
+/// * `None` - If the info string is blank
+pub fn language_name(info: &str) -> Option<&str> {
+    info.split_whitespace()
+        .next()?
+        .split(INFO_SEPARATORS)
+        .next()
+        .filter(|name| !name.is_empty())
+}
+
+// ============================================================================
+// TOKENS
+// ============================================================================
+
+/// What a highlighted token is, named by its CSS class
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Keyword,
+    Type,
+    Function,
+    Macro,
+    String,
+    Number,
+    Literal,
+    Comment,
+    Key,
+    Variable,
+    Attribute,
+    Section,
+}
+
+impl TokenKind {
+    /// The CSS class for this kind of token (see the module docs)
+    pub fn class(self) -> &'static str {
+        match self {
+            TokenKind::Keyword => "tok-keyword",
+            TokenKind::Type => "tok-type",
+            TokenKind::Function => "tok-function",
+            TokenKind::Macro => "tok-macro",
+            TokenKind::String => "tok-string",
+            TokenKind::Number => "tok-number",
+            TokenKind::Literal => "tok-literal",
+            TokenKind::Comment => "tok-comment",
+            TokenKind::Key => "tok-key",
+            TokenKind::Variable => "tok-variable",
+            TokenKind::Attribute => "tok-attribute",
+            TokenKind::Section => "tok-section",
+        }
+    }
+}
+
+/// A run of code: classified, or plain text (`kind` is `None`)
+///
+/// Neighbouring runs never share a kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: Option<TokenKind>,
+    pub text: &'a str,
+}
+
+/// Splits code into highlighted tokens
+///
+/// # Arguments
+/// * `language` - The language to tokenise as
+/// * `code` - The code block's contents
+///
+/// # Returns
+/// * `Vec<Token>` - Tokens whose texts concatenate to `code`
+///
+/// # Example
+/// ```
+/// use wtf::highlight::{Language, TokenKind, highlight};
+///
+/// let tokens = highlight(Language::Rust, "let x = 1;");
+/// assert_eq!(tokens[0].kind, Some(TokenKind::Keyword));
+/// assert_eq!(tokens[2].kind, Some(TokenKind::Number));
+/// ```
+pub fn highlight(language: Language, code: &str) -> Vec<Token<'_>> {
+    let mut scanner = Scanner {
+        language,
+        syntax: language.syntax(),
+        code,
+        bytes: code.as_bytes(),
+        pos: 0,
+        emitted: 0,
+        defining: false,
+        tokens: Vec::new(),
+    };
+    scanner.run();
+    scanner.tokens
+}
+
+// ============================================================================
+// SYNTAX TABLES
+// ============================================================================
+
+/// What the shared scanner needs to know about a language; the rest
+/// (raw strings, decorators, variables, ...) is handled per language
+struct Syntax {
+    keywords: &'static [&'static str],
+    literals: &'static [&'static str],
+    types: &'static [&'static str],
+    /// Keywords after which the next word is the name of a function
+    definitions: &'static [&'static str],
+    line_comment: Option<&'static str>,
+    block_comment: Option<(&'static str, &'static str)>,
+    /// The line comment only starts at the start of a word (`a#b` isn't one)
+    comment_needs_space: bool,
+    /// Quotes whose strings honour backslash escapes
+    quotes: &'static [u8],
+    /// Quotes whose strings are literal (no escapes)
+    raw_quotes: &'static [u8],
+    /// `"""` and `'''` open strings closed by the same triple
+    triple_quotes: bool,
+    /// Single-quoted strings may continue over newlines
+    multiline_strings: bool,
+    /// A string or word followed by this is a key
+    key_separator: Option<u8>,
+    /// Bytes besides letters, digits and `_` that continue a word
+    word_extra: &'static [u8],
+    /// Capitalized words are types, `ALL_CAPS` ones constants
+    capitalized_types: bool,
+    /// A word followed by `(` is a function
+    calls: bool,
+}
+
+static RUST: Syntax = Syntax {
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+        "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "type",
+        "unsafe", "use", "where", "while", "yield",
+    ],
+    literals: &["true", "false"],
+    types: &[
+        "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32",
+        "i64", "i128", "isize", "f32", "f64",
+    ],
+    definitions: &["fn"],
+    line_comment: Some("//"),
+    block_comment: Some(("/*", "*/")),
+    comment_needs_space: false,
+    quotes: b"\"",
+    raw_quotes: b"",
+    triple_quotes: false,
+    multiline_strings: true,
+    key_separator: None,
+    word_extra: b"",
+    capitalized_types: true,
+    calls: true,
+};
+
+static PYTHON: Syntax = Syntax {
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+        "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is",
+        "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while", "with",
+        "yield",
+    ],
+    literals: &["True", "False", "None"],
+    types: &[
+        "int",
+        "float",
+        "complex",
+        "str",
+        "bytes",
+        "bytearray",
+        "bool",
+        "list",
+        "dict",
+        "set",
+        "frozenset",
+        "tuple",
+        "object",
+    ],
+    definitions: &["def"],
+    line_comment: Some("#"),
+    block_comment: None,
+    comment_needs_space: false,
+    quotes: b"\"'",
+    raw_quotes: b"",
+    triple_quotes: true,
+    multiline_strings: false,
+    key_separator: None,
+    word_extra: b"",
+    capitalized_types: true,
+    calls: true,
+};
+
+static SHELL: Syntax = Syntax {
+    keywords: &[
+        "if", "then", "else", "elif", "fi", "for", "while", "until", "do", "done", "case", "esac",
+        "in", "function", "select", "return", "local", "export", "readonly", "declare", "unset",
+    ],
+    literals: &[],
+    types: &[],
+    definitions: &["function"],
+    line_comment: Some("#"),
+    block_comment: None,
+    comment_needs_space: true,
+    quotes: b"\"`",
+    raw_quotes: b"'",
+    triple_quotes: false,
+    multiline_strings: true,
+    key_separator: None,
+    word_extra: b"-",
+    capitalized_types: false,
+    calls: true,
+};
+
+static TOML: Syntax = Syntax {
+    keywords: &[],
+    literals: &["true", "false", "inf", "nan"],
+    types: &[],
+    definitions: &[],
+    line_comment: Some("#"),
+    block_comment: None,
+    comment_needs_space: false,
+    quotes: b"\"",
+    raw_quotes: b"'",
+    triple_quotes: true,
+    multiline_strings: false,
+    key_separator: Some(b'='),
+    word_extra: b"-.",
+    capitalized_types: false,
+    calls: false,
+};
+
+static YAML: Syntax = Syntax {
+    keywords: &[],
+    literals: &[
+        "true", "false", "null", "yes", "no", "on", "off", "True", "False", "Null", "TRUE",
+        "FALSE", "NULL",
+    ],
+    types: &[],
+    definitions: &[],
+    line_comment: Some(YAML_COMMENT),
+    block_comment: None,
+    comment_needs_space: true,
+    quotes: b"\"",
+    raw_quotes: b"'",
+    triple_quotes: false,
+    multiline_strings: false,
+    key_separator: Some(YAML_KEY_SEPARATOR),
+    word_extra: b"-",
+    capitalized_types: false,
+    calls: false,
+};
+
+static JSON: Syntax = Syntax {
+    keywords: &[],
+    literals: &["true", "false", "null"],
+    types: &[],
+    definitions: &[],
+    line_comment: None,
+    block_comment: None,
+    comment_needs_space: false,
+    quotes: b"\"",
+    raw_quotes: b"",
+    triple_quotes: false,
+    multiline_strings: false,
+    key_separator: Some(b':'),
+    word_extra: b"",
+    capitalized_types: false,
+    calls: false,
+};
+
+// ============================================================================
+// SCANNER
+// ============================================================================
+
+/// Single pass over the code: everything before `emitted` is in `tokens`,
+/// and the plain run `emitted..pos` is flushed when the next token starts
+struct Scanner<'a> {
+    language: Language,
+    syntax: &'static Syntax,
+    code: &'a str,
+    bytes: &'a [u8],
+    pos: usize,
+    emitted: usize,
+    /// The last word was a definition keyword (`fn`, `def`)
+    defining: bool,
+    tokens: Vec<Token<'a>>,
+}
+
+impl<'a> Scanner<'a> {
+    fn run(&mut self) {
+        while self.pos < self.bytes.len() {
+            // A token must cover at least one char, or the scan stalls
+            if let Some((kind, end)) = self.scan_token().filter(|&(_, end)| end > self.pos) {
+                self.token(kind, end);
+            } else {
+                self.pos += self.code[self.pos..]
+                    .chars()
+                    .next()
+                    .map_or(1, char::len_utf8);
+            }
+        }
+        self.push(None, self.bytes.len());
+    }
+
+    /// Classifies the token starting at `pos`, if any
+    ///
+    /// # Returns
+    /// * `Some((TokenKind, usize))` - The token's kind and end offset
+    /// * `None` - If the character at `pos` is plain text
+    fn scan_token(&mut self) -> Option<(TokenKind, usize)> {
+        let byte = self.bytes[self.pos];
+
+        if let Some(end) = self.scan_comment() {
+            return Some((TokenKind::Comment, end));
+        }
+        if let Some(token) = self.scan_language_specific() {
+            return Some(token);
+        }
+        if self.syntax.quotes.contains(&byte) || self.syntax.raw_quotes.contains(&byte) {
+            if self.language == Language::Yaml && !self.at_yaml_scalar_start() {
+                return None;
+            }
+            let end = self.scan_string(self.pos);
+            return Some((self.string_or_key(end), end));
+        }
+        if byte.is_ascii_digit() {
+            return Some((TokenKind::Number, self.scan_number()));
+        }
+        if is_word_start(byte) {
+            return self.scan_word();
+        }
+        None
+    }
+
+    // ========================================================================
+    // SHARED RULES
+    // ========================================================================
+
+    fn scan_comment(&self) -> Option<usize> {
+        let rest = &self.code[self.pos..];
+        if let Some(marker) = self.syntax.line_comment
+            && rest.starts_with(marker)
+            && (!self.syntax.comment_needs_space || self.after_space())
+        {
+            return Some(self.line_end(self.pos));
+        }
+        if let Some((open, close)) = self.syntax.block_comment
+            && rest.starts_with(open)
+        {
+            let body = self.pos + open.len();
+            return Some(
+                self.code[body..]
+                    .find(close)
+                    .map_or(self.bytes.len(), |offset| body + offset + close.len()),
+            );
+        }
+        None
+    }
+
+    /// Scans a string from its opening quote to just past the closing one
+    ///
+    /// Unterminated strings end at the end of the line (or of the code,
+    /// for languages whose strings span lines).
+    fn scan_string(&self, quote_pos: usize) -> usize {
+        let quote = self.bytes[quote_pos];
+        let escapes = !self.syntax.raw_quotes.contains(&quote);
+
+        if self.syntax.triple_quotes {
+            let triple = &self.bytes[quote_pos..];
+            if triple.len() >= TRIPLE_QUOTE_LEN
+                && triple[..TRIPLE_QUOTE_LEN].iter().all(|&b| b == quote)
+            {
+                return self.scan_until_quote(
+                    quote_pos + TRIPLE_QUOTE_LEN,
+                    quote,
+                    TRIPLE_QUOTE_LEN,
+                    escapes,
+                    true,
+                );
+            }
+        }
+
+        self.scan_until_quote(
+            quote_pos + 1,
+            quote,
+            1,
+            escapes,
+            self.syntax.multiline_strings,
+        )
+    }
+
+    /// Finds the end of a string body closed by `count` `quote`s
+    fn scan_until_quote(
+        &self,
+        start: usize,
+        quote: u8,
+        count: usize,
+        escapes: bool,
+        multiline: bool,
+    ) -> usize {
+        let mut i = start;
+        while i < self.bytes.len() {
+            match self.bytes[i] {
+                ESCAPE if escapes => i += 1,
+                NEWLINE if !multiline => return i,
+                byte if byte == quote
+                    && self.bytes[i..]
+                        .iter()
+                        .take(count)
+                        .filter(|&&b| b == quote)
+                        .count()
+                        == count =>
+                {
+                    return i + count;
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        self.bytes.len()
+    }
+
+    fn scan_number(&self) -> usize {
+        let hex =
+            self.bytes[self.pos..].starts_with(b"0x") || self.bytes[self.pos..].starts_with(b"0X");
+        let mut i = self.pos;
+        while i < self.bytes.len() {
+            let byte = self.bytes[i];
+            let continues = byte.is_ascii_alphanumeric()
+                || byte == UNDERSCORE
+                // A fraction, but not a range (`1..2`) or field access (`x.0.1`)
+                || (byte == b'.' && self.bytes.get(i + 1).is_some_and(u8::is_ascii_digit))
+                // An exponent sign (`1e-5`)
+                || ((byte == b'+' || byte == b'-')
+                    && !hex
+                    && matches!(self.bytes[i - 1], b'e' | b'E')
+                    && self.bytes.get(i + 1).is_some_and(u8::is_ascii_digit));
+            if !continues {
+                break;
+            }
+            i += 1;
+        }
+        i
+    }
+
+    fn scan_word(&mut self) -> Option<(TokenKind, usize)> {
+        let end = self.word_end(self.pos);
+        let word = &self.code[self.pos..end];
+        let syntax = self.syntax;
+        let defining = std::mem::replace(&mut self.defining, false);
+
+        if syntax.key_separator.is_some() && self.separator_follows(end) {
+            return Some((TokenKind::Key, end));
+        }
+        if syntax.keywords.contains(&word) {
+            self.defining = syntax.definitions.contains(&word);
+            return Some((TokenKind::Keyword, end));
+        }
+        if syntax.literals.contains(&word) {
+            return Some((TokenKind::Literal, end));
+        }
+        if syntax.types.contains(&word) {
+            return Some((TokenKind::Type, end));
+        }
+        if self.language == Language::Rust
+            && self.bytes.get(end) == Some(&RUST_MACRO_BANG)
+            && !self.code[end..].starts_with(RUST_NOT_EQUAL)
+        {
+            return Some((TokenKind::Macro, end + 1));
+        }
+        if defining || (syntax.calls && self.bytes.get(end) == Some(&OPEN_PAREN)) {
+            return Some((TokenKind::Function, end));
+        }
+        if syntax.capitalized_types && word.starts_with(|c: char| c.is_ascii_uppercase()) {
+            let constant = word.len() > 1
+                && word
+                    .bytes()
+                    .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit() || b == UNDERSCORE);
+            return Some((
+                if constant {
+                    TokenKind::Literal
+                } else {
+                    TokenKind::Type
+                },
+                end,
+            ));
+        }
+
+        // Plain words are skipped whole, so their digits aren't numbers
+        self.pos = end;
+        None
+    }
+
+    fn string_or_key(&self, end: usize) -> TokenKind {
+        if self.syntax.key_separator.is_some() && self.separator_follows(end) {
+            TokenKind::Key
+        } else {
+            TokenKind::String
+        }
+    }
+
+    // ========================================================================
+    // LANGUAGE-SPECIFIC RULES
+    // ========================================================================
+
+    fn scan_language_specific(&self) -> Option<(TokenKind, usize)> {
+        match self.language {
+            Language::Rust => self.scan_rust(),
+            Language::Python => self.scan_python(),
+            Language::Shell => self.scan_shell(),
+            Language::Toml => self.scan_toml(),
+            Language::Yaml => self.scan_yaml(),
+            Language::Json => None,
+        }
+    }
+
+    /// Attributes, character literals vs lifetimes, byte and raw strings
+    fn scan_rust(&self) -> Option<(TokenKind, usize)> {
+        let rest = &self.code[self.pos..];
+        if rest.starts_with(RUST_ATTRIBUTE) || rest.starts_with(RUST_INNER_ATTRIBUTE) {
+            return Some((TokenKind::Attribute, self.matching_bracket(self.pos)));
+        }
+
+        match self.bytes[self.pos] {
+            SINGLE_QUOTE => self.scan_rust_char(self.pos),
+            RUST_BYTE_PREFIX | RUST_RAW_PREFIX => {
+                let mut i = self.pos;
+                if self.bytes[i] == RUST_BYTE_PREFIX {
+                    i += 1;
+                    match self.bytes.get(i) {
+                        Some(&DOUBLE_QUOTE) => {
+                            return Some((TokenKind::String, self.scan_string(i)));
+                        }
+                        Some(&SINGLE_QUOTE) => {
+                            return self
+                                .scan_rust_char(i)
+                                .filter(|(kind, _)| *kind == TokenKind::String);
+                        }
+                        _ => {}
+                    }
+                }
+                if self.bytes.get(i) != Some(&RUST_RAW_PREFIX) {
+                    return None;
+                }
+                let hashes = self.bytes[i + 1..]
+                    .iter()
+                    .take_while(|&&b| b == RUST_RAW_HASH)
+                    .count();
+                let body = i + 1 + hashes;
+                if self.bytes.get(body) != Some(&DOUBLE_QUOTE) {
+                    return None;
+                }
+                let close: String = std::iter::once('"')
+                    .chain(std::iter::repeat_n('#', hashes))
+                    .collect();
+                let end = self.code[body + 1..]
+                    .find(&close)
+                    .map_or(self.bytes.len(), |offset| body + 1 + offset + close.len());
+                Some((TokenKind::String, end))
+            }
+            _ => None,
+        }
+    }
+
+    /// `'x'`, `'\n'` and `'\u{..}'` are characters; `'a` is a lifetime
+    fn scan_rust_char(&self, quote_pos: usize) -> Option<(TokenKind, usize)> {
+        let next = quote_pos + 1;
+        if self.bytes.get(next) == Some(&ESCAPE) {
+            let end = self.bytes[next + 1..]
+                .iter()
+                .position(|&b| b == SINGLE_QUOTE || b == NEWLINE)
+                .map_or(self.bytes.len(), |offset| next + 1 + offset + 1);
+            return Some((TokenKind::String, end));
+        }
+
+        let ch = self.code[next..].chars().next()?;
+        let after = next + ch.len_utf8();
+        if self.bytes.get(after) == Some(&SINGLE_QUOTE) {
+            return Some((TokenKind::String, after + 1));
+        }
+        if ch.is_ascii() && is_word_start(ch as u8) {
+            return Some((TokenKind::Type, self.word_end(next)));
+        }
+        None
+    }
+
+    /// Decorators at the start of a line, prefixed strings (`f"..."`)
+    fn scan_python(&self) -> Option<(TokenKind, usize)> {
+        let byte = self.bytes[self.pos];
+        if byte == PYTHON_DECORATOR && self.at_line_start(b"") {
+            let mut end = self.pos + 1;
+            while end < self.bytes.len()
+                && (is_word_byte(self.bytes[end]) || self.bytes[end] == b'.')
+            {
+                end += 1;
+            }
+            return Some((TokenKind::Attribute, end));
+        }
+
+        let prefix_len = self.bytes[self.pos..]
+            .iter()
+            .take(PYTHON_MAX_PREFIX_LEN + 1)
+            .take_while(|b| PYTHON_STRING_PREFIXES.contains(b))
+            .count();
+        if (1..=PYTHON_MAX_PREFIX_LEN).contains(&prefix_len)
+            && let Some(&quote) = self.bytes.get(self.pos + prefix_len)
+            && self.syntax.quotes.contains(&quote)
+        {
+            return Some((TokenKind::String, self.scan_string(self.pos + prefix_len)));
+        }
+        None
+    }
+
+    /// `$NAME`, `${NAME...}`, `$1` and special parameters (`$?`)
+    fn scan_shell(&self) -> Option<(TokenKind, usize)> {
+        if self.bytes[self.pos] != SHELL_VARIABLE {
+            return None;
+        }
+        if self.code[self.pos..].starts_with(SHELL_BRACED_VARIABLE) {
+            let end = self.bytes[self.pos..]
+                .iter()
+                .position(|&b| b == CLOSE_BRACE || b == NEWLINE)
+                .map_or(self.bytes.len(), |offset| self.pos + offset + 1);
+            return Some((TokenKind::Variable, end));
+        }
+
+        let next = *self.bytes.get(self.pos + 1)?;
+        if next.is_ascii_digit() || SHELL_SPECIAL_VARIABLES.contains(&next) {
+            return Some((TokenKind::Variable, self.pos + 2));
+        }
+        if is_word_start(next) {
+            // Variable names don't continue with '-' like other shell words
+            let end = self.bytes[self.pos + 1..]
+                .iter()
+                .position(|&b| !is_word_byte(b))
+                .map_or(self.bytes.len(), |offset| self.pos + 1 + offset);
+            return Some((TokenKind::Variable, end));
+        }
+        None
+    }
+
+    /// `[table]` and `[[array]]` headers at the start of a line
+    fn scan_toml(&self) -> Option<(TokenKind, usize)> {
+        if self.bytes[self.pos] == TOML_TABLE && self.at_line_start(b"") {
+            return Some((TokenKind::Section, self.matching_bracket(self.pos)));
+        }
+        None
+    }
+
+    /// Mapping keys (`name: value`, `- name: value`), anchors and aliases
+    fn scan_yaml(&self) -> Option<(TokenKind, usize)> {
+        let byte = self.bytes[self.pos];
+        if (byte == YAML_ANCHOR || byte == YAML_ALIAS)
+            && self
+                .bytes
+                .get(self.pos + 1)
+                .is_some_and(|&b| is_word_start(b))
+        {
+            let end = self.word_end(self.pos + 1);
+            return Some((TokenKind::Variable, end));
+        }
+
+        let is_sequence_item = byte == YAML_SEQUENCE_ITEM
+            && self
+                .bytes
+                .get(self.pos + 1)
+                .is_none_or(u8::is_ascii_whitespace);
+        let is_plain_start = !(is_sequence_item
+            || byte.is_ascii_whitespace()
+            || self.syntax.quotes.contains(&byte)
+            || self.syntax.raw_quotes.contains(&byte)
+            || b"#[{".contains(&byte));
+        if !is_plain_start || !self.at_line_start(&[YAML_SEQUENCE_ITEM]) {
+            return None;
+        }
+
+        // A plain key runs to the first ': ' (or ':' ending the line) on the line
+        let line_end = self.line_end(self.pos);
+        let mut i = self.pos;
+        while i < line_end {
+            match self.bytes[i] {
+                YAML_KEY_SEPARATOR if self.bytes.get(i + 1).is_none_or(u8::is_ascii_whitespace) => {
+                    // `: value` with no key before it (e.g. after `? key`)
+                    return (i > self.pos).then_some((TokenKind::Key, i));
+                }
+                b'#' if self.bytes[i - 1].is_ascii_whitespace() => return None,
+                _ => i += 1,
+            }
+        }
+        None
+    }
+
+    // ========================================================================
+    // POSITION HELPERS
+    // ========================================================================
+
+    /// Emits the plain run before `pos`, then `pos..end` as a token
+    fn token(&mut self, kind: TokenKind, end: usize) {
+        self.push(None, self.pos);
+        self.push(Some(kind), end);
+        self.pos = end;
+    }
+
+    /// Emits `emitted..end`, merged into the last token if it's the same kind
+    fn push(&mut self, kind: Option<TokenKind>, end: usize) {
+        if end <= self.emitted {
+            return;
+        }
+        match self.tokens.last_mut() {
+            Some(last) if last.kind == kind => {
+                last.text = &self.code[self.emitted - last.text.len()..end];
+            }
+            _ => self.tokens.push(Token {
+                kind,
+                text: &self.code[self.emitted..end],
+            }),
+        }
+        self.emitted = end;
+    }
+
+    fn word_end(&self, start: usize) -> usize {
+        let extra = self.syntax.word_extra;
+        self.bytes[start..]
+            .iter()
+            .position(|&b| !(is_word_byte(b) || extra.contains(&b)))
+            .map_or(self.bytes.len(), |offset| start + offset)
+    }
+
+    fn line_end(&self, start: usize) -> usize {
+        self.bytes[start..]
+            .iter()
+            .position(|&b| b == NEWLINE)
+            .map_or(self.bytes.len(), |offset| start + offset)
+    }
+
+    /// Offset just past the `]` closing the bracket at `start`, or the end
+    /// of the code if it's never closed
+    fn matching_bracket(&self, start: usize) -> usize {
+        let mut depth = 0usize;
+        for (offset, &byte) in self.bytes[start..].iter().enumerate() {
+            match byte {
+                OPEN_BRACKET => depth += 1,
+                CLOSE_BRACKET => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return start + offset + 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.bytes.len()
+    }
+
+    /// Whether only whitespace (and any of `extra`) precedes `pos` on its line
+    fn at_line_start(&self, extra: &[u8]) -> bool {
+        self.bytes[..self.pos]
+            .iter()
+            .rev()
+            .take_while(|&&b| b != NEWLINE)
+            .all(|b| b.is_ascii_whitespace() || extra.contains(b))
+    }
+
+    fn after_space(&self) -> bool {
+        self.pos == 0 || self.bytes[self.pos - 1].is_ascii_whitespace()
+    }
+
+    /// Whether a quote at `pos` opens a YAML scalar (rather than being an
+    /// apostrophe inside one, as in `title: It's here`)
+    fn at_yaml_scalar_start(&self) -> bool {
+        self.bytes[..self.pos]
+            .iter()
+            .rev()
+            .find(|&&b| b != b' ' && b != b'\t')
+            .is_none_or(|b| *b == NEWLINE || YAML_SCALAR_STARTS.contains(b))
+    }
+
+    /// Whether the language's key separator follows `end`, after spaces
+    fn separator_follows(&self, end: usize) -> bool {
+        let Some(separator) = self.syntax.key_separator else {
+            return false;
+        };
+        let i = end
+            + self.bytes[end..]
+                .iter()
+                .take_while(|&&b| b == b' ' || b == b'\t')
+                .count();
+        if self.bytes.get(i) != Some(&separator) {
+            return false;
+        }
+        // YAML keys need a space after the colon (`a:b` is a plain scalar)
+        self.language != Language::Yaml || self.bytes.get(i + 1).is_none_or(u8::is_ascii_whitespace)
+    }
+}
+
+fn is_word_start(byte: u8) -> bool {
+    byte.is_ascii_alphabetic() || byte == UNDERSCORE
+}
+
+fn is_word_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == UNDERSCORE
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Renders tokens as `kind:text` for classified runs, text for plain ones
+    fn marked(language: Language, code: &str) -> Vec<String> {
+        let tokens = highlight(language, code);
+        assert_eq!(tokens.iter().map(|t| t.text).collect::<String>(), code);
+        assert!(tokens.windows(2).all(|pair| pair[0].kind != pair[1].kind));
+        tokens
+            .iter()
+            .map(|token| match token.kind {
+                Some(kind) => format!("{}:{}", &kind.class()["tok-".len()..], token.text),
+                None => token.text.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_language_from_info() {
+        assert_eq!(Language::from_info("rust"), Some(Language::Rust));
+        assert_eq!(
+            Language::from_info("  RS,ignore extra"),
+            Some(Language::Rust)
+        );
+        assert_eq!(Language::from_info("yml"), Some(Language::Yaml));
+        assert_eq!(Language::from_info("bash"), Some(Language::Shell));
+        assert_eq!(Language::from_info("py title=x"), Some(Language::Python));
+        assert_eq!(Language::from_info("cobol"), None);
+        assert_eq!(Language::from_info(""), None);
+        assert_eq!(language_name("rust,no_run"), Some("rust"));
+        assert_eq!(language_name(",x"), None);
+    }
+
+    #[test]
+    fn test_rust() {
+        assert_eq!(
+            marked(
+                Language::Rust,
+                "#[derive(Debug)]\npub fn first<'a>(s: &'a str) -> Option<u8> {\n    \
+                 println!(\"{}\", '\\n'); // done\n    s.len().max(MAX_LEN) != 0x1F\n}"
+            ),
+            vec![
+                "attribute:#[derive(Debug)]",
+                "\n",
+                "keyword:pub",
+                " ",
+                "keyword:fn",
+                " ",
+                "function:first",
+                "<",
+                "type:'a",
+                ">(s: &",
+                "type:'a",
+                " ",
+                "type:str",
+                ") -> ",
+                "type:Option",
+                "<",
+                "type:u8",
+                "> {\n    ",
+                "macro:println!",
+                "(",
+                "string:\"{}\"",
+                ", ",
+                "string:'\\n'",
+                "); ",
+                "comment:// done",
+                "\n    s.",
+                "function:len",
+                "().",
+                "function:max",
+                "(",
+                "literal:MAX_LEN",
+                ") != ",
+                "number:0x1F",
+                "\n}",
+            ]
+        );
+        assert_eq!(
+            marked(
+                Language::Rust,
+                "r#\"a \"quoted\" b\"# b'x' 1..2 1.5e-3 x1 /* c */"
+            ),
+            vec![
+                "string:r#\"a \"quoted\" b\"#",
+                " ",
+                "string:b'x'",
+                " ",
+                "number:1",
+                "..",
+                "number:2",
+                " ",
+                "number:1.5e-3",
+                " x1 ",
+                "comment:/* c */",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_python() {
+        assert_eq!(
+            marked(
+                Language::Python,
+                "@app.route\ndef run(x: int) -> None:\n    return f\"{x}\" if x else '''a\nb'''  # note"
+            ),
+            vec![
+                "attribute:@app.route",
+                "\n",
+                "keyword:def",
+                " ",
+                "function:run",
+                "(x: ",
+                "type:int",
+                ") -> ",
+                "literal:None",
+                ":\n    ",
+                "keyword:return",
+                " ",
+                "string:f\"{x}\"",
+                " ",
+                "keyword:if",
+                " x ",
+                "keyword:else",
+                " ",
+                "string:'''a\nb'''",
+                "  ",
+                "comment:# note",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shell() {
+        assert_eq!(
+            marked(
+                Language::Shell,
+                "if [ -n \"$HOME\" ]; then\n  echo ${USER:-me} $1 'a#b' # hi\nfi"
+            ),
+            vec![
+                "keyword:if",
+                " [ -n ",
+                "string:\"$HOME\"",
+                " ]; ",
+                "keyword:then",
+                "\n  echo ",
+                "variable:${USER:-me}",
+                " ",
+                "variable:$1",
+                " ",
+                "string:'a#b'",
+                " ",
+                "comment:# hi",
+                "\n",
+                "keyword:fi",
+            ]
+        );
+        assert_eq!(
+            marked(Language::Shell, "cargo build --target-dir=x#y"),
+            vec!["cargo build --target-dir=x#y"]
+        );
+    }
+
+    #[test]
+    fn test_toml() {
+        assert_eq!(
+            marked(
+                Language::Toml,
+                "[package]\nname = \"wtf\" # crate\nedition = '2024'\n\n[dependencies]\n\
+                 serde.version = \"1\"\nflags = [true, 1.5]\n\"quoted key\" = { opt-level = 3 }"
+            ),
+            vec![
+                "section:[package]",
+                "\n",
+                "key:name",
+                " = ",
+                "string:\"wtf\"",
+                " ",
+                "comment:# crate",
+                "\n",
+                "key:edition",
+                " = ",
+                "string:'2024'",
+                "\n\n",
+                "section:[dependencies]",
+                "\n",
+                "key:serde.version",
+                " = ",
+                "string:\"1\"",
+                "\n",
+                "key:flags",
+                " = [",
+                "literal:true",
+                ", ",
+                "number:1.5",
+                "]\n",
+                "key:\"quoted key\"",
+                " = { ",
+                "key:opt-level",
+                " = ",
+                "number:3",
+                " }",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_yaml() {
+        assert_eq!(
+            marked(
+                Language::Yaml,
+                "title: It's here # c\nitems:\n  - full name: \"x\"\n    on: &me true\n  - *me\nurl: a:b"
+            ),
+            vec![
+                "key:title",
+                ": It's here ",
+                "comment:# c",
+                "\n",
+                "key:items",
+                ":\n  - ",
+                "key:full name",
+                ": ",
+                "string:\"x\"",
+                "\n    ",
+                "key:on",
+                ": ",
+                "variable:&me",
+                " ",
+                "literal:true",
+                "\n  - ",
+                "variable:*me",
+                "\n",
+                "key:url",
+                ": a:b",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_yaml_separator_without_key() {
+        assert_eq!(marked(Language::Yaml, ": x"), vec![": x"]);
+        assert_eq!(marked(Language::Yaml, ":"), vec![":"]);
+        assert_eq!(marked(Language::Yaml, "? a\n: b"), vec!["? a\n: b"]);
+    }
+
+    #[test]
+    fn test_json() {
+        assert_eq!(
+            marked(
+                Language::Json,
+                "{\"a\": [1, -2.5e+3, null], \"b\" : \"c\\\"d\", \"e\": false}"
+            ),
+            vec![
+                "{",
+                "key:\"a\"",
+                ": [",
+                "number:1",
+                ", -",
+                "number:2.5e+3",
+                ", ",
+                "literal:null",
+                "], ",
+                "key:\"b\"",
+                " : ",
+                "string:\"c\\\"d\"",
+                ", ",
+                "key:\"e\"",
+                ": ",
+                "literal:false",
+                "}",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unterminated_and_non_ascii() {
+        assert_eq!(
+            marked(Language::Json, "\"open\n\"é\" ü"),
+            vec!["string:\"open", "\n", "string:\"é\"", " ü"]
+        );
+        assert_eq!(
+            marked(Language::Rust, "/* never closed\n'é' 'ü"),
+            vec!["comment:/* never closed\n'é' 'ü"]
+        );
+        assert_eq!(marked(Language::Rust, "'"), vec!["'"]);
+        assert!(highlight(Language::Python, "").is_empty());
+    }
+}
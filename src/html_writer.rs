@@ -1,5 +1,11 @@
-use crate::parser::{BlockNode, Document, InlineNode, Section};
+use crate::highlight::{self, Language};
+use crate::image_size::{ImageSize, read_image_size};
+use crate::parser::{Document, Event, ParseError, Tag, TagEnd};
+use crate::renderer::{self, Renderer};
+use crate::router::LinkRewriter;
+use std::cell::Cell;
 use std::fmt;
+use std::io;
 
 // HTML entity escape sequences
 const ESCAPE_AMP: &str = "&amp;";
@@ -8,32 +14,54 @@ const ESCAPE_GT: &str = "&gt;";
 const ESCAPE_QUOT: &str = "&quot;";
 const ESCAPE_APOS: &str = "&#39;";
 
-// HTML tag constants - no string literals flying around
-const TAG_H1_OPEN: &str = "<h1>";
-const TAG_H1_CLOSE: &str = "</h1>";
-const TAG_H2_OPEN: &str = "<h2>";
-const TAG_H2_CLOSE: &str = "</h2>";
-const TAG_H3_OPEN: &str = "<h3>";
-const TAG_H3_CLOSE: &str = "</h3>";
-const TAG_H4_OPEN: &str = "<h4>";
-const TAG_H4_CLOSE: &str = "</h4>";
-const TAG_H5_OPEN: &str = "<h5>";
-const TAG_H5_CLOSE: &str = "</h5>";
-const TAG_H6_OPEN: &str = "<h6>";
-const TAG_H6_CLOSE: &str = "</h6>";
-const TAG_P_OPEN: &str = "<p>";
-const TAG_P_CLOSE: &str = "</p>";
-const TAG_STRONG_OPEN: &str = "<strong>";
-const TAG_STRONG_CLOSE: &str = "</strong>";
-const TAG_EM_OPEN: &str = "<em>";
-const TAG_EM_CLOSE: &str = "</em>";
-const TAG_BR: &str = "<br>";
-const TAG_A_OPEN: &str = "<a href=\"";
-const TAG_A_MIDDLE: &str = "\">";
-const TAG_A_CLOSE: &str = "</a>";
-const TAG_IMG_OPEN: &str = "<img src=\"";
-const TAG_IMG_MIDDLE: &str = "\" alt=\"";
-const TAG_IMG_CLOSE: &str = "\">";
+// HTML element names - no string literals flying around
+const TAG_HEADINGS: [&str; 6] = ["h1", "h2", "h3", "h4", "h5", "h6"];
+const TAG_P: &str = "p";
+const TAG_STRONG: &str = "strong";
+const TAG_EM: &str = "em";
+const TAG_BR: &str = "br";
+const TAG_A: &str = "a";
+const TAG_IMG: &str = "img";
+const TAG_SECTION: &str = "section";
+const TAG_FIGURE: &str = "figure";
+const TAG_FIGCAPTION: &str = "figcaption";
+const TAG_PRE: &str = "pre";
+const TAG_CODE: &str = "code";
+const TAG_SPAN: &str = "span";
+
+// Attribute and tag punctuation
+const ATTR_CLASS: &str = " class=\"";
+const ATTR_HREF: &str = " href=\"";
+const ATTR_SRC: &str = " src=\"";
+const ATTR_ALT: &str = " alt=\"";
+const ATTR_TARGET: &str = " target=\"";
+const ATTR_REL: &str = " rel=\"";
+const ATTR_WIDTH: &str = " width=\"";
+const ATTR_HEIGHT: &str = " height=\"";
+const ATTR_LOADING: &str = " loading=\"";
+const ATTR_DECODING: &str = " decoding=\"";
+const ATTR_END: &str = "\"";
+const TAG_START: char = '<';
+const TAG_CLOSE_START: &str = "</";
+const TAG_END: &str = ">";
+const TAG_END_VOID_XHTML: &str = " />";
+
+// External links
+const EXTERNAL_PREFIXES: [&str; 3] = ["http://", "https://", "//"];
+const TARGET_BLANK: &str = "_blank";
+const REL_NOOPENER: &str = "noopener noreferrer";
+const CLASS_SEPARATOR: char = ' ';
+
+// Images
+const LOADING_LAZY: &str = "lazy";
+const DECODING_ASYNC: &str = "async";
+
+// Code blocks
+const LANGUAGE_CLASS_PREFIX: &str = "language-";
+
+// Pretty-printing
+const INDENT: &str = "  ";
+const NEWLINE: char = '\n';
 
 const MIN_HEADING_LEVEL: u8 = 1;
 const MAX_HEADING_LEVEL: u8 = 6;
@@ -41,7 +69,10 @@ const MAX_HEADING_LEVEL: u8 = 6;
 #[derive(Debug, Clone, PartialEq)]
 pub enum HtmlError {
     InvalidHeadingLevel(u8),
-    // Future errors can be added here as the package evolves
+    /// The event stream being rendered ended in a parse error
+    ParseError(ParseError),
+    /// The output writer failed
+    WriteError(String),
 }
 
 impl fmt::Display for HtmlError {
@@ -52,65 +83,235 @@ impl fmt::Display for HtmlError {
                 "Invalid heading level: {}. Heading level must be between {} and {} (inclusive)",
                 level, MIN_HEADING_LEVEL, MAX_HEADING_LEVEL
             ),
+            HtmlError::ParseError(error) => write!(f, "Parse error: {}", error),
+            HtmlError::WriteError(message) => write!(f, "Write error: {}", message),
         }
     }
 }
 
 impl std::error::Error for HtmlError {}
 
-/// Configuration for HTML writer
-/// This is a placeholder for future blog-specific customization:
-/// - CSS classes for elements
-/// - Custom wrapper tags (article, section)
-/// - ID generation for headings (anchor links, TOC)
-/// - Custom attributes (data-*, aria-*)
-/// - Metadata handling
-#[derive(Debug, Clone)]
-struct HtmlConfig {
-    // Reserved for future blog customization
+impl From<ParseError> for HtmlError {
+    fn from(error: ParseError) -> Self {
+        HtmlError::ParseError(error)
+    }
 }
 
-impl Default for HtmlConfig {
-    fn default() -> Self {
-        Self {}
+impl From<fmt::Error> for HtmlError {
+    fn from(_: fmt::Error) -> Self {
+        HtmlError::WriteError("formatter error".to_string())
+    }
+}
+
+impl From<io::Error> for HtmlError {
+    fn from(error: io::Error) -> Self {
+        HtmlError::WriteError(error.to_string())
+    }
+}
+
+/// Output options for `HtmlWriter`
+///
+/// The default is minified HTML5 with plain tags, matching `HtmlWriter::new`.
+///
+/// # Example
+/// ```
+/// use wtf::html_writer::{HtmlConfig, HtmlWriter};
+/// use wtf::parser::MarkdownParser;
+///
+/// let config = HtmlConfig::new()
+///     .with_heading_offset(1)
+///     .with_class_prefix("md-");
+/// let doc = MarkdownParser::parse("# Hi").unwrap();
+/// let html = HtmlWriter::with_config(config).write_html(&doc).unwrap();
+/// assert_eq!(html, "<h2 class=\"md-h2\">Hi</h2>");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HtmlConfig {
+    /// Close void elements XHTML-style (`<br />` instead of `<br>`)
+    pub xhtml: bool,
+    /// Put each block on its own line, indented by section nesting
+    pub pretty: bool,
+    /// Wrap each section (heading and content) in a `<section>` element
+    pub sections: bool,
+    /// Added to every heading level, capped at `<h6>` (1 renders `#` as `<h2>`)
+    pub heading_offset: u8,
+    /// Gives every generated element a `class` of this prefix plus its tag
+    /// name (`md-` gives `<p class="md-p">`), and prefixes highlight token
+    /// classes (`md-tok-keyword`)
+    pub class_prefix: Option<String>,
+    /// Open external (`http(s)://`) links in a new tab (`target="_blank"`)
+    pub external_new_tab: bool,
+    /// Add `rel="noopener noreferrer"` to external links
+    pub external_noopener: bool,
+    /// Extra class for external links
+    pub external_class: Option<String>,
+    /// Render images as `<figure>`, captioned with the alt text
+    pub figures: bool,
+    /// Add `loading="lazy"` and `decoding="async"` to images
+    pub lazy_images: bool,
+    /// Add `width` and `height` to images that are local files, read from
+    /// their headers (needs a `LinkRewriter` to find the files)
+    pub image_dimensions: bool,
+    /// Highlight code blocks in known languages with `<span class="tok-...">`
+    /// (see `highlight` for the classes)
+    pub highlight: bool,
+}
+
+impl HtmlConfig {
+    /// Creates the default configuration (minified HTML5, no extras)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enables or disables XHTML-style self-closing void elements
+    pub fn with_xhtml(mut self, xhtml: bool) -> Self {
+        self.xhtml = xhtml;
+        self
+    }
+
+    /// Enables or disables indented, one-block-per-line output
+    pub fn with_pretty(mut self, pretty: bool) -> Self {
+        self.pretty = pretty;
+        self
+    }
+
+    /// Enables or disables `<section>` wrappers around sections
+    pub fn with_sections(mut self, sections: bool) -> Self {
+        self.sections = sections;
+        self
+    }
+
+    /// Sets how many levels headings are shifted down
+    pub fn with_heading_offset(mut self, heading_offset: u8) -> Self {
+        self.heading_offset = heading_offset;
+        self
+    }
+
+    /// Sets the class prefix for generated elements
+    pub fn with_class_prefix(mut self, class_prefix: impl Into<String>) -> Self {
+        self.class_prefix = Some(class_prefix.into());
+        self
+    }
+
+    /// Enables or disables `target="_blank"` on external links
+    pub fn with_external_new_tab(mut self, external_new_tab: bool) -> Self {
+        self.external_new_tab = external_new_tab;
+        self
+    }
+
+    /// Enables or disables `rel="noopener noreferrer"` on external links
+    pub fn with_external_noopener(mut self, external_noopener: bool) -> Self {
+        self.external_noopener = external_noopener;
+        self
+    }
+
+    /// Sets the class added to external links
+    pub fn with_external_class(mut self, external_class: impl Into<String>) -> Self {
+        self.external_class = Some(external_class.into());
+        self
+    }
+
+    /// Enables or disables `<figure>` wrappers with captions for images
+    pub fn with_figures(mut self, figures: bool) -> Self {
+        self.figures = figures;
+        self
+    }
+
+    /// Enables or disables lazy loading and async decoding of images
+    pub fn with_lazy_images(mut self, lazy_images: bool) -> Self {
+        self.lazy_images = lazy_images;
+        self
+    }
+
+    /// Enables or disables `width`/`height` attributes for local images
+    pub fn with_image_dimensions(mut self, image_dimensions: bool) -> Self {
+        self.image_dimensions = image_dimensions;
+        self
+    }
+
+    /// Enables or disables syntax highlighting of code blocks
+    pub fn with_highlight(mut self, highlight: bool) -> Self {
+        self.highlight = highlight;
+        self
     }
 }
 
 /// HTML writer for converting parsed markdown documents to HTML
 ///
 /// This is a "dirty" package that will contain blog-specific logic
-/// and customization in the future. Currently provides basic HTML conversion.
+/// and customization in the future.
 ///
-/// # Layer 1: Basic HTML Conversion (Current)
+/// # Layer 1: Basic HTML Conversion
 /// - Converts Document tree to semantic HTML tags
 /// - Handles hierarchical sections recursively
 /// - Supports inline formatting (bold, italic, links, line breaks)
 /// - Escapes HTML entities for security
-/// - Outputs minified HTML (no unnecessary whitespace)
+/// - Outputs minified HTML (no unnecessary whitespace) by default
+/// - Implements `Renderer`, so a wrapper can change how single node types
+///   render (see `renderer`)
 ///
-/// # Layer 2: Blog Customization (Future)
-/// - Custom CSS classes
-/// - Wrapper elements
+/// # Layer 2: Output Options (`HtmlConfig`)
+/// - XHTML-style void elements
+/// - Pretty-printed output
+/// - `<section>` wrapper elements
+/// - Heading level offset
+/// - CSS class prefix
+/// - External link attributes
+/// - Image figures, lazy loading and dimensions
+/// - Code block syntax highlighting
+///
+/// With a `LinkRewriter` (`with_link_rewriter`), links to markdown files
+/// are written as the routes that serve them, and local images as URLs
+/// from the site root.
+///
+/// # Layer 3: Blog Customization (Future)
 /// - ID generation for headings
 /// - Custom attributes
 pub struct HtmlWriter {
-    _config: HtmlConfig,
+    config: HtmlConfig,
+    /// Open `<section>` wrappers, for pretty-printed indentation
+    depth: Cell<usize>,
+    links: Option<LinkRewriter>,
 }
 
 impl HtmlWriter {
     pub fn new() -> Self {
+        Self::with_config(HtmlConfig::default())
+    }
+
+    /// Creates a writer with the given output options
+    pub fn with_config(config: HtmlConfig) -> Self {
         Self {
-            _config: HtmlConfig::default(),
+            config,
+            depth: Cell::new(0),
+            links: None,
         }
     }
 
-    /// Converts a parsed markdown document to minified HTML
+    /// Rewrites links to markdown files as route URLs, and local image
+    /// URLs relative to the site root
+    ///
+    /// # Example
+    /// ```no_run
+    /// use std::path::{Path, PathBuf};
+    /// use wtf::html_writer::HtmlWriter;
+    /// use wtf::router::Router;
+    ///
+    /// let router = Router::new(PathBuf::from("site")).unwrap();
+    /// let writer = HtmlWriter::new().with_link_rewriter(router.link_rewriter(Path::new("site/team/team.md")));
+    /// ```
+    pub fn with_link_rewriter(mut self, links: LinkRewriter) -> Self {
+        self.links = Some(links);
+        self
+    }
+
+    /// Converts a parsed markdown document to HTML (minified by default)
     ///
     /// # Arguments
     /// * `document` - The parsed markdown document
     ///
     /// # Returns
-    /// * `Ok(String)` - Minified HTML output
+    /// * `Ok(String)` - HTML output
     /// * `Err(HtmlError)` - If conversion fails (e.g., invalid heading level)
     ///
     /// # Example
@@ -125,116 +326,339 @@ impl HtmlWriter {
     /// assert_eq!(html, "<h1>Hello World</h1>");
     /// ```
     pub fn write_html(&self, document: &Document) -> Result<String, HtmlError> {
-        let mut output = String::new();
-
-        // Render preamble (content before first heading)
-        for block in document.content() {
-            output.push_str(&self.render_block(block)?);
-        }
+        renderer::render_to_string(self, document)
+    }
 
-        // Render all sections
-        for section in document.sections() {
-            output.push_str(&self.render_section(section)?);
-        }
+    /// Streams the HTML for a document into a writer as it is rendered
+    ///
+    /// Nothing is buffered here, so pass a buffered writer (`BufWriter`,
+    /// a locked stdout, a `Vec<u8>`) rather than a raw file or socket.
+    ///
+    /// # Arguments
+    /// * `document` - The parsed markdown document
+    /// * `writer` - Destination for the HTML
+    ///
+    /// # Returns
+    /// * `Err(HtmlError)` - If rendering fails or the writer returns an error
+    pub fn write_html_to<W: io::Write>(
+        &self,
+        document: &Document,
+        writer: W,
+    ) -> Result<(), HtmlError> {
+        renderer::render_to_io(self, document, writer)
+    }
 
-        Ok(output)
+    /// Renders an event stream (e.g. `MarkdownParser::events`) to HTML
+    ///
+    /// Produces the same output as `write_html` on the tree the events
+    /// describe, without building it. Events from other sources can be
+    /// passed as `events.map(Ok)`.
+    ///
+    /// # Returns
+    /// * `Ok(String)` - HTML output
+    /// * `Err(HtmlError)` - For the first parse error in the stream or an
+    ///   invalid heading level
+    ///
+    /// # Example
+    /// ```
+    /// use wtf::html_writer::HtmlWriter;
+    /// use wtf::parser::MarkdownParser;
+    ///
+    /// let events = MarkdownParser::events("# Hi\n**there**");
+    /// let html = HtmlWriter::new().write_html_events(events).unwrap();
+    /// assert_eq!(html, "<h1>Hi</h1><p><strong>there</strong></p>");
+    /// ```
+    pub fn write_html_events<'a, I>(&self, events: I) -> Result<String, HtmlError>
+    where
+        I: IntoIterator<Item = Result<Event<'a>, ParseError>>,
+    {
+        renderer::render_events_to_string(self, events)
     }
+}
 
-    /// Render a section and its subsections recursively
-    fn render_section(&self, section: &Section) -> Result<String, HtmlError> {
-        let mut output = String::new();
+/// Semantic HTML; sections add no markup of their own unless
+/// `HtmlConfig::sections` is set
+impl Renderer for HtmlWriter {
+    type Error = HtmlError;
 
-        // Render heading
-        output.push_str(&self.render_heading(section.level(), section.title())?);
+    fn begin(&self, _out: &mut dyn fmt::Write) -> Result<(), HtmlError> {
+        self.depth.set(0);
+        Ok(())
+    }
 
-        // Render content blocks
-        for block in section.content() {
-            output.push_str(&self.render_block(block)?);
-        }
+    fn start(&self, out: &mut dyn fmt::Write, tag: &Tag) -> Result<(), HtmlError> {
+        match tag {
+            Tag::Section(_) => {
+                if self.config.sections {
+                    self.write_indent(out)?;
+                    self.write_open(out, TAG_SECTION)?;
+                    out.write_str(TAG_END)?;
+                    self.write_newline(out)?;
+                    self.depth.set(self.depth.get() + 1);
+                }
+            }
+            Tag::Heading(level) => {
+                self.write_indent(out)?;
+                self.write_open(out, self.heading_tag(*level)?)?;
+                out.write_str(TAG_END)?;
+            }
+            Tag::Paragraph => {
+                self.write_indent(out)?;
+                self.write_open(out, TAG_P)?;
+                out.write_str(TAG_END)?;
+            }
+            Tag::Bold => {
+                self.write_open(out, TAG_STRONG)?;
+                out.write_str(TAG_END)?;
+            }
+            Tag::Italic => {
+                self.write_open(out, TAG_EM)?;
+                out.write_str(TAG_END)?;
+            }
+            Tag::Link(url) => {
+                let rewritten = self.links.as_ref().and_then(|links| links.rewrite(url));
+                let url = rewritten.as_deref().unwrap_or(url);
+                let external = is_external(url);
 
-        // Render subsections recursively
-        for subsection in section.subsections() {
-            output.push_str(&self.render_section(subsection)?);
+                let extra_class = self.config.external_class.as_deref().filter(|_| external);
+                self.write_open_with_class(out, TAG_A, extra_class)?;
+                write_attribute(out, ATTR_HREF, url)?;
+                if external && self.config.external_new_tab {
+                    write_attribute(out, ATTR_TARGET, TARGET_BLANK)?;
+                }
+                if external && self.config.external_noopener {
+                    write_attribute(out, ATTR_REL, REL_NOOPENER)?;
+                }
+                out.write_str(TAG_END)?;
+            }
         }
-
-        Ok(output)
+        Ok(())
     }
 
-    /// Render a block-level node (paragraph or image)
-    fn render_block(&self, block: &BlockNode) -> Result<String, HtmlError> {
-        match block {
-            BlockNode::Paragraph(inlines) => {
-                let content = self.render_inline_nodes(inlines)?;
-                Ok(format!("{}{}{}", TAG_P_OPEN, content, TAG_P_CLOSE))
+    fn end(&self, out: &mut dyn fmt::Write, tag: TagEnd) -> Result<(), HtmlError> {
+        match tag {
+            TagEnd::Section(_) => {
+                if self.config.sections {
+                    self.depth.set(self.depth.get().saturating_sub(1));
+                    self.write_indent(out)?;
+                    write_close(out, TAG_SECTION)?;
+                    self.write_newline(out)?;
+                }
             }
-            BlockNode::Image { alt_text, url } => {
-                let escaped_alt = escape_html(alt_text);
-                let escaped_url = escape_html(url);
-                Ok(format!(
-                    "{}{}{}{}{}",
-                    TAG_IMG_OPEN, escaped_url, TAG_IMG_MIDDLE, escaped_alt, TAG_IMG_CLOSE
-                ))
+            TagEnd::Heading(level) => {
+                write_close(out, self.heading_tag(level)?)?;
+                self.write_newline(out)?;
             }
+            TagEnd::Paragraph => {
+                write_close(out, TAG_P)?;
+                self.write_newline(out)?;
+            }
+            TagEnd::Bold => write_close(out, TAG_STRONG)?,
+            TagEnd::Italic => write_close(out, TAG_EM)?,
+            TagEnd::Link => write_close(out, TAG_A)?,
         }
+        Ok(())
+    }
+
+    fn text(&self, out: &mut dyn fmt::Write, text: &str) -> Result<(), HtmlError> {
+        Ok(write_escaped(out, text)?)
     }
 
-    /// Render a slice of inline nodes
-    fn render_inline_nodes(&self, nodes: &[InlineNode]) -> Result<String, HtmlError> {
-        let mut output = String::new();
-        for node in nodes {
-            output.push_str(&self.render_inline(node)?);
+    fn line_break(&self, out: &mut dyn fmt::Write) -> Result<(), HtmlError> {
+        self.write_open(out, TAG_BR)?;
+        self.write_void_end(out)?;
+        Ok(())
+    }
+
+    fn image(&self, out: &mut dyn fmt::Write, alt_text: &str, url: &str) -> Result<(), HtmlError> {
+        self.write_indent(out)?;
+        if self.config.figures {
+            self.write_open(out, TAG_FIGURE)?;
+            out.write_str(TAG_END)?;
         }
-        Ok(output)
-    }
-
-    /// Render a single inline node
-    fn render_inline(&self, node: &InlineNode) -> Result<String, HtmlError> {
-        match node {
-            InlineNode::Text(text) => Ok(escape_html(text)),
-            InlineNode::LineBreak => Ok(TAG_BR.to_string()),
-            InlineNode::Bold(children) => {
-                let content = self.render_inline_nodes(children)?;
-                Ok(format!(
-                    "{}{}{}",
-                    TAG_STRONG_OPEN, content, TAG_STRONG_CLOSE
-                ))
-            }
-            InlineNode::Italic(children) => {
-                let content = self.render_inline_nodes(children)?;
-                Ok(format!("{}{}{}", TAG_EM_OPEN, content, TAG_EM_CLOSE))
+
+        let rewritten = self
+            .links
+            .as_ref()
+            .and_then(|links| links.rewrite_file(url));
+        self.write_open(out, TAG_IMG)?;
+        write_attribute(out, ATTR_SRC, rewritten.as_deref().unwrap_or(url))?;
+        write_attribute(out, ATTR_ALT, alt_text)?;
+        if let Some(size) = self.local_image_size(url) {
+            write!(out, "{}{}{}", ATTR_WIDTH, size.width, ATTR_END)?;
+            write!(out, "{}{}{}", ATTR_HEIGHT, size.height, ATTR_END)?;
+        }
+        if self.config.lazy_images {
+            write_attribute(out, ATTR_LOADING, LOADING_LAZY)?;
+            write_attribute(out, ATTR_DECODING, DECODING_ASYNC)?;
+        }
+        self.write_void_end(out)?;
+
+        if self.config.figures {
+            if !alt_text.is_empty() {
+                self.write_open(out, TAG_FIGCAPTION)?;
+                out.write_str(TAG_END)?;
+                write_escaped(out, alt_text)?;
+                write_close(out, TAG_FIGCAPTION)?;
             }
-            InlineNode::Link { text, url } => {
-                let content = self.render_inline_nodes(text)?;
-                let escaped_url = escape_html(url);
-                Ok(format!(
-                    "{}{}{}{}{}",
-                    TAG_A_OPEN, escaped_url, TAG_A_MIDDLE, content, TAG_A_CLOSE
-                ))
+            write_close(out, TAG_FIGURE)?;
+        }
+        self.write_newline(out)?;
+        Ok(())
+    }
+
+    fn code_block(
+        &self,
+        out: &mut dyn fmt::Write,
+        info: &str,
+        code: &str,
+    ) -> Result<(), HtmlError> {
+        self.write_indent(out)?;
+        self.write_open(out, TAG_PRE)?;
+        out.write_str(TAG_END)?;
+
+        let language_class =
+            highlight::language_name(info).map(|name| format!("{}{}", LANGUAGE_CLASS_PREFIX, name));
+        self.write_open_with_class(out, TAG_CODE, language_class.as_deref())?;
+        out.write_str(TAG_END)?;
+
+        // Unknown languages (and unhighlighted output) are plain escaped text
+        match Language::from_info(info).filter(|_| self.config.highlight) {
+            Some(language) => {
+                for token in highlight::highlight(language, code) {
+                    let Some(kind) = token.kind else {
+                        write_escaped(out, token.text)?;
+                        continue;
+                    };
+                    let prefix = self.config.class_prefix.as_deref().unwrap_or_default();
+                    let token_class = format!("{}{}", prefix, kind.class());
+                    self.write_open_with_class(out, TAG_SPAN, Some(&token_class))?;
+                    out.write_str(TAG_END)?;
+                    write_escaped(out, token.text)?;
+                    write_close(out, TAG_SPAN)?;
+                }
             }
+            None => write_escaped(out, code)?,
         }
+
+        write_close(out, TAG_CODE)?;
+        write_close(out, TAG_PRE)?;
+        self.write_newline(out)?;
+        Ok(())
     }
+}
+
+// ============================================================================
+// MARKUP HELPERS
+// ============================================================================
 
-    /// Render a heading with inline-formatted title
-    fn render_heading(&self, level: u8, title: &[InlineNode]) -> Result<String, HtmlError> {
+impl HtmlWriter {
+    /// Returns the element name for a heading level, after the offset
+    fn heading_tag(&self, level: u8) -> Result<&'static str, HtmlError> {
         if !(MIN_HEADING_LEVEL..=MAX_HEADING_LEVEL).contains(&level) {
             return Err(HtmlError::InvalidHeadingLevel(level));
         }
+        let shifted = level
+            .saturating_add(self.config.heading_offset)
+            .min(MAX_HEADING_LEVEL);
+        Ok(TAG_HEADINGS[(shifted - MIN_HEADING_LEVEL) as usize])
+    }
 
-        let content = self.render_inline_nodes(title)?;
-        let (open_tag, close_tag) = match level {
-            1 => (TAG_H1_OPEN, TAG_H1_CLOSE),
-            2 => (TAG_H2_OPEN, TAG_H2_CLOSE),
-            3 => (TAG_H3_OPEN, TAG_H3_CLOSE),
-            4 => (TAG_H4_OPEN, TAG_H4_CLOSE),
-            5 => (TAG_H5_OPEN, TAG_H5_CLOSE),
-            6 => (TAG_H6_OPEN, TAG_H6_CLOSE),
-            _ => unreachable!("Heading level already validated"),
-        };
+    /// Size of an image that is a local file, when dimensions are enabled
+    ///
+    /// Images that can't be found or read are written without a size
+    /// rather than failing the page.
+    fn local_image_size(&self, url: &str) -> Option<ImageSize> {
+        if !self.config.image_dimensions {
+            return None;
+        }
+        let file = self.links.as_ref()?.local_file(url)?;
+        read_image_size(&file).ok()
+    }
+
+    /// Writes `<name` and its class attribute, leaving the tag open for
+    /// further attributes
+    fn write_open(&self, out: &mut dyn fmt::Write, name: &str) -> fmt::Result {
+        self.write_open_with_class(out, name, None)
+    }
+
+    /// `write_open` with an extra class alongside the prefixed one
+    fn write_open_with_class(
+        &self,
+        out: &mut dyn fmt::Write,
+        name: &str,
+        extra_class: Option<&str>,
+    ) -> fmt::Result {
+        out.write_char(TAG_START)?;
+        out.write_str(name)?;
+        let prefix = self.config.class_prefix.as_deref();
+        if prefix.is_none() && extra_class.is_none() {
+            return Ok(());
+        }
 
-        Ok(format!("{}{}{}", open_tag, content, close_tag))
+        out.write_str(ATTR_CLASS)?;
+        if let Some(prefix) = prefix {
+            write_escaped(out, prefix)?;
+            out.write_str(name)?;
+        }
+        if let Some(extra_class) = extra_class {
+            if prefix.is_some() {
+                out.write_char(CLASS_SEPARATOR)?;
+            }
+            write_escaped(out, extra_class)?;
+        }
+        out.write_str(ATTR_END)
+    }
+
+    /// Ends a void element's tag (`>` or ` />`)
+    fn write_void_end(&self, out: &mut dyn fmt::Write) -> fmt::Result {
+        out.write_str(if self.config.xhtml {
+            TAG_END_VOID_XHTML
+        } else {
+            TAG_END
+        })
+    }
+
+    /// Indents a block's first line when pretty-printing
+    fn write_indent(&self, out: &mut dyn fmt::Write) -> fmt::Result {
+        if self.config.pretty {
+            for _ in 0..self.depth.get() {
+                out.write_str(INDENT)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Ends a block's line when pretty-printing
+    fn write_newline(&self, out: &mut dyn fmt::Write) -> fmt::Result {
+        if self.config.pretty {
+            out.write_char(NEWLINE)?;
+        }
+        Ok(())
     }
 }
 
+/// Whether a link leaves the site (absolute or protocol-relative web URL)
+fn is_external(url: &str) -> bool {
+    EXTERNAL_PREFIXES
+        .iter()
+        .any(|prefix| url.starts_with(prefix))
+}
+
+/// Writes `</name>`
+fn write_close(out: &mut dyn fmt::Write, name: &str) -> fmt::Result {
+    out.write_str(TAG_CLOSE_START)?;
+    out.write_str(name)?;
+    out.write_str(TAG_END)
+}
+
+/// Writes an attribute (`prefix` is ` name="`) with an escaped value
+fn write_attribute(out: &mut dyn fmt::Write, prefix: &str, value: &str) -> fmt::Result {
+    out.write_str(prefix)?;
+    write_escaped(out, value)?;
+    out.write_str(ATTR_END)
+}
+
 impl Default for HtmlWriter {
     fn default() -> Self {
         Self::new()
@@ -250,14 +674,24 @@ impl Default for HtmlWriter {
 /// - `"` → `&quot;`
 /// - `'` → `&#39;`
 ///
-/// Note: & is escaped first to avoid double-escaping
-fn escape_html(content: &str) -> String {
-    content
-        .replace('&', ESCAPE_AMP)
-        .replace('<', ESCAPE_LT)
-        .replace('>', ESCAPE_GT)
-        .replace('"', ESCAPE_QUOT)
-        .replace('\'', ESCAPE_APOS)
+/// Written in unescaped runs between entities, so no intermediate string is
+/// built
+fn write_escaped(out: &mut dyn fmt::Write, content: &str) -> fmt::Result {
+    let mut last = 0;
+    for (i, c) in content.char_indices() {
+        let entity = match c {
+            '&' => ESCAPE_AMP,
+            '<' => ESCAPE_LT,
+            '>' => ESCAPE_GT,
+            '"' => ESCAPE_QUOT,
+            '\'' => ESCAPE_APOS,
+            _ => continue,
+        };
+        out.write_str(&content[last..i])?;
+        out.write_str(entity)?;
+        last = i + 1;
+    }
+    out.write_str(&content[last..])
 }
 
 #[cfg(test)]
@@ -394,4 +828,287 @@ mod tests {
         assert!(!html.contains('\n'));
         assert_eq!(html, "<h1>H1</h1><h2>H2</h2>");
     }
+
+    #[test]
+    fn test_events_render_like_tree() {
+        let writer = HtmlWriter::new();
+        for source in [
+            "",
+            "Intro <&>\n\n![a\"lt](i.png)\n# One\ntext *em **b** [l](u?a=1&b=2)*\nnext\n## Two\n### Three\n# Four",
+            "**[x](y)** and *[**z**](w)*",
+        ] {
+            let tree = writer
+                .write_html(&MarkdownParser::parse(source).unwrap())
+                .unwrap();
+            let events = writer
+                .write_html_events(MarkdownParser::events(source))
+                .unwrap();
+            assert_eq!(events, tree, "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn test_events_parse_error() {
+        let writer = HtmlWriter::new();
+        assert_eq!(
+            writer.write_html_events(MarkdownParser::events("ok\n\n[bad")),
+            Err(HtmlError::ParseError(ParseError::MalformedLink {
+                position: 4
+            }))
+        );
+    }
+
+    #[test]
+    fn test_write_html_to_matches_write_html() {
+        let document =
+            MarkdownParser::parse("# T\n\nA <b> & \"q\" 'a'\n**x** [l](u?a=1&b=2)\n![a<t>](i.png)")
+                .unwrap();
+        let writer = HtmlWriter::new();
+
+        let mut streamed = Vec::new();
+        writer.write_html_to(&document, &mut streamed).unwrap();
+        assert_eq!(
+            String::from_utf8(streamed).unwrap(),
+            writer.write_html(&document).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_write_html_to_reports_writer_error() {
+        struct Full;
+
+        impl io::Write for Full {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::StorageFull, "disk full"))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let document = MarkdownParser::parse("Hello").unwrap();
+        assert_eq!(
+            HtmlWriter::new().write_html_to(&document, Full),
+            Err(HtmlError::WriteError("disk full".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_config_xhtml() {
+        let document = MarkdownParser::parse("a\nb\n\n![x](y.png)").unwrap();
+        let writer = HtmlWriter::with_config(HtmlConfig::new().with_xhtml(true));
+        assert_eq!(
+            writer.write_html(&document).unwrap(),
+            "<p>a<br />b</p><img src=\"y.png\" alt=\"x\" />"
+        );
+    }
+
+    #[test]
+    fn test_config_sections_pretty() {
+        let document =
+            MarkdownParser::parse("Intro\n\n# One\n\nText\n\n## Two\n\n![x](y.png)\n\n# Three")
+                .unwrap();
+        let config = HtmlConfig::new().with_sections(true).with_pretty(true);
+        assert_eq!(
+            HtmlWriter::with_config(config)
+                .write_html(&document)
+                .unwrap(),
+            "<p>Intro</p>\n\
+             <section>\n\
+             \x20 <h1>One</h1>\n\
+             \x20 <p>Text</p>\n\
+             \x20 <section>\n\
+             \x20   <h2>Two</h2>\n\
+             \x20   <img src=\"y.png\" alt=\"x\">\n\
+             \x20 </section>\n\
+             </section>\n\
+             <section>\n\
+             \x20 <h1>Three</h1>\n\
+             </section>\n"
+        );
+    }
+
+    #[test]
+    fn test_config_pretty_without_sections() {
+        let document = MarkdownParser::parse("# One\n\nSome **bold**\n## Two").unwrap();
+        let writer = HtmlWriter::with_config(HtmlConfig::new().with_pretty(true));
+        assert_eq!(
+            writer.write_html(&document).unwrap(),
+            "<h1>One</h1>\n<p>Some <strong>bold</strong></p>\n<h2>Two</h2>\n"
+        );
+    }
+
+    #[test]
+    fn test_config_heading_offset() {
+        let document = MarkdownParser::parse("# One\n## Two\n###### Six").unwrap();
+        let writer = HtmlWriter::with_config(HtmlConfig::new().with_heading_offset(2));
+        assert_eq!(
+            writer.write_html(&document).unwrap(),
+            "<h3>One</h3><h4>Two</h4><h6>Six</h6>"
+        );
+    }
+
+    #[test]
+    fn test_config_class_prefix() {
+        let document = MarkdownParser::parse("# T\n*i* [l](u)\n\n![a](b)").unwrap();
+        let config = HtmlConfig::new()
+            .with_class_prefix("md-")
+            .with_sections(true);
+        assert_eq!(
+            HtmlWriter::with_config(config)
+                .write_html(&document)
+                .unwrap(),
+            "<section class=\"md-section\"><h1 class=\"md-h1\">T</h1>\
+             <p class=\"md-p\"><em class=\"md-em\">i</em> \
+             <a class=\"md-a\" href=\"u\">l</a></p>\
+             <img class=\"md-img\" src=\"b\" alt=\"a\"></section>"
+        );
+    }
+
+    #[test]
+    fn test_config_events_render_like_tree() {
+        let source = "Intro\n# A\ntext *x*\n## B\n![i](j)\n# C\nline\nbreak";
+        let document = MarkdownParser::parse(source).unwrap();
+        let config = HtmlConfig::new()
+            .with_xhtml(true)
+            .with_pretty(true)
+            .with_sections(true)
+            .with_heading_offset(1)
+            .with_class_prefix("x-");
+        let writer = HtmlWriter::with_config(config);
+        assert_eq!(
+            writer
+                .write_html_events(MarkdownParser::events(source))
+                .unwrap(),
+            writer.write_html(&document).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_config_external_links() {
+        let document = MarkdownParser::parse(
+            "[a](https://example.com) [b](/local) [c](//cdn.example.com/x) [d](mailto:x@y.z)",
+        )
+        .unwrap();
+        let config = HtmlConfig::new()
+            .with_external_new_tab(true)
+            .with_external_noopener(true)
+            .with_external_class("ext");
+        assert_eq!(
+            HtmlWriter::with_config(config.clone())
+                .write_html(&document)
+                .unwrap(),
+            "<p><a class=\"ext\" href=\"https://example.com\" target=\"_blank\" \
+             rel=\"noopener noreferrer\">a</a> <a href=\"/local\">b</a> \
+             <a class=\"ext\" href=\"//cdn.example.com/x\" target=\"_blank\" \
+             rel=\"noopener noreferrer\">c</a> <a href=\"mailto:x@y.z\">d</a></p>"
+        );
+
+        // The external class joins the prefixed one
+        let writer = HtmlWriter::with_config(config.with_class_prefix("md-"));
+        let document = MarkdownParser::parse("[a](http://x) [b](y)").unwrap();
+        assert_eq!(
+            writer.write_html(&document).unwrap(),
+            "<p class=\"md-p\"><a class=\"md-a ext\" href=\"http://x\" target=\"_blank\" \
+             rel=\"noopener noreferrer\">a</a> <a class=\"md-a\" href=\"y\">b</a></p>"
+        );
+    }
+
+    #[test]
+    fn test_link_rewriter() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir(root.join("team")).unwrap();
+        std::fs::write(root.join("team/team.md"), "Team").unwrap();
+        std::fs::write(root.join("team/alice.md"), "Alice").unwrap();
+        std::fs::write(root.join("about.md"), "About").unwrap();
+
+        let router = crate::router::Router::new(root.to_path_buf()).unwrap();
+        let writer =
+            HtmlWriter::new().with_link_rewriter(router.link_rewriter(&root.join("team/team.md")));
+        let document = MarkdownParser::parse(
+            "[a](alice.md) [b](../about.md#x) [c](missing.md) [d](https://x.org/y.md)",
+        )
+        .unwrap();
+        assert_eq!(
+            writer.write_html(&document).unwrap(),
+            "<p><a href=\"/team/alice\">a</a> <a href=\"/about#x\">b</a> \
+             <a href=\"missing.md\">c</a> <a href=\"https://x.org/y.md\">d</a></p>"
+        );
+    }
+
+    #[test]
+    fn test_config_figures_and_lazy_images() {
+        let document = MarkdownParser::parse("![A <cat>](cat.png)\n\n![](bare.png)").unwrap();
+        let config = HtmlConfig::new()
+            .with_figures(true)
+            .with_lazy_images(true)
+            .with_pretty(true);
+        assert_eq!(
+            HtmlWriter::with_config(config)
+                .write_html(&document)
+                .unwrap(),
+            "<figure><img src=\"cat.png\" alt=\"A &lt;cat&gt;\" loading=\"lazy\" \
+             decoding=\"async\"><figcaption>A &lt;cat&gt;</figcaption></figure>\n\
+             <figure><img src=\"bare.png\" alt=\"\" loading=\"lazy\" decoding=\"async\">\
+             </figure>\n"
+        );
+    }
+
+    #[test]
+    fn test_config_image_dimensions() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend_from_slice(&[0, 0, 0, 32, 0, 0, 0, 16]);
+        std::fs::write(root.join("icon.png"), &png).unwrap();
+        std::fs::write(root.join("broken.png"), "not an image").unwrap();
+        std::fs::write(root.join("page.md"), "").unwrap();
+
+        let router = crate::router::Router::new(root.to_path_buf()).unwrap();
+        let writer = HtmlWriter::with_config(HtmlConfig::new().with_image_dimensions(true))
+            .with_link_rewriter(router.link_rewriter(&root.join("page.md")));
+        let document = MarkdownParser::parse(
+            "![a](icon.png)\n\n![b](broken.png)\n\n![c](missing.png)\n\n![d](https://x/y.png)",
+        )
+        .unwrap();
+        assert_eq!(
+            writer.write_html(&document).unwrap(),
+            "<img src=\"/icon.png\" alt=\"a\" width=\"32\" height=\"16\">\
+             <img src=\"/broken.png\" alt=\"b\"><img src=\"missing.png\" alt=\"c\">\
+             <img src=\"https://x/y.png\" alt=\"d\">"
+        );
+    }
+
+    #[test]
+    fn test_code_block() {
+        let document =
+            MarkdownParser::parse("```cobol extra\nIF A < B\n```\n\n```\nplain & simple\n```")
+                .unwrap();
+        assert_eq!(
+            HtmlWriter::new().write_html(&document).unwrap(),
+            "<pre><code class=\"language-cobol\">IF A &lt; B</code></pre>\
+             <pre><code>plain &amp; simple</code></pre>"
+        );
+
+        // Highlighting needs the option and a known language
+        let document = MarkdownParser::parse("```rust\nlet s = \"<a>\";\n```").unwrap();
+        assert_eq!(
+            HtmlWriter::new().write_html(&document).unwrap(),
+            "<pre><code class=\"language-rust\">let s = &quot;&lt;a&gt;&quot;;</code></pre>"
+        );
+        let config = HtmlConfig::new()
+            .with_highlight(true)
+            .with_class_prefix("md-")
+            .with_pretty(true);
+        assert_eq!(
+            HtmlWriter::with_config(config)
+                .write_html(&document)
+                .unwrap(),
+            "<pre class=\"md-pre\"><code class=\"md-code language-rust\">\
+             <span class=\"md-span md-tok-keyword\">let</span> s = \
+             <span class=\"md-span md-tok-string\">&quot;&lt;a&gt;&quot;</span>;</code></pre>\n"
+        );
+    }
 }
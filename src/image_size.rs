@@ -0,0 +1,368 @@
+//! Image dimensions from file headers
+//!
+//! Reads just enough of a PNG, JPEG, GIF or WebP file to find its pixel
+//! size, so pages can give `<img>` a `width` and `height` without decoding
+//! the image.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+// Signatures
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const PNG_IHDR: &[u8] = b"IHDR";
+const GIF87_SIGNATURE: &[u8] = b"GIF87a";
+const GIF89_SIGNATURE: &[u8] = b"GIF89a";
+const RIFF_SIGNATURE: &[u8] = b"RIFF";
+const WEBP_SIGNATURE: &[u8] = b"WEBP";
+const WEBP_LOSSY: &[u8] = b"VP8 ";
+const WEBP_LOSSLESS: &[u8] = b"VP8L";
+const WEBP_EXTENDED: &[u8] = b"VP8X";
+const VP8_START_CODE: &[u8] = b"\x9d\x01\x2a";
+const VP8L_SIGNATURE: u8 = 0x2f;
+const JPEG_SIGNATURE: &[u8] = b"\xff\xd8";
+
+// Header length covering every fixed-position format (WebP VP8X is longest)
+const HEADER_LEN: usize = 30;
+
+// JPEG markers
+const JPEG_MARKER_PREFIX: u8 = 0xff;
+const JPEG_SOF_FIRST: u8 = 0xc0;
+const JPEG_SOF_LAST: u8 = 0xcf;
+const JPEG_DHT: u8 = 0xc4;
+const JPEG_JPG: u8 = 0xc8;
+const JPEG_DAC: u8 = 0xcc;
+const JPEG_TEM: u8 = 0x01;
+const JPEG_RST_FIRST: u8 = 0xd0;
+const JPEG_RST_LAST: u8 = 0xd7;
+const JPEG_EOI: u8 = 0xd9;
+const JPEG_SOS: u8 = 0xda;
+
+// ============================================================================
+// ERROR HANDLING
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImageSizeError {
+    /// Not a PNG, JPEG, GIF or WebP file
+    UnknownFormat,
+    /// The header is truncated or inconsistent
+    InvalidHeader {
+        format: &'static str,
+    },
+    IoError {
+        message: String,
+    },
+}
+
+impl fmt::Display for ImageSizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageSizeError::UnknownFormat => {
+                write!(f, "Unknown image format (expected PNG, JPEG, GIF or WebP)")
+            }
+            ImageSizeError::InvalidHeader { format } => {
+                write!(f, "Invalid {} header", format)
+            }
+            ImageSizeError::IoError { message } => write!(f, "IO error reading image: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ImageSizeError {}
+
+impl From<io::Error> for ImageSizeError {
+    fn from(error: io::Error) -> Self {
+        ImageSizeError::IoError {
+            message: error.to_string(),
+        }
+    }
+}
+
+// ============================================================================
+// IMAGE SIZE
+// ============================================================================
+
+/// Pixel dimensions of an image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Reads the dimensions of an image file from its header
+///
+/// # Arguments
+/// * `path` - Path to a PNG, JPEG, GIF or WebP file
+///
+/// # Returns
+/// * `Ok(ImageSize)` - Width and height in pixels
+/// * `Err(ImageSizeError)` - If the file can't be read or isn't a supported image
+pub fn read_image_size(path: &Path) -> Result<ImageSize, ImageSizeError> {
+    image_size(BufReader::new(File::open(path)?))
+}
+
+/// Reads the dimensions of an image from the start of its data
+///
+/// Only the header is consumed (for JPEG, the segments up to the frame
+/// header).
+///
+/// # Arguments
+/// * `reader` - Image data, positioned at the start of the file
+///
+/// # Returns
+/// * `Ok(ImageSize)` - Width and height in pixels
+/// * `Err(ImageSizeError)` - If reading fails or the data isn't a supported image
+pub fn image_size<R: Read>(mut reader: R) -> Result<ImageSize, ImageSizeError> {
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    reader
+        .by_ref()
+        .take(HEADER_LEN as u64)
+        .read_to_end(&mut header)?;
+
+    if header.starts_with(PNG_SIGNATURE) {
+        png_size(&header)
+    } else if header.starts_with(GIF87_SIGNATURE) || header.starts_with(GIF89_SIGNATURE) {
+        gif_size(&header)
+    } else if header.starts_with(RIFF_SIGNATURE) && header.get(8..12) == Some(WEBP_SIGNATURE) {
+        webp_size(&header)
+    } else if header.starts_with(JPEG_SIGNATURE) {
+        jpeg_size(&header[JPEG_SIGNATURE.len()..], reader)
+    } else {
+        Err(ImageSizeError::UnknownFormat)
+    }
+}
+
+// ============================================================================
+// FORMAT PARSERS
+// ============================================================================
+
+/// PNG: the IHDR chunk always comes first, with big-endian width and height
+fn png_size(header: &[u8]) -> Result<ImageSize, ImageSizeError> {
+    let invalid = ImageSizeError::InvalidHeader { format: "PNG" };
+    if header.get(12..16) != Some(PNG_IHDR) {
+        return Err(invalid);
+    }
+    match (be_u32(header, 16), be_u32(header, 20)) {
+        (Some(width), Some(height)) => Ok(ImageSize { width, height }),
+        _ => Err(invalid),
+    }
+}
+
+/// GIF: little-endian logical screen width and height after the signature
+fn gif_size(header: &[u8]) -> Result<ImageSize, ImageSizeError> {
+    match (le_u16(header, 6), le_u16(header, 8)) {
+        (Some(width), Some(height)) => Ok(ImageSize {
+            width: width.into(),
+            height: height.into(),
+        }),
+        _ => Err(ImageSizeError::InvalidHeader { format: "GIF" }),
+    }
+}
+
+/// WebP: the first chunk is lossy (VP8), lossless (VP8L) or extended (VP8X)
+fn webp_size(header: &[u8]) -> Result<ImageSize, ImageSizeError> {
+    let invalid = ImageSizeError::InvalidHeader { format: "WebP" };
+    let size = match header.get(12..16) {
+        // Frame tag (3 bytes), start code, then 14-bit width and height
+        Some(WEBP_LOSSY) if header.get(23..26) == Some(VP8_START_CODE) => le_u16(header, 26)
+            .zip(le_u16(header, 28))
+            .map(|(w, h)| (u32::from(w & 0x3fff), u32::from(h & 0x3fff))),
+        // Signature byte, then width - 1 and height - 1 as 14-bit fields
+        Some(WEBP_LOSSLESS) if header.get(20) == Some(&VP8L_SIGNATURE) => {
+            le_u32(header, 21).map(|bits| ((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1))
+        }
+        // Flags (4 bytes), then canvas width - 1 and height - 1 as 24-bit fields
+        Some(WEBP_EXTENDED) => le_u24(header, 24)
+            .zip(le_u24(header, 27))
+            .map(|(w, h)| (w + 1, h + 1)),
+        _ => None,
+    };
+    let (width, height) = size.ok_or(invalid)?;
+    Ok(ImageSize { width, height })
+}
+
+/// JPEG: walks the marker segments to the first start-of-frame, which holds
+/// the big-endian height and width
+///
+/// # Arguments
+/// * `buffered` - Bytes already read after the SOI marker
+/// * `reader` - The rest of the file
+fn jpeg_size<R: Read>(buffered: &[u8], reader: R) -> Result<ImageSize, ImageSizeError> {
+    let invalid = || ImageSizeError::InvalidHeader { format: "JPEG" };
+    let mut bytes = buffered.chain(reader);
+    let mut byte = || -> Result<u8, ImageSizeError> {
+        let mut buf = [0u8; 1];
+        match bytes.read_exact(&mut buf) {
+            Ok(()) => Ok(buf[0]),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Err(invalid()),
+            Err(e) => Err(e.into()),
+        }
+    };
+
+    loop {
+        if byte()? != JPEG_MARKER_PREFIX {
+            return Err(invalid());
+        }
+        // Any number of fill bytes may precede the marker
+        let mut marker = byte()?;
+        while marker == JPEG_MARKER_PREFIX {
+            marker = byte()?;
+        }
+
+        match marker {
+            JPEG_TEM | JPEG_RST_FIRST..=JPEG_RST_LAST => continue,
+            JPEG_EOI | JPEG_SOS => return Err(invalid()),
+            _ => {}
+        }
+
+        let length = u16::from_be_bytes([byte()?, byte()?]);
+        if length < 2 {
+            return Err(invalid());
+        }
+
+        let is_frame = (JPEG_SOF_FIRST..=JPEG_SOF_LAST).contains(&marker)
+            && ![JPEG_DHT, JPEG_JPG, JPEG_DAC].contains(&marker);
+        if is_frame {
+            // Sample precision, then height and width
+            byte()?;
+            let height = u16::from_be_bytes([byte()?, byte()?]);
+            let width = u16::from_be_bytes([byte()?, byte()?]);
+            return Ok(ImageSize {
+                width: width.into(),
+                height: height.into(),
+            });
+        }
+
+        for _ in 2..length {
+            byte()?;
+        }
+    }
+}
+
+// ============================================================================
+// BYTE HELPERS
+// ============================================================================
+
+fn be_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn le_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn le_u24(bytes: &[u8], at: usize) -> Option<u32> {
+    let b = bytes.get(at..at + 3)?;
+    Some(u32::from_le_bytes([b[0], b[1], b[2], 0]))
+}
+
+fn le_u16(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn size(width: u32, height: u32) -> Result<ImageSize, ImageSizeError> {
+        Ok(ImageSize { width, height })
+    }
+
+    #[test]
+    fn test_png() {
+        let mut data = PNG_SIGNATURE.to_vec();
+        data.extend_from_slice(&13u32.to_be_bytes());
+        data.extend_from_slice(PNG_IHDR);
+        data.extend_from_slice(&640u32.to_be_bytes());
+        data.extend_from_slice(&480u32.to_be_bytes());
+        data.extend_from_slice(&[8, 6, 0, 0, 0]);
+        assert_eq!(image_size(&data[..]), size(640, 480));
+
+        assert_eq!(
+            image_size(&data[..20]),
+            Err(ImageSizeError::InvalidHeader { format: "PNG" })
+        );
+    }
+
+    #[test]
+    fn test_gif() {
+        let mut data = GIF89_SIGNATURE.to_vec();
+        data.extend_from_slice(&[0x2c, 0x01, 0x96, 0x00, 0, 0, 0]);
+        assert_eq!(image_size(&data[..]), size(300, 150));
+    }
+
+    #[test]
+    fn test_webp_lossy() {
+        let mut data = b"RIFF\0\0\0\0WEBPVP8 \0\0\0\0".to_vec();
+        data.extend_from_slice(&[0, 0, 0]);
+        data.extend_from_slice(VP8_START_CODE);
+        data.extend_from_slice(&(1024u16 | 0x4000).to_le_bytes());
+        data.extend_from_slice(&768u16.to_le_bytes());
+        assert_eq!(image_size(&data[..]), size(1024, 768));
+    }
+
+    #[test]
+    fn test_webp_lossless() {
+        let mut data = b"RIFF\0\0\0\0WEBPVP8L\0\0\0\0".to_vec();
+        data.push(VP8L_SIGNATURE);
+        let bits: u32 = (200 - 1) | ((100 - 1) << 14);
+        data.extend_from_slice(&bits.to_le_bytes());
+        assert_eq!(image_size(&data[..]), size(200, 100));
+    }
+
+    #[test]
+    fn test_webp_extended() {
+        let mut data = b"RIFF\0\0\0\0WEBPVP8X\0\0\0\0".to_vec();
+        data.extend_from_slice(&[0, 0, 0, 0]);
+        data.extend_from_slice(&[0x1f, 0x4e, 0x00]); // 19999 + 1
+        data.extend_from_slice(&[0x00, 0x00, 0x00]); // 0 + 1
+        assert_eq!(image_size(&data[..]), size(20000, 1));
+    }
+
+    #[test]
+    fn test_jpeg_skips_segments() {
+        let mut data = JPEG_SIGNATURE.to_vec();
+        // APP0 with a 40-byte body, past the fixed header length
+        data.extend_from_slice(&[0xff, 0xe0, 0x00, 42]);
+        data.extend_from_slice(&[0u8; 40]);
+        // Fill byte, then SOF2 (progressive)
+        data.extend_from_slice(&[0xff, 0xff, 0xc2, 0x00, 0x11, 8]);
+        data.extend_from_slice(&480u16.to_be_bytes());
+        data.extend_from_slice(&640u16.to_be_bytes());
+        assert_eq!(image_size(&data[..]), size(640, 480));
+    }
+
+    #[test]
+    fn test_jpeg_without_frame() {
+        let data = [0xff, 0xd8, 0xff, 0xc4, 0x00, 0x02, 0xff, 0xda];
+        assert_eq!(
+            image_size(&data[..]),
+            Err(ImageSizeError::InvalidHeader { format: "JPEG" })
+        );
+        assert_eq!(
+            image_size(&data[..5]),
+            Err(ImageSizeError::InvalidHeader { format: "JPEG" })
+        );
+    }
+
+    #[test]
+    fn test_unknown_format() {
+        assert_eq!(
+            image_size(&b"<svg></svg>"[..]),
+            Err(ImageSizeError::UnknownFormat)
+        );
+        assert_eq!(image_size(&b""[..]), Err(ImageSizeError::UnknownFormat));
+    }
+
+    #[test]
+    fn test_read_image_size_missing_file() {
+        let result = read_image_size(Path::new("/nonexistent/image.png"));
+        assert!(matches!(result, Err(ImageSizeError::IoError { .. })));
+    }
+}
@@ -0,0 +1,604 @@
+use crate::parser::closing_fence_len;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Constants
+const DIRECTIVE_OPEN: &str = "{{";
+const DIRECTIVE_CLOSE: &str = "}}";
+const INCLUDE_KEYWORD: &str = "include";
+const QUOTE_CHAR: char = '"';
+const ESCAPE_CHAR: char = '\\';
+const SECTION_SEPARATOR: char = '#';
+const HEADING_CHAR: char = '#';
+const PATH_SEPARATOR: char = '/';
+const FENCE_CHAR: u8 = b'`';
+const FENCE_MIN_LEN: usize = 3;
+
+/// Default limit for nested includes (an include inside an include, ...)
+pub const DEFAULT_MAX_DEPTH: usize = 8;
+
+// ============================================================================
+// ERROR HANDLING
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IncludeError {
+    IoError { path: PathBuf, message: String },
+    InvalidDirective { directive: String, reason: String },
+    OutsideContentRoot { target: String },
+    Cycle { chain: Vec<PathBuf> },
+    DepthExceeded { target: String, limit: usize },
+    SectionNotFound { path: PathBuf, section: String },
+}
+
+impl fmt::Display for IncludeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IncludeError::IoError { path, message } => {
+                write!(f, "IO error including '{}': {}", path.display(), message)
+            }
+            IncludeError::InvalidDirective { directive, reason } => {
+                write!(f, "Invalid include directive '{}': {}", directive, reason)
+            }
+            IncludeError::OutsideContentRoot { target } => {
+                write!(f, "Include '{}' points outside the content root", target)
+            }
+            IncludeError::Cycle { chain } => {
+                let chain: Vec<String> = chain.iter().map(|p| p.display().to_string()).collect();
+                write!(f, "Include cycle detected: {}", chain.join(" -> "))
+            }
+            IncludeError::DepthExceeded { target, limit } => {
+                write!(
+                    f,
+                    "Include '{}' exceeds the maximum include depth of {}",
+                    target, limit
+                )
+            }
+            IncludeError::SectionNotFound { path, section } => {
+                write!(f, "Section '{}' not found in '{}'", section, path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for IncludeError {}
+
+// ============================================================================
+// INCLUDE RESOLVER
+// ============================================================================
+
+/// Resolves `{{include "..."}}` directives in markdown source
+///
+/// Runs as a pre-pass over the raw text before it reaches `MarkdownParser`:
+/// - `{{include "shared/contact.md"}}` inserts the whole file
+/// - `{{include "about.md#contact"}}` inserts only the section whose heading
+///   matches `contact` (compared by slug, so `# Contact Us` matches `contact-us`)
+///
+/// Paths are resolved relative to the content root (a leading `/` is allowed)
+/// and may not escape it. Included files are expanded recursively, with cycle
+/// detection and a depth limit. `\{{include ...}}` and directives inside
+/// fenced code blocks are left untouched.
+#[derive(Debug, Clone)]
+pub struct IncludeResolver {
+    content_root: PathBuf,
+    max_depth: usize,
+}
+
+impl IncludeResolver {
+    /// Creates a resolver for the given content root
+    ///
+    /// # Arguments
+    /// * `content_root` - Directory include paths are resolved against
+    ///   (usually `Router::content_root`)
+    pub fn new(content_root: &Path) -> Self {
+        Self {
+            content_root: content_root.to_path_buf(),
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+
+    /// Sets the maximum nesting depth for includes
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Expands all include directives in `source`
+    ///
+    /// # Arguments
+    /// * `source` - Markdown source text
+    /// * `origin` - Path of the file `source` was read from (for cycle detection)
+    ///
+    /// # Returns
+    /// * `Ok(String)` - Source with every directive replaced by the included text
+    /// * `Err(IncludeError)` - If an include can't be resolved
+    pub fn expand(&self, source: &str, origin: &Path) -> Result<String, IncludeError> {
+        self.expand_with_files(source, origin)
+            .map(|(expanded, _)| expanded)
+    }
+
+    /// Expands all include directives in `source`, listing the files read
+    ///
+    /// # Arguments
+    /// * `source` - Markdown source text
+    /// * `origin` - Path of the file `source` was read from (for cycle detection)
+    ///
+    /// # Returns
+    /// * `Ok((String, Vec<PathBuf>))` - The expanded source, and the canonical
+    ///   path of every included file in the order they were read (a file
+    ///   included twice is listed twice)
+    /// * `Err(IncludeError)` - If an include can't be resolved
+    pub fn expand_with_files(
+        &self,
+        source: &str,
+        origin: &Path,
+    ) -> Result<(String, Vec<PathBuf>), IncludeError> {
+        let content_root = self.canonical_content_root()?;
+        let origin = origin
+            .canonicalize()
+            .unwrap_or_else(|_| origin.to_path_buf());
+
+        let mut stack = vec![origin];
+        let mut files = Vec::new();
+        let expanded = self.expand_recursive(source, &content_root, &mut stack, &mut files)?;
+        Ok((expanded, files))
+    }
+
+    // ========================================================================
+    // PRIVATE HELPER METHODS
+    // ========================================================================
+
+    fn canonical_content_root(&self) -> Result<PathBuf, IncludeError> {
+        self.content_root
+            .canonicalize()
+            .map_err(|e| IncludeError::IoError {
+                path: self.content_root.clone(),
+                message: e.to_string(),
+            })
+    }
+
+    /// Expands the directives outside fenced code blocks, which are copied
+    /// verbatim (like the parser, an unclosed fence runs to the end)
+    fn expand_recursive(
+        &self,
+        source: &str,
+        content_root: &Path,
+        stack: &mut Vec<PathBuf>,
+        files: &mut Vec<PathBuf>,
+    ) -> Result<String, IncludeError> {
+        let mut output = String::with_capacity(source.len());
+        let mut text_start = 0;
+        let mut fence: Option<usize> = None;
+        let mut offset = 0;
+
+        for line in source.split_inclusive('\n') {
+            let line_end = offset + line.len();
+            match fence {
+                Some(open_len) => {
+                    if closing_fence_len(line).is_some_and(|len| len >= open_len) {
+                        output.push_str(&source[text_start..line_end]);
+                        text_start = line_end;
+                        fence = None;
+                    }
+                }
+                None => {
+                    let ticks = line.bytes().take_while(|&byte| byte == FENCE_CHAR).count();
+                    if ticks >= FENCE_MIN_LEN {
+                        let text = &source[text_start..offset];
+                        output.push_str(&self.expand_directives(
+                            text,
+                            content_root,
+                            stack,
+                            files,
+                        )?);
+                        text_start = offset;
+                        fence = Some(ticks);
+                    }
+                }
+            }
+            offset = line_end;
+        }
+
+        let rest = &source[text_start..];
+        if fence.is_some() {
+            output.push_str(rest);
+        } else {
+            output.push_str(&self.expand_directives(rest, content_root, stack, files)?);
+        }
+        Ok(output)
+    }
+
+    /// Replaces every include directive in a stretch of text
+    fn expand_directives(
+        &self,
+        source: &str,
+        content_root: &Path,
+        stack: &mut Vec<PathBuf>,
+        files: &mut Vec<PathBuf>,
+    ) -> Result<String, IncludeError> {
+        let mut output = String::with_capacity(source.len());
+        let mut rest = source;
+
+        while let Some(open) = rest.find(DIRECTIVE_OPEN) {
+            // Escaped directive: keep it verbatim (including the backslash)
+            if rest[..open].ends_with(ESCAPE_CHAR) {
+                output.push_str(&rest[..open + DIRECTIVE_OPEN.len()]);
+                rest = &rest[open + DIRECTIVE_OPEN.len()..];
+                continue;
+            }
+
+            let after_open = &rest[open + DIRECTIVE_OPEN.len()..];
+            let Some(close) = after_open.find(DIRECTIVE_CLOSE) else {
+                break;
+            };
+
+            let inner = after_open[..close].trim();
+            let argument = inner.strip_prefix(INCLUDE_KEYWORD).filter(|argument| {
+                argument.is_empty() || argument.starts_with([' ', '\t', QUOTE_CHAR])
+            });
+            let Some(argument) = argument else {
+                // Not an include (e.g. a template variable) - leave it alone
+                output.push_str(&rest[..open + DIRECTIVE_OPEN.len()]);
+                rest = after_open;
+                continue;
+            };
+
+            let directive =
+                &rest[open..open + DIRECTIVE_OPEN.len() + close + DIRECTIVE_CLOSE.len()];
+            let target = parse_include_target(argument, directive)?;
+
+            output.push_str(&rest[..open]);
+            output.push_str(&self.include_target(&target, content_root, stack, files)?);
+            rest = &after_open[close + DIRECTIVE_CLOSE.len()..];
+        }
+
+        output.push_str(rest);
+        Ok(output)
+    }
+
+    fn include_target(
+        &self,
+        target: &str,
+        content_root: &Path,
+        stack: &mut Vec<PathBuf>,
+        files: &mut Vec<PathBuf>,
+    ) -> Result<String, IncludeError> {
+        // stack[0] is the page itself, so depth is the number of includes above us
+        if stack.len() > self.max_depth {
+            return Err(IncludeError::DepthExceeded {
+                target: target.to_string(),
+                limit: self.max_depth,
+            });
+        }
+
+        let (file_part, section) = match target.split_once(SECTION_SEPARATOR) {
+            Some((file_part, section)) => (file_part, Some(section)),
+            None => (target, None),
+        };
+
+        let path = resolve_within_root(file_part, content_root)?;
+
+        if stack.contains(&path) {
+            let mut chain = stack.clone();
+            chain.push(path);
+            return Err(IncludeError::Cycle { chain });
+        }
+
+        let content = fs::read_to_string(&path).map_err(|e| IncludeError::IoError {
+            path: path.clone(),
+            message: e.to_string(),
+        })?;
+        files.push(path.clone());
+
+        let content = match section {
+            Some(section) => {
+                extract_section(&content, section).ok_or_else(|| IncludeError::SectionNotFound {
+                    path: path.clone(),
+                    section: section.to_string(),
+                })?
+            }
+            None => content.as_str(),
+        };
+
+        stack.push(path);
+        let expanded =
+            self.expand_recursive(content.trim_end_matches('\n'), content_root, stack, files);
+        stack.pop();
+
+        expanded
+    }
+}
+
+/// Parses the quoted target out of a directive's argument (`"shared/contact.md"`)
+fn parse_include_target(argument: &str, directive: &str) -> Result<String, IncludeError> {
+    let argument = argument.trim();
+
+    let target = argument
+        .strip_prefix(QUOTE_CHAR)
+        .and_then(|a| a.strip_suffix(QUOTE_CHAR))
+        .filter(|t| !t.contains(QUOTE_CHAR))
+        .ok_or_else(|| IncludeError::InvalidDirective {
+            directive: directive.to_string(),
+            reason: "expected a quoted path".to_string(),
+        })?;
+
+    if target.is_empty() {
+        return Err(IncludeError::InvalidDirective {
+            directive: directive.to_string(),
+            reason: "path is empty".to_string(),
+        });
+    }
+
+    Ok(target.to_string())
+}
+
+/// Resolves an include path against the content root, refusing to leave it
+fn resolve_within_root(target: &str, content_root: &Path) -> Result<PathBuf, IncludeError> {
+    let relative = target.trim_start_matches(PATH_SEPARATOR);
+    let joined = content_root.join(relative);
+
+    let canonical = joined.canonicalize().map_err(|e| IncludeError::IoError {
+        path: joined.clone(),
+        message: e.to_string(),
+    })?;
+
+    // Canonicalizing resolves "..", "." and symlinks, so this catches every escape
+    if !canonical.starts_with(content_root) {
+        return Err(IncludeError::OutsideContentRoot {
+            target: target.to_string(),
+        });
+    }
+
+    Ok(canonical)
+}
+
+/// Returns the source of the section whose heading matches `name`
+///
+/// The section runs from its heading line up to (not including) the next
+/// heading of the same or a higher level. `#` lines inside fenced code
+/// blocks aren't headings.
+fn extract_section<'a>(content: &'a str, name: &str) -> Option<&'a str> {
+    let wanted = slugify(name);
+    let mut start: Option<(usize, usize)> = None;
+    let mut fence: Option<usize> = None;
+    let mut offset = 0;
+
+    for line in content.split_inclusive('\n') {
+        let level = line.chars().take_while(|&ch| ch == HEADING_CHAR).count();
+        let ticks = line.bytes().take_while(|&byte| byte == FENCE_CHAR).count();
+
+        if let Some(open_len) = fence {
+            if closing_fence_len(line).is_some_and(|len| len >= open_len) {
+                fence = None;
+            }
+        } else if ticks >= FENCE_MIN_LEN {
+            fence = Some(ticks);
+        } else if level > 0 {
+            match start {
+                Some((begin, start_level)) if level <= start_level => {
+                    return Some(&content[begin..offset]);
+                }
+                None if slugify(&line[level..]) == wanted => {
+                    start = Some((offset, level));
+                }
+                _ => {}
+            }
+        }
+
+        offset += line.len();
+    }
+
+    start.map(|(begin, _)| &content[begin..])
+}
+
+/// Converts heading text to a comparable slug ("Contact *Us*" → "contact-us")
+pub(crate) fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+
+    for ch in text.trim().chars() {
+        if ch.is_alphanumeric() {
+            slug.extend(ch.to_lowercase());
+        } else if (ch.is_whitespace() || ch == '-' || ch == '_') && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    slug.trim_matches('-').to_string()
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_content_root() -> tempfile::TempDir {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+
+        fs::create_dir(root.join("shared")).unwrap();
+        fs::write(
+            root.join("shared/contact.md"),
+            "Mail us at **team@example.com**\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("shared/disclaimer.md"),
+            "No warranty.\n\n{{include \"shared/contact.md\"}}\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("about.md"),
+            "# About\n\nIntro\n\n## Contact Us\n\nCall us.\n\n### Hours\n\n9-5\n\n## History\n\nLong ago.\n",
+        )
+        .unwrap();
+
+        temp_dir
+    }
+
+    fn expand(root: &Path, source: &str) -> Result<String, IncludeError> {
+        IncludeResolver::new(root).expand(source, &root.join("page.md"))
+    }
+
+    #[test]
+    fn test_include_whole_file() {
+        let temp_dir = create_content_root();
+        let result = expand(
+            temp_dir.path(),
+            "Before\n\n{{include \"shared/contact.md\"}}\n\nAfter",
+        );
+        assert_eq!(
+            result.unwrap(),
+            "Before\n\nMail us at **team@example.com**\n\nAfter"
+        );
+    }
+
+    #[test]
+    fn test_nested_include_and_leading_slash() {
+        let temp_dir = create_content_root();
+        let result = expand(temp_dir.path(), "{{ include \"/shared/disclaimer.md\" }}");
+        assert_eq!(
+            result.unwrap(),
+            "No warranty.\n\nMail us at **team@example.com**"
+        );
+    }
+
+    #[test]
+    fn test_expand_with_files_lists_nested_includes() {
+        let temp_dir = create_content_root();
+        let root = temp_dir.path().canonicalize().unwrap();
+        let (_, files) = IncludeResolver::new(&root)
+            .expand_with_files(
+                "{{include \"shared/disclaimer.md\"}}",
+                &root.join("page.md"),
+            )
+            .unwrap();
+        assert_eq!(
+            files,
+            vec![
+                root.join("shared/disclaimer.md"),
+                root.join("shared/contact.md")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_include_named_section() {
+        let temp_dir = create_content_root();
+        let result = expand(temp_dir.path(), "{{include \"about.md#contact-us\"}}");
+        assert_eq!(
+            result.unwrap(),
+            "## Contact Us\n\nCall us.\n\n### Hours\n\n9-5"
+        );
+
+        let result = expand(temp_dir.path(), "{{include \"about.md#History\"}}");
+        assert_eq!(result.unwrap(), "## History\n\nLong ago.");
+    }
+
+    #[test]
+    fn test_section_ignores_headings_in_code_fences() {
+        let temp_dir = create_content_root();
+        fs::write(
+            temp_dir.path().join("setup.md"),
+            "## Setup\nRun:\n```sh\n# comment\nmake\n```\n## Usage\nGo.\n",
+        )
+        .unwrap();
+        let result = expand(temp_dir.path(), "{{include \"setup.md#setup\"}}");
+        assert_eq!(
+            result.unwrap(),
+            "## Setup\nRun:\n```sh\n# comment\nmake\n```"
+        );
+    }
+
+    #[test]
+    fn test_missing_section() {
+        let temp_dir = create_content_root();
+        let result = expand(temp_dir.path(), "{{include \"about.md#pricing\"}}");
+        assert!(matches!(result, Err(IncludeError::SectionNotFound { .. })));
+    }
+
+    #[test]
+    fn test_non_include_and_escaped_directives_untouched() {
+        let temp_dir = create_content_root();
+        let source = "Hi {{ site.name }}, {{ includes }} and \\{{include \"shared/contact.md\"}}";
+        assert_eq!(expand(temp_dir.path(), source).unwrap(), source);
+    }
+
+    #[test]
+    fn test_directives_in_code_fences_untouched() {
+        let temp_dir = create_content_root();
+        let fenced = "```markdown\n{{include \"missing.md\"}}\n````\n\
+                      ````\n```\n{{include \"missing.md\"}}\n````\n";
+        let source = format!("{}After: {{{{include \"shared/contact.md\"}}}}", fenced);
+        assert_eq!(
+            expand(temp_dir.path(), &source).unwrap(),
+            format!("{}After: Mail us at **team@example.com**", fenced)
+        );
+
+        // An unclosed fence runs to the end
+        let source = "```\n{{include \"missing.md\"}}";
+        assert_eq!(expand(temp_dir.path(), source).unwrap(), source);
+    }
+
+    #[test]
+    fn test_invalid_directive() {
+        let temp_dir = create_content_root();
+        let result = expand(temp_dir.path(), "{{include shared/contact.md}}");
+        assert!(matches!(result, Err(IncludeError::InvalidDirective { .. })));
+    }
+
+    #[test]
+    fn test_refuses_to_escape_content_root() {
+        let outer = tempfile::tempdir().unwrap();
+        fs::write(outer.path().join("secret.md"), "secret").unwrap();
+        fs::create_dir(outer.path().join("site")).unwrap();
+        let root = outer.path().join("site");
+
+        let result = expand(&root, "{{include \"../secret.md\"}}");
+        assert!(matches!(
+            result,
+            Err(IncludeError::OutsideContentRoot { .. })
+        ));
+    }
+
+    #[test]
+    fn test_cycle_detection() {
+        let temp_dir = create_content_root();
+        let root = temp_dir.path();
+        fs::write(root.join("a.md"), "{{include \"b.md\"}}").unwrap();
+        fs::write(root.join("b.md"), "{{include \"a.md\"}}").unwrap();
+
+        let result =
+            IncludeResolver::new(root).expand("{{include \"a.md\"}}", &root.join("page.md"));
+        assert!(matches!(result, Err(IncludeError::Cycle { .. })));
+
+        // A page including itself is a cycle too
+        let result = IncludeResolver::new(root).expand("{{include \"a.md\"}}", &root.join("a.md"));
+        assert!(matches!(result, Err(IncludeError::Cycle { .. })));
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        let temp_dir = create_content_root();
+        let result = IncludeResolver::new(temp_dir.path())
+            .with_max_depth(1)
+            .expand(
+                "{{include \"shared/disclaimer.md\"}}",
+                &temp_dir.path().join("page.md"),
+            );
+        assert!(matches!(
+            result,
+            Err(IncludeError::DepthExceeded { limit: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify(" Contact *Us* "), "contact-us");
+        assert_eq!(slugify("FAQ & Support"), "faq-support");
+        assert_eq!(slugify("snake_case-name"), "snake-case-name");
+    }
+}
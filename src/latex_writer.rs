@@ -0,0 +1,324 @@
+use crate::parser::{Document, Event, ParseError, Tag, TagEnd};
+use crate::renderer::{self, RenderError, Renderer};
+use std::cell::Cell;
+use std::fmt;
+use std::io;
+
+// Sectioning commands by heading level (levels 5 and 6 share the last)
+const SECTION_COMMANDS: [&str; 5] = [
+    "\\section",
+    "\\subsection",
+    "\\subsubsection",
+    "\\paragraph",
+    "\\subparagraph",
+];
+
+// Inline commands
+const BOLD_OPEN: &str = "\\textbf{";
+const ITALIC_OPEN: &str = "\\emph{";
+const LINK_OPEN: &str = "\\href{";
+const GROUP_CLOSE: char = '}';
+const LINE_BREAK: &str = "\\\\\n";
+
+// Block environments
+const FIGURE_OPEN: &str = "\\begin{figure}[htbp]\n\\centering\n";
+const FIGURE_CLOSE: &str = "\\end{figure}\n";
+const INCLUDE_GRAPHICS: &str = "\\includegraphics[width=\\linewidth]";
+const CAPTION: &str = "\\caption";
+const VERBATIM_OPEN: &str = "\\begin{verbatim}\n";
+const VERBATIM_END: &str = "\\end{verbatim}";
+/// Breaks up an end marker inside code so it doesn't close the environment
+const VERBATIM_END_GUARDED: &str = "\\end {verbatim}";
+
+// Standalone documents
+const PREAMBLE: &str = "\\documentclass{article}\n\
+\\usepackage[utf8]{inputenc}\n\
+\\usepackage[T1]{fontenc}\n\
+\\usepackage{graphicx}\n\
+\\usepackage{hyperref}\n\
+\\begin{document}\n\n";
+const POSTAMBLE: &str = "\n\\end{document}\n";
+
+const NEWLINE: char = '\n';
+
+/// LaTeX writer for printable documents
+///
+/// - Sections from the heading tree: `\section` for level 1 down to
+///   `\subparagraph` for levels 5 and 6
+/// - Paragraphs separated by blank lines, line breaks as `\\`
+/// - Bold as `\textbf`, italic as `\emph`, links as `\href`
+/// - Images as figures with `\includegraphics`, captioned by their alt text
+/// - Code blocks as `verbatim` environments
+///
+/// Text is escaped for LaTeX's special characters (`\ { } $ & # % _ ^ ~`
+/// and `< > |`, which the default font encoding lacks). With
+/// `with_standalone` the output is a complete `article` document loading
+/// `graphicx` and `hyperref`; otherwise it's a fragment for `\input`.
+///
+/// # Example
+/// ```
+/// use wtf::latex_writer::LatexWriter;
+/// use wtf::parser::MarkdownParser;
+///
+/// let doc = MarkdownParser::parse("# Costs\n100% of **$5**").unwrap();
+/// let latex = LatexWriter::new().write_latex(&doc).unwrap();
+/// assert_eq!(latex, "\\section{Costs}\n\n100\\% of \\textbf{\\$5}\n");
+/// ```
+pub struct LatexWriter {
+    standalone: bool,
+    /// Whether a block has been written (blocks are separated by blank lines)
+    started: Cell<bool>,
+}
+
+impl LatexWriter {
+    /// Creates a writer that outputs a document fragment
+    pub fn new() -> Self {
+        Self {
+            standalone: false,
+            started: Cell::new(false),
+        }
+    }
+
+    /// Wraps the output in a complete document with the packages it needs
+    pub fn with_standalone(mut self, standalone: bool) -> Self {
+        self.standalone = standalone;
+        self
+    }
+
+    /// Converts a parsed markdown document to LaTeX
+    ///
+    /// # Returns
+    /// * `Ok(String)` - The LaTeX source, ending in a newline unless it's empty
+    /// * `Err(RenderError)` - If writing fails
+    pub fn write_latex(&self, document: &Document) -> Result<String, RenderError> {
+        renderer::render_to_string(self, document)
+    }
+
+    /// Streams the LaTeX for a document into a writer
+    ///
+    /// # Returns
+    /// * `Err(RenderError)` - If the writer returns an error
+    pub fn write_latex_to<W: io::Write>(
+        &self,
+        document: &Document,
+        writer: W,
+    ) -> Result<(), RenderError> {
+        renderer::render_to_io(self, document, writer)
+    }
+
+    /// Renders an event stream (e.g. `MarkdownParser::events`) to LaTeX
+    ///
+    /// # Returns
+    /// * `Ok(String)` - The same LaTeX as `write_latex` on the events' tree
+    /// * `Err(RenderError)` - For the first parse error in the stream
+    pub fn write_latex_events<'a, I>(&self, events: I) -> Result<String, RenderError>
+    where
+        I: IntoIterator<Item = Result<Event<'a>, ParseError>>,
+    {
+        renderer::render_events_to_string(self, events)
+    }
+
+    fn write_preamble(&self, out: &mut dyn fmt::Write) -> fmt::Result {
+        self.started.set(false);
+        if self.standalone {
+            out.write_str(PREAMBLE)?;
+        }
+        Ok(())
+    }
+
+    fn write_postamble(&self, out: &mut dyn fmt::Write) -> fmt::Result {
+        if self.standalone {
+            out.write_str(POSTAMBLE)?;
+        }
+        Ok(())
+    }
+
+    /// Separates a new block from the previous one by a blank line
+    fn start_block(&self, out: &mut dyn fmt::Write) -> fmt::Result {
+        if self.started.replace(true) {
+            out.write_char(NEWLINE)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for LatexWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer for LatexWriter {
+    type Error = RenderError;
+
+    fn begin(&self, out: &mut dyn fmt::Write) -> Result<(), RenderError> {
+        Ok(self.write_preamble(out)?)
+    }
+
+    fn finish(&self, out: &mut dyn fmt::Write) -> Result<(), RenderError> {
+        Ok(self.write_postamble(out)?)
+    }
+
+    fn start(&self, out: &mut dyn fmt::Write, tag: &Tag) -> Result<(), RenderError> {
+        match tag {
+            Tag::Heading(level) => {
+                self.start_block(out)?;
+                let index = usize::from(*level).clamp(1, SECTION_COMMANDS.len()) - 1;
+                write!(out, "{}{{", SECTION_COMMANDS[index])?;
+            }
+            Tag::Paragraph => self.start_block(out)?,
+            Tag::Bold => out.write_str(BOLD_OPEN)?,
+            Tag::Italic => out.write_str(ITALIC_OPEN)?,
+            Tag::Link(url) => write!(out, "{}{}}}{{", LINK_OPEN, escape_url(url))?,
+            Tag::Section(_) => {}
+        }
+        Ok(())
+    }
+
+    fn end(&self, out: &mut dyn fmt::Write, tag: TagEnd) -> Result<(), RenderError> {
+        match tag {
+            TagEnd::Heading(_) => {
+                out.write_char(GROUP_CLOSE)?;
+                out.write_char(NEWLINE)?;
+            }
+            TagEnd::Paragraph => out.write_char(NEWLINE)?,
+            TagEnd::Bold | TagEnd::Italic | TagEnd::Link => out.write_char(GROUP_CLOSE)?,
+            TagEnd::Section(_) => {}
+        }
+        Ok(())
+    }
+
+    fn text(&self, out: &mut dyn fmt::Write, text: &str) -> Result<(), RenderError> {
+        Ok(out.write_str(&escape_latex(text))?)
+    }
+
+    fn line_break(&self, out: &mut dyn fmt::Write) -> Result<(), RenderError> {
+        Ok(out.write_str(LINE_BREAK)?)
+    }
+
+    fn image(
+        &self,
+        out: &mut dyn fmt::Write,
+        alt_text: &str,
+        url: &str,
+    ) -> Result<(), RenderError> {
+        self.start_block(out)?;
+        out.write_str(FIGURE_OPEN)?;
+        writeln!(out, "{}{{{}}}", INCLUDE_GRAPHICS, escape_url(url))?;
+        if !alt_text.is_empty() {
+            writeln!(out, "{}{{{}}}", CAPTION, escape_latex(alt_text))?;
+        }
+        Ok(out.write_str(FIGURE_CLOSE)?)
+    }
+
+    fn code_block(
+        &self,
+        out: &mut dyn fmt::Write,
+        _info: &str,
+        code: &str,
+    ) -> Result<(), RenderError> {
+        self.start_block(out)?;
+        out.write_str(VERBATIM_OPEN)?;
+        for line in code.split(NEWLINE) {
+            writeln!(out, "{}", line.replace(VERBATIM_END, VERBATIM_END_GUARDED))?;
+        }
+        writeln!(out, "{}", VERBATIM_END)?;
+        Ok(())
+    }
+}
+
+/// Escapes LaTeX's special characters in text
+fn escape_latex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '{' | '}' | '$' | '&' | '#' | '%' | '_' => {
+                escaped.push('\\');
+                escaped.push(ch);
+            }
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '<' => escaped.push_str("\\textless{}"),
+            '>' => escaped.push_str("\\textgreater{}"),
+            '|' => escaped.push_str("\\textbar{}"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Escapes the characters `\href` and `\includegraphics` arguments can't
+/// contain as-is
+fn escape_url(url: &str) -> String {
+    let mut escaped = String::with_capacity(url.len());
+    for ch in url.chars() {
+        if matches!(ch, '\\' | '{' | '}' | '#' | '%') {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::MarkdownParser;
+
+    fn latex(source: &str) -> String {
+        LatexWriter::new()
+            .write_latex(&MarkdownParser::parse(source).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_sections() {
+        assert_eq!(
+            latex("# One\n## Two\n### Three\n#### Four\n##### Five\n###### Six"),
+            "\\section{One}\n\n\\subsection{Two}\n\n\\subsubsection{Three}\n\n\
+             \\paragraph{Four}\n\n\\subparagraph{Five}\n\n\\subparagraph{Six}\n"
+        );
+    }
+
+    #[test]
+    fn test_escaping() {
+        assert_eq!(
+            latex("a_b & c^2 ~ {x} <y> | 50% #1 \\\\"),
+            "a\\_b \\& c\\textasciicircum{}2 \\textasciitilde{} \\{x\\} \
+             \\textless{}y\\textgreater{} \\textbar{} 50\\% \\#1 \\textbackslash{}\n"
+        );
+    }
+
+    #[test]
+    fn test_inline_and_blocks() {
+        assert_eq!(
+            latex(
+                "**b *i* x** [a link](https://x.org/#top%20)\nnext\n\n![A cat](cat_1.png)\n\n```\n\\end{verbatim}\n```"
+            ),
+            "\\textbf{b \\emph{i} x} \\href{https://x.org/\\#top\\%20}{a link}\\\\\nnext\n\n\
+             \\begin{figure}[htbp]\n\\centering\n\
+             \\includegraphics[width=\\linewidth]{cat_1.png}\n\\caption{A cat}\n\\end{figure}\n\n\
+             \\begin{verbatim}\n\\end {verbatim}\n\\end{verbatim}\n"
+        );
+    }
+
+    #[test]
+    fn test_standalone() {
+        let doc = MarkdownParser::parse("Hi").unwrap();
+        let latex = LatexWriter::new()
+            .with_standalone(true)
+            .write_latex(&doc)
+            .unwrap();
+        assert!(latex.starts_with("\\documentclass{article}\n"));
+        assert!(latex.ends_with("\\begin{document}\n\nHi\n\n\\end{document}\n"));
+    }
+
+    #[test]
+    fn test_events_render_like_tree() {
+        renderer::assert_events_render_like_tree(
+            &LatexWriter::new().with_standalone(true),
+            renderer::EVENTS_FIXTURE,
+        );
+    }
+}
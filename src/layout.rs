@@ -0,0 +1,401 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+// Layout files
+pub const LAYOUT_FILENAME: &str = "_layout.html";
+const NAMED_LAYOUT_PREFIX: &str = "_layout-";
+const LAYOUT_EXTENSION: &str = ".html";
+/// Front matter key selecting a page's layout (`layout: wide` uses
+/// `_layout-wide.html`)
+pub const LAYOUT_KEY: &str = "layout";
+
+// Placeholders
+const PLACEHOLDER_OPEN: &str = "{{";
+const PLACEHOLDER_CLOSE: &str = "}}";
+const TITLE_PLACEHOLDER: &str = "title";
+const CONTENT_PLACEHOLDER: &str = "content";
+const SITEMAP_PLACEHOLDER: &str = "sitemap";
+const BREADCRUMBS_PLACEHOLDER: &str = "breadcrumbs";
+
+/// The built-in theme, used when the content root has no `_layout.html`
+const DEFAULT_LAYOUT: &str = concat!(
+    "<!DOCTYPE html><html><head><meta charset=\"utf-8\">",
+    "<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">",
+    "<title>{{title}}</title><style>",
+    "body{max-width:44rem;margin:0 auto;padding:1rem 1.25rem;color:#222;background:#fff;",
+    "font:1.0625rem/1.6 system-ui,-apple-system,\"Segoe UI\",Roboto,sans-serif}",
+    "h1,h2,h3,h4,h5,h6{line-height:1.25;margin:1.6em 0 .5em}",
+    "a{color:#0b57d0}",
+    "img{max-width:100%;height:auto}",
+    "figure{margin:1.5em 0}figcaption{color:#555;font-size:.9em}",
+    "code,pre{font-family:ui-monospace,SFMono-Regular,Menlo,Consolas,monospace;font-size:.9em}",
+    "pre{overflow-x:auto;padding:.75em 1em;background:#f5f5f5;border-radius:4px}",
+    ".breadcrumbs{list-style:none;margin:0;padding:0;color:#555;font-size:.9em}",
+    ".breadcrumbs li{display:inline}",
+    ".breadcrumbs li+li:before{content:\"/\";margin:0 .4em;color:#999}",
+    "footer{margin-top:3rem;font-size:.9em}",
+    ".tok-keyword{color:#a626a4}.tok-type,.tok-section{color:#c18401}",
+    ".tok-function{color:#4078f2}.tok-macro,.tok-attribute{color:#0184bc}",
+    ".tok-string{color:#50a14f}.tok-number,.tok-literal{color:#986801}",
+    ".tok-comment{color:#8e8f96;font-style:italic}.tok-key,.tok-variable{color:#e45649}",
+    "@media (prefers-color-scheme:dark){body{color:#ddd;background:#161616}",
+    "a{color:#8ab4f8}pre{background:#222}figcaption,.breadcrumbs{color:#aaa}}",
+    "</style></head><body><nav>{{breadcrumbs}}</nav><main>{{content}}</main>",
+    "<footer><hr><hr>{{sitemap}}</footer></body></html>",
+);
+
+// ============================================================================
+// ERROR HANDLING
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayoutError {
+    /// A `{{` without its `}}`
+    UnclosedPlaceholder { line: usize },
+    /// A placeholder other than title, content, sitemap and breadcrumbs
+    UnknownPlaceholder { name: String, line: usize },
+    /// No `{{content}}`, so pages would render empty
+    MissingContent,
+    /// A `layout:` value that isn't a plain name
+    InvalidName { name: String },
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutError::UnclosedPlaceholder { line } => {
+                write!(f, "Unclosed placeholder on line {}", line)
+            }
+            LayoutError::UnknownPlaceholder { name, line } => {
+                write!(f, "Unknown placeholder '{}' on line {}", name, line)
+            }
+            LayoutError::MissingContent => {
+                write!(f, "Layout has no {{{{content}}}} placeholder")
+            }
+            LayoutError::InvalidName { name } => {
+                write!(f, "Invalid layout name '{}'", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+// ============================================================================
+// LAYOUT
+// ============================================================================
+
+/// What a placeholder is replaced with
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Slot {
+    Title,
+    Content,
+    Sitemap,
+    Breadcrumbs,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Text(String),
+    Slot(Slot),
+}
+
+/// The parts of a page a layout places
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LayoutParts<'a> {
+    /// Page title as plain text (escaped when placed)
+    pub title: &'a str,
+    /// The page's HTML (`render_to` writes it with a callback instead)
+    pub content: &'a str,
+    /// Nested sitemap list (`router::generate_sitemap_list`)
+    pub sitemap: &'a str,
+    /// Breadcrumb trail (`router::generate_breadcrumbs`)
+    pub breadcrumbs: &'a str,
+}
+
+/// An HTML page template
+///
+/// Layouts are HTML with placeholders, spaces inside the braces optional:
+/// - `{{title}}` - The page title (its first H1)
+/// - `{{content}}` - The rendered page (required)
+/// - `{{sitemap}}` - The nested sitemap list, marking the current page
+/// - `{{breadcrumbs}}` - Links from the site root down to the page
+///
+/// A site's pages use `_layout.html` from the content root, or the
+/// built-in theme without one. A page picks another layout with
+/// `layout: <name>` in its front matter, which uses `_layout-<name>.html`.
+///
+/// # Example
+/// ```
+/// use wtf::layout::{Layout, LayoutParts};
+///
+/// let layout = Layout::parse("<title>{{ title }}</title>{{content}}")?;
+/// let parts = LayoutParts {
+///     title: "Q&A",
+///     content: "<p>Hi</p>",
+///     ..LayoutParts::default()
+/// };
+/// assert_eq!(layout.render(&parts), "<title>Q&amp;A</title><p>Hi</p>");
+/// # Ok::<(), wtf::layout::LayoutError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layout {
+    segments: Vec<Segment>,
+}
+
+impl Layout {
+    /// Parses a layout template
+    ///
+    /// # Returns
+    /// * `Ok(Layout)` - The parsed layout
+    /// * `Err(LayoutError)` - If a placeholder is unclosed or unknown, or
+    ///   `{{content}}` is missing
+    pub fn parse(text: &str) -> Result<Self, LayoutError> {
+        let mut segments = Vec::new();
+        let mut rest = text;
+
+        while let Some(open) = rest.find(PLACEHOLDER_OPEN) {
+            let line = text[..text.len() - rest.len() + open].matches('\n').count() + 1;
+            let after_open = &rest[open + PLACEHOLDER_OPEN.len()..];
+            let close = after_open
+                .find(PLACEHOLDER_CLOSE)
+                .ok_or(LayoutError::UnclosedPlaceholder { line })?;
+
+            let name = after_open[..close].trim();
+            let slot = match name {
+                TITLE_PLACEHOLDER => Slot::Title,
+                CONTENT_PLACEHOLDER => Slot::Content,
+                SITEMAP_PLACEHOLDER => Slot::Sitemap,
+                BREADCRUMBS_PLACEHOLDER => Slot::Breadcrumbs,
+                _ => {
+                    return Err(LayoutError::UnknownPlaceholder {
+                        name: name.to_string(),
+                        line,
+                    });
+                }
+            };
+
+            if open > 0 {
+                segments.push(Segment::Text(rest[..open].to_string()));
+            }
+            segments.push(Segment::Slot(slot));
+            rest = &after_open[close + PLACEHOLDER_CLOSE.len()..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Text(rest.to_string()));
+        }
+
+        if !segments.contains(&Segment::Slot(Slot::Content)) {
+            return Err(LayoutError::MissingContent);
+        }
+        Ok(Self { segments })
+    }
+
+    /// The built-in theme: readable typography, the breadcrumb trail above
+    /// the page and the sitemap below it, with colors for highlighted code
+    /// and a dark variant
+    pub fn default_theme() -> Self {
+        Self::parse(DEFAULT_LAYOUT).expect("built-in layout is valid")
+    }
+
+    /// Places a page's parts into the layout
+    pub fn render(&self, parts: &LayoutParts) -> String {
+        let mut output =
+            Vec::with_capacity(DEFAULT_LAYOUT.len() + parts.content.len() + parts.sitemap.len());
+        self.render_to(&mut output, parts, |out| {
+            out.write_all(parts.content.as_bytes())
+        })
+        .expect("writing to a Vec doesn't fail");
+        String::from_utf8(output).expect("layout parts are UTF-8")
+    }
+
+    /// Streams the layout into a writer, segment by segment
+    ///
+    /// The page itself is written by `write_content` at `{{content}}`, so
+    /// it never has to be held as a string (`parts.content` is unused).
+    ///
+    /// # Arguments
+    /// * `out` - Destination for the HTML
+    /// * `parts` - The title, sitemap and breadcrumbs to place
+    /// * `write_content` - Writes the page's HTML into `out`
+    ///
+    /// # Returns
+    /// * `Err(E)` - If `out` or `write_content` fails
+    pub fn render_to<W, E>(
+        &self,
+        out: &mut W,
+        parts: &LayoutParts,
+        mut write_content: impl FnMut(&mut W) -> Result<(), E>,
+    ) -> Result<(), E>
+    where
+        W: io::Write,
+        E: From<io::Error>,
+    {
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(text) => out.write_all(text.as_bytes())?,
+                Segment::Slot(Slot::Title) => out.write_all(escape_html(parts.title).as_bytes())?,
+                Segment::Slot(Slot::Content) => write_content(out)?,
+                Segment::Slot(Slot::Sitemap) => out.write_all(parts.sitemap.as_bytes())?,
+                Segment::Slot(Slot::Breadcrumbs) => out.write_all(parts.breadcrumbs.as_bytes())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Finds the file of a layout
+///
+/// # Arguments
+/// * `content_root` - Directory layouts live in
+/// * `name` - A page's `layout:` value, or `None` for the site's default
+///
+/// # Returns
+/// * `Ok(PathBuf)` - `_layout.html`, or `_layout-<name>.html` (which may
+///   not exist)
+/// * `Err(LayoutError::InvalidName)` - If the name is empty or could leave
+///   the content root
+pub fn layout_path(content_root: &Path, name: Option<&str>) -> Result<PathBuf, LayoutError> {
+    let Some(name) = name else {
+        return Ok(content_root.join(LAYOUT_FILENAME));
+    };
+
+    let is_plain = !name.is_empty()
+        && name
+            .chars()
+            .all(|ch| ch.is_alphanumeric() || ch == '-' || ch == '_');
+    if !is_plain {
+        return Err(LayoutError::InvalidName {
+            name: name.to_string(),
+        });
+    }
+    Ok(content_root.join(format!(
+        "{}{}{}",
+        NAMED_LAYOUT_PREFIX, name, LAYOUT_EXTENSION
+    )))
+}
+
+/// Whether a file name is a layout's (`_layout.html`, `_layout-*.html`)
+pub fn is_layout_file(file_name: &str) -> bool {
+    file_name == LAYOUT_FILENAME
+        || (file_name.starts_with(NAMED_LAYOUT_PREFIX) && file_name.ends_with(LAYOUT_EXTENSION))
+}
+
+/// Escapes HTML entities in the title
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_fills_every_placeholder() {
+        let layout = Layout::parse(
+            "<title>{{title}}</title>\n<nav>{{ breadcrumbs }}</nav>{{content}}{{sitemap}}{{content}}",
+        )
+        .unwrap();
+        let parts = LayoutParts {
+            title: "<Guide>",
+            content: "<p>x</p>",
+            sitemap: "<ul></ul>",
+            breadcrumbs: "<ol></ol>",
+        };
+        assert_eq!(
+            layout.render(&parts),
+            "<title>&lt;Guide&gt;</title>\n<nav><ol></ol></nav><p>x</p><ul></ul><p>x</p>"
+        );
+    }
+
+    #[test]
+    fn test_render_to_streams_content() {
+        let layout = Layout::parse("<title>{{title}}</title><main>{{content}}</main>").unwrap();
+        let parts = LayoutParts {
+            title: "A&B",
+            content: "ignored",
+            ..LayoutParts::default()
+        };
+        let mut output = Vec::new();
+        layout
+            .render_to(&mut output, &parts, |out| out.write_all(b"<p>streamed</p>"))
+            .unwrap();
+        assert_eq!(
+            output,
+            b"<title>A&amp;B</title><main><p>streamed</p></main>"
+        );
+
+        let result = layout.render_to(&mut Vec::new(), &parts, |_| {
+            Err(io::Error::other("content failed"))
+        });
+        assert_eq!(result.unwrap_err().to_string(), "content failed");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            Layout::parse("<p>\n{{content}}\n{{ site.name }}"),
+            Err(LayoutError::UnknownPlaceholder {
+                name: "site.name".to_string(),
+                line: 3,
+            })
+        );
+        assert_eq!(
+            Layout::parse("{{content}} {{title"),
+            Err(LayoutError::UnclosedPlaceholder { line: 1 })
+        );
+        assert_eq!(
+            Layout::parse("<title>{{title}}</title>"),
+            Err(LayoutError::MissingContent)
+        );
+    }
+
+    #[test]
+    fn test_default_theme() {
+        let html = Layout::default_theme().render(&LayoutParts {
+            title: "Test Title",
+            content: "<p>Content</p>",
+            sitemap: "<ul></ul>",
+            breadcrumbs: "",
+        });
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<meta charset=\"utf-8\">"));
+        assert!(html.contains("<title>Test Title</title>"));
+        assert!(html.contains("<main><p>Content</p></main>"));
+        assert!(html.contains("<footer><hr><hr><ul></ul></footer>"));
+    }
+
+    #[test]
+    fn test_escape_html() {
+        let escaped = escape_html("<script>alert('XSS')</script>");
+        assert_eq!(escaped, "&lt;script&gt;alert(&#39;XSS&#39;)&lt;/script&gt;");
+    }
+
+    #[test]
+    fn test_layout_path() {
+        let root = Path::new("/site");
+        assert_eq!(
+            layout_path(root, None).unwrap(),
+            PathBuf::from("/site/_layout.html")
+        );
+        assert_eq!(
+            layout_path(root, Some("wide")).unwrap(),
+            PathBuf::from("/site/_layout-wide.html")
+        );
+        assert!(layout_path(root, Some("../secret")).is_err());
+        assert!(layout_path(root, Some("")).is_err());
+
+        assert!(is_layout_file("_layout.html"));
+        assert!(is_layout_file("_layout-wide.html"));
+        assert!(!is_layout_file("layout.html"));
+    }
+}
@@ -1,9 +1,32 @@
+pub mod build;
+mod build_cache;
 pub mod cli;
+pub mod emoji;
+pub mod epub;
+mod export;
+pub mod front_matter;
+pub mod gemtext_writer;
+pub mod gopher_writer;
+pub mod highlight;
 pub mod html_writer;
+pub mod image_size;
+pub mod include;
+pub mod latex_writer;
+pub mod layout;
+pub mod man_writer;
+pub mod markdown_writer;
 pub mod parser;
+pub mod renderer;
 pub mod router;
 pub mod server;
+pub mod single_page;
+pub mod term_writer;
+pub mod text_writer;
+pub mod variables;
+pub mod visit;
+mod zip;
 
 // Re-export main types for convenience
-pub use router::{ResolvedPath, Router, RouterError};
+pub use export::ExportError;
+pub use router::{LinkRewriter, ResolvedPath, Router, RouterError};
 pub use server::{Server, ServerConfig, ServerError};
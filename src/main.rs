@@ -1,17 +1,28 @@
-mod cli;
-mod html_writer;
-mod parser;
-mod router;
-mod server;
-
 use clap::Parser;
-use cli::{Cli, Commands};
-use html_writer::HtmlWriter;
-use parser::MarkdownParser;
-use server::{Server, ServerConfig};
 use std::env;
 use std::fs;
 use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use wtf::build::SiteBuilder;
+use wtf::cli::{self, Cli, Commands, ExportFormat, IndexFormat, RenderFormat};
+use wtf::emoji::EmojiExpander;
+use wtf::epub::EpubExporter;
+use wtf::front_matter::split_front_matter;
+use wtf::gemtext_writer::GemtextWriter;
+use wtf::gopher_writer::GopherWriter;
+use wtf::html_writer::{HtmlConfig, HtmlWriter};
+use wtf::include::IncludeResolver;
+use wtf::latex_writer::LatexWriter;
+use wtf::man_writer::ManWriter;
+use wtf::markdown_writer::format_source;
+use wtf::parser::{Document, MarkdownParser};
+use wtf::router::ResolvedPath;
+use wtf::router::Router;
+use wtf::server::{Server, ServerConfig};
+use wtf::single_page::SinglePageExporter;
+use wtf::term_writer::TermWriter;
+use wtf::text_writer::TextWriter;
+use wtf::variables::{PAGE_UPDATED, Variables, format_date, load_site_config};
 
 // Constants for messages
 const ERROR_PREFIX: &str = "Error:";
@@ -20,6 +31,9 @@ const CONTENT_ROOT_PREFIX: &str = "Content root:";
 const LISTENING_PREFIX: &str = "Listening on:";
 const STOP_MESSAGE: &str = "Press Ctrl+C to stop the server";
 const HTTP_PREFIX: &str = "http://";
+const MD_EXTENSION: &str = "md";
+const WROTE_PREFIX: &str = "Wrote";
+const BUILT_PREFIX: &str = "Built";
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
@@ -27,6 +41,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     match cli.command {
         Commands::Serve(args) => handle_serve(args),
         Commands::Render(args) => handle_render(args),
+        Commands::View(args) => handle_view(args),
+        Commands::Index(args) => handle_index(args),
+        Commands::Export(args) => handle_export(args),
+        Commands::Build(args) => handle_build(args),
+        Commands::Fmt(args) => handle_fmt(args),
     }
 }
 
@@ -53,9 +72,14 @@ fn handle_serve(args: cli::ServeArgs) -> Result<(), Box<dyn std::error::Error>>
     }
 
     // Create server configuration
-    let config = ServerConfig::new(content_root.clone())
+    let mut config = ServerConfig::new(content_root.clone())
         .with_host(args.host.clone())
-        .with_port(args.port);
+        .with_port(args.port)
+        .with_emoji(args.emoji);
+
+    if let Some(emoji_map) = args.emoji_map {
+        config = config.with_emoji_map(emoji_map);
+    }
 
     // Print startup information to stderr
     eprintln!("{}", STARTING_SERVER);
@@ -107,35 +131,538 @@ fn handle_render(args: cli::RenderArgs) -> Result<(), Box<dyn std::error::Error>
         std::process::exit(1);
     }
 
-    // Read the markdown file
-    let content = fs::read_to_string(&args.file).map_err(|e| {
+    // Includes and _site.conf are found in the content root (default: the file's directory)
+    let content_root = match args.root {
+        Some(root) => root,
+        None => match args.file.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => env::current_dir()?,
+        },
+    };
+    let document = load_document(
+        &args.file,
+        &content_root,
+        args.emoji,
+        args.emoji_map.as_deref(),
+    )?;
+
+    // Convert to the requested output format
+    match args.format {
+        RenderFormat::Html => {
+            let mut config = HtmlConfig::new()
+                .with_xhtml(args.xhtml)
+                .with_pretty(args.pretty)
+                .with_sections(args.sections)
+                .with_heading_offset(args.heading_offset)
+                .with_external_new_tab(args.external_new_tab)
+                .with_external_noopener(args.external_noopener)
+                .with_figures(args.figures)
+                .with_lazy_images(args.lazy_images)
+                .with_image_dimensions(args.image_dimensions)
+                .with_highlight(args.highlight);
+            if let Some(prefix) = args.class_prefix {
+                config = config.with_class_prefix(prefix);
+            }
+            if let Some(class) = args.external_class {
+                config = config.with_external_class(class);
+            }
+
+            // Link .md files by the routes the server would serve them at
+            let router = open_router(&content_root)?;
+
+            // Stream straight to stdout rather than building the page in memory
+            let mut stdout = io::stdout().lock();
+            let writer = HtmlWriter::with_config(config)
+                .with_link_rewriter(router.link_rewriter(&args.file));
+            writer.write_html_to(&document, &mut stdout).map_err(|e| {
+                // Write error to stderr
+                let _ = writeln!(
+                    io::stderr(),
+                    "{} HTML generation error: {}",
+                    ERROR_PREFIX,
+                    e
+                );
+                e
+            })?;
+            // Pretty output already ends its last line
+            if !args.pretty {
+                writeln!(stdout)?;
+            }
+        }
+        RenderFormat::Text => {
+            let mut writer = TextWriter::new();
+            if let Some(width) = args.width {
+                writer = writer.with_width(width);
+            }
+            let mut stdout = io::stdout().lock();
+            writer.write_text_to(&document, &mut stdout).map_err(|e| {
+                // Write error to stderr
+                let _ = writeln!(
+                    io::stderr(),
+                    "{} Text generation error: {}",
+                    ERROR_PREFIX,
+                    e
+                );
+                e
+            })?;
+        }
+        RenderFormat::Term => write_term(&document, args.width)?,
+        RenderFormat::Gemtext => {
+            let router = open_router(&content_root)?;
+            let writer = GemtextWriter::new().with_link_rewriter(router.link_rewriter(&args.file));
+            let mut stdout = io::stdout().lock();
+            writer
+                .write_gemtext_to(&document, &mut stdout)
+                .map_err(|e| {
+                    // Write error to stderr
+                    let _ = writeln!(
+                        io::stderr(),
+                        "{} Gemtext generation error: {}",
+                        ERROR_PREFIX,
+                        e
+                    );
+                    e
+                })?;
+        }
+        RenderFormat::Gopher => {
+            let router = open_router(&content_root)?;
+            let mut writer = GopherWriter::new(args.gopher_host, args.gopher_port)
+                .with_link_rewriter(router.link_rewriter(&args.file));
+            if let Some(width) = args.width {
+                writer = writer.with_width(width);
+            }
+            let mut stdout = io::stdout().lock();
+            writer
+                .write_gophermap_to(&document, &mut stdout)
+                .map_err(|e| {
+                    // Write error to stderr
+                    let _ = writeln!(
+                        io::stderr(),
+                        "{} Gopher map generation error: {}",
+                        ERROR_PREFIX,
+                        e
+                    );
+                    e
+                })?;
+        }
+        RenderFormat::Man => {
+            // Man pages are titled after the command, i.e. the file name
+            let title = args
+                .file
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let writer = ManWriter::new(title).with_section(args.man_section);
+            let mut stdout = io::stdout().lock();
+            writer.write_man_to(&document, &mut stdout).map_err(|e| {
+                // Write error to stderr
+                let _ = writeln!(
+                    io::stderr(),
+                    "{} Man page generation error: {}",
+                    ERROR_PREFIX,
+                    e
+                );
+                e
+            })?;
+        }
+        RenderFormat::Latex => {
+            let writer = LatexWriter::new().with_standalone(args.standalone);
+            let mut stdout = io::stdout().lock();
+            writer.write_latex_to(&document, &mut stdout).map_err(|e| {
+                // Write error to stderr
+                let _ = writeln!(
+                    io::stderr(),
+                    "{} LaTeX generation error: {}",
+                    ERROR_PREFIX,
+                    e
+                );
+                e
+            })?;
+        }
+        RenderFormat::Json => {
+            let output = document_to_json(&document).map_err(|e| {
+                // Write error to stderr
+                let _ = writeln!(
+                    io::stderr(),
+                    "{} JSON generation error: {}",
+                    ERROR_PREFIX,
+                    e
+                );
+                e
+            })?;
+
+            // Output to stdout
+            println!("{}", output);
+        }
+    }
+
+    Ok(())
+}
+
+/// Handle the 'view' subcommand
+///
+/// Resolves a route the way the server would and prints the page it serves
+/// styled for the terminal.
+fn handle_view(args: cli::ViewArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let content_root = match args.path {
+        Some(path) => path,
+        None => env::current_dir()?,
+    };
+    let router = open_router(&content_root)?;
+
+    let file = match router.resolve_path(&args.route)? {
+        ResolvedPath::Found(file) => file,
+        ResolvedPath::NotFound { .. } => {
+            // Write error to stderr
+            writeln!(
+                io::stderr(),
+                "{} No page at route: {}",
+                ERROR_PREFIX,
+                args.route
+            )?;
+            std::process::exit(1);
+        }
+    };
+
+    let document = load_document(&file, &content_root, args.emoji, args.emoji_map.as_deref())?;
+    write_term(&document, args.width)
+}
+
+/// Handle the 'index' subcommand
+///
+/// Prints the site's sitemap as a Gemini or Gopher index page.
+fn handle_index(args: cli::IndexArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let content_root = match args.path {
+        Some(path) => path,
+        None => env::current_dir()?,
+    };
+    let sitemap = open_router(&content_root)?.build_sitemap().map_err(|e| {
         // Write error to stderr
-        let _ = writeln!(io::stderr(), "{} Failed to read file: {}", ERROR_PREFIX, e);
+        let _ = writeln!(io::stderr(), "{} {}", ERROR_PREFIX, e);
         e
     })?;
 
-    // Parse the markdown
-    let document = MarkdownParser::parse(&content).map_err(|e| {
+    let output = match args.format {
+        IndexFormat::Gemtext => GemtextWriter::new().write_sitemap(&sitemap),
+        IndexFormat::Gopher => {
+            GopherWriter::new(args.gopher_host, args.gopher_port).write_sitemap(&sitemap)
+        }
+    };
+    io::stdout().lock().write_all(output.as_bytes())?;
+    Ok(())
+}
+
+/// Handle the 'export' subcommand
+fn handle_export(args: cli::ExportArgs) -> Result<(), Box<dyn std::error::Error>> {
+    match args.format {
+        ExportFormat::Epub(args) => export_epub(args),
+        ExportFormat::SinglePage(args) => export_single_page(args),
+    }
+}
+
+/// Packages the site as an EPUB book, removing the partial file on failure
+fn export_epub(args: cli::EpubArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let content_root = match args.path {
+        Some(path) => path,
+        None => env::current_dir()?,
+    };
+    let mut config = ServerConfig::new(content_root).with_emoji(args.emoji);
+    if let Some(emoji_map) = args.emoji_map {
+        config = config.with_emoji_map(emoji_map);
+    }
+
+    let mut exporter = EpubExporter::new(config)
+        .map_err(|e| {
+            // Write error to stderr
+            let _ = writeln!(io::stderr(), "{} {}", ERROR_PREFIX, e);
+            e
+        })?
+        .with_language(args.language);
+    if let Some(title) = args.title {
+        exporter = exporter.with_title(title);
+    }
+
+    let file = fs::File::create(&args.output)?;
+    if let Err(e) = exporter.write_epub(io::BufWriter::new(file)) {
         // Write error to stderr
-        let _ = writeln!(io::stderr(), "{} Parse error: {}", ERROR_PREFIX, e);
+        let _ = writeln!(io::stderr(), "{} EPUB export error: {}", ERROR_PREFIX, e);
+        let _ = fs::remove_file(&args.output);
+        return Err(e.into());
+    }
+    eprintln!("{} {}", WROTE_PREFIX, args.output.display());
+    Ok(())
+}
+
+/// Combines the site into one HTML file, removing the partial file on
+/// failure
+fn export_single_page(args: cli::SinglePageArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let content_root = match args.path {
+        Some(path) => path,
+        None => env::current_dir()?,
+    };
+    let mut config = ServerConfig::new(content_root).with_emoji(args.emoji);
+    if let Some(emoji_map) = args.emoji_map {
+        config = config.with_emoji_map(emoji_map);
+    }
+
+    let mut exporter = SinglePageExporter::new(config).map_err(|e| {
+        // Write error to stderr
+        let _ = writeln!(io::stderr(), "{} {}", ERROR_PREFIX, e);
         e
     })?;
+    if let Some(title) = args.title {
+        exporter = exporter.with_title(title);
+    }
+    if let Some(css) = args.css {
+        let css = fs::read_to_string(&css).map_err(|e| {
+            // Write error to stderr
+            let _ = writeln!(
+                io::stderr(),
+                "{} Failed to read {}: {}",
+                ERROR_PREFIX,
+                css.display(),
+                e
+            );
+            e
+        })?;
+        exporter = exporter.with_stylesheet(css);
+    }
 
-    // Convert to HTML
-    let writer = HtmlWriter::new();
-    let html = writer.write_html(&document).map_err(|e| {
+    let file = fs::File::create(&args.output)?;
+    if let Err(e) = exporter.write_html(io::BufWriter::new(file)) {
         // Write error to stderr
         let _ = writeln!(
             io::stderr(),
-            "{} HTML generation error: {}",
+            "{} Single-page export error: {}",
             ERROR_PREFIX,
             e
         );
+        let _ = fs::remove_file(&args.output);
+        return Err(e.into());
+    }
+    eprintln!("{} {}", WROTE_PREFIX, args.output.display());
+    Ok(())
+}
+
+/// Renders the site into a static directory
+fn handle_build(args: cli::BuildArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = ServerConfig::new(args.source).with_emoji(args.emoji);
+    if let Some(emoji_map) = args.emoji_map {
+        config = config.with_emoji_map(emoji_map);
+    }
+
+    let summary = SiteBuilder::new(config)
+        .and_then(|builder| builder.with_force(args.force).build(&args.output))
+        .map_err(|e| {
+            // Write error to stderr
+            let _ = writeln!(io::stderr(), "{} Build error: {}", ERROR_PREFIX, e);
+            e
+        })?;
+    eprintln!(
+        "{} {} pages, {} error pages and {} assets into {} ({} unchanged)",
+        BUILT_PREFIX,
+        summary.pages,
+        summary.not_found_pages,
+        summary.assets,
+        args.output.display(),
+        summary.unchanged
+    );
+    Ok(())
+}
+
+/// Creates a router over the content root, reporting errors on stderr
+fn open_router(content_root: &Path) -> Result<Router, Box<dyn std::error::Error>> {
+    Router::new(content_root.to_path_buf()).map_err(|e| {
+        // Write error to stderr
+        let _ = writeln!(io::stderr(), "{} {}", ERROR_PREFIX, e);
+        e.into()
+    })
+}
+
+/// Prints a document to stdout styled for the terminal, wrapped at `width`
+/// (default: the terminal width)
+fn write_term(document: &Document, width: Option<usize>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = TermWriter::new();
+    if let Some(width) = width {
+        writer = writer.with_width(width);
+    }
+    let mut stdout = io::stdout().lock();
+    writer.write_term_to(document, &mut stdout).map_err(|e| {
+        // Write error to stderr
+        let _ = writeln!(
+            io::stderr(),
+            "{} Terminal output error: {}",
+            ERROR_PREFIX,
+            e
+        );
+        e
+    })?;
+    Ok(())
+}
+
+/// Reads a markdown file into the document the server would render: front
+/// matter split off, includes resolved, `{{ site.* }}`/`{{ page.* }}`
+/// variables substituted and, if requested, emoji expanded
+///
+/// Errors are reported on stderr as well as returned.
+fn load_document(
+    file: &Path,
+    content_root: &Path,
+    emoji: bool,
+    emoji_map: Option<&Path>,
+) -> Result<Document<'static>, Box<dyn std::error::Error>> {
+    // Read the markdown file
+    let content = fs::read_to_string(file).map_err(|e| {
+        // Write error to stderr
+        let _ = writeln!(io::stderr(), "{} Failed to read file: {}", ERROR_PREFIX, e);
+        e
+    })?;
+
+    // Split off front matter (page variables)
+    let (front_matter, body) = split_front_matter(&content).map_err(|e| {
+        // Write error to stderr
+        let _ = writeln!(io::stderr(), "{} Parse error: {}", ERROR_PREFIX, e);
+        e
+    })?;
+
+    // Resolve includes relative to the content root
+    let content = IncludeResolver::new(content_root)
+        .expand(body, file)
+        .map_err(|e| {
+            // Write error to stderr
+            let _ = writeln!(io::stderr(), "{} Include error: {}", ERROR_PREFIX, e);
+            e
+        })?;
+
+    // Parse the markdown
+    let mut document = MarkdownParser::parse(&content).map_err(|e| {
+        // Write error to stderr
+        let _ = writeln!(io::stderr(), "{} Parse error: {}", ERROR_PREFIX, e);
         e
     })?;
 
-    // Output HTML to stdout
-    println!("{}", html);
+    // Substitute {{ site.* }} and {{ page.* }} variables
+    let site_config = load_site_config(content_root).map_err(|e| {
+        // Write error to stderr
+        let _ = writeln!(io::stderr(), "{} {}", ERROR_PREFIX, e);
+        e
+    })?;
+    let mut variables = Variables::new();
+    if let Ok(modified) = fs::metadata(file).and_then(|m| m.modified()) {
+        variables.set(PAGE_UPDATED, &format_date(modified));
+    }
+    variables
+        .with_site_config(&site_config)
+        .with_front_matter(&front_matter)
+        .substitute_document(&mut document)
+        .map_err(|e| {
+            // Write error to stderr
+            let _ = writeln!(io::stderr(), "{} Parse error: {}", ERROR_PREFIX, e);
+            e
+        })?;
+
+    // Expand emoji shortcodes if requested
+    if emoji || emoji_map.is_some() {
+        let mut expander = EmojiExpander::new();
+        if let Some(emoji_map) = emoji_map {
+            expander = expander.with_custom_map(emoji_map).map_err(|e| {
+                // Write error to stderr
+                let _ = writeln!(io::stderr(), "{} {}", ERROR_PREFIX, e);
+                e
+            })?;
+        }
+        expander.expand_document(&mut document);
+    }
+
+    Ok(document.into_owned())
+}
+
+/// Handle the 'fmt' subcommand
+///
+/// Rewrites each file in canonical form, printing the paths it changed. With
+/// `--check` nothing is written; unformatted paths are printed and the exit
+/// status is 1 if there are any. Files that fail to parse are reported on
+/// stderr and also make the exit status 1.
+fn handle_fmt(args: cli::FmtArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let mut files = Vec::new();
+    for path in &args.paths {
+        if !path.exists() {
+            // Write error to stderr
+            writeln!(
+                io::stderr(),
+                "{} Path does not exist: {}",
+                ERROR_PREFIX,
+                path.display()
+            )?;
+            std::process::exit(1);
+        }
+        collect_markdown_files(path, &mut files)?;
+    }
+
+    let mut failed = false;
+    for file in &files {
+        let source = fs::read_to_string(file)?;
+        let formatted = match format_source(&source) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                // Write error to stderr
+                writeln!(io::stderr(), "{} {}: {}", ERROR_PREFIX, file.display(), e)?;
+                failed = true;
+                continue;
+            }
+        };
+
+        if formatted == source {
+            continue;
+        }
+
+        if args.check {
+            failed = true;
+        } else {
+            fs::write(file, formatted)?;
+        }
+        println!("{}", file.display());
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
 
     Ok(())
 }
+
+/// Collects markdown files under a path (the path itself if it's a file),
+/// in sorted order
+fn collect_markdown_files(path: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let mut entries = fs::read_dir(path)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() {
+            collect_markdown_files(&entry, files)?;
+        } else if entry.extension().is_some_and(|ext| ext == MD_EXTENSION) {
+            files.push(entry);
+        }
+    }
+
+    Ok(())
+}
+
+/// Serialises a document to pretty-printed JSON
+#[cfg(feature = "serde")]
+fn document_to_json(document: &Document) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(serde_json::to_string_pretty(document)?)
+}
+
+/// JSON output is unavailable without the 'serde' feature
+#[cfg(not(feature = "serde"))]
+fn document_to_json(_document: &Document) -> Result<String, Box<dyn std::error::Error>> {
+    Err("JSON output requires wtf to be built with the 'serde' feature".into())
+}
@@ -0,0 +1,389 @@
+use crate::parser::{Document, Event, ParseError, Tag, TagEnd};
+use crate::renderer::{self, RenderError, Renderer};
+use std::cell::RefCell;
+use std::fmt;
+use std::io;
+
+// Macros
+const TITLE_MACRO: &str = ".TH";
+const SECTION_MACRO: &str = ".SH";
+const SUBSECTION_MACRO: &str = ".SS";
+const PARAGRAPH_MACRO: &str = ".PP";
+const BOLD_MACRO: &str = ".B";
+const ITALIC_MACRO: &str = ".I";
+const LINE_BREAK_MACRO: &str = ".br";
+const CODE_START: &str = ".RS 4\n.nf";
+const CODE_END: &str = ".fi\n.RE";
+const DEFAULT_SECTION: &str = "1";
+
+// Font escapes
+const FONT_ROMAN: &str = "\\fR";
+const FONT_BOLD: &str = "\\fB";
+const FONT_ITALIC: &str = "\\fI";
+const FONT_BOLD_ITALIC: &str = "\\f(BI";
+
+// Escapes
+const ESCAPED_BACKSLASH: &str = "\\e";
+const ESCAPED_HYPHEN: &str = "\\-";
+const ESCAPED_QUOTE: &str = "\\(dq";
+/// Zero-width escape that stops a text line being read as a request
+const LINE_START_GUARD: &str = "\\&";
+const CONTROL_CHARS: [char; 2] = ['.', '\''];
+
+// Links and images
+const LINK_URL_OPEN: &str = " <";
+const LINK_URL_CLOSE: char = '>';
+const IMAGE_OPEN: &str = "[Image: ";
+const IMAGE_CLOSE: char = ']';
+
+const NEWLINE: char = '\n';
+const QUOTE: char = '"';
+
+/// roff writer for man pages, using the classic `man` macros
+///
+/// - A `.TH` header with the page title (upper-cased) and section
+/// - Level 1 headings as `.SH` (upper-cased, as man pages expect), level 2
+///   as `.SS`, deeper levels as bold `.B` lines in their own paragraph
+/// - Paragraphs as `.PP`, line breaks as `.br`
+/// - Bold and italic as `\fB`/`\fI` font escapes (`\f(BI` for both)
+/// - Links followed by their URL in angle brackets unless the text is the URL
+/// - Images as italic `.I [Image: alt text]` lines
+/// - Code blocks indented and unfilled (`.RS`/`.nf`)
+///
+/// Backslashes and hyphens are escaped (`\e`, `\-`) and text lines starting
+/// with `.` or `'` are guarded with `\&` so they aren't read as requests.
+///
+/// # Example
+/// ```
+/// use wtf::man_writer::ManWriter;
+/// use wtf::parser::MarkdownParser;
+///
+/// let doc = MarkdownParser::parse("# Name\nwtf - serve **markdown**").unwrap();
+/// let roff = ManWriter::new("wtf").write_man(&doc).unwrap();
+/// assert_eq!(
+///     roff,
+///     ".TH \"WTF\" \"1\"\n.SH \"NAME\"\n.PP\nwtf \\- serve \\fBmarkdown\\fR\n"
+/// );
+/// ```
+pub struct ManWriter {
+    title: String,
+    section: String,
+    state: RefCell<ManState>,
+}
+
+/// The block being collected (so links can be compared with their URL) and
+/// the fonts open in it
+#[derive(Default)]
+struct ManState {
+    block: String,
+    /// Level of the heading being collected
+    heading: Option<u8>,
+    /// URL and start offset in `block` of each open link
+    links: Vec<(String, usize)>,
+    bold: usize,
+    italic: usize,
+}
+
+impl ManState {
+    /// The font escape for the currently open bold/italic spans
+    fn font(&self) -> &'static str {
+        match (self.bold > 0, self.italic > 0) {
+            (true, true) => FONT_BOLD_ITALIC,
+            (true, false) => FONT_BOLD,
+            (false, true) => FONT_ITALIC,
+            (false, false) => FONT_ROMAN,
+        }
+    }
+}
+
+impl ManWriter {
+    /// Creates a writer for a page in section 1 (user commands)
+    ///
+    /// # Arguments
+    /// * `title` - Page title for the `.TH` header, usually the command name
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            section: DEFAULT_SECTION.to_string(),
+            state: RefCell::new(ManState::default()),
+        }
+    }
+
+    /// Sets the manual section (e.g. `5` for file formats, `7` for overviews)
+    pub fn with_section(mut self, section: impl Into<String>) -> Self {
+        self.section = section.into();
+        self
+    }
+
+    /// Converts a parsed markdown document to a man page
+    ///
+    /// # Returns
+    /// * `Ok(String)` - The roff source, starting with the `.TH` header
+    /// * `Err(RenderError)` - If writing fails
+    pub fn write_man(&self, document: &Document) -> Result<String, RenderError> {
+        renderer::render_to_string(self, document)
+    }
+
+    /// Streams the man page for a document into a writer
+    ///
+    /// # Returns
+    /// * `Err(RenderError)` - If the writer returns an error
+    pub fn write_man_to<W: io::Write>(
+        &self,
+        document: &Document,
+        writer: W,
+    ) -> Result<(), RenderError> {
+        renderer::render_to_io(self, document, writer)
+    }
+
+    /// Renders an event stream (e.g. `MarkdownParser::events`) to a man page
+    ///
+    /// # Returns
+    /// * `Ok(String)` - The same roff as `write_man` on the events' tree
+    /// * `Err(RenderError)` - For the first parse error in the stream
+    pub fn write_man_events<'a, I>(&self, events: I) -> Result<String, RenderError>
+    where
+        I: IntoIterator<Item = Result<Event<'a>, ParseError>>,
+    {
+        renderer::render_events_to_string(self, events)
+    }
+
+    fn write_header(&self, out: &mut dyn fmt::Write) -> fmt::Result {
+        self.state.replace(ManState::default());
+        writeln!(
+            out,
+            "{} {} {}",
+            TITLE_MACRO,
+            quote(&escape_roff(&self.title.to_uppercase())),
+            quote(&escape_roff(&self.section))
+        )
+    }
+}
+
+impl Renderer for ManWriter {
+    type Error = RenderError;
+
+    fn begin(&self, out: &mut dyn fmt::Write) -> Result<(), RenderError> {
+        Ok(self.write_header(out)?)
+    }
+
+    fn start(&self, _out: &mut dyn fmt::Write, tag: &Tag) -> Result<(), RenderError> {
+        let mut state = self.state.borrow_mut();
+        match tag {
+            Tag::Heading(level) => {
+                state.block.clear();
+                state.heading = Some(*level);
+                // Deep headings are bold lines, so their spans start bold
+                if *level > 2 {
+                    state.bold += 1;
+                }
+            }
+            Tag::Paragraph => state.block.clear(),
+            Tag::Bold | Tag::Italic => {
+                if matches!(tag, Tag::Bold) {
+                    state.bold += 1;
+                } else {
+                    state.italic += 1;
+                }
+                let font = state.font();
+                state.block.push_str(font);
+            }
+            Tag::Link(url) => {
+                let start = state.block.len();
+                state.links.push((url.to_string(), start));
+            }
+            Tag::Section(_) => {}
+        }
+        Ok(())
+    }
+
+    fn end(&self, out: &mut dyn fmt::Write, tag: TagEnd) -> Result<(), RenderError> {
+        let mut state = self.state.borrow_mut();
+        match tag {
+            TagEnd::Heading(level) => {
+                let title = std::mem::take(&mut state.block);
+                state.heading = None;
+                match level {
+                    1 => writeln!(out, "{} {}", SECTION_MACRO, quote(&title))?,
+                    2 => writeln!(out, "{} {}", SUBSECTION_MACRO, quote(&title))?,
+                    _ => {
+                        state.bold -= 1;
+                        writeln!(out, "{}", PARAGRAPH_MACRO)?;
+                        writeln!(out, "{} {}", BOLD_MACRO, quote(&title))?;
+                    }
+                }
+            }
+            TagEnd::Paragraph => {
+                let block = std::mem::take(&mut state.block);
+                writeln!(out, "{}", PARAGRAPH_MACRO)?;
+                for (i, line) in block.split(NEWLINE).enumerate() {
+                    if i > 0 {
+                        writeln!(out, "{}", LINE_BREAK_MACRO)?;
+                    }
+                    // Blank and indented lines would break the fill
+                    let line = line.trim_start();
+                    if !line.is_empty() {
+                        writeln!(out, "{}", guard_line(line))?;
+                    }
+                }
+            }
+            TagEnd::Bold | TagEnd::Italic => {
+                if tag == TagEnd::Bold {
+                    state.bold = state.bold.saturating_sub(1);
+                } else {
+                    state.italic = state.italic.saturating_sub(1);
+                }
+                let font = state.font();
+                state.block.push_str(font);
+            }
+            TagEnd::Link => {
+                if let Some((url, start)) = state.links.pop() {
+                    let url = escape_roff(&url);
+                    if state.block[start..] != url {
+                        state.block.push_str(LINK_URL_OPEN);
+                        state.block.push_str(&url);
+                        state.block.push(LINK_URL_CLOSE);
+                    }
+                }
+            }
+            TagEnd::Section(_) => {}
+        }
+        Ok(())
+    }
+
+    fn text(&self, _out: &mut dyn fmt::Write, text: &str) -> Result<(), RenderError> {
+        let mut state = self.state.borrow_mut();
+        let escaped = if state.heading == Some(1) {
+            escape_roff(&text.to_uppercase())
+        } else {
+            escape_roff(text)
+        };
+        state.block.push_str(&escaped);
+        Ok(())
+    }
+
+    fn line_break(&self, _out: &mut dyn fmt::Write) -> Result<(), RenderError> {
+        self.state.borrow_mut().block.push(NEWLINE);
+        Ok(())
+    }
+
+    fn image(
+        &self,
+        out: &mut dyn fmt::Write,
+        alt_text: &str,
+        url: &str,
+    ) -> Result<(), RenderError> {
+        let label = if alt_text.is_empty() { url } else { alt_text };
+        let label = format!("{}{}{}", IMAGE_OPEN, label, IMAGE_CLOSE);
+        writeln!(out, "{}", PARAGRAPH_MACRO)?;
+        writeln!(out, "{} {}", ITALIC_MACRO, quote(&escape_roff(&label)))?;
+        Ok(())
+    }
+
+    fn code_block(
+        &self,
+        out: &mut dyn fmt::Write,
+        _info: &str,
+        code: &str,
+    ) -> Result<(), RenderError> {
+        writeln!(out, "{}", PARAGRAPH_MACRO)?;
+        writeln!(out, "{}", CODE_START)?;
+        for line in code.split(NEWLINE) {
+            writeln!(out, "{}", guard_line(&escape_roff(line)))?;
+        }
+        writeln!(out, "{}", CODE_END)?;
+        Ok(())
+    }
+}
+
+/// Escapes backslashes and hyphens in text for roff
+fn escape_roff(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '\\' => escaped.push_str(ESCAPED_BACKSLASH),
+            '-' => escaped.push_str(ESCAPED_HYPHEN),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Quotes an escaped macro argument, escaping the quotes inside it
+fn quote(argument: &str) -> String {
+    format!(
+        "{}{}{}",
+        QUOTE,
+        argument.replace(QUOTE, ESCAPED_QUOTE),
+        QUOTE
+    )
+}
+
+/// Guards a text line that would otherwise be read as a request or macro
+fn guard_line(line: &str) -> String {
+    if line.starts_with(CONTROL_CHARS) {
+        format!("{}{}", LINE_START_GUARD, line)
+    } else {
+        line.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::MarkdownParser;
+
+    fn man(source: &str) -> String {
+        ManWriter::new("tool")
+            .write_man(&MarkdownParser::parse(source).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_headings() {
+        assert_eq!(
+            man("# Synopsis -x\n## Sub \"q\"\n### Deep *it*"),
+            ".TH \"TOOL\" \"1\"\n\
+             .SH \"SYNOPSIS \\-X\"\n\
+             .SS \"Sub \\(dqq\\(dq\"\n\
+             .PP\n.B \"Deep \\f(BIit\\fB\"\n"
+        );
+        assert_eq!(
+            ManWriter::new("conf")
+                .with_section("5")
+                .write_man(&MarkdownParser::parse("").unwrap())
+                .unwrap(),
+            ".TH \"CONF\" \"5\"\n"
+        );
+    }
+
+    #[test]
+    fn test_paragraphs_and_fonts() {
+        assert_eq!(
+            man("Use **bold *both* x** and *it*\n.dot line\n'quote\n\nC:\\\\path"),
+            ".TH \"TOOL\" \"1\"\n\
+             .PP\n\
+             Use \\fBbold \\f(BIboth\\fB x\\fR and \\fIit\\fR\n\
+             .br\n\\&.dot line\n.br\n\\&'quote\n\
+             .PP\nC:\\epath\n"
+        );
+    }
+
+    #[test]
+    fn test_links_images_and_code() {
+        assert_eq!(
+            man(
+                "See [docs](https://x.org/a-b) or [https://x.org](https://x.org)\n\n![Logo](logo.png)\n\n```sh\nls -l\n.hidden\n```"
+            ),
+            ".TH \"TOOL\" \"1\"\n\
+             .PP\nSee docs <https://x.org/a\\-b> or https://x.org\n\
+             .PP\n.I \"[Image: Logo]\"\n\
+             .PP\n.RS 4\n.nf\nls \\-l\n\\&.hidden\n.fi\n.RE\n"
+        );
+    }
+
+    #[test]
+    fn test_events_render_like_tree() {
+        renderer::assert_events_render_like_tree(&ManWriter::new("x"), renderer::EVENTS_FIXTURE);
+    }
+}
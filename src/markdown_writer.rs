@@ -0,0 +1,810 @@
+use crate::front_matter::split_front_matter;
+use crate::parser::{self, BlockNode, Document, InlineNode, MarkdownParser, ParseError, Section};
+use std::fmt;
+
+// Markdown syntax constants
+const HEADING_CHAR: char = '#';
+const HEADING_SPACE: char = ' ';
+/// Trimmed from both ends of a heading's title, as the parser does
+const HEADING_PADDING: [char; 2] = [' ', '\t'];
+const BOLD_DELIM: &str = "**";
+const ITALIC_DELIM: &str = "*";
+const LINK_OPEN: char = '[';
+const LINK_CLOSE: char = ']';
+const URL_OPEN: char = '(';
+const URL_CLOSE: char = ')';
+const IMAGE_OPEN: &str = "![";
+const ESCAPE_CHAR: char = '\\';
+const NEWLINE_CHAR: char = '\n';
+const BLOCK_SEPARATOR: &str = "\n\n";
+const FENCE_CHAR: char = '`';
+const MIN_FENCE_LEN: usize = 3;
+
+// ============================================================================
+// ERROR HANDLING
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MarkdownError {
+    /// Text the parser would read back differently (e.g. containing a newline)
+    UnrepresentableText { text: String, reason: String },
+    /// A link or image URL the markdown syntax can't express
+    UnrepresentableUrl { url: String, reason: String },
+    /// Node arrangement that would re-parse as a different tree
+    AmbiguousStructure { reason: String },
+    /// The source handed to `format_source` doesn't parse
+    ParseError { source: ParseError },
+}
+
+impl fmt::Display for MarkdownError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarkdownError::UnrepresentableText { text, reason } => {
+                write!(f, "Cannot write text {:?} as markdown: {}", text, reason)
+            }
+            MarkdownError::UnrepresentableUrl { url, reason } => {
+                write!(f, "Cannot write URL {:?} as markdown: {}", url, reason)
+            }
+            MarkdownError::AmbiguousStructure { reason } => {
+                write!(f, "Document has no unambiguous markdown form: {}", reason)
+            }
+            MarkdownError::ParseError { source } => write!(f, "{}", source),
+        }
+    }
+}
+
+impl std::error::Error for MarkdownError {}
+
+impl From<ParseError> for MarkdownError {
+    fn from(err: ParseError) -> Self {
+        MarkdownError::ParseError { source: err }
+    }
+}
+
+// ============================================================================
+// MARKDOWN WRITER
+// ============================================================================
+
+/// Markdown writer for turning a document tree back into source text
+///
+/// Output is canonical, so formatting the same tree always yields the same
+/// text:
+/// - ATX headings (`## Title`) with a single space after the `#`s and no
+///   padding around the title
+/// - `**bold**` and `*italic*` emphasis
+/// - One blank line between blocks, a single trailing newline
+/// - Code blocks fenced with three backticks (more if a line inside them
+///   would otherwise close the fence)
+/// - Backslash escapes only where a character would otherwise be syntax
+///   (`\*`, `\[`, `\]` in link text, `\#` at the start of a line, ...)
+///
+/// For every document the parser produces, `parse(write(doc)) == doc`.
+/// Hand-built trees the syntax can't express (a text node containing a
+/// newline, adjacent text nodes, `*italic*` directly touching another
+/// emphasis marker, ...) are rejected with a `MarkdownError` rather than
+/// written as something that would read back differently. The exception is
+/// spaces around a heading title, which the parser trims and so are dropped.
+pub struct MarkdownWriter;
+
+impl MarkdownWriter {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Converts a document to canonical markdown
+    ///
+    /// # Arguments
+    /// * `document` - The document to serialise
+    ///
+    /// # Returns
+    /// * `Ok(String)` - Markdown source that parses back to `document`
+    /// * `Err(MarkdownError)` - If the tree has no faithful markdown form
+    ///
+    /// # Example
+    /// ```
+    /// use wtf::markdown_writer::MarkdownWriter;
+    /// use wtf::parser::MarkdownParser;
+    ///
+    /// let doc = MarkdownParser::parse("#   Title\n\n\n\nSome  **bold**").unwrap();
+    /// let markdown = MarkdownWriter::new().write_markdown(&doc).unwrap();
+    /// assert_eq!(markdown, "# Title\n\nSome  **bold**\n");
+    /// assert_eq!(MarkdownParser::parse(&markdown).unwrap(), doc);
+    /// ```
+    pub fn write_markdown(&self, document: &Document) -> Result<String, MarkdownError> {
+        let mut blocks = Vec::new();
+
+        // Write preamble (content before first heading)
+        for block in document.content() {
+            blocks.push(self.write_block(block)?);
+        }
+
+        // Write all sections
+        check_sibling_levels(document.sections())?;
+        for section in document.sections() {
+            self.write_section(section, &mut blocks)?;
+        }
+
+        if blocks.is_empty() {
+            return Ok(String::new());
+        }
+
+        let mut output = blocks.join(BLOCK_SEPARATOR);
+        output.push(NEWLINE_CHAR);
+        Ok(output)
+    }
+
+    /// Write a section and its subsections recursively, one entry per block
+    fn write_section(
+        &self,
+        section: &Section,
+        blocks: &mut Vec<String>,
+    ) -> Result<(), MarkdownError> {
+        blocks.push(self.write_heading(section.level(), section.title())?);
+
+        for block in section.content() {
+            blocks.push(self.write_block(block)?);
+        }
+
+        check_sibling_levels(section.subsections())?;
+        for subsection in section.subsections() {
+            self.write_section(subsection, blocks)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write a heading line (`#` repeated `level` times, then the trimmed
+    /// title)
+    fn write_heading(&self, level: u8, title: &[InlineNode]) -> Result<String, MarkdownError> {
+        if title.contains(&InlineNode::LineBreak) {
+            return Err(MarkdownError::AmbiguousStructure {
+                reason: "headings can't contain line breaks".to_string(),
+            });
+        }
+
+        let mut output: String = std::iter::repeat_n(HEADING_CHAR, usize::from(level)).collect();
+
+        let mut writer = InlineWriter::new(false);
+        writer.write_nodes(title, None)?;
+        let title = writer.output.trim_matches(HEADING_PADDING);
+        if !title.is_empty() {
+            output.push(HEADING_SPACE);
+            output.push_str(title);
+        }
+
+        Ok(output)
+    }
+
+    /// Write a block-level node (paragraph, image or code block)
+    fn write_block(&self, block: &BlockNode) -> Result<String, MarkdownError> {
+        match block {
+            BlockNode::Paragraph(inlines) => {
+                check_line_breaks(inlines)?;
+
+                let mut writer = InlineWriter::new(true);
+
+                // "!" followed by a link at the start of a paragraph would
+                // read back as an image
+                if let [InlineNode::Text(first), InlineNode::Link { .. }, ..] = inlines.as_slice()
+                    && first == "!"
+                {
+                    writer.output.push(ESCAPE_CHAR);
+                }
+
+                writer.write_nodes(inlines, None)?;
+                Ok(writer.output)
+            }
+            BlockNode::Image { alt_text, url } => {
+                check_url(url)?;
+                Ok(format!(
+                    "{}{}{}{}{}{}",
+                    IMAGE_OPEN,
+                    escape_alt_text(alt_text),
+                    LINK_CLOSE,
+                    URL_OPEN,
+                    url,
+                    URL_CLOSE
+                ))
+            }
+            BlockNode::CodeBlock { info, code } => {
+                check_info(info)?;
+
+                // The fence must be longer than any line inside that could close it
+                let fence_len = code
+                    .lines()
+                    .filter_map(parser::closing_fence_len)
+                    .map(|len| len + 1)
+                    .fold(MIN_FENCE_LEN, usize::max);
+                let fence: String = std::iter::repeat_n(FENCE_CHAR, fence_len).collect();
+
+                let mut output = format!("{}{}{}", fence, info, NEWLINE_CHAR);
+                if !code.is_empty() {
+                    output.push_str(code);
+                    output.push(NEWLINE_CHAR);
+                }
+                output.push_str(&fence);
+                Ok(output)
+            }
+        }
+    }
+}
+
+impl Default for MarkdownWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reformats markdown source into canonical form
+///
+/// Front matter is kept verbatim; only the body below it is rewritten.
+///
+/// # Returns
+/// * `Ok(String)` - The formatted source (equal to `source` if already canonical)
+/// * `Err(MarkdownError)` - If the source doesn't parse
+pub fn format_source(source: &str) -> Result<String, MarkdownError> {
+    let (_, body) = split_front_matter(source)?;
+    let front_matter = &source[..source.len() - body.len()];
+
+    let document = MarkdownParser::parse(body)?;
+    let markdown = MarkdownWriter::new().write_markdown(&document)?;
+
+    Ok(format!("{}{}", front_matter, markdown))
+}
+
+// ============================================================================
+// INLINE WRITING
+// ============================================================================
+
+/// Emphasis markers, tracked so that ones which would merge are caught
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Delimiter {
+    BoldOpen,
+    BoldClose,
+    ItalicOpen,
+    ItalicClose,
+}
+
+/// Inline container a node list is written inside of
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Container {
+    Bold,
+    Italic,
+    Link,
+}
+
+/// Accumulates the markdown for one paragraph or heading title
+struct InlineWriter {
+    output: String,
+    /// Emphasis marker written last, if nothing has been written since
+    last_delimiter: Option<Delimiter>,
+    /// Whether the next character starts a line (so `#` needs escaping)
+    at_line_start: bool,
+}
+
+impl InlineWriter {
+    fn new(at_line_start: bool) -> Self {
+        Self {
+            output: String::new(),
+            last_delimiter: None,
+            at_line_start,
+        }
+    }
+
+    fn write_nodes(
+        &mut self,
+        nodes: &[InlineNode],
+        parent: Option<Container>,
+    ) -> Result<(), MarkdownError> {
+        for (index, node) in nodes.iter().enumerate() {
+            if index > 0
+                && let (InlineNode::Text(previous), InlineNode::Text(_)) = (&nodes[index - 1], node)
+            {
+                return Err(MarkdownError::UnrepresentableText {
+                    text: previous.to_string(),
+                    reason: "adjacent text nodes read back as one".to_string(),
+                });
+            }
+
+            self.write_node(node, parent)?;
+        }
+        Ok(())
+    }
+
+    fn write_node(
+        &mut self,
+        node: &InlineNode,
+        parent: Option<Container>,
+    ) -> Result<(), MarkdownError> {
+        match node {
+            InlineNode::Text(text) => {
+                check_text(text)?;
+                let escaped =
+                    escape_text(text, self.at_line_start, parent == Some(Container::Link));
+                self.push_str(&escaped);
+            }
+            InlineNode::LineBreak => {
+                if parent.is_some() {
+                    return Err(MarkdownError::AmbiguousStructure {
+                        reason: "line breaks can't appear inside emphasis or links".to_string(),
+                    });
+                }
+                self.push_str(&NEWLINE_CHAR.to_string());
+                self.at_line_start = true;
+            }
+            InlineNode::Bold(children) => {
+                check_nesting(Container::Bold, parent)?;
+                self.push_delimiter(Delimiter::BoldOpen)?;
+                self.write_nodes(children, Some(Container::Bold))?;
+                self.push_delimiter(Delimiter::BoldClose)?;
+            }
+            InlineNode::Italic(children) => {
+                check_nesting(Container::Italic, parent)?;
+                self.push_delimiter(Delimiter::ItalicOpen)?;
+                self.write_nodes(children, Some(Container::Italic))?;
+                self.push_delimiter(Delimiter::ItalicClose)?;
+            }
+            InlineNode::Link { text, url } => {
+                check_nesting(Container::Link, parent)?;
+                check_url(url)?;
+                self.push_str(&LINK_OPEN.to_string());
+                self.write_nodes(text, Some(Container::Link))?;
+                self.push_str(&format!("{}{}{}{}", LINK_CLOSE, URL_OPEN, url, URL_CLOSE));
+            }
+        }
+        Ok(())
+    }
+
+    fn push_str(&mut self, text: &str) {
+        if !text.is_empty() {
+            self.output.push_str(text);
+            self.last_delimiter = None;
+            self.at_line_start = false;
+        }
+    }
+
+    fn push_delimiter(&mut self, delimiter: Delimiter) -> Result<(), MarkdownError> {
+        if let Some(previous) = self.last_delimiter
+            && delimiters_merge(previous, delimiter)
+        {
+            return Err(MarkdownError::AmbiguousStructure {
+                reason: format!(
+                    "emphasis markers {:?} and {:?} would run together",
+                    previous, delimiter
+                ),
+            });
+        }
+
+        self.output.push_str(match delimiter {
+            Delimiter::BoldOpen | Delimiter::BoldClose => BOLD_DELIM,
+            Delimiter::ItalicOpen | Delimiter::ItalicClose => ITALIC_DELIM,
+        });
+        self.last_delimiter = Some(delimiter);
+        self.at_line_start = false;
+        Ok(())
+    }
+}
+
+/// Checks whether two emphasis markers written back to back would be read
+/// as different markers (e.g. `*` then `**` becoming `***`)
+fn delimiters_merge(previous: Delimiter, next: Delimiter) -> bool {
+    !matches!(
+        (previous, next),
+        (Delimiter::BoldOpen, Delimiter::ItalicOpen)
+            | (Delimiter::BoldClose, Delimiter::ItalicOpen)
+            | (Delimiter::BoldClose, Delimiter::ItalicClose)
+            | (Delimiter::BoldClose, Delimiter::BoldOpen)
+            | (Delimiter::BoldOpen, Delimiter::BoldClose)
+    )
+}
+
+/// Rejects emphasis or links directly inside a container of the same kind
+fn check_nesting(container: Container, parent: Option<Container>) -> Result<(), MarkdownError> {
+    if parent == Some(container) {
+        return Err(MarkdownError::AmbiguousStructure {
+            reason: format!(
+                "{:?} can't directly contain another {:?}",
+                container, container
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Rejects text nodes that can't be told apart from their neighbours
+fn check_text(text: &str) -> Result<(), MarkdownError> {
+    let reason = if text.is_empty() {
+        "empty text nodes aren't preserved"
+    } else if text.contains(NEWLINE_CHAR) {
+        "newlines in text read back as line breaks or block boundaries"
+    } else {
+        return Ok(());
+    };
+
+    Err(MarkdownError::UnrepresentableText {
+        text: text.to_string(),
+        reason: reason.to_string(),
+    })
+}
+
+/// Rejects URLs the parser would cut short or trim
+fn check_url(url: &str) -> Result<(), MarkdownError> {
+    let reason = if url.contains(URL_CLOSE) {
+        format!("'{}' ends the URL", URL_CLOSE)
+    } else if url.contains(NEWLINE_CHAR) {
+        "URLs can't span lines".to_string()
+    } else if url.trim() != url {
+        "surrounding whitespace is trimmed".to_string()
+    } else {
+        return Ok(());
+    };
+
+    Err(MarkdownError::UnrepresentableUrl {
+        url: url.to_string(),
+        reason,
+    })
+}
+
+/// Rejects code block info strings the opening fence line can't hold
+fn check_info(info: &str) -> Result<(), MarkdownError> {
+    let reason = if info.contains(NEWLINE_CHAR) {
+        "info strings can't span lines"
+    } else if info.trim() != info {
+        "surrounding whitespace is trimmed"
+    } else if info.starts_with(FENCE_CHAR) {
+        "a leading backtick reads back as part of the fence"
+    } else {
+        return Ok(());
+    };
+
+    Err(MarkdownError::UnrepresentableText {
+        text: info.to_string(),
+        reason: reason.to_string(),
+    })
+}
+
+/// Rejects line breaks a paragraph can't round-trip (leading, trailing or
+/// doubled ones read back as paragraph boundaries), and empty paragraphs
+fn check_line_breaks(inlines: &[InlineNode]) -> Result<(), MarkdownError> {
+    let reason = if inlines.is_empty() {
+        "empty paragraphs aren't preserved"
+    } else if matches!(inlines.first(), Some(InlineNode::LineBreak))
+        || matches!(inlines.last(), Some(InlineNode::LineBreak))
+    {
+        "paragraphs can't start or end with a line break"
+    } else if inlines
+        .windows(2)
+        .any(|pair| pair[0] == InlineNode::LineBreak && pair[1] == InlineNode::LineBreak)
+    {
+        "consecutive line breaks read back as a paragraph boundary"
+    } else {
+        return Ok(());
+    };
+
+    Err(MarkdownError::AmbiguousStructure {
+        reason: reason.to_string(),
+    })
+}
+
+/// Rejects a sibling heading shallower than its predecessor, which would be
+/// read back as the predecessor's subsection
+fn check_sibling_levels(sections: &[Section]) -> Result<(), MarkdownError> {
+    for pair in sections.windows(2) {
+        if pair[1].level() > pair[0].level() {
+            return Err(MarkdownError::AmbiguousStructure {
+                reason: format!(
+                    "a level {} heading following a level {} sibling would become its subsection",
+                    pair[1].level(),
+                    pair[0].level()
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Escapes characters in inline text that would otherwise be read as syntax
+///
+/// # Arguments
+/// * `text` - Literal text
+/// * `at_line_start` - Whether the text begins a line (a leading `#` would
+///   start a heading, a leading backtick a code fence)
+/// * `in_link` - Whether the text is inside link text (`]` would end the link)
+fn escape_text(text: &str, at_line_start: bool, in_link: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut first = true;
+
+    while let Some(ch) = chars.next() {
+        let needs_escape = match ch {
+            '*' | LINK_OPEN => true,
+            LINK_CLOSE => in_link,
+            HEADING_CHAR | FENCE_CHAR => first && at_line_start,
+            // A backslash is only an escape before a syntax character, but the
+            // character after the last one comes from whatever is written next
+            ESCAPE_CHAR => chars.peek().is_none_or(|&next| parser::is_escapable(next)),
+            _ => false,
+        };
+
+        if needs_escape {
+            escaped.push(ESCAPE_CHAR);
+        }
+        escaped.push(ch);
+        first = false;
+    }
+
+    escaped
+}
+
+/// Escapes image alt text (any character after a backslash is literal there)
+fn escape_alt_text(alt_text: &str) -> String {
+    let mut escaped = String::with_capacity(alt_text.len());
+    for ch in alt_text.chars() {
+        if ch == ESCAPE_CHAR || ch == LINK_CLOSE {
+            escaped.push(ESCAPE_CHAR);
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(source: &str) -> String {
+        let doc = MarkdownParser::parse(source).unwrap();
+        let markdown = MarkdownWriter::new().write_markdown(&doc).unwrap();
+        assert_eq!(
+            MarkdownParser::parse(&markdown).unwrap(),
+            doc,
+            "{:?}",
+            markdown
+        );
+        markdown
+    }
+
+    fn text(value: &str) -> InlineNode<'_> {
+        InlineNode::Text(value.into())
+    }
+
+    fn paragraph(inlines: Vec<InlineNode>) -> Document {
+        Document::from_parts(vec![BlockNode::Paragraph(inlines)], Vec::new()).unwrap()
+    }
+
+    #[test]
+    fn test_canonical_layout() {
+        assert_eq!(
+            roundtrip("\n\nIntro\nline two\n\n\n# Title\nBody\n\n![alt](a.png)\n## Sub\n\n\nEnd"),
+            "Intro\nline two\n\n# Title\n\nBody\n\n![alt](a.png)\n\n## Sub\n\nEnd\n"
+        );
+        assert_eq!(roundtrip(""), "");
+        assert_eq!(roundtrip("#\n"), "#\n");
+    }
+
+    #[test]
+    fn test_heading_title_trimmed() {
+        assert_eq!(roundtrip("#   Title *x*\t \n##  \n"), "# Title *x*\n\n##\n");
+
+        let doc = Document::from_parts(
+            Vec::new(),
+            vec![
+                Section::new(
+                    2,
+                    vec![
+                        text("  Padded "),
+                        InlineNode::Bold(vec![text("x")]),
+                        text(" "),
+                    ],
+                )
+                .unwrap(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            MarkdownWriter::new().write_markdown(&doc).unwrap(),
+            "## Padded **x**\n"
+        );
+    }
+
+    #[test]
+    fn test_inline_formatting() {
+        assert_eq!(
+            roundtrip("a **b *c* [d](e)** *f **g*** [*h*](i)"),
+            "a **b *c* [d](e)** *f **g*** [*h*](i)\n"
+        );
+        assert_eq!(roundtrip("***a* b**"), "***a* b**\n");
+        assert_eq!(roundtrip("**a****b**"), "**a****b**\n");
+    }
+
+    #[test]
+    fn test_escaping() {
+        let doc = paragraph(vec![text("2 * 3 [x] C:\\dir \\* end\\")]);
+        let markdown = MarkdownWriter::new().write_markdown(&doc).unwrap();
+        assert_eq!(markdown, "2 \\* 3 \\[x] C:\\dir \\\\\\* end\\\\\n");
+        assert_eq!(MarkdownParser::parse(&markdown).unwrap(), doc);
+
+        // "]" only needs escaping inside link text
+        let doc = paragraph(vec![InlineNode::Link {
+            text: vec![text("a]b")],
+            url: "u".into(),
+        }]);
+        let markdown = MarkdownWriter::new().write_markdown(&doc).unwrap();
+        assert_eq!(markdown, "[a\\]b](u)\n");
+        assert_eq!(MarkdownParser::parse(&markdown).unwrap(), doc);
+    }
+
+    #[test]
+    fn test_escape_line_start() {
+        let doc = paragraph(vec![
+            text("# not a heading"),
+            InlineNode::LineBreak,
+            text("#also not"),
+            InlineNode::LineBreak,
+            text("a # b"),
+        ]);
+        let markdown = MarkdownWriter::new().write_markdown(&doc).unwrap();
+        assert_eq!(markdown, "\\# not a heading\n\\#also not\na # b\n");
+        assert_eq!(MarkdownParser::parse(&markdown).unwrap(), doc);
+
+        let doc = paragraph(vec![
+            text("!"),
+            InlineNode::Link {
+                text: vec![text("x")],
+                url: "y".into(),
+            },
+        ]);
+        let markdown = MarkdownWriter::new().write_markdown(&doc).unwrap();
+        assert_eq!(markdown, "\\![x](y)\n");
+        assert_eq!(MarkdownParser::parse(&markdown).unwrap(), doc);
+    }
+
+    #[test]
+    fn test_image_alt_escaping() {
+        let doc = Document::from_parts(
+            vec![BlockNode::Image {
+                alt_text: "a [b] \\ c".into(),
+                url: "i.png".into(),
+            }],
+            Vec::new(),
+        )
+        .unwrap();
+        let markdown = MarkdownWriter::new().write_markdown(&doc).unwrap();
+        assert_eq!(markdown, "![a [b\\] \\\\ c](i.png)\n");
+        assert_eq!(MarkdownParser::parse(&markdown).unwrap(), doc);
+    }
+
+    #[test]
+    fn test_code_blocks() {
+        assert_eq!(
+            roundtrip("Intro\n```rust  \nfn main() {}\n\n# not a heading\n```\n```\n```"),
+            "Intro\n\n```rust\nfn main() {}\n\n# not a heading\n```\n\n```\n```\n"
+        );
+        assert_eq!(roundtrip("````\n```\n````"), "````\n```\n````\n");
+        assert_eq!(roundtrip("```\n\n\n```"), "```\n\n\n```\n");
+
+        // A line of backticks in the code needs a longer fence
+        let doc = Document::from_parts(
+            vec![BlockNode::CodeBlock {
+                info: "md".into(),
+                code: "a\n`````  \n```x".into(),
+            }],
+            Vec::new(),
+        )
+        .unwrap();
+        let markdown = MarkdownWriter::new().write_markdown(&doc).unwrap();
+        assert_eq!(markdown, "``````md\na\n`````  \n```x\n``````\n");
+        assert_eq!(MarkdownParser::parse(&markdown).unwrap(), doc);
+
+        // Paragraph text starting with a backtick mustn't open a fence
+        let doc = paragraph(vec![text("```not code")]);
+        let markdown = MarkdownWriter::new().write_markdown(&doc).unwrap();
+        assert_eq!(markdown, "\\```not code\n");
+        assert_eq!(MarkdownParser::parse(&markdown).unwrap(), doc);
+
+        for info in [" rust", "a\nb", "`x"] {
+            let doc = Document::from_parts(
+                vec![BlockNode::CodeBlock {
+                    info: info.into(),
+                    code: "x".into(),
+                }],
+                Vec::new(),
+            )
+            .unwrap();
+            assert!(matches!(
+                MarkdownWriter::new().write_markdown(&doc),
+                Err(MarkdownError::UnrepresentableText { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_unrepresentable_text_and_urls() {
+        let writer = MarkdownWriter::new();
+        assert!(matches!(
+            writer.write_markdown(&paragraph(vec![text("a\nb")])),
+            Err(MarkdownError::UnrepresentableText { .. })
+        ));
+        assert!(matches!(
+            writer.write_markdown(&paragraph(vec![text("a"), text("b")])),
+            Err(MarkdownError::UnrepresentableText { .. })
+        ));
+        assert!(matches!(
+            writer.write_markdown(&paragraph(vec![InlineNode::Link {
+                text: vec![text("x")],
+                url: "a)b".into(),
+            }])),
+            Err(MarkdownError::UnrepresentableUrl { .. })
+        ));
+    }
+
+    #[test]
+    fn test_ambiguous_structure() {
+        let writer = MarkdownWriter::new();
+        let cases = vec![
+            paragraph(vec![]),
+            paragraph(vec![InlineNode::LineBreak, text("a")]),
+            paragraph(vec![
+                text("a"),
+                InlineNode::LineBreak,
+                InlineNode::LineBreak,
+                text("b"),
+            ]),
+            paragraph(vec![InlineNode::Italic(vec![])]),
+            paragraph(vec![InlineNode::Italic(vec![InlineNode::Bold(vec![
+                text("a"),
+            ])])]),
+            paragraph(vec![InlineNode::Bold(vec![InlineNode::Italic(vec![
+                text("a"),
+            ])])]),
+            paragraph(vec![
+                InlineNode::Italic(vec![text("a")]),
+                InlineNode::Italic(vec![text("b")]),
+            ]),
+            paragraph(vec![InlineNode::Bold(vec![
+                text("a "),
+                InlineNode::Bold(vec![text("b")]),
+            ])]),
+        ];
+        for doc in cases {
+            assert!(
+                matches!(
+                    writer.write_markdown(&doc),
+                    Err(MarkdownError::AmbiguousStructure { .. })
+                ),
+                "{:?}",
+                doc
+            );
+        }
+
+        let doc = Document::from_parts(
+            Vec::new(),
+            vec![
+                Section::new(2, vec![text("a")]).unwrap(),
+                Section::new(3, vec![text("b")]).unwrap(),
+            ],
+        )
+        .unwrap();
+        assert!(matches!(
+            writer.write_markdown(&doc),
+            Err(MarkdownError::AmbiguousStructure { .. })
+        ));
+    }
+
+    #[test]
+    fn test_format_source_keeps_front_matter() {
+        let source = "---\ntitle: x\n---\n\n# Title\nBody\n\n\n";
+        assert_eq!(
+            format_source(source).unwrap(),
+            "---\ntitle: x\n---\n# Title\n\nBody\n"
+        );
+        assert!(matches!(
+            format_source("**unclosed"),
+            Err(MarkdownError::ParseError { .. })
+        ));
+    }
+}
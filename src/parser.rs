@@ -1,19 +1,32 @@
+use std::borrow::Cow;
+use std::collections::VecDeque;
 use std::fmt;
+use std::io::BufRead;
+use std::ops::{Deref, Range};
 
-// Constants
-const HEADING_CHAR: char = '#';
+// Constants (all syntax is ASCII, so the parser works on bytes)
+const HEADING_CHAR: u8 = b'#';
 const BOLD_DELIM: &str = "**";
-const ITALIC_DELIM: char = '*';
-const NEWLINE_CHAR: char = '\n';
-const LINK_OPEN: char = '[';
-const LINK_CLOSE: char = ']';
-const URL_OPEN: char = '(';
-const URL_CLOSE: char = ')';
-const IMAGE_PREFIX: char = '!';
-const ESCAPE_CHAR: char = '\\';
+const ITALIC_DELIM: u8 = b'*';
+const NEWLINE_CHAR: u8 = b'\n';
+const LINK_OPEN: u8 = b'[';
+const LINK_CLOSE: u8 = b']';
+const URL_OPEN: u8 = b'(';
+const URL_CLOSE: u8 = b')';
+const IMAGE_PREFIX: u8 = b'!';
+const ESCAPE_CHAR: u8 = b'\\';
+const ESCAPABLE_CHARS: &[u8] = b"\\*[]#!`";
+const FENCE: &str = "```";
+const FENCE_CHAR: u8 = b'`';
+
+// Bytes that end a run of plain text (inline content, emphasis and link text)
+const INLINE_TEXT_STOPS: &[u8] = &[NEWLINE_CHAR, ITALIC_DELIM, LINK_OPEN];
+const LINK_TEXT_STOPS: &[u8] = &[NEWLINE_CHAR, ITALIC_DELIM, LINK_CLOSE];
 
 const MIN_HEADING_LEVEL: u8 = 1;
 const MAX_HEADING_LEVEL: u8 = 6;
+/// Trimmed from both ends of a heading's title
+const HEADING_PADDING: [char; 2] = [' ', '\t'];
 
 // ============================================================================
 // ERROR HANDLING
@@ -26,6 +39,10 @@ pub enum ParseError {
     InvalidHeadingLevel { level: u8 },
     MalformedLink { position: usize },
     MalformedImage { position: usize },
+    InvalidFrontMatter { line: usize, reason: String },
+    UndefinedVariable { name: String },
+    InvalidVariable { expression: String, reason: String },
+    IoError { message: String },
 }
 
 impl fmt::Display for ParseError {
@@ -57,45 +74,177 @@ impl fmt::Display for ParseError {
             ParseError::MalformedImage { position } => {
                 write!(f, "Malformed image syntax at position {}", position)
             }
+            ParseError::InvalidFrontMatter { line, reason } => {
+                write!(f, "Invalid front matter on line {}: {}", line, reason)
+            }
+            ParseError::UndefinedVariable { name } => {
+                write!(f, "Undefined variable '{}'", name)
+            }
+            ParseError::InvalidVariable { expression, reason } => {
+                write!(f, "Invalid variable '{}': {}", expression, reason)
+            }
+            ParseError::IoError { message } => {
+                write!(f, "IO error reading markdown: {}", message)
+            }
         }
     }
 }
 
 impl std::error::Error for ParseError {}
 
+impl ParseError {
+    /// Moves a byte position reported within a chunk of the input to the
+    /// position in the whole input (`base` is the chunk's offset)
+    fn offset_by(self, base: usize) -> Self {
+        match self {
+            ParseError::UnclosedDelimiter {
+                delimiter,
+                position,
+            } => ParseError::UnclosedDelimiter {
+                delimiter,
+                position: position + base,
+            },
+            ParseError::MalformedLink { position } => ParseError::MalformedLink {
+                position: position + base,
+            },
+            ParseError::MalformedImage { position } => ParseError::MalformedImage {
+                position: position + base,
+            },
+            other => other,
+        }
+    }
+}
+
+/// Errors from building or mutating a document tree programmatically
+#[derive(Debug, Clone, PartialEq)]
+pub enum AstError {
+    InvalidHeadingLevel { level: u8 },
+    SubsectionLevel { parent_level: u8, level: u8 },
+    IndexOutOfBounds { index: usize, len: usize },
+}
+
+impl fmt::Display for AstError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AstError::InvalidHeadingLevel { level } => {
+                write!(
+                    f,
+                    "Invalid heading level: {}. Must be between {} and {}",
+                    level, MIN_HEADING_LEVEL, MAX_HEADING_LEVEL
+                )
+            }
+            AstError::SubsectionLevel {
+                parent_level,
+                level,
+            } => {
+                write!(
+                    f,
+                    "Subsection level {} must be deeper than its parent's level {}",
+                    level, parent_level
+                )
+            }
+            AstError::IndexOutOfBounds { index, len } => {
+                write!(f, "Index {} is out of bounds (length {})", index, len)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AstError {}
+
 // ============================================================================
 // INLINE NODES (within paragraphs and headings)
 // ============================================================================
 
 #[derive(Debug, PartialEq, Clone)]
-pub enum InlineNode {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
+pub enum InlineNode<'a> {
     /// Plain text
-    Text(String),
+    Text(Cow<'a, str>),
 
     /// Line break (single newline within paragraph)
     LineBreak,
 
     /// Bold text (can contain nested inline nodes)
-    Bold(Vec<InlineNode>),
+    Bold(Vec<InlineNode<'a>>),
 
     /// Italic text (can contain nested inline nodes)
-    Italic(Vec<InlineNode>),
+    Italic(Vec<InlineNode<'a>>),
 
     /// Link [text](url) - text can contain inline formatting
-    Link { text: Vec<InlineNode>, url: String },
+    Link {
+        text: Vec<InlineNode<'a>>,
+        url: Cow<'a, str>,
+    },
+}
+
+impl InlineNode<'_> {
+    /// Copies any borrowed text so the node no longer borrows the source
+    pub fn into_owned(self) -> InlineNode<'static> {
+        match self {
+            InlineNode::Text(text) => InlineNode::Text(Cow::Owned(text.into_owned())),
+            InlineNode::LineBreak => InlineNode::LineBreak,
+            InlineNode::Bold(children) => InlineNode::Bold(into_owned_inlines(children)),
+            InlineNode::Italic(children) => InlineNode::Italic(into_owned_inlines(children)),
+            InlineNode::Link { text, url } => InlineNode::Link {
+                text: into_owned_inlines(text),
+                url: Cow::Owned(url.into_owned()),
+            },
+        }
+    }
+}
+
+fn into_owned_inlines(nodes: Vec<InlineNode<'_>>) -> Vec<InlineNode<'static>> {
+    nodes.into_iter().map(InlineNode::into_owned).collect()
 }
 
 // ============================================================================
-// BLOCK NODES (paragraphs, images, etc.)
+// BLOCK NODES (paragraphs, images, code blocks)
 // ============================================================================
 
 #[derive(Debug, PartialEq, Clone)]
-pub enum BlockNode {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
+pub enum BlockNode<'a> {
     /// Paragraph containing inline elements
-    Paragraph(Vec<InlineNode>),
+    Paragraph(Vec<InlineNode<'a>>),
 
     /// Image block: ![alt](url)
-    Image { alt_text: String, url: String },
+    Image {
+        alt_text: Cow<'a, str>,
+        url: Cow<'a, str>,
+    },
+
+    /// Fenced code block: the info string after the opening fence (e.g.
+    /// `rust`) and the lines up to the closing fence, verbatim
+    CodeBlock {
+        info: Cow<'a, str>,
+        code: Cow<'a, str>,
+    },
+}
+
+impl BlockNode<'_> {
+    /// Copies any borrowed text so the block no longer borrows the source
+    pub fn into_owned(self) -> BlockNode<'static> {
+        match self {
+            BlockNode::Paragraph(inlines) => BlockNode::Paragraph(into_owned_inlines(inlines)),
+            BlockNode::Image { alt_text, url } => BlockNode::Image {
+                alt_text: Cow::Owned(alt_text.into_owned()),
+                url: Cow::Owned(url.into_owned()),
+            },
+            BlockNode::CodeBlock { info, code } => BlockNode::CodeBlock {
+                info: Cow::Owned(info.into_owned()),
+                code: Cow::Owned(code.into_owned()),
+            },
+        }
+    }
 }
 
 // ============================================================================
@@ -103,15 +252,29 @@ pub enum BlockNode {
 // ============================================================================
 
 #[derive(Debug, PartialEq, Clone)]
-pub struct Section {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "SectionFields<'a>"))]
+pub struct Section<'a> {
     level: u8,
-    title: Vec<InlineNode>,
-    content: Vec<BlockNode>,
-    subsections: Vec<Section>,
+    title: Vec<InlineNode<'a>>,
+    content: Vec<BlockNode<'a>>,
+    subsections: Vec<Section<'a>>,
 }
 
-impl Section {
-    pub fn new(level: u8, title: Vec<InlineNode>) -> Self {
+impl<'a> Section<'a> {
+    /// Creates a section with no content or subsections
+    ///
+    /// # Returns
+    /// * `Err(AstError::InvalidHeadingLevel)` - If `level` is outside 1-6
+    pub fn new(level: u8, title: Vec<InlineNode<'a>>) -> Result<Self, AstError> {
+        if !(MIN_HEADING_LEVEL..=MAX_HEADING_LEVEL).contains(&level) {
+            return Err(AstError::InvalidHeadingLevel { level });
+        }
+        Ok(Self::new_unchecked(level, title))
+    }
+
+    /// `new` for levels already known to be valid (the parser's)
+    fn new_unchecked(level: u8, title: Vec<InlineNode<'a>>) -> Self {
         Self {
             level,
             title,
@@ -124,209 +287,974 @@ impl Section {
         self.level
     }
 
-    pub fn title(&self) -> &[InlineNode] {
+    pub fn title(&self) -> &[InlineNode<'a>] {
         &self.title
     }
 
-    pub fn content(&self) -> &[BlockNode] {
+    pub fn content(&self) -> &[BlockNode<'a>] {
         &self.content
     }
 
-    pub fn subsections(&self) -> &[Section] {
+    pub fn subsections(&self) -> &[Section<'a>] {
         &self.subsections
     }
+
+    /// Mutable access to the heading title
+    pub fn title_mut(&mut self) -> &mut Vec<InlineNode<'a>> {
+        &mut self.title
+    }
+
+    /// Mutable access to the content blocks (use the insert/remove methods to add or drop blocks)
+    pub fn content_mut(&mut self) -> &mut [BlockNode<'a>] {
+        &mut self.content
+    }
+
+    /// Mutable access to the subsection at `index`
+    ///
+    /// # Returns
+    /// * `None` - If `index` is out of bounds
+    pub fn subsection_mut(&mut self, index: usize) -> Option<SectionMut<'_, 'a>> {
+        self.subsections.get_mut(index).map(SectionMut::new)
+    }
+
+    /// Block list access for `VisitorMut` (blocks carry no structural invariants)
+    pub(crate) fn content_vec_mut(&mut self) -> &mut Vec<BlockNode<'a>> {
+        &mut self.content
+    }
+
+    /// Handles to every subsection, for `VisitorMut`
+    pub(crate) fn subsections_iter_mut(&mut self) -> impl Iterator<Item = SectionMut<'_, 'a>> {
+        self.subsections.iter_mut().map(SectionMut::new)
+    }
+
+    // ========================================================================
+    // MUTATION
+    // ========================================================================
+
+    /// Replaces the heading title
+    pub fn set_title(&mut self, title: Vec<InlineNode<'a>>) {
+        self.title = title;
+    }
+
+    /// Appends a content block (after existing blocks, before subsections)
+    pub fn push_block(&mut self, block: BlockNode<'a>) {
+        self.content.push(block);
+    }
+
+    /// Appends a content block (builder style)
+    pub fn with_block(mut self, block: BlockNode<'a>) -> Self {
+        self.push_block(block);
+        self
+    }
+
+    /// Inserts a content block at `index`
+    pub fn insert_block(&mut self, index: usize, block: BlockNode<'a>) -> Result<(), AstError> {
+        check_insert_index(index, self.content.len())?;
+        self.content.insert(index, block);
+        Ok(())
+    }
+
+    /// Removes and returns the content block at `index`
+    pub fn remove_block(&mut self, index: usize) -> Result<BlockNode<'a>, AstError> {
+        check_index(index, self.content.len())?;
+        Ok(self.content.remove(index))
+    }
+
+    /// Appends a subsection
+    ///
+    /// # Returns
+    /// * `Err(AstError)` - If the subsection (or anything below it) isn't
+    ///   deeper than its parent, or has a level outside 1-6
+    pub fn push_subsection(&mut self, subsection: Section<'a>) -> Result<(), AstError> {
+        self.insert_subsection(self.subsections.len(), subsection)
+    }
+
+    /// Appends a subsection (builder style)
+    pub fn with_subsection(mut self, subsection: Section<'a>) -> Result<Self, AstError> {
+        self.push_subsection(subsection)?;
+        Ok(self)
+    }
+
+    /// Inserts a subsection at `index`, validating heading levels
+    pub fn insert_subsection(
+        &mut self,
+        index: usize,
+        subsection: Section<'a>,
+    ) -> Result<(), AstError> {
+        check_insert_index(index, self.subsections.len())?;
+        subsection.validate_under(Some(self.level))?;
+        self.subsections.insert(index, subsection);
+        Ok(())
+    }
+
+    /// Removes and returns the subsection at `index`
+    pub fn remove_subsection(&mut self, index: usize) -> Result<Section<'a>, AstError> {
+        check_index(index, self.subsections.len())?;
+        Ok(self.subsections.remove(index))
+    }
+
+    /// Copies any borrowed text so the section no longer borrows the source
+    pub fn into_owned(self) -> Section<'static> {
+        Section {
+            level: self.level,
+            title: into_owned_inlines(self.title),
+            content: self
+                .content
+                .into_iter()
+                .map(BlockNode::into_owned)
+                .collect(),
+            subsections: self
+                .subsections
+                .into_iter()
+                .map(Section::into_owned)
+                .collect(),
+        }
+    }
+
+    /// Checks this section's level (against an optional parent level) and
+    /// recursively that every subsection is deeper than its parent
+    fn validate_under(&self, parent_level: Option<u8>) -> Result<(), AstError> {
+        if !(MIN_HEADING_LEVEL..=MAX_HEADING_LEVEL).contains(&self.level) {
+            return Err(AstError::InvalidHeadingLevel { level: self.level });
+        }
+
+        if let Some(parent_level) = parent_level
+            && self.level <= parent_level
+        {
+            return Err(AstError::SubsectionLevel {
+                parent_level,
+                level: self.level,
+            });
+        }
+
+        for subsection in &self.subsections {
+            subsection.validate_under(Some(self.level))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A deserialized section before its heading levels are checked
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct SectionFields<'a> {
+    level: u8,
+    title: Vec<InlineNode<'a>>,
+    content: Vec<BlockNode<'a>>,
+    subsections: Vec<Section<'a>>,
+}
+
+#[cfg(feature = "serde")]
+impl<'a> TryFrom<SectionFields<'a>> for Section<'a> {
+    type Error = AstError;
+
+    fn try_from(fields: SectionFields<'a>) -> Result<Self, AstError> {
+        let section = Self {
+            level: fields.level,
+            title: fields.title,
+            content: fields.content,
+            subsections: fields.subsections,
+        };
+        section.validate_under(None)?;
+        Ok(section)
+    }
+}
+
+// ============================================================================
+// SECTION HANDLE (mutable access inside a tree)
+// ============================================================================
+
+/// Mutable access to a section that's part of a document tree
+///
+/// A `&mut Section` could be overwritten with a section of any level, so
+/// trees hand out this handle instead. It edits everything but the heading
+/// level, and adds or drops subsections only through the validated methods.
+/// It derefs to `Section` for reading.
+#[derive(Debug)]
+pub struct SectionMut<'s, 'a> {
+    section: &'s mut Section<'a>,
+}
+
+impl<'s, 'a> SectionMut<'s, 'a> {
+    fn new(section: &'s mut Section<'a>) -> Self {
+        Self { section }
+    }
+
+    /// Mutable access to the heading title
+    pub fn title_mut(&mut self) -> &mut Vec<InlineNode<'a>> {
+        self.section.title_mut()
+    }
+
+    /// Mutable access to the content blocks (use the insert/remove methods to add or drop blocks)
+    pub fn content_mut(&mut self) -> &mut [BlockNode<'a>] {
+        self.section.content_mut()
+    }
+
+    /// Mutable access to the subsection at `index`
+    pub fn subsection_mut(&mut self, index: usize) -> Option<SectionMut<'_, 'a>> {
+        self.section.subsection_mut(index)
+    }
+
+    /// Replaces the heading title
+    pub fn set_title(&mut self, title: Vec<InlineNode<'a>>) {
+        self.section.set_title(title);
+    }
+
+    /// Appends a content block (after existing blocks, before subsections)
+    pub fn push_block(&mut self, block: BlockNode<'a>) {
+        self.section.push_block(block);
+    }
+
+    /// Inserts a content block at `index`
+    pub fn insert_block(&mut self, index: usize, block: BlockNode<'a>) -> Result<(), AstError> {
+        self.section.insert_block(index, block)
+    }
+
+    /// Removes and returns the content block at `index`
+    pub fn remove_block(&mut self, index: usize) -> Result<BlockNode<'a>, AstError> {
+        self.section.remove_block(index)
+    }
+
+    /// Appends a subsection, validating heading levels
+    pub fn push_subsection(&mut self, subsection: Section<'a>) -> Result<(), AstError> {
+        self.section.push_subsection(subsection)
+    }
+
+    /// Inserts a subsection at `index`, validating heading levels
+    pub fn insert_subsection(
+        &mut self,
+        index: usize,
+        subsection: Section<'a>,
+    ) -> Result<(), AstError> {
+        self.section.insert_subsection(index, subsection)
+    }
+
+    /// Removes and returns the subsection at `index`
+    pub fn remove_subsection(&mut self, index: usize) -> Result<Section<'a>, AstError> {
+        self.section.remove_subsection(index)
+    }
+
+    /// Block list access for `VisitorMut`
+    pub(crate) fn content_vec_mut(&mut self) -> &mut Vec<BlockNode<'a>> {
+        self.section.content_vec_mut()
+    }
+
+    /// Handles to every subsection, for `VisitorMut`
+    pub(crate) fn subsections_iter_mut(&mut self) -> impl Iterator<Item = SectionMut<'_, 'a>> {
+        self.section.subsections_iter_mut()
+    }
+}
+
+impl<'a> Deref for SectionMut<'_, 'a> {
+    type Target = Section<'a>;
+
+    fn deref(&self) -> &Section<'a> {
+        self.section
+    }
+}
+
+/// Checks an index for access/removal
+fn check_index(index: usize, len: usize) -> Result<(), AstError> {
+    if index >= len {
+        return Err(AstError::IndexOutOfBounds { index, len });
+    }
+    Ok(())
+}
+
+/// Checks an index for insertion (may equal the length, i.e. append)
+fn check_insert_index(index: usize, len: usize) -> Result<(), AstError> {
+    if index > len {
+        return Err(AstError::IndexOutOfBounds { index, len });
+    }
+    Ok(())
 }
 
 // ============================================================================
 // DOCUMENT (root of the parse tree)
 // ============================================================================
 
-#[derive(Debug, PartialEq, Clone)]
-pub struct Document {
+/// Root of the parse tree: preamble blocks followed by top-level sections
+///
+/// Text, URLs and alt text borrow from the parsed source wherever they
+/// appear in it verbatim (`Cow::Borrowed`), so a `Document<'a>` can't outlive
+/// its input. `into_owned` detaches it, e.g. to cache or return it.
+///
+/// # JSON schema (`serde` feature)
+///
+/// With the `serde` feature enabled, the AST serialises to the following
+/// stable shape. Enum nodes are objects with a snake_case `type` tag and,
+/// unless the variant carries no data, a `value`:
+///
+/// ```text
+/// Document    { "content": [Block], "sections": [Section] }
+/// Section     { "level": 1-6, "title": [Inline], "content": [Block],
+///               "subsections": [Section] }
+/// Block       { "type": "paragraph", "value": [Inline] }
+///           | { "type": "image", "value": { "alt_text": str, "url": str } }
+///           | { "type": "code_block", "value": { "info": str, "code": str } }
+/// Inline      { "type": "text", "value": str }
+///           | { "type": "line_break" }
+///           | { "type": "bold", "value": [Inline] }
+///           | { "type": "italic", "value": [Inline] }
+///           | { "type": "link", "value": { "text": [Inline], "url": str } }
+/// ```
+///
+/// New node types may be added as new `type` values; existing ones keep
+/// their shape. Source spans aren't serialised. Deserialising checks the
+/// heading levels like `from_parts`, so a malformed tree is an error.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "DocumentFields<'a>"))]
+pub struct Document<'a> {
     /// Content before the first heading (preamble)
-    content: Vec<BlockNode>,
+    content: Vec<BlockNode<'a>>,
 
     /// Top-level sections (H1, or highest level heading)
-    sections: Vec<Section>,
+    sections: Vec<Section<'a>>,
+
+    /// Where the top-level blocks and sections came from (parsed documents only)
+    #[cfg_attr(feature = "serde", serde(skip))]
+    spans: Option<SourceSpans>,
+}
+
+/// Documents are equal when their trees are; source spans are ignored
+impl PartialEq for Document<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.content == other.content && self.sections == other.sections
+    }
+}
+
+/// A deserialized document before its section trees are checked
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct DocumentFields<'a> {
+    content: Vec<BlockNode<'a>>,
+    sections: Vec<Section<'a>>,
+}
+
+#[cfg(feature = "serde")]
+impl<'a> TryFrom<DocumentFields<'a>> for Document<'a> {
+    type Error = AstError;
+
+    fn try_from(fields: DocumentFields<'a>) -> Result<Self, AstError> {
+        Self::from_parts(fields.content, fields.sections)
+    }
+}
+
+/// Byte ranges of a document's top-level units in the text it was parsed from
+///
+/// A top-level section's range runs from its heading to the end of its last
+/// block or subsection. Blank lines between units belong to neither.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SourceSpans {
+    /// One range per preamble block
+    pub blocks: Vec<Range<usize>>,
+
+    /// One range per top-level section
+    pub sections: Vec<Range<usize>>,
+
+    /// Length of the source text in bytes
+    pub source_len: usize,
 }
 
-impl Document {
+impl<'a> Document<'a> {
     pub fn new() -> Self {
         Self {
             content: Vec::new(),
             sections: Vec::new(),
+            spans: None,
         }
     }
 
-    pub fn content(&self) -> &[BlockNode] {
+    /// Builds a document from preamble blocks and top-level sections
+    ///
+    /// # Returns
+    /// * `Ok(Document)` - If every section tree is well formed
+    /// * `Err(AstError)` - If a heading level is outside 1-6 or a subsection
+    ///   isn't deeper than its parent
+    pub fn from_parts(
+        content: Vec<BlockNode<'a>>,
+        sections: Vec<Section<'a>>,
+    ) -> Result<Self, AstError> {
+        let document = Self {
+            content,
+            sections,
+            spans: None,
+        };
+        document.validate()?;
+        Ok(document)
+    }
+
+    pub fn content(&self) -> &[BlockNode<'a>] {
         &self.content
     }
 
-    pub fn sections(&self) -> &[Section] {
+    pub fn sections(&self) -> &[Section<'a>] {
         &self.sections
     }
+
+    /// Source ranges of the top-level blocks and sections
+    ///
+    /// Set by the parser and cleared by the insert/remove methods. Edits made
+    /// through `content_mut`/`section_mut` keep them, so `MarkdownParser::reparse`
+    /// keeps such edits in every unit it doesn't re-parse.
+    pub fn spans(&self) -> Option<&SourceSpans> {
+        self.spans.as_ref()
+    }
+
+    /// Mutable access to the preamble blocks (use the insert/remove methods to add or drop blocks)
+    pub fn content_mut(&mut self) -> &mut [BlockNode<'a>] {
+        &mut self.content
+    }
+
+    /// Mutable access to the top-level section at `index` (use the
+    /// insert/remove methods to add or drop sections)
+    ///
+    /// # Returns
+    /// * `None` - If `index` is out of bounds
+    pub fn section_mut(&mut self, index: usize) -> Option<SectionMut<'_, 'a>> {
+        self.sections.get_mut(index).map(SectionMut::new)
+    }
+
+    /// Block list access for `VisitorMut` (blocks carry no structural invariants)
+    pub(crate) fn content_vec_mut(&mut self) -> &mut Vec<BlockNode<'a>> {
+        &mut self.content
+    }
+
+    /// Handles to every top-level section, for `VisitorMut`
+    pub(crate) fn sections_iter_mut(&mut self) -> impl Iterator<Item = SectionMut<'_, 'a>> {
+        self.sections.iter_mut().map(SectionMut::new)
+    }
+
+    /// Copies any borrowed text so the document no longer borrows the source
+    pub fn into_owned(self) -> Document<'static> {
+        Document {
+            content: self
+                .content
+                .into_iter()
+                .map(BlockNode::into_owned)
+                .collect(),
+            sections: self.sections.into_iter().map(Section::into_owned).collect(),
+            spans: self.spans,
+        }
+    }
+
+    /// Checks the heading-level invariants of every section tree
+    pub fn validate(&self) -> Result<(), AstError> {
+        for section in &self.sections {
+            section.validate_under(None)?;
+        }
+        Ok(())
+    }
+
+    // ========================================================================
+    // MUTATION
+    // ========================================================================
+
+    /// Appends a preamble block (content before the first heading)
+    pub fn push_block(&mut self, block: BlockNode<'a>) {
+        self.spans = None;
+        self.content.push(block);
+    }
+
+    /// Inserts a preamble block at `index`
+    pub fn insert_block(&mut self, index: usize, block: BlockNode<'a>) -> Result<(), AstError> {
+        check_insert_index(index, self.content.len())?;
+        self.spans = None;
+        self.content.insert(index, block);
+        Ok(())
+    }
+
+    /// Removes and returns the preamble block at `index`
+    pub fn remove_block(&mut self, index: usize) -> Result<BlockNode<'a>, AstError> {
+        check_index(index, self.content.len())?;
+        self.spans = None;
+        Ok(self.content.remove(index))
+    }
+
+    /// Appends a top-level section, validating its heading levels
+    pub fn push_section(&mut self, section: Section<'a>) -> Result<(), AstError> {
+        self.insert_section(self.sections.len(), section)
+    }
+
+    /// Inserts a top-level section at `index`, validating its heading levels
+    pub fn insert_section(&mut self, index: usize, section: Section<'a>) -> Result<(), AstError> {
+        check_insert_index(index, self.sections.len())?;
+        section.validate_under(None)?;
+        self.spans = None;
+        self.sections.insert(index, section);
+        Ok(())
+    }
+
+    /// Removes and returns the top-level section at `index`
+    pub fn remove_section(&mut self, index: usize) -> Result<Section<'a>, AstError> {
+        check_index(index, self.sections.len())?;
+        self.spans = None;
+        Ok(self.sections.remove(index))
+    }
 }
 
-impl Default for Document {
+impl Default for Document<'_> {
     fn default() -> Self {
         Self::new()
     }
 }
 
 // ============================================================================
-// PARSER
+// EVENTS (pull-parser stream)
 // ============================================================================
 
-pub struct MarkdownParser {
-    chars: Vec<char>,
-    pos: usize,
+/// One step of a document's event stream, in source order
+///
+/// Containers are bracketed by `Start`/`End`; leaves are single events. A
+/// section's events are its heading, its blocks and then its subsections,
+/// so the stream nests exactly like the `Document` tree:
+///
+/// ```text
+/// "# A\nHi *there*"  =>  Start(Section(1)) Start(Heading(1)) Text("A")
+///                        End(Heading(1)) Start(Paragraph) Text("Hi ")
+///                        Start(Italic) Text("there") End(Italic)
+///                        End(Paragraph) End(Section(1))
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event<'a> {
+    Start(Tag<'a>),
+    End(TagEnd),
+    Text(Cow<'a, str>),
+    LineBreak,
+    Image {
+        alt_text: Cow<'a, str>,
+        url: Cow<'a, str>,
+    },
+    CodeBlock {
+        info: Cow<'a, str>,
+        code: Cow<'a, str>,
+    },
 }
 
-impl MarkdownParser {
-    pub fn new() -> Self {
-        Self {
-            chars: Vec::new(),
-            pos: 0,
+/// A container opened by `Event::Start`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tag<'a> {
+    /// A heading with its content and subsections (level 1-6)
+    Section(u8),
+    /// The heading line of a section
+    Heading(u8),
+    Paragraph,
+    Bold,
+    Italic,
+    /// Link to a URL; the events up to the matching end are its text
+    Link(Cow<'a, str>),
+}
+
+/// A container closed by `Event::End`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TagEnd {
+    Section(u8),
+    Heading(u8),
+    Paragraph,
+    Bold,
+    Italic,
+    Link,
+}
+
+impl Event<'_> {
+    /// Copies any borrowed text so the event no longer borrows the source
+    pub fn into_owned(self) -> Event<'static> {
+        match self {
+            Event::Start(tag) => Event::Start(tag.into_owned()),
+            Event::End(tag) => Event::End(tag),
+            Event::Text(text) => Event::Text(Cow::Owned(text.into_owned())),
+            Event::LineBreak => Event::LineBreak,
+            Event::Image { alt_text, url } => Event::Image {
+                alt_text: Cow::Owned(alt_text.into_owned()),
+                url: Cow::Owned(url.into_owned()),
+            },
+            Event::CodeBlock { info, code } => Event::CodeBlock {
+                info: Cow::Owned(info.into_owned()),
+                code: Cow::Owned(code.into_owned()),
+            },
         }
     }
+}
 
-    /// Main parsing entry point
-    pub fn parse(text: &str) -> Result<Document, ParseError> {
-        let mut parser = Self::new();
-        parser.chars = text.chars().collect();
-        parser.pos = 0;
-        parser.parse_document()
+impl Tag<'_> {
+    /// Copies a borrowed link URL so the tag no longer borrows the source
+    pub fn into_owned(self) -> Tag<'static> {
+        match self {
+            Tag::Section(level) => Tag::Section(level),
+            Tag::Heading(level) => Tag::Heading(level),
+            Tag::Paragraph => Tag::Paragraph,
+            Tag::Bold => Tag::Bold,
+            Tag::Italic => Tag::Italic,
+            Tag::Link(url) => Tag::Link(Cow::Owned(url.into_owned())),
+        }
     }
+}
 
-    // ========================================================================
-    // DOCUMENT-LEVEL PARSING
-    // ========================================================================
-
-    fn parse_document(&mut self) -> Result<Document, ParseError> {
-        let mut document = Document::new();
-
-        // Parse blocks until we hit a heading or EOF
-        while !self.is_eof() {
-            self.skip_empty_lines();
+// ============================================================================
+// TEXT EDITS (incremental re-parsing)
+// ============================================================================
 
-            if self.is_eof() {
-                break;
-            }
+/// A change to a source text: the bytes in `range` were replaced by
+/// `new_len` bytes of new text
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEdit {
+    /// Replaced byte range of the text before the edit
+    pub range: Range<usize>,
 
-            // Check if this is a heading
-            if self.is_heading() {
-                // We hit a heading - parse all sections
-                let sections = self.parse_all_sections()?;
-                document.sections = sections;
-                break;
-            }
+    /// Length of the replacement in bytes
+    pub new_len: usize,
+}
 
-            // Parse a block for the preamble
-            let block = self.parse_block()?;
-            document.content.push(block);
+impl TextEdit {
+    /// Describes replacing `range` with `replacement`
+    pub fn new(range: Range<usize>, replacement: &str) -> Self {
+        Self {
+            range,
+            new_len: replacement.len(),
         }
-
-        Ok(document)
     }
+}
 
-    // ========================================================================
-    // SECTION-LEVEL PARSING (hierarchical heading structure)
-    // ========================================================================
+// ============================================================================
+// PARSER
+// ============================================================================
 
-    fn parse_all_sections(&mut self) -> Result<Vec<Section>, ParseError> {
-        let mut sections = Vec::new();
+/// Recursive-descent parser over the bytes of a `&str`
+///
+/// All markdown syntax is ASCII, so the parser scans bytes and only ever
+/// splits the input at ASCII delimiters (always a char boundary). Text nodes
+/// are slices of the input unless they contain backslash escapes, so parsing
+/// allocates little beyond the tree itself.
+pub struct MarkdownParser<'a> {
+    input: &'a str,
+    /// Byte offset of the next unread byte
+    pos: usize,
+}
 
-        while !self.is_eof() {
-            self.skip_empty_lines();
+impl<'a> MarkdownParser<'a> {
+    pub fn new() -> Self {
+        Self::over("")
+    }
 
-            if self.is_eof() {
-                break;
-            }
+    fn over(input: &'a str) -> Self {
+        Self { input, pos: 0 }
+    }
 
-            if self.is_heading() {
-                let section = self.parse_section_tree(None)?;
-                sections.push(section);
-            } else {
-                // This shouldn't happen but handle gracefully
-                break;
-            }
+    /// Main parsing entry point
+    ///
+    /// Builds the tree from the same event stream `events` yields. Error
+    /// positions are byte offsets into `text`.
+    pub fn parse(text: &'a str) -> Result<Document<'a>, ParseError> {
+        let mut events = Events::new(text);
+        let mut builder = TreeBuilder::new();
+
+        while let Some(span) = events.parse_next()? {
+            builder.push_all(events.queue.drain(..));
+            builder.record_span(span);
         }
 
-        Ok(sections)
+        Ok(builder.finish(text.len()))
+    }
+
+    /// Pull parser over `text`: an iterator of events instead of a tree
+    ///
+    /// Only the current heading or block is parsed ahead, so single-pass
+    /// tasks like counting words or collecting links never build a tree.
+    /// The iterator ends after the first `Err`.
+    ///
+    /// # Example
+    /// ```
+    /// use wtf::parser::{Event, MarkdownParser, Tag};
+    ///
+    /// let links: Vec<String> = MarkdownParser::events("[a](x.md) and **[b](y.md)**")
+    ///     .filter_map(|event| match event {
+    ///         Ok(Event::Start(Tag::Link(url))) => Some(url.to_string()),
+    ///         _ => None,
+    ///     })
+    ///     .collect();
+    /// assert_eq!(links, ["x.md", "y.md"]);
+    /// ```
+    pub fn events(text: &'a str) -> Events<'a> {
+        Events::new(text)
     }
 
-    /// Parse a section tree recursively
-    /// parent_level: None for top-level, Some(level) for subsections
-    fn parse_section_tree(&mut self, _parent_level: Option<u8>) -> Result<Section, ParseError> {
-        let (level, title) = self.parse_heading_line()?;
-        let mut section = Section::new(level, title);
+    /// Parses markdown from a reader one block at a time
+    ///
+    /// Only the block being parsed is buffered, so the source never has to
+    /// fit in memory as a whole (the returned tree still does). The result
+    /// and error positions are the same as for `parse` on the whole text.
+    ///
+    /// # Returns
+    /// * `Ok(Document)` - The parsed document, owning all its text
+    /// * `Err(ParseError)` - On a syntax error or if reading fails
+    pub fn parse_reader<R: BufRead>(mut reader: R) -> Result<Document<'static>, ParseError> {
+        let mut builder = TreeBuilder::new();
+        let mut buffer = String::new();
+        // Offset of the buffer's first byte in the whole input
+        let mut base = 0;
+        // Levels of the sections still open at the end of the last chunk
+        let mut open_sections = Vec::new();
 
-        // Parse content until next heading or EOF
         loop {
-            self.skip_empty_lines();
+            let line_start = buffer.len();
+            let read = reader
+                .read_line(&mut buffer)
+                .map_err(|e| ParseError::IoError {
+                    message: e.to_string(),
+                })?;
+            let at_eof = read == 0;
+
+            // Only a blank line, a heading or a fence can end the pending block
+            let line = &buffer.as_bytes()[line_start..];
+            if !at_eof
+                && line != [NEWLINE_CHAR]
+                && line.first() != Some(&HEADING_CHAR)
+                && !line.starts_with(FENCE.as_bytes())
+            {
+                continue;
+            }
 
-            if self.is_eof() {
-                break;
+            let mut events = Events::new(&buffer);
+            events.open_sections = std::mem::take(&mut open_sections);
+            let consumed = events
+                .parse_complete_items(at_eof, base, &mut builder)
+                .map_err(|e| e.offset_by(base))?;
+            open_sections = events.open_sections;
+            buffer.drain(..consumed);
+            base += consumed;
+
+            if at_eof {
+                return Ok(builder.finish(base));
             }
+        }
+    }
 
-            if self.is_heading() {
-                let next_level = self.peek_heading_level();
+    /// Updates a parsed document after an edit to its source, re-parsing
+    /// only the top-level blocks and sections the edit can affect
+    ///
+    /// `document` must have been parsed (or re-parsed) from the text before
+    /// the edit and `edited` is the whole text after it. Units before the
+    /// edit are kept; re-parsing stops as soon as it reaches an unchanged
+    /// unit that still starts a top-level block or section. Without usable
+    /// spans (a document built or restructured in code, or an edit that
+    /// doesn't fit them) the whole text is parsed.
+    ///
+    /// # Returns
+    /// * `Ok(Range<usize>)` - The byte range of `edited` that was re-parsed
+    /// * `Err(ParseError)` - If the edited text doesn't parse (`document` is unchanged)
+    pub fn reparse(
+        document: &mut Document<'static>,
+        edited: &str,
+        edit: &TextEdit,
+    ) -> Result<Range<usize>, ParseError> {
+        let spans = match &document.spans {
+            Some(spans) if spans_fit(document, spans, edited.len(), edit) => spans.clone(),
+            _ => {
+                *document = MarkdownParser::parse(edited)?.into_owned();
+                return Ok(0..edited.len());
+            }
+        };
+
+        // Units in source order: preamble blocks, then top-level sections
+        let block_count = spans.blocks.len();
+        let units: Vec<Range<usize>> = spans
+            .blocks
+            .iter()
+            .chain(&spans.sections)
+            .cloned()
+            .collect();
+
+        // The first unit the edit can change ends at most a fence's length
+        // before its start (a paragraph's end depends on whether the next
+        // line starts with a blank, a heading or a fence). A section's
+        // extent depends on the headings that follow, so the one before it
+        // is re-parsed too.
+        let mut first = units
+            .iter()
+            .position(|span| span.end + FENCE.len() > edit.range.start)
+            .unwrap_or(units.len());
+        if first > block_count {
+            first -= 1;
+        }
+        let region_start = if first == 0 { 0 } else { units[first - 1].end };
+        let prefix_level =
+            (first > block_count).then(|| document.sections[first - block_count - 1].level);
+
+        let edit_end = edit.range.start + edit.new_len;
+        let shift = |offset: usize| offset - edit.range.end + edit_end;
+
+        let mut events = Events::new(edited);
+        events.parser.pos = region_start;
+        let mut builder = TreeBuilder::new();
+        let mut candidate = first;
+
+        let resume = loop {
+            events.parser.skip_empty_lines();
+            let pos = events.parser.pos;
+
+            // Resume at the first unit after the edit that starts here and
+            // is still top-level after the re-parsed units
+            if pos >= edit_end {
+                while candidate < units.len()
+                    && (units[candidate].start < edit.range.end
+                        || shift(units[candidate].start) < pos)
+                {
+                    candidate += 1;
+                }
 
-                // If next heading is deeper, it's a subsection
-                if next_level > level {
-                    let subsection = self.parse_section_tree(Some(level))?;
-                    section.subsections.push(subsection);
+                let fits = if candidate < block_count {
+                    !builder.has_sections()
                 } else {
-                    // Same or higher level - return to parent
-                    break;
+                    candidate < units.len() && {
+                        let level = document.sections[candidate - block_count].level;
+                        builder
+                            .last_top_level()
+                            .or(prefix_level)
+                            .is_none_or(|last| level <= last)
+                    }
+                };
+
+                if fits && shift(units[candidate].start) == pos {
+                    break candidate;
                 }
-            } else {
-                // Parse content block
-                let block = self.parse_block()?;
-                section.content.push(block);
             }
+
+            let Some(span) = events.parse_next()? else {
+                break units.len();
+            };
+            builder.push_all(events.queue.drain(..).map(Event::into_owned));
+            builder.record_span(span);
+        };
+        let region_end = events.parser.pos;
+
+        let region = builder.finish(edited.len());
+        let region_spans = region.spans.unwrap_or_default();
+        let (prefix_blocks, prefix_sections) =
+            (first.min(block_count), first.saturating_sub(block_count));
+        let (suffix_blocks, suffix_sections) =
+            (resume.min(block_count), resume.saturating_sub(block_count));
+        let shift_span = |span: &Range<usize>| shift(span.start)..shift(span.end);
+
+        let mut content = std::mem::take(&mut document.content);
+        let suffix = content.split_off(suffix_blocks);
+        content.truncate(prefix_blocks);
+        content.extend(region.content);
+        content.extend(suffix);
+
+        let mut sections = std::mem::take(&mut document.sections);
+        let suffix = sections.split_off(suffix_sections);
+        sections.truncate(prefix_sections);
+        sections.extend(region.sections);
+        sections.extend(suffix);
+
+        let mut block_spans = spans.blocks[..prefix_blocks].to_vec();
+        block_spans.extend(region_spans.blocks);
+        block_spans.extend(spans.blocks[suffix_blocks..].iter().map(shift_span));
+
+        let mut section_spans = spans.sections[..prefix_sections].to_vec();
+        section_spans.extend(region_spans.sections);
+        section_spans.extend(spans.sections[suffix_sections..].iter().map(shift_span));
+
+        document.content = content;
+        document.sections = sections;
+        document.spans = Some(SourceSpans {
+            blocks: block_spans,
+            sections: section_spans,
+            source_len: edited.len(),
+        });
+
+        Ok(region_start..region_end)
+    }
+
+    // ========================================================================
+    // ITEM-LEVEL PARSING (a heading line or a block at a time)
+    // ========================================================================
+
+    /// Parses the next heading line or block after any blank lines into
+    /// `events` (cleared first)
+    ///
+    /// # Returns
+    /// * `Ok(Some(ParsedItem))` - What was parsed and from where
+    /// * `Ok(None)` - At the end of the input
+    fn parse_item(
+        &mut self,
+        events: &mut Vec<Event<'a>>,
+    ) -> Result<Option<ParsedItem>, ParseError> {
+        events.clear();
+        self.skip_empty_lines();
+        if self.is_eof() {
+            return Ok(None);
         }
 
-        Ok(section)
+        let start = self.pos;
+        let heading = if self.is_heading() {
+            Some(self.parse_heading_line(events)?)
+        } else {
+            self.parse_block(events)?;
+            None
+        };
+
+        Ok(Some(ParsedItem {
+            heading,
+            span: start..self.pos,
+        }))
     }
 
     // ========================================================================
-    // BLOCK-LEVEL PARSING (paragraphs, images)
+    // BLOCK-LEVEL PARSING (paragraphs, images, code blocks)
     // ========================================================================
 
-    fn parse_block(&mut self) -> Result<BlockNode, ParseError> {
+    fn parse_block(&mut self, events: &mut Vec<Event<'a>>) -> Result<(), ParseError> {
+        // Check for fenced code block: ```info
+        if self.is_fence() {
+            events.push(self.parse_code_block()?);
+            return Ok(());
+        }
+
         // Check for image block: ![alt](url)
         if self.peek() == Some(IMAGE_PREFIX) && self.peek_at(1) == Some(LINK_OPEN) {
-            return self.parse_image_block();
+            events.push(self.parse_image_block()?);
+            return Ok(());
         }
 
         // Otherwise, parse as paragraph
-        self.parse_paragraph()
+        self.parse_paragraph(events)
     }
 
-    fn parse_paragraph(&mut self) -> Result<BlockNode, ParseError> {
-        let mut inline_nodes = Vec::new();
+    fn parse_paragraph(&mut self, events: &mut Vec<Event<'a>>) -> Result<(), ParseError> {
+        events.push(Event::Start(Tag::Paragraph));
         let mut first_line = true;
 
         loop {
+            // Add line break between lines (but not before first line), and
+            // drop it again if the line turns out to be empty
+            let line_start = events.len();
+            if !first_line {
+                events.push(Event::LineBreak);
+            }
+            let content_start = events.len();
+
             // Parse a line of inline content
-            let line_content = self.parse_inline_content()?;
+            self.parse_inline_content(events)?;
 
-            if !line_content.is_empty() {
-                // Add line break between lines (but not before first line)
-                if !first_line {
-                    inline_nodes.push(InlineNode::LineBreak);
-                }
-                inline_nodes.extend(line_content);
+            if events.len() == content_start {
+                events.truncate(line_start);
+            } else {
                 first_line = false;
             }
 
             // Check what's next
             if self.peek() == Some(NEWLINE_CHAR) {
-                self.advance(); // consume newline
+                self.pos += 1; // consume newline
 
                 // Check for double newline (paragraph boundary)
-                if self.peek() == Some(NEWLINE_CHAR) || self.is_eof() || self.is_heading() {
+                if self.peek() == Some(NEWLINE_CHAR)
+                    || self.is_eof()
+                    || self.is_heading()
+                    || self.is_fence()
+                {
                     break;
                 }
                 // Single newline - continue with next line
@@ -336,149 +1264,190 @@ impl MarkdownParser {
             }
         }
 
-        Ok(BlockNode::Paragraph(inline_nodes))
+        events.push(Event::End(TagEnd::Paragraph));
+        Ok(())
     }
 
-    fn parse_image_block(&mut self) -> Result<BlockNode, ParseError> {
+    fn parse_image_block(&mut self) -> Result<Event<'a>, ParseError> {
         let start_pos = self.pos;
 
         // Consume "!["
-        self.advance(); // !
-        self.advance(); // [
-
-        // Parse alt text with escape support for brackets
-        let mut alt_text = String::new();
-        while let Some(ch) = self.peek() {
-            if ch == ESCAPE_CHAR {
-                self.advance();
-                if let Some(next_ch) = self.peek() {
-                    alt_text.push(next_ch);
-                    self.advance();
+        self.pos += 2;
+
+        // Parse alt text with escape support (any character after a backslash is literal)
+        let alt_start = self.pos;
+        let mut owned: Option<String> = None;
+        let mut segment_start = alt_start;
+        let mut alt_end = None;
+
+        while let Some(byte) = self.peek() {
+            if byte == ESCAPE_CHAR {
+                let buffer = owned.get_or_insert_with(String::new);
+                buffer.push_str(&self.input[segment_start..self.pos]);
+                self.pos += 1;
+                // The escaped character (possibly multi-byte) starts the next segment
+                segment_start = self.pos;
+                if let Some(ch) = self.input[self.pos..].chars().next() {
+                    self.pos += ch.len_utf8();
                 }
-            } else if ch == LINK_CLOSE {
-                self.advance();
+            } else if byte == LINK_CLOSE {
+                alt_end = Some(self.pos);
+                self.pos += 1;
                 break;
             } else {
-                alt_text.push(ch);
-                self.advance();
+                self.pos += 1;
             }
         }
 
+        let alt_end = alt_end.unwrap_or(self.pos);
+        let alt_text = match owned {
+            None => Cow::Borrowed(&self.input[alt_start..alt_end]),
+            Some(mut buffer) => {
+                buffer.push_str(&self.input[segment_start..alt_end]);
+                Cow::Owned(buffer)
+            }
+        };
+
         // Expect '('
         if self.peek() != Some(URL_OPEN) {
             return Err(ParseError::MalformedImage {
                 position: start_pos,
             });
         }
-        self.advance();
+        self.pos += 1;
 
-        // Parse URL
-        let mut url = String::new();
-        while let Some(ch) = self.peek() {
-            if ch == URL_CLOSE {
-                self.advance();
+        // Parse URL (an unclosed URL runs to the end of the input)
+        let url_start = self.pos;
+        let mut url_end = None;
+        while let Some(byte) = self.peek() {
+            if byte == URL_CLOSE {
+                url_end = Some(self.pos);
+                self.pos += 1;
                 break;
             }
-            if ch == NEWLINE_CHAR {
+            if byte == NEWLINE_CHAR {
                 return Err(ParseError::MalformedImage {
                     position: start_pos,
                 });
             }
-            url.push(ch);
-            self.advance();
+            self.pos += 1;
         }
 
         // Trim whitespace from URL
-        let url = url.trim().to_string();
+        let url = self.input[url_start..url_end.unwrap_or(self.pos)].trim();
 
         // Consume trailing newline if present
         if self.peek() == Some(NEWLINE_CHAR) {
-            self.advance();
+            self.pos += 1;
         }
 
-        Ok(BlockNode::Image { alt_text, url })
+        Ok(Event::Image {
+            alt_text,
+            url: Cow::Borrowed(url),
+        })
     }
 
-    // ========================================================================
-    // INLINE-LEVEL PARSING (text, bold, italic, links, line breaks)
-    // ========================================================================
+    /// Parses a fenced code block
+    ///
+    /// The opening fence is three or more backticks followed by the info
+    /// string; the block ends at a line of at least as many backticks (and
+    /// nothing else but whitespace). The lines between are kept verbatim.
+    fn parse_code_block(&mut self) -> Result<Event<'a>, ParseError> {
+        let start_pos = self.pos;
+        let unclosed = ParseError::UnclosedDelimiter {
+            delimiter: FENCE.to_string(),
+            position: start_pos,
+        };
+
+        let fence_len = self.input.as_bytes()[self.pos..]
+            .iter()
+            .take_while(|&&byte| byte == FENCE_CHAR)
+            .count();
+        self.pos += fence_len;
+        let info = self.input[self.pos..self.line_end()].trim();
+        self.pos = self.line_end();
+        if self.peek() != Some(NEWLINE_CHAR) {
+            return Err(unclosed);
+        }
+        self.pos += 1;
 
-    /// Parse inline content until newline or EOF
-    fn parse_inline_content(&mut self) -> Result<Vec<InlineNode>, ParseError> {
-        let mut nodes = Vec::new();
+        let code_start = self.pos;
+        while !self.is_eof() {
+            let line_start = self.pos;
+            let line_end = self.line_end();
+            self.pos = line_end;
+            if self.peek() == Some(NEWLINE_CHAR) {
+                self.pos += 1;
+            }
 
-        while !self.is_eof() && self.peek() != Some(NEWLINE_CHAR) {
-            // Check for inline elements
-            if self.starts_with(BOLD_DELIM) {
-                nodes.push(self.parse_bold()?);
-            } else if self.peek() == Some(ITALIC_DELIM) && !self.starts_with(BOLD_DELIM) {
-                nodes.push(self.parse_italic()?);
-            } else if self.peek() == Some(LINK_OPEN) {
-                nodes.push(self.parse_link()?);
-            } else {
-                // Parse plain text until next delimiter
-                let text = self.parse_text_inline()?;
-                if !text.is_empty() {
-                    nodes.push(InlineNode::Text(text));
-                }
+            if closing_fence_len(&self.input[line_start..line_end])
+                .is_some_and(|len| len >= fence_len)
+            {
+                // The newline before the closing fence isn't part of the code
+                let code = &self.input[code_start..line_start];
+                return Ok(Event::CodeBlock {
+                    info: Cow::Borrowed(info),
+                    code: Cow::Borrowed(code.strip_suffix('\n').unwrap_or(code)),
+                });
             }
         }
 
-        Ok(nodes)
+        Err(unclosed)
     }
 
-    fn parse_text_inline(&mut self) -> Result<String, ParseError> {
-        let mut text = String::new();
+    // ========================================================================
+    // INLINE-LEVEL PARSING (text, bold, italic, links, line breaks)
+    // ========================================================================
 
-        while let Some(ch) = self.peek() {
-            // Stop at delimiters or newline
-            if ch == NEWLINE_CHAR || ch == ITALIC_DELIM || ch == LINK_OPEN {
-                break;
+    /// Parse inline content until newline or EOF, appending to `events`
+    fn parse_inline_content(&mut self, events: &mut Vec<Event<'a>>) -> Result<(), ParseError> {
+        while let Some(byte) = self.peek()
+            && byte != NEWLINE_CHAR
+        {
+            // Check for inline elements
+            if self.starts_with(BOLD_DELIM) {
+                self.parse_bold(events)?;
+            } else if byte == ITALIC_DELIM {
+                self.parse_italic(events)?;
+            } else if byte == LINK_OPEN {
+                self.parse_link(events)?;
+            } else {
+                // Parse plain text until next delimiter
+                self.push_text(events, INLINE_TEXT_STOPS);
             }
-
-            text.push(ch);
-            self.advance();
         }
 
-        Ok(text)
+        Ok(())
     }
 
-    fn parse_bold(&mut self) -> Result<InlineNode, ParseError> {
+    fn parse_bold(&mut self, events: &mut Vec<Event<'a>>) -> Result<(), ParseError> {
         let start_pos = self.pos;
 
         // Consume opening "**"
-        self.advance();
-        self.advance();
-
-        let mut children = Vec::new();
+        self.pos += BOLD_DELIM.len();
+        events.push(Event::Start(Tag::Bold));
 
         // Parse until closing "**"
-        while !self.is_eof() {
+        while let Some(byte) = self.peek() {
             if self.starts_with(BOLD_DELIM) {
                 // Found closing delimiter
-                self.advance();
-                self.advance();
-                return Ok(InlineNode::Bold(children));
+                self.pos += BOLD_DELIM.len();
+                events.push(Event::End(TagEnd::Bold));
+                return Ok(());
             }
 
             // Stop at newline (unclosed bold)
-            if self.peek() == Some(NEWLINE_CHAR) {
-                return Err(ParseError::UnclosedDelimiter {
-                    delimiter: BOLD_DELIM.to_string(),
-                    position: start_pos,
-                });
+            if byte == NEWLINE_CHAR {
+                break;
             }
 
             // Parse inline content (italic, link, text - but not nested bold)
-            if self.peek() == Some(ITALIC_DELIM) && !self.starts_with(BOLD_DELIM) {
-                children.push(self.parse_italic()?);
-            } else if self.peek() == Some(LINK_OPEN) {
-                children.push(self.parse_link()?);
+            if byte == ITALIC_DELIM {
+                self.parse_italic(events)?;
+            } else if byte == LINK_OPEN {
+                self.parse_link(events)?;
             } else {
-                let text = self.parse_text_until(&[BOLD_DELIM, "*", "[", "\n"])?;
-                if !text.is_empty() {
-                    children.push(InlineNode::Text(text));
-                }
+                self.push_text(events, INLINE_TEXT_STOPS);
             }
         }
 
@@ -488,178 +1457,189 @@ impl MarkdownParser {
         })
     }
 
-    fn parse_italic(&mut self) -> Result<InlineNode, ParseError> {
+    fn parse_italic(&mut self, events: &mut Vec<Event<'a>>) -> Result<(), ParseError> {
         let start_pos = self.pos;
 
         // Consume opening "*"
-        self.advance();
-
-        let mut children = Vec::new();
+        self.pos += 1;
+        events.push(Event::Start(Tag::Italic));
 
         // Parse until closing "*"
-        while !self.is_eof() {
-            if self.peek() == Some(ITALIC_DELIM) && !self.starts_with(BOLD_DELIM) {
-                // Found closing delimiter (single *)
-                self.advance();
-                return Ok(InlineNode::Italic(children));
-            }
-
-            // Stop at newline (unclosed italic)
-            if self.peek() == Some(NEWLINE_CHAR) {
-                return Err(ParseError::UnclosedDelimiter {
-                    delimiter: ITALIC_DELIM.to_string(),
-                    position: start_pos,
-                });
-            }
-
+        while let Some(byte) = self.peek() {
             // Parse inline content (bold, link, text - but not nested italic)
             if self.starts_with(BOLD_DELIM) {
-                children.push(self.parse_bold()?);
-            } else if self.peek() == Some(LINK_OPEN) {
-                children.push(self.parse_link()?);
+                self.parse_bold(events)?;
+            } else if byte == ITALIC_DELIM {
+                // Found closing delimiter (single *)
+                self.pos += 1;
+                events.push(Event::End(TagEnd::Italic));
+                return Ok(());
+            } else if byte == NEWLINE_CHAR {
+                // Stop at newline (unclosed italic)
+                break;
+            } else if byte == LINK_OPEN {
+                self.parse_link(events)?;
             } else {
-                let text = self.parse_text_until(&["*", "[", "\n"])?;
-                if !text.is_empty() {
-                    children.push(InlineNode::Text(text));
-                }
+                self.push_text(events, INLINE_TEXT_STOPS);
             }
         }
 
         Err(ParseError::UnclosedDelimiter {
-            delimiter: ITALIC_DELIM.to_string(),
+            delimiter: char::from(ITALIC_DELIM).to_string(),
             position: start_pos,
         })
     }
 
-    fn parse_link(&mut self) -> Result<InlineNode, ParseError> {
+    fn parse_link(&mut self, events: &mut Vec<Event<'a>>) -> Result<(), ParseError> {
         let start_pos = self.pos;
+        let malformed = ParseError::MalformedLink {
+            position: start_pos,
+        };
 
-        // Consume '['
-        self.advance();
+        // Consume '['; the URL comes after the text, so the start event is
+        // filled in once it has been parsed
+        self.pos += 1;
+        let start_index = events.len();
+        events.push(Event::Start(Tag::Link(Cow::Borrowed(""))));
 
         // Parse link text (can contain inline formatting)
-        let mut text = Vec::new();
-        while !self.is_eof() && self.peek() != Some(LINK_CLOSE) {
-            if self.peek() == Some(NEWLINE_CHAR) {
-                return Err(ParseError::MalformedLink {
-                    position: start_pos,
-                });
+        while let Some(byte) = self.peek()
+            && byte != LINK_CLOSE
+        {
+            if byte == NEWLINE_CHAR {
+                return Err(malformed);
             }
 
             if self.starts_with(BOLD_DELIM) {
-                text.push(self.parse_bold()?);
-            } else if self.peek() == Some(ITALIC_DELIM) && !self.starts_with(BOLD_DELIM) {
-                text.push(self.parse_italic()?);
+                self.parse_bold(events)?;
+            } else if byte == ITALIC_DELIM {
+                self.parse_italic(events)?;
             } else {
-                let txt = self.parse_text_until(&[BOLD_DELIM, "*", "]", "\n"])?;
-                if !txt.is_empty() {
-                    text.push(InlineNode::Text(txt));
-                }
+                self.push_text(events, LINK_TEXT_STOPS);
             }
         }
 
         // Consume ']'
         if self.peek() != Some(LINK_CLOSE) {
-            return Err(ParseError::MalformedLink {
-                position: start_pos,
-            });
+            return Err(malformed);
         }
-        self.advance();
+        self.pos += 1;
 
         // Expect '('
         if self.peek() != Some(URL_OPEN) {
-            return Err(ParseError::MalformedLink {
-                position: start_pos,
-            });
+            return Err(malformed);
         }
-        self.advance();
+        self.pos += 1;
 
         // Parse URL (plain string until ')')
-        let mut url = String::new();
-        while !self.is_eof() && self.peek() != Some(URL_CLOSE) {
-            if self.peek() == Some(NEWLINE_CHAR) {
-                return Err(ParseError::MalformedLink {
-                    position: start_pos,
-                });
+        let url_start = self.pos;
+        while let Some(byte) = self.peek()
+            && byte != URL_CLOSE
+        {
+            if byte == NEWLINE_CHAR {
+                return Err(malformed);
             }
-            url.push(self.peek().unwrap());
-            self.advance();
+            self.pos += 1;
         }
 
         // Consume ')'
         if self.peek() != Some(URL_CLOSE) {
-            return Err(ParseError::MalformedLink {
-                position: start_pos,
-            });
+            return Err(malformed);
         }
-        self.advance();
+        let url = &self.input[url_start..self.pos];
+        self.pos += 1;
 
         // Trim whitespace from URL
-        let url = url.trim().to_string();
-
-        Ok(InlineNode::Link { text, url })
+        events[start_index] = Event::Start(Tag::Link(Cow::Borrowed(url.trim())));
+        events.push(Event::End(TagEnd::Link));
+        Ok(())
     }
 
-    /// Parse text until one of the stop strings is encountered
-    fn parse_text_until(&mut self, stop_strings: &[&str]) -> Result<String, ParseError> {
-        let mut text = String::new();
+    /// Parses text up to the next stop byte and appends it as a text event
+    /// (if non-empty)
+    fn push_text(&mut self, events: &mut Vec<Event<'a>>, stops: &[u8]) {
+        let text = self.parse_text_until(stops);
+        if !text.is_empty() {
+            events.push(Event::Text(text));
+        }
+    }
 
-        while let Some(ch) = self.peek() {
-            // Check if we hit any stop string
-            let mut should_stop = false;
-            for stop_str in stop_strings {
-                if self.starts_with(stop_str) {
-                    should_stop = true;
-                    break;
-                }
+    /// Parse text until one of the stop bytes is encountered
+    ///
+    /// Borrows the input unless the text contains backslash escapes, which
+    /// have to be removed into an owned copy.
+    fn parse_text_until(&mut self, stops: &[u8]) -> Cow<'a, str> {
+        let start = self.pos;
+        let mut owned: Option<String> = None;
+        let mut segment_start = start;
+
+        while let Some(byte) = self.peek() {
+            if byte == ESCAPE_CHAR && self.peek_at(1).is_some_and(is_escapable_byte) {
+                let buffer = owned.get_or_insert_with(String::new);
+                buffer.push_str(&self.input[segment_start..self.pos]);
+                // Skip the backslash; the escaped byte starts the next segment
+                segment_start = self.pos + 1;
+                self.pos += 2;
+                continue;
             }
 
-            if should_stop {
+            if stops.contains(&byte) {
                 break;
             }
 
-            text.push(ch);
-            self.advance();
+            self.pos += 1;
         }
 
-        Ok(text)
+        match owned {
+            None => Cow::Borrowed(&self.input[start..self.pos]),
+            Some(mut buffer) => {
+                buffer.push_str(&self.input[segment_start..self.pos]);
+                Cow::Owned(buffer)
+            }
+        }
     }
 
     // ========================================================================
     // HEADING PARSING
     // ========================================================================
 
-    fn parse_heading_line(&mut self) -> Result<(u8, Vec<InlineNode>), ParseError> {
+    /// Parses a heading line into `events`
+    ///
+    /// # Returns
+    /// * `Ok(u8)` - The heading level
+    fn parse_heading_line(&mut self, events: &mut Vec<Event<'a>>) -> Result<u8, ParseError> {
         let level = self.parse_heading_level()?;
 
-        // Skip space after #'s
-        if self.peek() == Some(' ') {
-            self.advance();
+        // Skip the spaces after #'s (the title is trimmed at both ends)
+        while self
+            .peek()
+            .is_some_and(|byte| HEADING_PADDING.contains(&char::from(byte)))
+        {
+            self.pos += 1;
         }
 
         // Parse heading title (inline formatted)
-        let title = self.parse_inline_content()?;
+        events.push(Event::Start(Tag::Heading(level)));
+        self.parse_inline_content(events)?;
+        trim_title_end(events);
+        events.push(Event::End(TagEnd::Heading(level)));
 
         // Consume newline
         if self.peek() == Some(NEWLINE_CHAR) {
-            self.advance();
+            self.pos += 1;
         }
 
-        Ok((level, title))
+        Ok(level)
     }
 
     fn parse_heading_level(&mut self) -> Result<u8, ParseError> {
-        let mut level = 0u8;
-
-        while self.peek() == Some(HEADING_CHAR) && level < MAX_HEADING_LEVEL {
-            level += 1;
-            self.advance();
-        }
+        let level = self.peek_heading_level();
 
         if level < MIN_HEADING_LEVEL {
             return Err(ParseError::InvalidHeadingLevel { level });
         }
 
+        self.pos += usize::from(level);
         Ok(level)
     }
 
@@ -667,64 +1647,370 @@ impl MarkdownParser {
         self.peek() == Some(HEADING_CHAR)
     }
 
-    fn peek_heading_level(&self) -> u8 {
-        let mut level = 0u8;
-        let mut temp_pos = self.pos;
-
-        while temp_pos < self.chars.len()
-            && self.chars[temp_pos] == HEADING_CHAR
-            && level < MAX_HEADING_LEVEL
-        {
-            level += 1;
-            temp_pos += 1;
-        }
+    fn is_fence(&self) -> bool {
+        self.starts_with(FENCE)
+    }
 
-        level
+    /// Counts the leading '#'s at the current position (at most 6)
+    fn peek_heading_level(&self) -> u8 {
+        self.input.as_bytes()[self.pos..]
+            .iter()
+            .take(usize::from(MAX_HEADING_LEVEL))
+            .take_while(|&&byte| byte == HEADING_CHAR)
+            .count() as u8
     }
 
     // ========================================================================
     // UTILITY METHODS
     // ========================================================================
 
-    fn peek(&self) -> Option<char> {
-        self.chars.get(self.pos).copied()
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).copied()
     }
 
-    fn peek_at(&self, offset: usize) -> Option<char> {
-        self.chars.get(self.pos + offset).copied()
+    fn peek_at(&self, offset: usize) -> Option<u8> {
+        self.input.as_bytes().get(self.pos + offset).copied()
     }
 
     fn starts_with(&self, s: &str) -> bool {
-        let s_chars: Vec<char> = s.chars().collect();
-        if self.pos + s_chars.len() > self.chars.len() {
-            return false;
-        }
-        &self.chars[self.pos..self.pos + s_chars.len()] == &s_chars[..]
+        self.input.as_bytes()[self.pos..].starts_with(s.as_bytes())
     }
 
-    fn advance(&mut self) -> Option<char> {
-        let ch = self.peek();
-        self.pos += 1;
-        ch
+    fn is_eof(&self) -> bool {
+        self.pos >= self.input.len()
     }
 
-    fn is_eof(&self) -> bool {
-        self.pos >= self.chars.len()
+    /// Byte offset of the end of the current line (its newline, or the end
+    /// of the input)
+    fn line_end(&self) -> usize {
+        self.input.as_bytes()[self.pos..]
+            .iter()
+            .position(|&byte| byte == NEWLINE_CHAR)
+            .map_or(self.input.len(), |offset| self.pos + offset)
     }
 
     fn skip_empty_lines(&mut self) {
         while self.peek() == Some(NEWLINE_CHAR) {
-            self.advance();
+            self.pos += 1;
+        }
+    }
+}
+
+/// Checks whether a backslash before this character escapes it in inline text
+///
+/// Only characters with a meaning in the markdown syntax are escapable, so
+/// other backslashes (`C:\docs`, `\{{ name }}`) are kept as literal text.
+pub(crate) fn is_escapable(ch: char) -> bool {
+    u8::try_from(ch).is_ok_and(is_escapable_byte)
+}
+
+fn is_escapable_byte(byte: u8) -> bool {
+    ESCAPABLE_CHARS.contains(&byte)
+}
+
+/// Drops trailing spaces from the text that ends a heading's title
+fn trim_title_end(events: &mut Vec<Event<'_>>) {
+    while let Some(Event::Text(text)) = events.last_mut() {
+        match text {
+            Cow::Borrowed(borrowed) => *borrowed = borrowed.trim_end_matches(HEADING_PADDING),
+            Cow::Owned(owned) => owned.truncate(owned.trim_end_matches(HEADING_PADDING).len()),
+        }
+        if !text.is_empty() {
+            return;
         }
+        events.pop();
     }
 }
 
-impl Default for MarkdownParser {
+/// Checks whether a line (without its newline) could close a code block
+///
+/// # Returns
+/// * `Some(usize)` - The number of backticks, for a line of three or more
+///   backticks followed only by whitespace (it closes fences up to that long)
+/// * `None` - For any other line
+pub(crate) fn closing_fence_len(line: &str) -> Option<usize> {
+    let ticks = line.bytes().take_while(|&byte| byte == FENCE_CHAR).count();
+    (ticks >= FENCE.len() && line[ticks..].trim().is_empty()).then_some(ticks)
+}
+
+impl Default for MarkdownParser<'_> {
     fn default() -> Self {
         Self::new()
     }
 }
 
+/// Checks that a document's spans are complete and that an edit fits them
+fn spans_fit(
+    document: &Document<'_>,
+    spans: &SourceSpans,
+    edited_len: usize,
+    edit: &TextEdit,
+) -> bool {
+    spans.blocks.len() == document.content.len()
+        && spans.sections.len() == document.sections.len()
+        && edit.range.start <= edit.range.end
+        && edit.range.end <= spans.source_len
+        && spans.source_len - edit.range.len() + edit.new_len == edited_len
+}
+
+// ============================================================================
+// PULL PARSER
+// ============================================================================
+
+/// A heading line or block parsed into the event buffer
+struct ParsedItem {
+    /// The heading level, or `None` for a block
+    heading: Option<u8>,
+    span: Range<usize>,
+}
+
+/// Iterator of parse events, returned by `MarkdownParser::events`
+///
+/// Parses one heading or block at a time into a queue, adding the section
+/// starts and ends its heading level implies (a heading closes the open
+/// sections at its level or deeper).
+pub struct Events<'a> {
+    parser: MarkdownParser<'a>,
+    /// Levels of the open sections, outermost first
+    open_sections: Vec<u8>,
+    /// Scratch buffer for the events of the item being parsed
+    item: Vec<Event<'a>>,
+    queue: VecDeque<Event<'a>>,
+    finished: bool,
+}
+
+impl<'a> Events<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            parser: MarkdownParser::over(input),
+            open_sections: Vec::new(),
+            item: Vec::new(),
+            queue: VecDeque::new(),
+            finished: false,
+        }
+    }
+
+    /// Parses the next heading or block and queues its events, or queues
+    /// the ends of the open sections at the end of the input
+    ///
+    /// # Returns
+    /// * `Ok(Some(Range))` - The byte range of the parsed item
+    /// * `Ok(None)` - At the end of the input
+    fn parse_next(&mut self) -> Result<Option<Range<usize>>, ParseError> {
+        match self.parser.parse_item(&mut self.item)? {
+            Some(item) => {
+                self.queue_item(item.heading);
+                Ok(Some(item.span))
+            }
+            None => {
+                while let Some(level) = self.open_sections.pop() {
+                    self.queue.push_back(Event::End(TagEnd::Section(level)));
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// Queues the parsed item's events after the section events of its heading
+    fn queue_item(&mut self, heading: Option<u8>) {
+        if let Some(level) = heading {
+            while let Some(&open) = self.open_sections.last()
+                && open >= level
+            {
+                self.open_sections.pop();
+                self.queue.push_back(Event::End(TagEnd::Section(open)));
+            }
+            self.open_sections.push(level);
+            self.queue.push_back(Event::Start(Tag::Section(level)));
+        }
+        self.queue.extend(self.item.drain(..));
+    }
+
+    /// Parses the items of a buffer holding the input read so far, stopping
+    /// before the first one that more input could still change
+    ///
+    /// Unless `at_eof`, the buffer ends with a newline, so an item that
+    /// stops short of the buffer's end (completed or failed) is final.
+    /// Spans are offset by `base`, the buffer's position in the input.
+    ///
+    /// # Returns
+    /// * `Ok(usize)` - Number of bytes consumed; the rest must be parsed again with more input
+    /// * `Err(ParseError)` - For a final syntax error (positions relative to the buffer)
+    fn parse_complete_items(
+        &mut self,
+        at_eof: bool,
+        base: usize,
+        builder: &mut TreeBuilder<'static>,
+    ) -> Result<usize, ParseError> {
+        loop {
+            self.parser.skip_empty_lines();
+            let start = self.parser.pos;
+            let result = self.parser.parse_item(&mut self.item);
+
+            if !at_eof && self.parser.is_eof() {
+                return Ok(start);
+            }
+
+            let Some(item) = result? else {
+                return Ok(start);
+            };
+            self.queue_item(item.heading);
+            builder.push_all(self.queue.drain(..).map(Event::into_owned));
+            builder.record_span(item.span.start + base..item.span.end + base);
+        }
+    }
+}
+
+impl<'a> Iterator for Events<'a> {
+    type Item = Result<Event<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.queue.pop_front() {
+                return Some(Ok(event));
+            }
+            if self.finished {
+                return None;
+            }
+
+            match self.parse_next() {
+                Ok(Some(_)) => {}
+                Ok(None) => self.finished = true,
+                Err(error) => {
+                    self.finished = true;
+                    return Some(Err(error));
+                }
+            }
+        }
+    }
+}
+
+// ============================================================================
+// TREE ASSEMBLY (events into a document)
+// ============================================================================
+
+/// Builds a document from an event stream, recording the source span of
+/// every top-level unit
+struct TreeBuilder<'a> {
+    document: Document<'a>,
+    spans: SourceSpans,
+    /// Open sections, outermost first
+    sections: Vec<Section<'a>>,
+    /// Open headings, paragraphs and inline containers with their children
+    containers: Vec<(Tag<'a>, Vec<InlineNode<'a>>)>,
+    /// Whether the item being recorded opened a top-level section
+    opened_top_level: bool,
+}
+
+impl<'a> TreeBuilder<'a> {
+    fn new() -> Self {
+        Self {
+            document: Document::new(),
+            spans: SourceSpans::default(),
+            sections: Vec::new(),
+            containers: Vec::new(),
+            opened_top_level: false,
+        }
+    }
+
+    fn push_all(&mut self, events: impl IntoIterator<Item = Event<'a>>) {
+        for event in events {
+            self.push(event);
+        }
+    }
+
+    fn push(&mut self, event: Event<'a>) {
+        match event {
+            Event::Start(Tag::Section(level)) => {
+                self.opened_top_level |= self.sections.is_empty();
+                self.sections
+                    .push(Section::new_unchecked(level, Vec::new()));
+            }
+            Event::End(TagEnd::Section(_)) => self.close_section(),
+            Event::Start(tag) => self.containers.push((tag, Vec::new())),
+            Event::End(_) => {
+                let Some((tag, children)) = self.containers.pop() else {
+                    return;
+                };
+                match tag {
+                    Tag::Heading(_) => {
+                        if let Some(section) = self.sections.last_mut() {
+                            section.title = children;
+                        }
+                    }
+                    Tag::Paragraph => self.push_block(BlockNode::Paragraph(children)),
+                    Tag::Bold => self.push_inline(InlineNode::Bold(children)),
+                    Tag::Italic => self.push_inline(InlineNode::Italic(children)),
+                    Tag::Link(url) => self.push_inline(InlineNode::Link {
+                        text: children,
+                        url,
+                    }),
+                    Tag::Section(_) => {}
+                }
+            }
+            Event::Text(text) => self.push_inline(InlineNode::Text(text)),
+            Event::LineBreak => self.push_inline(InlineNode::LineBreak),
+            Event::Image { alt_text, url } => self.push_block(BlockNode::Image { alt_text, url }),
+            Event::CodeBlock { info, code } => self.push_block(BlockNode::CodeBlock { info, code }),
+        }
+    }
+
+    fn push_inline(&mut self, node: InlineNode<'a>) {
+        if let Some((_, children)) = self.containers.last_mut() {
+            children.push(node);
+        }
+    }
+
+    fn push_block(&mut self, block: BlockNode<'a>) {
+        match self.sections.last_mut() {
+            Some(section) => section.content.push(block),
+            None => self.document.content.push(block),
+        }
+    }
+
+    fn close_section(&mut self) {
+        if let Some(section) = self.sections.pop() {
+            match self.sections.last_mut() {
+                Some(parent) => parent.subsections.push(section),
+                None => self.document.sections.push(section),
+            }
+        }
+    }
+
+    /// Records the source range of the item whose events were just pushed
+    fn record_span(&mut self, span: Range<usize>) {
+        if std::mem::take(&mut self.opened_top_level) {
+            self.spans.sections.push(span);
+        } else if self.sections.is_empty() {
+            self.spans.blocks.push(span);
+        } else if let Some(last) = self.spans.sections.last_mut() {
+            last.end = span.end;
+        }
+    }
+
+    /// Whether any section has been started
+    fn has_sections(&self) -> bool {
+        !self.sections.is_empty() || !self.document.sections.is_empty()
+    }
+
+    /// Level of the latest top-level section
+    fn last_top_level(&self) -> Option<u8> {
+        self.sections
+            .first()
+            .or(self.document.sections.last())
+            .map(Section::level)
+    }
+
+    fn finish(mut self, source_len: usize) -> Document<'a> {
+        while !self.sections.is_empty() {
+            self.close_section();
+        }
+        self.spans.source_len = source_len;
+        self.document.spans = Some(self.spans);
+        self.document
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -835,4 +2121,406 @@ mod tests {
             _ => panic!("Expected Image"),
         }
     }
+
+    #[test]
+    fn test_code_block() {
+        let source = "Intro\n```rust  \nfn main() {\n# not a heading\n```x\n}\n````  \nafter";
+        let doc = MarkdownParser::parse(source).unwrap();
+        assert_eq!(doc.content().len(), 3);
+        match &doc.content()[1] {
+            BlockNode::CodeBlock { info, code } => {
+                assert_eq!(info, "rust");
+                assert_eq!(code, "fn main() {\n# not a heading\n```x\n}");
+                assert!(matches!((info, code), (Cow::Borrowed(_), Cow::Borrowed(_))));
+            }
+            _ => panic!("Expected CodeBlock"),
+        }
+
+        // A longer opening fence needs a closing one at least as long
+        let doc = MarkdownParser::parse("````\n```\n````").unwrap();
+        assert_eq!(
+            doc.content(),
+            [BlockNode::CodeBlock {
+                info: "".into(),
+                code: "```".into()
+            }]
+        );
+
+        let doc = MarkdownParser::parse("```\n```\n```\n\n\n```").unwrap();
+        let codes: Vec<_> = doc
+            .content()
+            .iter()
+            .map(|block| match block {
+                BlockNode::CodeBlock { code, .. } => code.as_ref(),
+                _ => panic!("Expected CodeBlock"),
+            })
+            .collect();
+        assert_eq!(codes, ["", "\n"]);
+
+        assert_eq!(
+            MarkdownParser::parse("text\n\n```sh\nls\n``"),
+            Err(ParseError::UnclosedDelimiter {
+                delimiter: "```".to_string(),
+                position: 6
+            })
+        );
+        assert!(MarkdownParser::parse("```").is_err());
+
+        // Escaped backticks are text
+        let doc = MarkdownParser::parse("\\```").unwrap();
+        assert_eq!(
+            doc.content(),
+            [BlockNode::Paragraph(vec![InlineNode::Text("```".into())])]
+        );
+    }
+
+    #[test]
+    fn test_text_borrows_from_source() {
+        let source = "# Title\nplain [link](  url  ) **b\\*ld**\n\n![alt](img.png)";
+        let doc = MarkdownParser::parse(source).unwrap();
+
+        let section = &doc.sections()[0];
+        assert!(matches!(
+            &section.title()[0],
+            InlineNode::Text(Cow::Borrowed("Title"))
+        ));
+
+        let BlockNode::Paragraph(inlines) = &section.content()[0] else {
+            panic!("Expected Paragraph");
+        };
+        assert!(matches!(
+            &inlines[0],
+            InlineNode::Text(Cow::Borrowed("plain "))
+        ));
+        assert!(matches!(
+            &inlines[1],
+            InlineNode::Link {
+                url: Cow::Borrowed("url"),
+                ..
+            }
+        ));
+        // Escapes force an owned copy
+        assert_eq!(
+            inlines[3],
+            InlineNode::Bold(vec![InlineNode::Text(Cow::Owned("b*ld".to_string()))])
+        );
+
+        assert!(matches!(
+            &section.content()[1],
+            BlockNode::Image {
+                alt_text: Cow::Borrowed("alt"),
+                url: Cow::Borrowed("img.png"),
+            }
+        ));
+    }
+
+    #[test]
+    fn test_into_owned() {
+        let owned: Document<'static> = {
+            let source = String::from("Intro [a](b)\n\n# T\n![x\\]](y)\n## U\n*i*");
+            let doc = MarkdownParser::parse(&source).unwrap();
+            let owned = doc.clone().into_owned();
+            assert_eq!(owned, doc);
+            owned
+        };
+        assert_eq!(owned.sections()[0].subsections()[0].title(), text("U"));
+    }
+
+    #[test]
+    fn test_error_positions_are_byte_offsets() {
+        assert_eq!(
+            MarkdownParser::parse("héllo **world"),
+            Err(ParseError::UnclosedDelimiter {
+                delimiter: "**".to_string(),
+                position: 7,
+            })
+        );
+    }
+
+    #[test]
+    fn test_backslash_escapes() {
+        let doc =
+            MarkdownParser::parse("\\# a \\*b\\* **\\*\\*** [\\]](u) C:\\dir \\{{ x }}").unwrap();
+        assert_eq!(
+            doc.content(),
+            &[BlockNode::Paragraph(vec![
+                InlineNode::Text("# a *b* ".into()),
+                InlineNode::Bold(text("**")),
+                InlineNode::Text(" ".into()),
+                InlineNode::Link {
+                    text: text("]"),
+                    url: "u".into(),
+                },
+                InlineNode::Text(" C:\\dir \\{{ x }}".into()),
+            ])]
+        );
+        assert!(doc.sections().is_empty());
+
+        // An escaped "!" doesn't start an image block
+        let doc = MarkdownParser::parse("\\![a](b)").unwrap();
+        assert!(matches!(&doc.content()[0], BlockNode::Paragraph(_)));
+    }
+
+    fn text(value: &str) -> Vec<InlineNode<'_>> {
+        vec![InlineNode::Text(value.into())]
+    }
+
+    #[test]
+    fn test_build_document_matches_parse() {
+        let section = Section::new(1, text("Title"))
+            .unwrap()
+            .with_block(BlockNode::Paragraph(text("Body")))
+            .with_subsection(Section::new(2, text("Sub")).unwrap())
+            .unwrap();
+        let document =
+            Document::from_parts(vec![BlockNode::Paragraph(text("Intro"))], vec![section]).unwrap();
+
+        assert_eq!(
+            document,
+            MarkdownParser::parse("Intro\n\n# Title\nBody\n## Sub").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_subsection_level_validation() {
+        let mut section = Section::new(2, text("H2")).unwrap();
+        assert_eq!(
+            section.push_subsection(Section::new(2, text("Same")).unwrap()),
+            Err(AstError::SubsectionLevel {
+                parent_level: 2,
+                level: 2
+            })
+        );
+        assert_eq!(
+            Section::new(7, text("Too deep")),
+            Err(AstError::InvalidHeadingLevel { level: 7 })
+        );
+        assert!(
+            section
+                .push_subsection(Section::new(4, text("H4")).unwrap())
+                .is_ok()
+        );
+        assert!(section.subsections().len() == 1);
+    }
+
+    #[test]
+    fn test_section_handles_keep_levels_valid() {
+        let mut document = MarkdownParser::parse("# A\n## B").unwrap();
+        let mut a = document.section_mut(0).unwrap();
+        let mut b = a.subsection_mut(0).unwrap();
+        b.set_title(text("Beta"));
+        assert_eq!(
+            b.push_subsection(Section::new(2, text("C")).unwrap()),
+            Err(AstError::SubsectionLevel {
+                parent_level: 2,
+                level: 2
+            })
+        );
+        assert!(a.subsection_mut(1).is_none());
+        assert_eq!(
+            document.sections()[0].subsections()[0].title(),
+            text("Beta").as_slice()
+        );
+        assert!(document.validate().is_ok());
+    }
+
+    #[test]
+    fn test_from_parts_validates_nested_sections() {
+        let mut bad = Section::new_unchecked(2, text("H2"));
+        bad.subsections.push(Section::new_unchecked(1, text("H1")));
+        assert!(matches!(
+            Document::from_parts(vec![], vec![bad]),
+            Err(AstError::SubsectionLevel { .. })
+        ));
+        assert!(matches!(
+            Document::from_parts(vec![], vec![Section::new_unchecked(0, text("H0"))]),
+            Err(AstError::InvalidHeadingLevel { level: 0 })
+        ));
+    }
+
+    #[test]
+    fn test_insert_and_remove() {
+        let mut document = MarkdownParser::parse("A\n\nC\n\n# One\n# Three").unwrap();
+
+        document
+            .insert_block(1, BlockNode::Paragraph(text("B")))
+            .unwrap();
+        assert_eq!(document.content()[1], BlockNode::Paragraph(text("B")));
+        assert_eq!(
+            document.insert_block(9, BlockNode::Paragraph(text("X"))),
+            Err(AstError::IndexOutOfBounds { index: 9, len: 3 })
+        );
+
+        document
+            .insert_section(1, Section::new(1, text("Two")).unwrap())
+            .unwrap();
+        let removed = document.remove_section(0).unwrap();
+        assert_eq!(removed.title(), text("One").as_slice());
+        assert_eq!(document.sections()[0].title(), text("Two").as_slice());
+        assert!(document.remove_section(5).is_err());
+
+        assert!(document.section_mut(2).is_none());
+        let mut section = document.section_mut(0).unwrap();
+        section.set_title(text("Second"));
+        section.push_block(BlockNode::Paragraph(text("x")));
+        section
+            .insert_block(0, BlockNode::Paragraph(text("w")))
+            .unwrap();
+        assert_eq!(
+            section.remove_block(1).unwrap(),
+            BlockNode::Paragraph(text("x"))
+        );
+        assert_eq!(section.content().len(), 1);
+        assert_eq!(
+            document.remove_block(0).unwrap(),
+            BlockNode::Paragraph(text("A"))
+        );
+    }
+
+    #[test]
+    fn test_spans_of_top_level_units() {
+        let doc =
+            MarkdownParser::parse("Intro\n\n![a](b)\n# One\nBody\n## Sub\n\n# Two\n").unwrap();
+        let spans = doc.spans().unwrap();
+        assert_eq!(spans.blocks, vec![0..6, 7..15]);
+        assert_eq!(spans.sections, vec![15..33, 34..40]);
+        assert_eq!(spans.source_len, 40);
+
+        // Structural edits invalidate the spans; equality ignores them
+        let mut edited = doc.clone();
+        edited.push_block(BlockNode::Paragraph(text("x")));
+        edited.remove_block(2).unwrap();
+        assert_eq!(edited.spans(), None);
+        assert_eq!(edited, doc);
+    }
+
+    #[test]
+    fn test_reparse_only_affected_units() {
+        let source = "Intro\n\n# One\nBody\n\n# Two\nMore\n\n# Three\n";
+        let mut doc = MarkdownParser::parse(source).unwrap().into_owned();
+
+        // Editing "More" re-parses "# One" (its extent may change) and "# Two"
+        let edited = source.replace("More", "Mo*r*e");
+        let edit = TextEdit::new(25..29, "Mo*r*e");
+        let reparsed = MarkdownParser::reparse(&mut doc, &edited, &edit).unwrap();
+        assert_eq!(reparsed, 6..33);
+        assert_eq!(doc, MarkdownParser::parse(&edited).unwrap());
+
+        // Demoting a heading nests it under the one before; "# Three" is reused
+        let demoted = edited.replace("# Two", "## Two");
+        let reparsed =
+            MarkdownParser::reparse(&mut doc, &demoted, &TextEdit::new(19..19, "#")).unwrap();
+        assert_eq!(reparsed, 6..34);
+        assert_eq!(doc.sections().len(), 2);
+        assert_eq!(doc, MarkdownParser::parse(&demoted).unwrap());
+        assert_eq!(
+            doc.spans(),
+            MarkdownParser::parse(&demoted).unwrap().spans()
+        );
+    }
+
+    #[test]
+    fn test_reparse_error_leaves_document_unchanged() {
+        let mut doc = MarkdownParser::parse("A\n\nB\n").unwrap().into_owned();
+        let before = doc.clone();
+        assert_eq!(
+            MarkdownParser::reparse(&mut doc, "A\n\n**B\n", &TextEdit::new(3..3, "**")),
+            Err(ParseError::UnclosedDelimiter {
+                delimiter: "**".to_string(),
+                position: 3,
+            })
+        );
+        assert_eq!(doc, before);
+
+        // Without spans the whole text is parsed
+        let mut built = Document::new();
+        let reparsed =
+            MarkdownParser::reparse(&mut built, "# New", &TextEdit::new(0..0, "# New")).unwrap();
+        assert_eq!(reparsed, 0..5);
+        assert_eq!(built.sections().len(), 1);
+    }
+
+    #[test]
+    fn test_parse_reader() {
+        let source = "Intro\n# One\n![multi\n\nline](a.png)\n## Two\n*x*\n```\na\n\n# b\n```";
+        let doc = MarkdownParser::parse_reader(source.as_bytes()).unwrap();
+        assert_eq!(doc, MarkdownParser::parse(source).unwrap());
+        assert_eq!(doc.spans(), MarkdownParser::parse(source).unwrap().spans());
+
+        // Error positions are offsets into the whole stream
+        assert_eq!(
+            MarkdownParser::parse_reader("# A\n\nok\n\n[bad\n".as_bytes()),
+            Err(ParseError::MalformedLink { position: 9 })
+        );
+
+        // Invalid UTF-8 surfaces as an IO error
+        assert!(matches!(
+            MarkdownParser::parse_reader(&b"ok\n\xff\n"[..]),
+            Err(ParseError::IoError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_event_stream() {
+        let events: Vec<Event> = MarkdownParser::events("Hi\n# A\n## B\n[*x*](u)\n# C")
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            events,
+            vec![
+                Event::Start(Tag::Paragraph),
+                Event::Text("Hi".into()),
+                Event::End(TagEnd::Paragraph),
+                Event::Start(Tag::Section(1)),
+                Event::Start(Tag::Heading(1)),
+                Event::Text("A".into()),
+                Event::End(TagEnd::Heading(1)),
+                Event::Start(Tag::Section(2)),
+                Event::Start(Tag::Heading(2)),
+                Event::Text("B".into()),
+                Event::End(TagEnd::Heading(2)),
+                Event::Start(Tag::Paragraph),
+                Event::Start(Tag::Link("u".into())),
+                Event::Start(Tag::Italic),
+                Event::Text("x".into()),
+                Event::End(TagEnd::Italic),
+                Event::End(TagEnd::Link),
+                Event::End(TagEnd::Paragraph),
+                Event::End(TagEnd::Section(2)),
+                Event::End(TagEnd::Section(1)),
+                Event::Start(Tag::Section(1)),
+                Event::Start(Tag::Heading(1)),
+                Event::Text("C".into()),
+                Event::End(TagEnd::Heading(1)),
+                Event::End(TagEnd::Section(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_event_stream_ends_after_error() {
+        let mut events = MarkdownParser::events("ok\n\n**open\n\nnever");
+        assert_eq!(events.next(), Some(Ok(Event::Start(Tag::Paragraph))));
+        assert_eq!(events.next(), Some(Ok(Event::Text("ok".into()))));
+        assert_eq!(events.next(), Some(Ok(Event::End(TagEnd::Paragraph))));
+        assert_eq!(
+            events.next(),
+            Some(Err(ParseError::UnclosedDelimiter {
+                delimiter: "**".to_string(),
+                position: 4,
+            }))
+        );
+        assert_eq!(events.next(), None);
+    }
+
+    #[test]
+    fn test_word_count_from_events() {
+        let words: usize = MarkdownParser::events("# Two words\nand **three more** [here](u)")
+            .filter_map(|event| match event {
+                Ok(Event::Text(text)) => Some(text.split_whitespace().count()),
+                _ => None,
+            })
+            .sum();
+        assert_eq!(words, 6);
+    }
 }
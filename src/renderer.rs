@@ -0,0 +1,581 @@
+//! Output-format-independent rendering of a `Document`
+//!
+//! A `Renderer` is a set of per-node hooks (`start`/`end` of a container,
+//! `text`, `line_break`, `image`, `code_block`) plus structural methods
+//! that walk the tree and call them. Hooks write straight into any
+//! `fmt::Write` (a `String`, or an `io::Write` behind an adapter), so
+//! nothing is built per node. A backend implements the hooks for its
+//! format; the walk defaults come from the matching `walk_*` functions, so
+//! a backend only overrides structure when its format needs a different
+//! order. The same hooks render an event stream through `render_events`.
+//! `begin` and `finish` bracket both, for preambles and per-document state.
+//!
+//! The writers' `write_*` methods are thin wrappers over `render_to_string`,
+//! `render_to_io` and `render_events_to_string`. Those whose only failures
+//! are parse and write errors share `RenderError`.
+//!
+//! To customise one node type of an existing backend, wrap it and forward
+//! every hook except the one to change (see `Renderer`).
+
+use crate::parser::{BlockNode, Document, Event, InlineNode, ParseError, Section, Tag, TagEnd};
+use std::borrow::Cow;
+use std::fmt;
+use std::io;
+
+// ============================================================================
+// ERRORS
+// ============================================================================
+
+/// Error of the writers whose hooks only fail when the output does (text,
+/// terminal, Gemtext, Gopher, man and LaTeX)
+#[derive(Debug, Clone, PartialEq)]
+pub enum RenderError {
+    /// The event stream being rendered ended in a parse error
+    ParseError(ParseError),
+    /// The output writer failed
+    WriteError(String),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::ParseError(error) => write!(f, "Parse error: {}", error),
+            RenderError::WriteError(message) => write!(f, "Write error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+impl From<ParseError> for RenderError {
+    fn from(error: ParseError) -> Self {
+        RenderError::ParseError(error)
+    }
+}
+
+impl From<fmt::Error> for RenderError {
+    fn from(_: fmt::Error) -> Self {
+        RenderError::WriteError("formatter error".to_string())
+    }
+}
+
+impl From<io::Error> for RenderError {
+    fn from(error: io::Error) -> Self {
+        RenderError::WriteError(error.to_string())
+    }
+}
+
+// ============================================================================
+// RENDERER TRAIT
+// ============================================================================
+
+/// A rendering backend: per-node output hooks and a tree walk over them
+///
+/// The walk calls the hooks in event order (`start(Section)`,
+/// `start(Heading)`, title, `end(Heading)`, blocks, subsections,
+/// `end(Section)`), so a renderer produces the same output from
+/// `render_document` and from `render_events` on the same source.
+///
+/// # Example
+/// ```
+/// use wtf::html_writer::{HtmlError, HtmlWriter};
+/// use wtf::parser::{MarkdownParser, Tag, TagEnd};
+/// use wtf::renderer::Renderer;
+/// use std::fmt::Write;
+///
+/// /// HTML with every image wrapped in a `<figure>`
+/// struct Figures(HtmlWriter);
+///
+/// impl Renderer for Figures {
+///     type Error = HtmlError;
+///
+///     fn begin(&self, out: &mut dyn Write) -> Result<(), HtmlError> {
+///         self.0.begin(out)
+///     }
+///     fn start(&self, out: &mut dyn Write, tag: &Tag) -> Result<(), HtmlError> {
+///         self.0.start(out, tag)
+///     }
+///     fn end(&self, out: &mut dyn Write, tag: TagEnd) -> Result<(), HtmlError> {
+///         self.0.end(out, tag)
+///     }
+///     fn text(&self, out: &mut dyn Write, text: &str) -> Result<(), HtmlError> {
+///         self.0.text(out, text)
+///     }
+///     fn line_break(&self, out: &mut dyn Write) -> Result<(), HtmlError> {
+///         self.0.line_break(out)
+///     }
+///     fn image(&self, out: &mut dyn Write, alt_text: &str, url: &str) -> Result<(), HtmlError> {
+///         out.write_str("<figure>")?;
+///         self.0.image(out, alt_text, url)?;
+///         out.write_str("</figure>")?;
+///         Ok(())
+///     }
+///     fn code_block(&self, out: &mut dyn Write, info: &str, code: &str) -> Result<(), HtmlError> {
+///         self.0.code_block(out, info, code)
+///     }
+/// }
+///
+/// let doc = MarkdownParser::parse("![cat](cat.png)").unwrap();
+/// let mut html = String::new();
+/// Figures(HtmlWriter::new()).render_document(&mut html, &doc).unwrap();
+/// assert_eq!(html, "<figure><img src=\"cat.png\" alt=\"cat\"></figure>");
+/// ```
+pub trait Renderer {
+    type Error;
+
+    // ========================================================================
+    // STRUCTURE (overridable, default to the walk_* functions)
+    // ========================================================================
+
+    fn render_document(
+        &self,
+        out: &mut dyn fmt::Write,
+        document: &Document,
+    ) -> Result<(), Self::Error> {
+        walk_document(self, out, document)
+    }
+
+    fn render_section(
+        &self,
+        out: &mut dyn fmt::Write,
+        section: &Section,
+    ) -> Result<(), Self::Error> {
+        walk_section(self, out, section)
+    }
+
+    fn render_block(&self, out: &mut dyn fmt::Write, block: &BlockNode) -> Result<(), Self::Error> {
+        walk_block(self, out, block)
+    }
+
+    fn render_inline(
+        &self,
+        out: &mut dyn fmt::Write,
+        node: &InlineNode,
+    ) -> Result<(), Self::Error> {
+        walk_inline(self, out, node)
+    }
+
+    // ========================================================================
+    // DOCUMENT HOOKS (optional)
+    // ========================================================================
+
+    /// Called before a document or event stream is rendered, to reset
+    /// per-document state and write any preamble
+    fn begin(&self, _out: &mut dyn fmt::Write) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Called after a document or event stream is rendered, to write any
+    /// closing lines
+    fn finish(&self, _out: &mut dyn fmt::Write) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    // ========================================================================
+    // NODE HOOKS (required)
+    // ========================================================================
+
+    /// Opens a container: section, heading, paragraph, bold, italic or link
+    fn start(&self, out: &mut dyn fmt::Write, tag: &Tag) -> Result<(), Self::Error>;
+
+    /// Closes the container opened by the matching `start`
+    fn end(&self, out: &mut dyn fmt::Write, tag: TagEnd) -> Result<(), Self::Error>;
+
+    /// Writes a run of plain text (unescaped source text)
+    fn text(&self, out: &mut dyn fmt::Write, text: &str) -> Result<(), Self::Error>;
+
+    /// Writes a line break within a paragraph
+    fn line_break(&self, out: &mut dyn fmt::Write) -> Result<(), Self::Error>;
+
+    /// Writes an image block
+    fn image(&self, out: &mut dyn fmt::Write, alt_text: &str, url: &str)
+    -> Result<(), Self::Error>;
+
+    /// Writes a fenced code block (`info` is the text after the opening
+    /// fence, `code` the unescaped lines inside it)
+    fn code_block(
+        &self,
+        out: &mut dyn fmt::Write,
+        info: &str,
+        code: &str,
+    ) -> Result<(), Self::Error>;
+}
+
+// ============================================================================
+// TREE WALK
+// ============================================================================
+
+/// Renders the preamble blocks, then every top-level section, between
+/// `begin` and `finish`
+pub fn walk_document<R: Renderer + ?Sized>(
+    renderer: &R,
+    out: &mut dyn fmt::Write,
+    document: &Document,
+) -> Result<(), R::Error> {
+    renderer.begin(out)?;
+
+    for block in document.content() {
+        renderer.render_block(out, block)?;
+    }
+
+    for section in document.sections() {
+        renderer.render_section(out, section)?;
+    }
+
+    renderer.finish(out)
+}
+
+/// Renders a section: the heading, the content blocks, then every
+/// subsection, inside `start`/`end(Section)`
+pub fn walk_section<R: Renderer + ?Sized>(
+    renderer: &R,
+    out: &mut dyn fmt::Write,
+    section: &Section,
+) -> Result<(), R::Error> {
+    let level = section.level();
+    renderer.start(out, &Tag::Section(level))?;
+
+    renderer.start(out, &Tag::Heading(level))?;
+    render_inlines(renderer, out, section.title())?;
+    renderer.end(out, TagEnd::Heading(level))?;
+
+    for block in section.content() {
+        renderer.render_block(out, block)?;
+    }
+
+    for subsection in section.subsections() {
+        renderer.render_section(out, subsection)?;
+    }
+
+    renderer.end(out, TagEnd::Section(level))
+}
+
+/// Renders a paragraph inside `start`/`end(Paragraph)`, or an image or
+/// code block
+pub fn walk_block<R: Renderer + ?Sized>(
+    renderer: &R,
+    out: &mut dyn fmt::Write,
+    block: &BlockNode,
+) -> Result<(), R::Error> {
+    match block {
+        BlockNode::Paragraph(inlines) => {
+            renderer.start(out, &Tag::Paragraph)?;
+            render_inlines(renderer, out, inlines)?;
+            renderer.end(out, TagEnd::Paragraph)
+        }
+        BlockNode::Image { alt_text, url } => renderer.image(out, alt_text, url),
+        BlockNode::CodeBlock { info, code } => renderer.code_block(out, info, code),
+    }
+}
+
+/// Renders a leaf, or a container's children inside its `start`/`end`
+pub fn walk_inline<R: Renderer + ?Sized>(
+    renderer: &R,
+    out: &mut dyn fmt::Write,
+    node: &InlineNode,
+) -> Result<(), R::Error> {
+    let (tag, end, children) = match node {
+        InlineNode::Text(text) => return renderer.text(out, text),
+        InlineNode::LineBreak => return renderer.line_break(out),
+        InlineNode::Bold(children) => (Tag::Bold, TagEnd::Bold, children),
+        InlineNode::Italic(children) => (Tag::Italic, TagEnd::Italic, children),
+        InlineNode::Link { text, url } => {
+            (Tag::Link(Cow::Borrowed(url.as_ref())), TagEnd::Link, text)
+        }
+    };
+
+    renderer.start(out, &tag)?;
+    render_inlines(renderer, out, children)?;
+    renderer.end(out, end)
+}
+
+fn render_inlines<R: Renderer + ?Sized>(
+    renderer: &R,
+    out: &mut dyn fmt::Write,
+    nodes: &[InlineNode],
+) -> Result<(), R::Error> {
+    for node in nodes {
+        renderer.render_inline(out, node)?;
+    }
+    Ok(())
+}
+
+// ============================================================================
+// EVENT STREAMS
+// ============================================================================
+
+/// Renders an event stream (e.g. `MarkdownParser::events`) through a
+/// renderer's node hooks, without building a tree
+///
+/// Structural overrides (`render_section`, ...) are not consulted, since
+/// the stream has no tree to pass them. `begin` and `finish` are.
+///
+/// # Returns
+/// * `Err(R::Error)` - For the first parse error in the stream or hook error
+pub fn render_events<'a, R, I>(
+    renderer: &R,
+    out: &mut dyn fmt::Write,
+    events: I,
+) -> Result<(), R::Error>
+where
+    R: Renderer + ?Sized,
+    R::Error: From<ParseError>,
+    I: IntoIterator<Item = Result<Event<'a>, ParseError>>,
+{
+    renderer.begin(out)?;
+    for event in events {
+        match event? {
+            Event::Start(tag) => renderer.start(out, &tag)?,
+            Event::End(tag) => renderer.end(out, tag)?,
+            Event::Text(text) => renderer.text(out, &text)?,
+            Event::LineBreak => renderer.line_break(out)?,
+            Event::Image { alt_text, url } => renderer.image(out, &alt_text, &url)?,
+            Event::CodeBlock { info, code } => renderer.code_block(out, &info, &code)?,
+        }
+    }
+    renderer.finish(out)
+}
+
+// ============================================================================
+// OUTPUT HELPERS
+// ============================================================================
+
+/// Renders a document into a new string
+///
+/// # Returns
+/// * `Ok(String)` - The rendered document
+/// * `Err(R::Error)` - If a hook fails
+pub fn render_to_string<R: Renderer + ?Sized>(
+    renderer: &R,
+    document: &Document,
+) -> Result<String, R::Error> {
+    let mut output = String::new();
+    renderer.render_document(&mut output, document)?;
+    Ok(output)
+}
+
+/// Streams a document into a writer as it is rendered
+///
+/// Nothing is buffered here, so pass a buffered writer (`BufWriter`, a
+/// locked stdout, a `Vec<u8>`) rather than a raw file or socket.
+///
+/// # Returns
+/// * `Err(R::Error)` - If a hook fails or the writer returns an error
+pub fn render_to_io<R, W>(renderer: &R, document: &Document, writer: W) -> Result<(), R::Error>
+where
+    R: Renderer + ?Sized,
+    R::Error: From<io::Error>,
+    W: io::Write,
+{
+    let mut adapter = IoAdapter::new(writer);
+    renderer
+        .render_document(&mut adapter, document)
+        .map_err(|e| adapter.take_error().map_or(e, R::Error::from))
+}
+
+/// Renders an event stream (e.g. `MarkdownParser::events`) into a new
+/// string
+///
+/// # Returns
+/// * `Ok(String)` - The same output as `render_to_string` on the events' tree
+/// * `Err(R::Error)` - For the first parse error in the stream or hook error
+pub fn render_events_to_string<'a, R, I>(renderer: &R, events: I) -> Result<String, R::Error>
+where
+    R: Renderer + ?Sized,
+    R::Error: From<ParseError>,
+    I: IntoIterator<Item = Result<Event<'a>, ParseError>>,
+{
+    let mut output = String::new();
+    render_events(renderer, &mut output, events)?;
+    Ok(output)
+}
+
+// ============================================================================
+// I/O ADAPTER
+// ============================================================================
+
+/// Adapts an `io::Write` to the `fmt::Write` the renderer hooks expect,
+/// keeping the underlying I/O error that `fmt::Error` can't carry
+struct IoAdapter<W> {
+    inner: W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> IoAdapter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, error: None }
+    }
+
+    /// The I/O error behind the last `fmt::Error`, if a write failed
+    fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+}
+
+impl<W: io::Write> fmt::Write for IoAdapter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+/// Source covering every node type, for `assert_events_render_like_tree`
+#[cfg(test)]
+pub(crate) const EVENTS_FIXTURE: &str =
+    "Intro **b** *i* [l](u)\n# One\ntext\n## Two\n![a](b)\n```sh\nls $HOME\n```\n# Three";
+
+/// Checks that a renderer gives the same output for the events of a
+/// source as for its tree, and fails on a stream ending in a parse error
+#[cfg(test)]
+pub(crate) fn assert_events_render_like_tree<R>(renderer: &R, source: &str)
+where
+    R: Renderer,
+    R::Error: From<ParseError> + fmt::Debug + PartialEq,
+{
+    use crate::parser::MarkdownParser;
+
+    let document = MarkdownParser::parse(source).unwrap();
+    assert_eq!(
+        render_events_to_string(renderer, MarkdownParser::events(source)),
+        render_to_string(renderer, &document)
+    );
+    assert!(render_events_to_string(renderer, MarkdownParser::events("*open")).is_err());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html_writer::HtmlError;
+    use crate::parser::MarkdownParser;
+
+    /// Writes the hook calls in a compact bracket notation
+    struct Outline;
+
+    impl Renderer for Outline {
+        type Error = HtmlError;
+
+        fn start(&self, out: &mut dyn fmt::Write, tag: &Tag) -> Result<(), HtmlError> {
+            match tag {
+                Tag::Section(level) => write!(out, "[s{}", level)?,
+                Tag::Heading(level) => write!(out, "[h{}", level)?,
+                Tag::Paragraph => out.write_str("[p")?,
+                Tag::Bold => out.write_str("[b")?,
+                Tag::Italic => out.write_str("[i")?,
+                Tag::Link(url) => write!(out, "[a={}", url)?,
+            }
+            Ok(())
+        }
+
+        fn end(&self, out: &mut dyn fmt::Write, _tag: TagEnd) -> Result<(), HtmlError> {
+            Ok(out.write_char(']')?)
+        }
+
+        fn text(&self, out: &mut dyn fmt::Write, text: &str) -> Result<(), HtmlError> {
+            Ok(write!(out, " {:?}", text)?)
+        }
+
+        fn line_break(&self, out: &mut dyn fmt::Write) -> Result<(), HtmlError> {
+            Ok(out.write_str(" /")?)
+        }
+
+        fn image(
+            &self,
+            out: &mut dyn fmt::Write,
+            alt_text: &str,
+            url: &str,
+        ) -> Result<(), HtmlError> {
+            Ok(write!(out, "[img {} {}]", alt_text, url)?)
+        }
+
+        fn code_block(
+            &self,
+            out: &mut dyn fmt::Write,
+            info: &str,
+            code: &str,
+        ) -> Result<(), HtmlError> {
+            Ok(write!(out, "[code {} {:?}]", info, code)?)
+        }
+    }
+
+    /// Skips sections below level 2 by overriding the structural walk
+    struct ShallowOutline;
+
+    impl Renderer for ShallowOutline {
+        type Error = HtmlError;
+
+        fn render_section(
+            &self,
+            out: &mut dyn fmt::Write,
+            section: &Section,
+        ) -> Result<(), HtmlError> {
+            if section.level() > 2 {
+                return Ok(());
+            }
+            walk_section(self, out, section)
+        }
+
+        fn start(&self, out: &mut dyn fmt::Write, tag: &Tag) -> Result<(), HtmlError> {
+            Outline.start(out, tag)
+        }
+
+        fn end(&self, out: &mut dyn fmt::Write, tag: TagEnd) -> Result<(), HtmlError> {
+            Outline.end(out, tag)
+        }
+
+        fn text(&self, out: &mut dyn fmt::Write, text: &str) -> Result<(), HtmlError> {
+            Outline.text(out, text)
+        }
+
+        fn line_break(&self, out: &mut dyn fmt::Write) -> Result<(), HtmlError> {
+            Outline.line_break(out)
+        }
+
+        fn image(
+            &self,
+            out: &mut dyn fmt::Write,
+            alt_text: &str,
+            url: &str,
+        ) -> Result<(), HtmlError> {
+            Outline.image(out, alt_text, url)
+        }
+
+        fn code_block(
+            &self,
+            out: &mut dyn fmt::Write,
+            info: &str,
+            code: &str,
+        ) -> Result<(), HtmlError> {
+            Outline.code_block(out, info, code)
+        }
+    }
+
+    #[test]
+    fn test_walk_calls_hooks_in_order() {
+        let doc = MarkdownParser::parse("Hi\nthere\n\n# A *b*\n![x](y)\n## [c](d)").unwrap();
+        let mut out = String::new();
+        Outline.render_document(&mut out, &doc).unwrap();
+        assert_eq!(
+            out,
+            "[p \"Hi\" / \"there\"][s1[h1 \"A \"[i \"b\"]][img x y][s2[h2[a=d \"c\"]]]]"
+        );
+    }
+
+    #[test]
+    fn test_events_match_tree_walk() {
+        assert_events_render_like_tree(&Outline, EVENTS_FIXTURE);
+    }
+
+    #[test]
+    fn test_structural_override() {
+        let doc = MarkdownParser::parse("# A\n## B\n### C\ndeep\n## D").unwrap();
+        let mut out = String::new();
+        ShallowOutline.render_document(&mut out, &doc).unwrap();
+        assert_eq!(out, "[s1[h1 \"A\"][s2[h2 \"B\"]][s2[h2 \"D\"]]]");
+    }
+}
@@ -10,6 +10,9 @@ const PATH_SEPARATOR: char = '/';
 const PARENT_DIR: &str = "..";
 const CURRENT_DIR: &str = ".";
 const ROOT_URL_PATH: &str = "/";
+const FRAGMENT_SEPARATOR: char = '#';
+const QUERY_SEPARATOR: char = '?';
+const SCHEME_SEPARATOR: char = ':';
 
 // HTML constants for footer generation
 const HR_DOUBLE: &str = "<hr><hr>";
@@ -20,6 +23,9 @@ const LI_CLOSE: &str = "</li>";
 const A_HREF_OPEN: &str = "<a href=\"";
 const A_HREF_MIDDLE: &str = "\">";
 const A_CLOSE: &str = "</a>";
+const BREADCRUMBS_OPEN: &str = "<ol class=\"breadcrumbs\">";
+const BREADCRUMBS_CLOSE: &str = "</ol>";
+const CURRENT_CRUMB_OPEN: &str = "<li aria-current=\"page\">";
 
 // ============================================================================
 // ERROR HANDLING
@@ -111,6 +117,7 @@ impl ResolvedPath {
 /// - `/home/about` → `<content_root>/home/about.md` OR `<content_root>/home/about/about.md`
 ///
 /// Security: Validates paths to prevent directory traversal attacks
+#[derive(Debug, Clone)]
 pub struct Router {
     content_root: PathBuf,
 }
@@ -268,6 +275,59 @@ impl Router {
         &self.content_root
     }
 
+    /// Finds the canonical route URL that serves a markdown file
+    ///
+    /// The inverse of `resolve_path`: `root.md` → `/`, `home/home.md` →
+    /// `/home`, `home/about.md` → `/home/about`.
+    ///
+    /// # Arguments
+    /// * `file` - Path to a markdown file (absolute, or relative to the
+    ///   working directory)
+    ///
+    /// # Returns
+    /// * `Some(String)` - The route whose request renders this file
+    /// * `None` - If the file doesn't exist, is outside the content root,
+    ///   isn't a `.md` file, or no route serves it (a file shadowed by a
+    ///   directory index)
+    pub fn route_for_file(&self, file: &Path) -> Option<String> {
+        let file = file.canonicalize().ok()?;
+        let relative = file.strip_prefix(&self.content_root).ok()?;
+
+        let mut components = relative
+            .iter()
+            .map(|component| component.to_str())
+            .collect::<Option<Vec<&str>>>()?;
+        let file_name = components.pop()?;
+        let name = file_name.strip_suffix(MD_EXTENSION)?;
+
+        let route = if components.is_empty() && file_name == ROOT_FILENAME {
+            ROOT_URL_PATH.to_string()
+        } else {
+            // A directory's index file is served at the directory's route
+            if components.last() != Some(&name) {
+                components.push(name);
+            }
+            format!("{}{}", PATH_SEPARATOR, components.join("/"))
+        };
+
+        // Only a route that resolves back to this file serves it
+        match self.resolve_path(&route).ok()? {
+            ResolvedPath::Found(found) if found == file => Some(route),
+            _ => None,
+        }
+    }
+
+    /// Creates a rewriter for the links on one page
+    ///
+    /// # Arguments
+    /// * `page` - Path to the markdown file the links appear in
+    pub fn link_rewriter(&self, page: &Path) -> LinkRewriter {
+        LinkRewriter {
+            router: self.clone(),
+            page_dir: page.parent().map(Path::to_path_buf).unwrap_or_default(),
+        }
+    }
+
     /// Builds a hierarchical sitemap by scanning the content directory
     ///
     /// The sitemap includes:
@@ -287,7 +347,7 @@ impl Router {
             entries.push(SitemapEntry::new(
                 ROOT_FILENAME
                     .strip_suffix(MD_EXTENSION)
-                    .unwrap_or(&ROOT_FILENAME)
+                    .unwrap_or(ROOT_FILENAME)
                     .to_string(),
                 ROOT_URL_PATH.to_string(),
             ));
@@ -373,7 +433,7 @@ impl Router {
         // Get the last component (directory name)
         let last_component = sanitized_path
             .split(PATH_SEPARATOR)
-            .last()
+            .next_back()
             .unwrap_or(sanitized_path);
 
         // Build path: parent_dirs/last_component/last_component.md
@@ -419,10 +479,8 @@ impl Router {
                 }
 
                 // Skip the directory's index file (e.g., skip "home.md" when scanning inside "home/" directory)
-                if let Some(idx_filename) = index_filename {
-                    if file_name_str == idx_filename {
-                        continue;
-                    }
+                if index_filename.is_some_and(|idx_filename| file_name_str == idx_filename) {
+                    continue;
                 }
 
                 // Extract name without extension
@@ -490,6 +548,108 @@ impl Router {
     }
 }
 
+// ============================================================================
+// LINK REWRITING
+// ============================================================================
+
+/// Maps links to markdown files on a page to the routes that serve them,
+/// and other local links to the files they name
+///
+/// Authors link files (`[team](team.md)`, `[about](../about/about.md)`),
+/// but routes have no extension, so those links would 404 as written.
+/// Created by `Router::link_rewriter`.
+#[derive(Debug, Clone)]
+pub struct LinkRewriter {
+    router: Router,
+    /// Directory of the page, which relative links are resolved against
+    page_dir: PathBuf,
+}
+
+impl LinkRewriter {
+    /// Rewrites a link URL to a route URL, if it links a routable `.md` file
+    ///
+    /// Relative links resolve against the page's directory and links
+    /// starting with `/` against the content root. A query or fragment is
+    /// kept (`team.md#contact` → `/team#contact`).
+    ///
+    /// # Returns
+    /// * `Some(String)` - The route URL to link instead
+    /// * `None` - If the link should be left as written (not a `.md` file,
+    ///   has a scheme, or no route serves the file)
+    pub fn rewrite(&self, url: &str) -> Option<String> {
+        let (path, suffix) = split_url(url);
+        if !path.ends_with(MD_EXTENSION) {
+            return None;
+        }
+
+        let route = self.router.route_for_file(&self.resolve(path)?)?;
+        Some(format!("{}{}", route, suffix))
+    }
+
+    /// Finds the file under the content root that a URL refers to
+    ///
+    /// Resolved like `rewrite`, for links to files that are not pages
+    /// (e.g. an image's `src`).
+    ///
+    /// # Returns
+    /// * `Some(PathBuf)` - Canonical path of an existing file under the
+    ///   content root
+    /// * `None` - If the URL has a scheme or doesn't name such a file
+    pub fn local_file(&self, url: &str) -> Option<PathBuf> {
+        let (path, _) = split_url(url);
+        let file = self.resolve(path)?.canonicalize().ok()?;
+        (file.starts_with(&self.router.content_root) && file.is_file()).then_some(file)
+    }
+
+    /// Rewrites a link to a local file as a URL from the site root
+    ///
+    /// Pages are served below their own route (`guide/intro.md` at
+    /// `/guide/intro/` in a static build), so a relative `pic.png` would
+    /// resolve against the wrong directory; `/guide/pic.png` doesn't.
+    ///
+    /// # Returns
+    /// * `Some(String)` - The root-relative URL, with any query or fragment
+    /// * `None` - If the URL doesn't name a file under the content root
+    pub fn rewrite_file(&self, url: &str) -> Option<String> {
+        let (_, suffix) = split_url(url);
+        let file = self.local_file(url)?;
+        let relative = file.strip_prefix(&self.router.content_root).ok()?;
+        let components: Vec<_> = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect();
+        Some(format!(
+            "{}{}{}",
+            PATH_SEPARATOR,
+            components.join("/"),
+            suffix
+        ))
+    }
+
+    /// Joins a URL path onto the page directory (or the content root, for
+    /// `/`-absolute paths), without checking the result exists
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        if path.contains(SCHEME_SEPARATOR) {
+            return None;
+        }
+
+        match path.strip_prefix(PATH_SEPARATOR) {
+            // Protocol-relative (`//host/...`) links point at another site
+            Some(rest) if rest.starts_with(PATH_SEPARATOR) => None,
+            Some(rest) => Some(self.router.content_root.join(rest)),
+            None => Some(self.page_dir.join(path)),
+        }
+    }
+}
+
+/// Splits a URL into its path and its query/fragment suffix
+fn split_url(url: &str) -> (&str, &str) {
+    let path_end = url
+        .find([QUERY_SEPARATOR, FRAGMENT_SEPARATOR])
+        .unwrap_or(url.len());
+    url.split_at(path_end)
+}
+
 // ============================================================================
 // SITEMAP FOOTER GENERATION
 // ============================================================================
@@ -525,13 +685,104 @@ pub fn generate_sitemap_footer(entries: &[SitemapEntry], current_path: Option<&s
     output.push_str(HR_DOUBLE);
 
     // Generate nested sitemap list
-    if !entries.is_empty() {
-        output.push_str(&render_sitemap_list(entries, current_path));
+    output.push_str(&generate_sitemap_list(entries, current_path));
+
+    output
+}
+
+/// Generates the nested sitemap list of the footer, without its separator
+///
+/// # Arguments
+/// * `entries` - Slice of top-level sitemap entries
+/// * `current_path` - Optional current URL path to highlight with "you're here"
+///
+/// # Returns
+/// Nested `<ul><li>` structure with links, or an empty string if there are
+/// no entries
+pub fn generate_sitemap_list(entries: &[SitemapEntry], current_path: Option<&str>) -> String {
+    if entries.is_empty() {
+        return String::new();
     }
+    render_sitemap_list(entries, current_path)
+}
+
+// ============================================================================
+// BREADCRUMB GENERATION
+// ============================================================================
+
+/// Generates a breadcrumb trail from the site root down to a page
+///
+/// Every route on the way to `current_path` (`/`, `/home`, `/home/about`)
+/// that has a sitemap entry becomes a crumb, named as in the sitemap. The
+/// page itself ends the trail, unlinked and marked `aria-current`.
+///
+/// # Arguments
+/// * `entries` - Slice of top-level sitemap entries
+/// * `current_path` - URL path of the page
+///
+/// # Returns
+/// An `<ol class="breadcrumbs">` list, or an empty string if no route on
+/// the way has a sitemap entry
+///
+/// # Example Output
+/// ```html
+/// <ol class="breadcrumbs">
+///   <li><a href="/">root</a></li>
+///   <li><a href="/home">home</a></li>
+///   <li aria-current="page">about</li>
+/// </ol>
+/// ```
+pub fn generate_breadcrumbs(entries: &[SitemapEntry], current_path: &str) -> String {
+    let mut routes = vec![ROOT_URL_PATH.to_string()];
+    let mut route = String::new();
+    for component in current_path
+        .split(PATH_SEPARATOR)
+        .filter(|component| !component.is_empty())
+    {
+        route.push(PATH_SEPARATOR);
+        route.push_str(component);
+        routes.push(route.clone());
+    }
+
+    let crumbs: Vec<&SitemapEntry> = routes
+        .iter()
+        .filter_map(|route| find_sitemap_entry(entries, route))
+        .collect();
+    if crumbs.is_empty() {
+        return String::new();
+    }
+
+    let mut output = String::from(BREADCRUMBS_OPEN);
+    for entry in crumbs {
+        if entry.url_path == current_path {
+            output.push_str(CURRENT_CRUMB_OPEN);
+            output.push_str(&escape_html_text(&entry.name));
+        } else {
+            output.push_str(LI_OPEN);
+            output.push_str(A_HREF_OPEN);
+            output.push_str(&escape_html_attr(&entry.url_path));
+            output.push_str(A_HREF_MIDDLE);
+            output.push_str(&escape_html_text(&entry.name));
+            output.push_str(A_CLOSE);
+        }
+        output.push_str(LI_CLOSE);
+    }
+    output.push_str(BREADCRUMBS_CLOSE);
 
     output
 }
 
+/// Finds the sitemap entry for a route, at any depth
+fn find_sitemap_entry<'a>(entries: &'a [SitemapEntry], url_path: &str) -> Option<&'a SitemapEntry> {
+    entries.iter().find_map(|entry| {
+        if entry.url_path == url_path {
+            Some(entry)
+        } else {
+            find_sitemap_entry(&entry.children, url_path)
+        }
+    })
+}
+
 /// Recursively renders a nested sitemap list as HTML
 ///
 /// # Arguments
@@ -554,7 +805,7 @@ fn render_sitemap_list(entries: &[SitemapEntry], current_path: Option<&str>) ->
         output.push_str(A_HREF_MIDDLE);
 
         // Check if this is the current page
-        let is_current = current_path.map_or(false, |path| path == entry.url_path);
+        let is_current = current_path == Some(entry.url_path.as_str());
 
         if is_current {
             output.push_str("<b>");
@@ -892,6 +1143,43 @@ mod tests {
         assert_eq!(html, "<hr><hr>");
     }
 
+    #[test]
+    fn test_generate_sitemap_list() {
+        let entries = vec![SitemapEntry::new("home".to_string(), "/home".to_string())];
+        assert_eq!(
+            generate_sitemap_list(&entries, None),
+            "<ul><li><a href=\"/home\">home</a></li></ul>"
+        );
+        assert_eq!(generate_sitemap_list(&[], None), "");
+    }
+
+    #[test]
+    fn test_generate_breadcrumbs() {
+        let mut home_entry = SitemapEntry::new("home".to_string(), "/home".to_string());
+        home_entry.children.push(SitemapEntry::new(
+            "a & b".to_string(),
+            "/home/about".to_string(),
+        ));
+        let entries = vec![
+            SitemapEntry::new("root".to_string(), "/".to_string()),
+            home_entry,
+        ];
+
+        assert_eq!(
+            generate_breadcrumbs(&entries, "/home/about"),
+            "<ol class=\"breadcrumbs\"><li><a href=\"/\">root</a></li>\
+             <li><a href=\"/home\">home</a></li>\
+             <li aria-current=\"page\">a &amp; b</li></ol>"
+        );
+        // Routes without entries (e.g. a missing page) are left out
+        assert_eq!(
+            generate_breadcrumbs(&entries, "/home/missing"),
+            "<ol class=\"breadcrumbs\"><li><a href=\"/\">root</a></li>\
+             <li><a href=\"/home\">home</a></li></ol>"
+        );
+        assert_eq!(generate_breadcrumbs(&entries[1..], "/other"), "");
+    }
+
     #[test]
     fn test_generate_sitemap_footer_with_current_path() {
         let entries = vec![
@@ -1041,4 +1329,94 @@ mod tests {
         let result = router.resolve_path("/home/about/me").unwrap();
         assert!(result.is_found());
     }
+
+    #[test]
+    fn test_route_for_file() {
+        let temp_dir = create_test_content_root();
+        let root = temp_dir.path();
+        let router = Router::new(root.to_path_buf()).unwrap();
+
+        let route = |path: &str| router.route_for_file(&root.join(path));
+        assert_eq!(route("root.md"), Some("/".to_string()));
+        assert_eq!(route("home/home.md"), Some("/home".to_string()));
+        assert_eq!(
+            route("home/about/about.md"),
+            Some("/home/about".to_string())
+        );
+        assert_eq!(
+            route("home/about/me.md"),
+            Some("/home/about/me".to_string())
+        );
+
+        // Shadowed by directory indexes, not markdown, or missing
+        assert_eq!(route("home.md"), None);
+        assert_eq!(route("home/about.md"), None);
+        assert_eq!(route("missing.md"), None);
+        assert_eq!(route("home"), None);
+    }
+
+    #[test]
+    fn test_route_for_file_outside_root() {
+        let temp_dir = create_test_content_root();
+        let root = temp_dir.path();
+        let router = Router::new(root.join("home")).unwrap();
+
+        assert_eq!(router.route_for_file(&root.join("root.md")), None);
+        assert_eq!(
+            router.route_for_file(&root.join("home/about/me.md")),
+            Some("/about/me".to_string())
+        );
+    }
+
+    #[test]
+    fn test_link_rewriter() {
+        let temp_dir = create_test_content_root();
+        let root = temp_dir.path();
+        let router = Router::new(root.to_path_buf()).unwrap();
+        let rewriter = router.link_rewriter(&root.join("home/about/me.md"));
+
+        let rewrite = |url: &str| rewriter.rewrite(url);
+        assert_eq!(rewrite("about.md"), Some("/home/about".to_string()));
+        assert_eq!(rewrite("../home.md"), Some("/home".to_string()));
+        assert_eq!(rewrite("../../root.md#top"), Some("/#top".to_string()));
+        assert_eq!(
+            rewrite("./about.md?x=1"),
+            Some("/home/about?x=1".to_string())
+        );
+        assert_eq!(rewrite("/home/home.md"), Some("/home".to_string()));
+
+        // Left as written
+        assert_eq!(rewrite("missing.md"), None);
+        assert_eq!(rewrite("../../../outside.md"), None);
+        assert_eq!(rewrite("https://example.com/readme.md"), None);
+        assert_eq!(rewrite("//example.com/readme.md"), None);
+        assert_eq!(rewrite("/home/about"), None);
+        assert_eq!(rewrite("picture.png"), None);
+        assert_eq!(rewrite("#section"), None);
+    }
+
+    #[test]
+    fn test_link_rewriter_local_file() {
+        let temp_dir = create_test_content_root();
+        let root = temp_dir.path();
+        fs::write(root.join("home/cat.png"), "png").unwrap();
+        let router = Router::new(root.join("home")).unwrap();
+        let rewriter = router.link_rewriter(&root.join("home/home.md"));
+        let cat = root.join("home/cat.png").canonicalize().unwrap();
+
+        assert_eq!(rewriter.local_file("cat.png"), Some(cat.clone()));
+        assert_eq!(rewriter.local_file("/cat.png?v=2"), Some(cat.clone()));
+        assert_eq!(rewriter.local_file("about/../cat.png"), Some(cat));
+        assert_eq!(
+            rewriter.rewrite_file("about/../cat.png#top"),
+            Some("/cat.png#top".to_string())
+        );
+        assert_eq!(rewriter.local_file("missing.png"), None);
+        assert_eq!(rewriter.local_file("about"), None);
+        assert_eq!(rewriter.local_file("https://example.com/cat.png"), None);
+
+        // Exists, but outside the content root
+        assert_eq!(rewriter.local_file("../root.md"), None);
+        assert_eq!(rewriter.rewrite_file("../root.md"), None);
+    }
 }
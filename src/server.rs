@@ -1,23 +1,22 @@
-use crate::html_writer::HtmlWriter;
-use crate::parser::{InlineNode, MarkdownParser};
-use crate::router::{ResolvedPath, Router, RouterError};
+use crate::emoji::{EmojiError, EmojiExpander};
+use crate::front_matter::{FrontMatter, split_front_matter};
+use crate::html_writer::{HtmlConfig, HtmlError, HtmlWriter};
+use crate::include::{IncludeError, IncludeResolver};
+use crate::layout::{LAYOUT_KEY, Layout, LayoutError, LayoutParts, layout_path};
+use crate::parser::{BlockNode, Document, InlineNode, MarkdownParser, ParseError};
+use crate::router::{
+    LinkRewriter, ResolvedPath, Router, RouterError, SitemapEntry, generate_breadcrumbs,
+    generate_sitemap_list,
+};
+use crate::variables::{
+    PAGE_UPDATED, PAGE_URL, SiteConfigError, Variables, format_date, load_site_config,
+};
+use crate::visit::{self, Visitor};
 use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tiny_http::{Response, Server as TinyServer};
 
-// HTML document structure constants
-const DOCTYPE: &str = "<!DOCTYPE html>";
-const HTML_OPEN: &str = "<html>";
-const HTML_CLOSE: &str = "</html>";
-const HEAD_OPEN: &str = "<head>";
-const HEAD_CLOSE: &str = "</head>";
-const META_CHARSET: &str = "<meta charset=\"utf-8\">";
-const TITLE_OPEN: &str = "<title>";
-const TITLE_CLOSE: &str = "</title>";
-const BODY_OPEN: &str = "<body>";
-const BODY_CLOSE: &str = "</body>";
-
 // HTTP status codes
 const HTTP_STATUS_OK: u16 = 200;
 const HTTP_STATUS_NOT_FOUND: u16 = 404;
@@ -47,9 +46,43 @@ const SERVER_ADDRESS_PREFIX: &str = "Listening on";
 
 #[derive(Debug)]
 pub enum ServerError {
-    BindError { address: String, source: String },
-    RouterError { source: RouterError },
-    IoError { path: PathBuf, source: String },
+    BindError {
+        address: String,
+        source: String,
+    },
+    RouterError {
+        source: RouterError,
+    },
+    IoError {
+        path: PathBuf,
+        source: String,
+    },
+    /// Front matter, markdown or `_site.conf` that doesn't parse
+    ParseError {
+        path: PathBuf,
+        source: ParseError,
+    },
+    /// A `{{ name }}` that's undefined or malformed
+    VariableError {
+        path: PathBuf,
+        source: ParseError,
+    },
+    /// The page's HTML couldn't be written
+    RenderError {
+        path: PathBuf,
+        source: HtmlError,
+    },
+    EmojiError {
+        source: EmojiError,
+    },
+    IncludeError {
+        path: PathBuf,
+        source: IncludeError,
+    },
+    LayoutError {
+        path: PathBuf,
+        source: LayoutError,
+    },
 }
 
 impl fmt::Display for ServerError {
@@ -64,6 +97,24 @@ impl fmt::Display for ServerError {
             ServerError::IoError { path, source } => {
                 write!(f, "IO error reading {}: {}", path.display(), source)
             }
+            ServerError::ParseError { path, source } => {
+                write!(f, "Parse error in {}: {}", path.display(), source)
+            }
+            ServerError::VariableError { path, source } => {
+                write!(f, "Variable error in {}: {}", path.display(), source)
+            }
+            ServerError::RenderError { path, source } => {
+                write!(f, "Error rendering {}: {}", path.display(), source)
+            }
+            ServerError::EmojiError { source } => {
+                write!(f, "Emoji error: {}", source)
+            }
+            ServerError::IncludeError { path, source } => {
+                write!(f, "Include error in {}: {}", path.display(), source)
+            }
+            ServerError::LayoutError { path, source } => {
+                write!(f, "Layout error in {}: {}", path.display(), source)
+            }
         }
     }
 }
@@ -76,6 +127,23 @@ impl From<RouterError> for ServerError {
     }
 }
 
+impl From<EmojiError> for ServerError {
+    fn from(err: EmojiError) -> Self {
+        ServerError::EmojiError { source: err }
+    }
+}
+
+impl From<SiteConfigError> for ServerError {
+    fn from(err: SiteConfigError) -> Self {
+        match err {
+            SiteConfigError::IoError { path, source } => ServerError::IoError { path, source },
+            SiteConfigError::ParseError { path, source } => {
+                ServerError::ParseError { path, source }
+            }
+        }
+    }
+}
+
 // ============================================================================
 // SERVER CONFIGURATION
 // ============================================================================
@@ -89,6 +157,10 @@ pub struct ServerConfig {
     pub host: String,
     /// Port to listen on (default: 8080)
     pub port: u16,
+    /// Expand `:shortcode:` emoji in page text (default: false)
+    pub emoji: bool,
+    /// Optional file with custom `shortcode = emoji` mappings
+    pub emoji_map: Option<PathBuf>,
 }
 
 impl ServerConfig {
@@ -104,6 +176,8 @@ impl ServerConfig {
             content_root,
             host: "0.0.0.0".to_string(),
             port: 8080,
+            emoji: false,
+            emoji_map: None,
         }
     }
 
@@ -119,6 +193,19 @@ impl ServerConfig {
         self
     }
 
+    /// Enables or disables emoji shortcode expansion
+    pub fn with_emoji(mut self, emoji: bool) -> Self {
+        self.emoji = emoji;
+        self
+    }
+
+    /// Sets a custom emoji mapping file (also enables emoji expansion)
+    pub fn with_emoji_map(mut self, emoji_map: PathBuf) -> Self {
+        self.emoji = true;
+        self.emoji_map = Some(emoji_map);
+        self
+    }
+
     /// Returns the server address in "host:port" format
     fn address(&self) -> String {
         format!("{}:{}", self.host, self.port)
@@ -129,17 +216,35 @@ impl ServerConfig {
 // SERVER
 // ============================================================================
 
+/// A response rendered by `Server::render_request`, with the files it was
+/// rendered from
+#[derive(Debug, Clone)]
+pub struct RenderedPage {
+    /// HTTP status code
+    pub status: u16,
+    /// The complete HTML document
+    pub html: Vec<u8>,
+    /// The markdown file rendered (`None` for the generic 404 page)
+    pub source: Option<PathBuf>,
+    /// Other files the page was rendered from: includes, the local images
+    /// it's sized from and its layout. The site config and emoji map, which
+    /// every page shares, aren't listed.
+    pub dependencies: Vec<PathBuf>,
+}
+
 /// HTTP server for serving directory-based markdown websites
 ///
 /// The server:
 /// - Uses a `Router` to resolve URL paths to markdown files
 /// - Parses markdown files using `MarkdownParser`
-/// - Converts to HTML using `HtmlWriter`
-/// - Wraps content in a complete HTML5 document structure
+/// - Converts to HTML using `HtmlWriter`, linking `.md` files by route
+/// - Places it in the page's layout (see `Layout`), with breadcrumbs and
+///   the sitemap
 /// - Supports hierarchical 404.md error pages
 pub struct Server {
     router: Router,
     config: ServerConfig,
+    emoji: Option<EmojiExpander>,
 }
 
 impl Server {
@@ -150,11 +255,30 @@ impl Server {
     ///
     /// # Returns
     /// * `Ok(Server)` - Successfully created server
-    /// * `Err(ServerError)` - If router initialization fails
+    /// * `Err(ServerError)` - If router initialization or loading the emoji map fails
     pub fn new(config: ServerConfig) -> Result<Self, ServerError> {
         let router = Router::new(config.content_root.clone())?;
 
-        Ok(Self { router, config })
+        let emoji = if config.emoji {
+            let expander = EmojiExpander::new();
+            Some(match &config.emoji_map {
+                Some(map_path) => expander.with_custom_map(map_path)?,
+                None => expander,
+            })
+        } else {
+            None
+        };
+
+        Ok(Self {
+            router,
+            config,
+            emoji,
+        })
+    }
+
+    /// The router resolving this server's routes
+    pub fn router(&self) -> &Router {
+        &self.router
     }
 
     /// Starts the HTTP server and begins handling requests
@@ -180,12 +304,12 @@ impl Server {
 
             match self.handle_request(&url_path) {
                 Ok((status, html)) => {
-                    let response = Response::from_string(html)
+                    let response = Response::from_data(html)
                         .with_status_code(status)
                         .with_header(
                             tiny_http::Header::from_bytes(
                                 &b"Content-Type"[..],
-                                &CONTENT_TYPE_HTML.as_bytes()[..],
+                                CONTENT_TYPE_HTML.as_bytes(),
                             )
                             .unwrap(),
                         );
@@ -197,13 +321,18 @@ impl Server {
                 Err(e) => {
                     eprintln!("Error handling request for {}: {}", url_path, e);
 
-                    let html = wrap_html_document(GENERIC_500_TITLE, GENERIC_500_BODY);
+                    // The built-in theme: a broken layout may be the cause
+                    let html = Layout::default_theme().render(&LayoutParts {
+                        title: GENERIC_500_TITLE,
+                        content: GENERIC_500_BODY,
+                        ..LayoutParts::default()
+                    });
                     let response = Response::from_string(html)
                         .with_status_code(HTTP_STATUS_INTERNAL_ERROR)
                         .with_header(
                             tiny_http::Header::from_bytes(
                                 &b"Content-Type"[..],
-                                &CONTENT_TYPE_HTML.as_bytes()[..],
+                                CONTENT_TYPE_HTML.as_bytes(),
                             )
                             .unwrap(),
                         );
@@ -224,9 +353,25 @@ impl Server {
     /// * `url_path` - The URL path from the HTTP request
     ///
     /// # Returns
-    /// * `Ok((status_code, html))` - HTTP status and rendered HTML
+    /// * `Ok((status_code, html))` - HTTP status and rendered HTML bytes
+    /// * `Err(ServerError)` - If an error occurs during processing
+    pub fn handle_request(&self, url_path: &str) -> Result<(u16, Vec<u8>), ServerError> {
+        self.render_request(url_path)
+            .map(|page| (page.status, page.html))
+    }
+
+    /// Renders the response to a request, recording what it was rendered from
+    ///
+    /// Static builds call this too, so built pages match served ones byte
+    /// for byte.
+    ///
+    /// # Arguments
+    /// * `url_path` - The URL path from the HTTP request
+    ///
+    /// # Returns
+    /// * `Ok(RenderedPage)` - HTTP status, HTML and the files read
     /// * `Err(ServerError)` - If an error occurs during processing
-    fn handle_request(&self, url_path: &str) -> Result<(u16, String), ServerError> {
+    pub fn render_request(&self, url_path: &str) -> Result<RenderedPage, ServerError> {
         // Try to resolve the path
         let resolved = self.router.resolve_path(url_path)?;
 
@@ -241,13 +386,23 @@ impl Server {
                     // Render the custom 404 page (footer already added in render_markdown_file)
                     self.render_markdown_file(&not_found_path, HTTP_STATUS_NOT_FOUND, url_path)
                 } else {
-                    // Use generic 404 page with footer
+                    // Use generic 404 page in the site's layout
+                    let mut dependencies = Vec::new();
+                    let layout = self.load_layout(None, &mut dependencies)?;
                     let sitemap = self.router.build_sitemap()?;
-                    let footer_html =
-                        crate::router::generate_sitemap_footer(&sitemap, Some(url_path));
-                    let body_with_footer = format!("{}{}", GENERIC_404_BODY, footer_html);
-                    let html = wrap_html_document(GENERIC_404_TITLE, &body_with_footer);
-                    Ok((HTTP_STATUS_NOT_FOUND, html))
+                    let html = render_layout(
+                        &layout,
+                        GENERIC_404_TITLE,
+                        GENERIC_404_BODY,
+                        &sitemap,
+                        url_path,
+                    );
+                    Ok(RenderedPage {
+                        status: HTTP_STATUS_NOT_FOUND,
+                        html: html.into_bytes(),
+                        source: None,
+                        dependencies,
+                    })
                 }
             }
         }
@@ -261,49 +416,187 @@ impl Server {
     /// * `url_path` - The URL path being requested (for "you're here" indicator)
     ///
     /// # Returns
-    /// * `Ok((status_code, html))` - HTTP status and rendered HTML document
+    /// * `Ok(RenderedPage)` - HTTP status and rendered HTML document
     /// * `Err(ServerError)` - If reading or parsing fails
     fn render_markdown_file(
         &self,
-        path: &PathBuf,
+        path: &Path,
         status_code: u16,
         url_path: &str,
-    ) -> Result<(u16, String), ServerError> {
+    ) -> Result<RenderedPage, ServerError> {
+        let mut dependencies = Vec::new();
+        let (document, front_matter) = self.load_page_tracked(path, url_path, &mut dependencies)?;
+        let links = self.router.link_rewriter(path);
+        dependencies.extend(local_images(&document, &links));
+        let layout = self.load_layout(front_matter.get(LAYOUT_KEY), &mut dependencies)?;
+
+        // Extract title from first heading (if available)
+        let title = extract_title(&document);
+
+        // Sitemap and breadcrumbs mark the current path
+        let sitemap = self.router.build_sitemap()?;
+        let sitemap_list = generate_sitemap_list(&sitemap, Some(url_path));
+        let breadcrumbs = generate_breadcrumbs(&sitemap, url_path);
+        let parts = LayoutParts {
+            title: &title,
+            sitemap: &sitemap_list,
+            breadcrumbs: &breadcrumbs,
+            ..LayoutParts::default()
+        };
+
+        // Stream the body straight into the response at `{{content}}`
+        let writer = HtmlWriter::with_config(page_html_config()).with_link_rewriter(links);
+        let mut html = Vec::new();
+        layout
+            .render_to(&mut html, &parts, |out| {
+                writer.write_html_to(&document, out)
+            })
+            .map_err(|source| ServerError::RenderError {
+                path: path.to_path_buf(),
+                source,
+            })?;
+
+        Ok(RenderedPage {
+            status: status_code,
+            html,
+            source: Some(path.to_path_buf()),
+            dependencies,
+        })
+    }
+
+    /// Reads a markdown file into the document a request for it renders
+    ///
+    /// Front matter is split off, includes resolved, `{{ site.* }}` and
+    /// `{{ page.* }}` variables substituted and, if enabled, emoji expanded.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the markdown file
+    /// * `url_path` - The URL path the page is served at (for `page.url`)
+    ///
+    /// # Returns
+    /// * `Ok(Document)` - The page, ready to render
+    /// * `Err(ServerError)` - If reading, including or parsing fails
+    pub fn load_page(&self, path: &Path, url_path: &str) -> Result<Document<'static>, ServerError> {
+        self.load_page_tracked(path, url_path, &mut Vec::new())
+            .map(|(document, _)| document)
+    }
+
+    /// `load_page`, also returning the front matter and adding each
+    /// included file to `dependencies`
+    fn load_page_tracked(
+        &self,
+        path: &Path,
+        url_path: &str,
+        dependencies: &mut Vec<PathBuf>,
+    ) -> Result<(Document<'static>, FrontMatter), ServerError> {
         // Read the markdown file
         let content = fs::read_to_string(path).map_err(|e| ServerError::IoError {
-            path: path.clone(),
+            path: path.to_path_buf(),
             source: e.to_string(),
         })?;
 
-        // Parse the markdown
-        let document = MarkdownParser::parse(&content).map_err(|e| ServerError::IoError {
-            path: path.clone(),
-            source: e.to_string(),
-        })?;
+        // Split off front matter (page variables)
+        let (front_matter, body) =
+            split_front_matter(&content).map_err(|source| ServerError::ParseError {
+                path: path.to_path_buf(),
+                source,
+            })?;
 
-        // Extract title from first heading (if available)
-        let title = extract_title(&document);
+        // Resolve {{include "..."}} directives against the content root
+        let (content, included) = IncludeResolver::new(self.router.content_root())
+            .expand_with_files(body, path)
+            .map_err(|e| ServerError::IncludeError {
+                path: path.to_path_buf(),
+                source: e,
+            })?;
+        dependencies.extend(included);
 
-        // Convert to HTML
-        let writer = HtmlWriter::new();
-        let body_html = writer
-            .write_html(&document)
-            .map_err(|e| ServerError::IoError {
-                path: path.clone(),
-                source: e.to_string(),
+        // Parse the markdown
+        let mut document =
+            MarkdownParser::parse(&content).map_err(|source| ServerError::ParseError {
+                path: path.to_path_buf(),
+                source,
             })?;
 
-        // Generate sitemap footer with current path indicator
-        let sitemap = self.router.build_sitemap()?;
-        let footer_html = crate::router::generate_sitemap_footer(&sitemap, Some(url_path));
+        // Substitute {{ site.* }} and {{ page.* }} variables
+        let variables = self.page_variables(path, url_path, &front_matter)?;
+        variables
+            .substitute_document(&mut document)
+            .map_err(|source| ServerError::VariableError {
+                path: path.to_path_buf(),
+                source,
+            })?;
+
+        // Expand emoji shortcodes if enabled
+        if let Some(emoji) = &self.emoji {
+            emoji.expand_document(&mut document);
+        }
+
+        Ok((document.into_owned(), front_matter))
+    }
+
+    /// Loads the layout a page selects, adding its file to `dependencies`
+    ///
+    /// # Arguments
+    /// * `name` - The page's `layout:` front matter, or `None` for the
+    ///   site's `_layout.html` (the built-in theme if there is none)
+    ///
+    /// # Returns
+    /// * `Ok(Layout)` - The layout to render the page in
+    /// * `Err(ServerError)` - If a named layout doesn't exist, or the layout
+    ///   can't be read or parsed
+    fn load_layout(
+        &self,
+        name: Option<&str>,
+        dependencies: &mut Vec<PathBuf>,
+    ) -> Result<Layout, ServerError> {
+        let content_root = self.router.content_root();
+        let path = layout_path(content_root, name).map_err(|e| ServerError::LayoutError {
+            path: content_root.to_path_buf(),
+            source: e,
+        })?;
+        if name.is_none() && !path.is_file() {
+            return Ok(Layout::default_theme());
+        }
+
+        let text = fs::read_to_string(&path).map_err(|e| ServerError::IoError {
+            path: path.clone(),
+            source: e.to_string(),
+        })?;
+        let layout = Layout::parse(&text).map_err(|e| ServerError::LayoutError {
+            path: path.clone(),
+            source: e,
+        })?;
+        dependencies.push(path);
+        Ok(layout)
+    }
 
-        // Combine body and footer
-        let complete_body = format!("{}{}", body_html, footer_html);
+    /// Collects the variables available to a page
+    ///
+    /// # Arguments
+    /// * `path` - Path to the markdown file (for `page.updated`)
+    /// * `url_path` - The URL path being requested (for `page.url`)
+    /// * `front_matter` - The page's front matter (for `page.*`)
+    ///
+    /// # Returns
+    /// * `Ok(Variables)` - Site config, front matter and built-in page variables
+    /// * `Err(ServerError)` - If the site config can't be read or parsed
+    fn page_variables(
+        &self,
+        path: &Path,
+        url_path: &str,
+        front_matter: &FrontMatter,
+    ) -> Result<Variables, ServerError> {
+        let site_config = load_site_config(self.router.content_root())?;
 
-        // Wrap in HTML document structure
-        let html = wrap_html_document(&title, &complete_body);
+        let mut variables = Variables::new().with(PAGE_URL, url_path);
+        if let Ok(modified) = fs::metadata(path).and_then(|m| m.modified()) {
+            variables.set(PAGE_UPDATED, &format_date(modified));
+        }
 
-        Ok((status_code, html))
+        Ok(variables
+            .with_site_config(&site_config)
+            .with_front_matter(front_matter))
     }
 }
 
@@ -311,30 +604,41 @@ impl Server {
 // HTML DOCUMENT GENERATION
 // ============================================================================
 
-/// Wraps content in a complete HTML5 document structure
+/// Places a page in a layout, with the sitemap and breadcrumbs for its path
 ///
 /// # Arguments
-/// * `title` - Text for the <title> tag
-/// * `content` - HTML content for the <body>
+/// * `layout` - The page's layout
+/// * `title` - Page title as plain text
+/// * `content` - The page's HTML
+/// * `sitemap` - The site's sitemap
+/// * `url_path` - The URL path being requested (for "you're here")
 ///
 /// # Returns
 /// A complete HTML document string
-fn wrap_html_document(title: &str, content: &str) -> String {
-    format!(
-        "{}{}{}{}{}{}{}{}{}{}{}{}",
-        DOCTYPE,
-        HTML_OPEN,
-        HEAD_OPEN,
-        META_CHARSET,
-        TITLE_OPEN,
-        escape_html_title(title),
-        TITLE_CLOSE,
-        HEAD_CLOSE,
-        BODY_OPEN,
+fn render_layout(
+    layout: &Layout,
+    title: &str,
+    content: &str,
+    sitemap: &[SitemapEntry],
+    url_path: &str,
+) -> String {
+    layout.render(&LayoutParts {
+        title,
         content,
-        BODY_CLOSE,
-        HTML_CLOSE
-    )
+        sitemap: &generate_sitemap_list(sitemap, Some(url_path)),
+        breadcrumbs: &generate_breadcrumbs(sitemap, url_path),
+    })
+}
+
+/// HTML options for served pages: images as captioned figures that load
+/// lazily, sized from their files to avoid layout shift, and highlighted
+/// code blocks
+fn page_html_config() -> HtmlConfig {
+    HtmlConfig::new()
+        .with_figures(true)
+        .with_lazy_images(true)
+        .with_image_dimensions(true)
+        .with_highlight(true)
 }
 
 /// Extracts the title from a document (first H1 heading text)
@@ -344,15 +648,47 @@ fn wrap_html_document(title: &str, content: &str) -> String {
 ///
 /// # Returns
 /// Title string (from first H1, or default if none found)
-fn extract_title(document: &crate::parser::Document) -> String {
-    // Check if first section exists and is H1
-    if let Some(section) = document.sections().first() {
-        if section.level() == 1 {
-            return inline_nodes_to_text(section.title());
+fn extract_title(document: &Document) -> String {
+    document_title(document).unwrap_or_else(|| DEFAULT_TITLE.to_string())
+}
+
+/// Returns the plain text of a document's first heading, if it's an H1
+pub fn document_title(document: &Document) -> Option<String> {
+    document
+        .sections()
+        .first()
+        .filter(|section| section.level() == 1)
+        .map(|section| inline_nodes_to_text(section.title()))
+}
+
+/// Finds the local files of a page's images, which sized images are read from
+///
+/// # Arguments
+/// * `document` - The page
+/// * `links` - The page's link rewriter, resolving image URLs to files
+fn local_images(document: &Document, links: &LinkRewriter) -> Vec<PathBuf> {
+    let mut collector = ImageCollector::default();
+    collector.visit_document(document);
+    collector
+        .urls
+        .iter()
+        .filter_map(|url| links.local_file(url))
+        .collect()
+}
+
+/// Visitor that collects the URL of every image
+#[derive(Default)]
+struct ImageCollector {
+    urls: Vec<String>,
+}
+
+impl Visitor for ImageCollector {
+    fn visit_block(&mut self, block: &BlockNode) {
+        if let BlockNode::Image { url, .. } = block {
+            self.urls.push(url.to_string());
         }
+        visit::walk_block(self, block);
     }
-
-    DEFAULT_TITLE.to_string()
 }
 
 /// Converts inline nodes to plain text (for title extraction)
@@ -363,34 +699,27 @@ fn extract_title(document: &crate::parser::Document) -> String {
 /// # Returns
 /// Plain text representation (formatting removed)
 fn inline_nodes_to_text(nodes: &[InlineNode]) -> String {
-    let mut text = String::new();
-
+    let mut collector = PlainTextCollector::default();
     for node in nodes {
-        match node {
-            InlineNode::Text(t) => text.push_str(t),
-            InlineNode::LineBreak => text.push(' '),
-            InlineNode::Bold(children) => text.push_str(&inline_nodes_to_text(children)),
-            InlineNode::Italic(children) => text.push_str(&inline_nodes_to_text(children)),
-            InlineNode::Link {
-                text: link_text, ..
-            } => text.push_str(&inline_nodes_to_text(link_text)),
-        }
+        collector.visit_inline(node);
     }
+    collector.text
+}
 
-    text
+/// Visitor that concatenates the text of inline nodes, dropping formatting
+#[derive(Default)]
+struct PlainTextCollector {
+    text: String,
 }
 
-/// Escapes HTML entities in title text
-///
-/// Note: This is simpler than the full escaping in html_writer.rs
-/// since titles are plain text only
-fn escape_html_title(title: &str) -> String {
-    title
-        .replace('&', "&amp;")
-        .replace('<', "&lt;")
-        .replace('>', "&gt;")
-        .replace('"', "&quot;")
-        .replace('\'', "&#39;")
+impl Visitor for PlainTextCollector {
+    fn visit_inline(&mut self, node: &InlineNode) {
+        match node {
+            InlineNode::Text(t) => self.text.push_str(t),
+            InlineNode::LineBreak => self.text.push(' '),
+            _ => visit::walk_inline(self, node),
+        }
+    }
 }
 
 // ============================================================================
@@ -424,26 +753,21 @@ mod tests {
     }
 
     #[test]
-    fn test_wrap_html_document() {
-        let html = wrap_html_document("Test Title", "<p>Content</p>");
-        assert!(html.contains("<!DOCTYPE html>"));
-        assert!(html.contains("<title>Test Title</title>"));
-        assert!(html.contains("<p>Content</p>"));
-        assert!(html.contains("<meta charset=\"utf-8\">"));
-    }
+    fn test_server_config_with_emoji_map() {
+        let config = ServerConfig::new(PathBuf::from("/test"));
+        assert!(!config.emoji);
 
-    #[test]
-    fn test_escape_html_title() {
-        let escaped = escape_html_title("<script>alert('XSS')</script>");
-        assert_eq!(escaped, "&lt;script&gt;alert(&#39;XSS&#39;)&lt;/script&gt;");
+        let config = config.with_emoji_map(PathBuf::from("/test/emoji.map"));
+        assert!(config.emoji);
+        assert_eq!(config.emoji_map, Some(PathBuf::from("/test/emoji.map")));
     }
 
     #[test]
     fn test_inline_nodes_to_text() {
         let nodes = vec![
-            InlineNode::Text("Hello ".to_string()),
-            InlineNode::Bold(vec![InlineNode::Text("world".to_string())]),
-            InlineNode::Text("!".to_string()),
+            InlineNode::Text("Hello ".into()),
+            InlineNode::Bold(vec![InlineNode::Text("world".into())]),
+            InlineNode::Text("!".into()),
         ];
 
         let text = inline_nodes_to_text(&nodes);
@@ -465,4 +789,216 @@ mod tests {
         let title = extract_title(&document);
         assert_eq!(title, DEFAULT_TITLE);
     }
+
+    #[test]
+    fn test_handle_request_resolves_includes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join("shared")).unwrap();
+        fs::write(root.join("shared/contact.md"), "Contact **us**").unwrap();
+        fs::write(
+            root.join("root.md"),
+            "# Welcome\n\n{{include \"shared/contact.md\"}}",
+        )
+        .unwrap();
+        fs::write(root.join("broken.md"), "{{include \"../outside.md\"}}").unwrap();
+
+        let server = Server::new(ServerConfig::new(root.to_path_buf())).unwrap();
+
+        let (status, html) = server.handle_request("/").unwrap();
+        let html = String::from_utf8(html).unwrap();
+        assert_eq!(status, HTTP_STATUS_OK);
+        assert!(html.contains("<p>Contact <strong>us</strong></p>"));
+
+        let result = server.handle_request("/broken");
+        assert!(matches!(result, Err(ServerError::IncludeError { .. })));
+    }
+
+    #[test]
+    fn test_render_request_records_dependencies() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        fs::write(root.join("note.md"), "A note").unwrap();
+        fs::write(root.join("logo.png"), b"not really a png").unwrap();
+        fs::write(
+            root.join("root.md"),
+            "# Home\n\n{{include \"note.md\"}}\n\n![Logo](logo.png)\n\n![Gone](gone.png)",
+        )
+        .unwrap();
+
+        let server = Server::new(ServerConfig::new(root.clone())).unwrap();
+
+        let page = server.render_request("/").unwrap();
+        assert_eq!(page.status, HTTP_STATUS_OK);
+        assert_eq!(page.source, Some(root.join("root.md")));
+        assert_eq!(
+            page.dependencies,
+            vec![root.join("note.md"), root.join("logo.png")]
+        );
+
+        let page = server.render_request("/missing").unwrap();
+        assert_eq!(page.status, HTTP_STATUS_NOT_FOUND);
+        assert_eq!(page.source, None);
+        assert!(page.dependencies.is_empty());
+    }
+
+    #[test]
+    fn test_handle_request_substitutes_variables() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("_site.conf"), "name: Handbook\n").unwrap();
+        fs::write(
+            root.join("root.md"),
+            "---\nowner: Ops\n---\n# {{ site.name }}\n\nOwned by {{ page.owner }} at {{ page.url }}",
+        )
+        .unwrap();
+        fs::write(root.join("broken.md"), "Hi {{ site.missing }}").unwrap();
+        fs::write(
+            root.join("bad-front-matter.md"),
+            "---\nno separator\n---\nHi",
+        )
+        .unwrap();
+
+        let server = Server::new(ServerConfig::new(root.to_path_buf())).unwrap();
+
+        let (_, html) = server.handle_request("/").unwrap();
+        let html = String::from_utf8(html).unwrap();
+        assert!(html.contains("<title>Handbook</title>"));
+        assert!(html.contains("<p>Owned by Ops at /</p>"));
+        assert!(!html.contains("owner:"));
+
+        let result = server.handle_request("/broken");
+        assert!(matches!(result, Err(ServerError::VariableError { .. })));
+        let result = server.handle_request("/bad-front-matter");
+        assert!(matches!(result, Err(ServerError::ParseError { .. })));
+
+        fs::write(root.join("_site.conf"), "no separator\n").unwrap();
+        let error = server.handle_request("/").unwrap_err();
+        assert!(error.to_string().starts_with("Parse error in"));
+    }
+
+    #[test]
+    fn test_page_fills_default_layout() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        let markdown = "# A <Title>\n\nSome *text* & [a link](/x?a=1&b=2)";
+        fs::write(root.join("root.md"), markdown).unwrap();
+
+        let server = Server::new(ServerConfig::new(root.to_path_buf())).unwrap();
+        let (_, html) = server.handle_request("/").unwrap();
+
+        let document = MarkdownParser::parse(markdown).unwrap();
+        let sitemap = server.router.build_sitemap().unwrap();
+        let expected = Layout::default_theme().render(&LayoutParts {
+            title: &extract_title(&document),
+            content: &HtmlWriter::with_config(page_html_config())
+                .write_html(&document)
+                .unwrap(),
+            sitemap: &generate_sitemap_list(&sitemap, Some("/")),
+            breadcrumbs: &generate_breadcrumbs(&sitemap, "/"),
+        });
+        let html = String::from_utf8(html).unwrap();
+        assert_eq!(html, expected);
+        assert!(html.contains("<title>A &lt;Title&gt;</title>"));
+    }
+
+    #[test]
+    fn test_handle_request_uses_site_and_page_layouts() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::write(
+            root.join("_layout.html"),
+            "<title>{{title}}</title>{{breadcrumbs}}<main>{{content}}</main>{{sitemap}}",
+        )
+        .unwrap();
+        fs::write(root.join("_layout-bare.html"), "[{{ content }}]").unwrap();
+        fs::write(root.join("root.md"), "# Home").unwrap();
+        fs::write(root.join("bare.md"), "---\nlayout: bare\n---\nHi").unwrap();
+        fs::write(root.join("typo.md"), "---\nlayout: wide\n---\nHi").unwrap();
+
+        let server = Server::new(ServerConfig::new(root.to_path_buf())).unwrap();
+
+        let (_, html) = server.handle_request("/").unwrap();
+        assert_eq!(
+            String::from_utf8(html).unwrap(),
+            "<title>Home</title>\
+             <ol class=\"breadcrumbs\"><li aria-current=\"page\">root</li></ol>\
+             <main><h1>Home</h1></main>\
+             <ul><li><a href=\"/\"><b>root ← you're here</b></a></li>\
+             <li><a href=\"/bare\">bare</a></li><li><a href=\"/typo\">typo</a></li></ul>"
+        );
+
+        let (_, html) = server.handle_request("/bare").unwrap();
+        assert_eq!(String::from_utf8(html).unwrap(), "[<p>Hi</p>]");
+
+        let (status, html) = server.handle_request("/missing").unwrap();
+        assert_eq!(status, HTTP_STATUS_NOT_FOUND);
+        assert!(
+            String::from_utf8(html)
+                .unwrap()
+                .starts_with("<title>404 Not Found</title>")
+        );
+
+        let result = server.handle_request("/typo");
+        assert!(matches!(result, Err(ServerError::IoError { .. })));
+
+        fs::write(root.join("_layout.html"), "<title>{{title}}</title>").unwrap();
+        let result = server.handle_request("/");
+        assert!(matches!(result, Err(ServerError::LayoutError { .. })));
+    }
+
+    #[test]
+    fn test_handle_request_rewrites_md_links() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join("docs")).unwrap();
+        fs::write(root.join("docs/docs.md"), "[team](../team.md)").unwrap();
+        fs::write(root.join("team.md"), "[docs](docs/docs.md)").unwrap();
+
+        let server = Server::new(ServerConfig::new(root.to_path_buf())).unwrap();
+
+        let (_, html) = server.handle_request("/docs").unwrap();
+        assert!(
+            String::from_utf8(html)
+                .unwrap()
+                .contains("<a href=\"/team\">team</a>")
+        );
+        let (_, html) = server.handle_request("/team").unwrap();
+        assert!(
+            String::from_utf8(html)
+                .unwrap()
+                .contains("<a href=\"/docs\">docs</a>")
+        );
+    }
+
+    #[test]
+    fn test_handle_request_renders_sized_figures() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        let mut gif = b"GIF89a".to_vec();
+        gif.extend_from_slice(&[40, 0, 30, 0, 0, 0, 0]);
+        fs::write(root.join("chart.gif"), gif).unwrap();
+        fs::write(root.join("root.md"), "![Sales](chart.gif)").unwrap();
+
+        let server = Server::new(ServerConfig::new(root.to_path_buf())).unwrap();
+        let (_, html) = server.handle_request("/").unwrap();
+        assert!(String::from_utf8(html).unwrap().contains(
+            "<figure><img src=\"/chart.gif\" alt=\"Sales\" width=\"40\" height=\"30\" \
+             loading=\"lazy\" decoding=\"async\"><figcaption>Sales</figcaption></figure>"
+        ));
+    }
+
+    #[test]
+    fn test_handle_request_highlights_code() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("root.md"), "```toml\n[package]\n```").unwrap();
+
+        let server = Server::new(ServerConfig::new(root.to_path_buf())).unwrap();
+        let (_, html) = server.handle_request("/").unwrap();
+        assert!(String::from_utf8(html).unwrap().contains(
+            "<pre><code class=\"language-toml\"><span class=\"tok-section\">[package]</span>\
+             </code></pre>"
+        ));
+    }
 }
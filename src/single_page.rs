@@ -0,0 +1,366 @@
+use crate::export::{
+    ExportError, UrlRewriter, collect_pages, escape_xml, image_media_type, page_link,
+};
+use crate::html_writer::{HtmlConfig, HtmlWriter};
+use crate::parser::Document;
+use crate::router::SitemapEntry;
+use crate::server::{Server, ServerConfig, document_title};
+use crate::visit::VisitorMut;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// Document structure
+const PAGE_ID_PREFIX: &str = "page-";
+const PAGE_CLASS: &str = "page";
+const TOC_CLASS: &str = "toc";
+const DEFAULT_TITLE: &str = "Untitled";
+const MAX_HEADING_OFFSET: usize = 5;
+
+// Data URIs
+const DATA_URI_PREFIX: &str = "data:";
+const DATA_URI_BASE64: &str = ";base64,";
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const BASE64_PAD: char = '=';
+
+/// Readable defaults for the combined page, with each page starting on a
+/// new sheet when printed
+const DEFAULT_STYLESHEET: &str = "body{max-width:46em;margin:0 auto;padding:1em;\
+font-family:Georgia,serif;line-height:1.5}\
+img{max-width:100%;height:auto}\
+pre{overflow-x:auto;padding:.5em;background:#f6f6f6}\
+nav.toc ol{padding-left:1.5em}\
+section.page{margin-top:2em;border-top:1px solid #ccc}\
+@media print{nav.toc,section.page{break-before:page}section.page{border-top:0}}";
+
+// ============================================================================
+// SINGLE-PAGE EXPORTER
+// ============================================================================
+
+/// Combines every page of a site into one self-contained HTML file
+///
+/// Pages follow each other in sitemap order (depth first), each rendered as
+/// the server would render it and wrapped in a `<section id="page-N">`.
+/// A table of contents nested like the sitemap comes first.
+///
+/// To read as one document:
+/// - Headings are demoted by the page's depth in the sitemap (a child
+///   page's `#` renders as `<h2>`)
+/// - Links to other pages jump to their section (fragments are dropped, as
+///   headings have no ids)
+/// - Local images are inlined as `data:` URIs and the stylesheet is
+///   embedded, so the file needs nothing else to display or print
+///
+/// # Example
+/// ```no_run
+/// use wtf::single_page::SinglePageExporter;
+/// use wtf::server::ServerConfig;
+/// use std::fs::File;
+///
+/// let exporter = SinglePageExporter::new(ServerConfig::new("content".into()))?
+///     .with_title("Handbook");
+/// exporter.write_html(File::create("handbook.html")?)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct SinglePageExporter {
+    server: Server,
+    title: String,
+    stylesheet: Option<String>,
+}
+
+impl SinglePageExporter {
+    /// Creates an exporter for the content tree a server config describes
+    ///
+    /// The document is titled after the content root directory until
+    /// `with_title` is used.
+    ///
+    /// # Returns
+    /// * `Ok(SinglePageExporter)` - Ready to export
+    /// * `Err(ExportError)` - If the content root or emoji map is invalid
+    pub fn new(config: ServerConfig) -> Result<Self, ExportError> {
+        let server = Server::new(config)?;
+        let title = server
+            .router()
+            .content_root()
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| DEFAULT_TITLE.to_string());
+
+        Ok(Self {
+            server,
+            title,
+            stylesheet: None,
+        })
+    }
+
+    /// Sets the document title, shown above the table of contents
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// Adds CSS after the built-in stylesheet
+    pub fn with_stylesheet(mut self, css: impl Into<String>) -> Self {
+        self.stylesheet = Some(css.into());
+        self
+    }
+
+    /// Renders every page into one HTML document
+    ///
+    /// # Returns
+    /// * `Ok(())` - The complete document was written
+    /// * `Err(ExportError)` - If a page or image can't be read or
+    ///   writing fails
+    pub fn write_html<W: Write>(&self, mut writer: W) -> Result<(), ExportError> {
+        let sitemap = self.server.router().build_sitemap()?;
+        let pages = collect_pages(self.server.router(), &sitemap)?;
+        let anchors: HashMap<&str, String> = pages
+            .iter()
+            .enumerate()
+            .map(|(i, page)| (page.route.as_str(), page_id(i)))
+            .collect();
+
+        // Load everything first: the table of contents needs the titles
+        let mut data_uris = HashMap::new();
+        let mut documents: Vec<Document<'static>> = Vec::with_capacity(pages.len());
+        let mut titles = HashMap::new();
+        for page in &pages {
+            let mut document = self.server.load_page(&page.file, &page.route)?;
+            titles.insert(
+                page.route.as_str(),
+                document_title(&document).unwrap_or_else(|| page.name.clone()),
+            );
+
+            let links = self.server.router().link_rewriter(&page.file);
+            let mut image_error = None;
+            UrlRewriter {
+                link: |url: &str| {
+                    let (route, _) = page_link(&links, url)?;
+                    Some(format!("#{}", anchors.get(route.as_str())?))
+                },
+                image: |url: &str| {
+                    let file = links.local_file(url)?;
+                    match data_uri(&file, &mut data_uris) {
+                        Ok(uri) => uri,
+                        Err(e) => {
+                            image_error.get_or_insert(e);
+                            None
+                        }
+                    }
+                },
+            }
+            .visit_document_mut(&mut document);
+            if let Some(error) = image_error {
+                return Err(error);
+            }
+            documents.push(document);
+        }
+
+        write!(
+            writer,
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{}</title><style>{}{}</style></head><body>",
+            escape_xml(&self.title),
+            DEFAULT_STYLESHEET,
+            // A stylesheet can't close the style element early
+            self.stylesheet
+                .as_deref()
+                .unwrap_or_default()
+                .replace("</style", "<\\/style")
+        )?;
+
+        let mut toc = String::new();
+        write_toc(&mut toc, &sitemap, &anchors, &titles);
+        write!(
+            writer,
+            "<nav class=\"{}\"><h1>{}</h1>{}</nav>",
+            TOC_CLASS,
+            escape_xml(&self.title),
+            toc
+        )?;
+
+        for (i, (page, document)) in pages.iter().zip(&documents).enumerate() {
+            write!(
+                writer,
+                "<section class=\"{}\" id=\"{}\">",
+                PAGE_CLASS,
+                page_id(i)
+            )?;
+            let offset = page.depth.min(MAX_HEADING_OFFSET) as u8;
+            HtmlWriter::with_config(single_page_html_config().with_heading_offset(offset))
+                .write_html_to(document, &mut writer)
+                .map_err(|e| ExportError::PageError {
+                    path: page.file.clone(),
+                    source: e.to_string(),
+                })?;
+            writer.write_all(b"</section>")?;
+        }
+
+        writer.write_all(b"</body></html>\n")?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// HTML options for the combined pages: those of served pages, minus lazy
+/// loading and file-based sizing (every image is already in the file)
+fn single_page_html_config() -> HtmlConfig {
+    HtmlConfig::new().with_figures(true).with_highlight(true)
+}
+
+/// The id of the `index`th page's section
+fn page_id(index: usize) -> String {
+    format!("{}{}", PAGE_ID_PREFIX, index + 1)
+}
+
+/// Writes a nested `<ol>` of section links for sitemap entries
+fn write_toc(
+    out: &mut String,
+    entries: &[SitemapEntry],
+    anchors: &HashMap<&str, String>,
+    titles: &HashMap<&str, String>,
+) {
+    out.push_str("<ol>");
+    for entry in entries {
+        let route = entry.url_path.as_str();
+        let label = escape_xml(titles.get(route).unwrap_or(&entry.name));
+        match anchors.get(route) {
+            Some(id) => out.push_str(&format!("<li><a href=\"#{}\">{}</a>", id, label)),
+            None => out.push_str(&format!("<li>{}", label)),
+        }
+        if !entry.children.is_empty() {
+            write_toc(out, &entry.children, anchors, titles);
+        }
+        out.push_str("</li>");
+    }
+    out.push_str("</ol>");
+}
+
+/// Reads an image into a `data:` URI (cached per file)
+///
+/// # Returns
+/// * `Ok(Some(String))` - The URI
+/// * `Ok(None)` - If the file isn't an image format browsers display
+/// * `Err(ExportError)` - If it can't be read
+fn data_uri(
+    file: &Path,
+    cache: &mut HashMap<PathBuf, String>,
+) -> Result<Option<String>, ExportError> {
+    if let Some(uri) = cache.get(file) {
+        return Ok(Some(uri.clone()));
+    }
+    let Some((_, media_type)) = image_media_type(file) else {
+        return Ok(None);
+    };
+
+    let data = fs::read(file).map_err(|e| ExportError::IoError {
+        path: file.to_path_buf(),
+        source: e.to_string(),
+    })?;
+    let uri = format!(
+        "{}{}{}{}",
+        DATA_URI_PREFIX,
+        media_type,
+        DATA_URI_BASE64,
+        base64(&data)
+    );
+    cache.insert(file.to_path_buf(), uri.clone());
+    Ok(Some(uri))
+}
+
+/// Standard base64 with padding
+fn base64(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let group = (u32::from(bytes[0]) << 16) | (u32::from(bytes[1]) << 8) | u32::from(bytes[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0x3f;
+                encoded.push(char::from(BASE64_ALPHABET[index as usize]));
+            } else {
+                encoded.push(BASE64_PAD);
+            }
+        }
+    }
+    encoded
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64(&[0xff, 0xfe]), "//4=");
+    }
+
+    fn export(root: &Path) -> String {
+        let mut output = Vec::new();
+        SinglePageExporter::new(ServerConfig::new(root.to_path_buf()))
+            .unwrap()
+            .with_title("Hand & Book")
+            .with_stylesheet("h1{color:red}</style>")
+            .write_html(&mut output)
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_combined_document() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join("guide")).unwrap();
+        fs::write(
+            root.join("root.md"),
+            "# Welcome\nSee the [guide](guide/guide.md#start).",
+        )
+        .unwrap();
+        fs::write(
+            root.join("guide/guide.md"),
+            "# Guide\n![Dot](dot.gif)\n\nBack [home](/?x=1) or [away](https://x.org).",
+        )
+        .unwrap();
+        fs::write(root.join("guide/intro.md"), "## Intro").unwrap();
+        fs::write(root.join("guide/dot.gif"), b"GIF89a").unwrap();
+
+        let html = export(root);
+        assert!(html.starts_with(
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Hand &amp; Book</title>"
+        ));
+        assert!(html.contains("h1{color:red}<\\/style></style>"));
+        assert!(html.contains(
+            "<nav class=\"toc\"><h1>Hand &amp; Book</h1><ol>\
+             <li><a href=\"#page-1\">Welcome</a></li>\
+             <li><a href=\"#page-2\">Guide</a><ol><li><a href=\"#page-3\">intro</a></li></ol></li>\
+             </ol></nav>"
+        ));
+
+        // Sections in sitemap order, headings demoted by depth
+        assert!(html.contains(
+            "<section class=\"page\" id=\"page-1\"><h1>Welcome</h1><p>See the \
+             <a href=\"#page-2\">guide</a>.</p></section>"
+        ));
+        assert!(html.contains("<section class=\"page\" id=\"page-3\"><h3>Intro</h3></section>"));
+
+        // Links between pages jump to sections; images are inlined
+        assert!(html.contains("<a href=\"#page-1\">home</a>"));
+        assert!(html.contains("<a href=\"https://x.org\">away</a>"));
+        assert!(html.contains("src=\"data:image/gif;base64,R0lGODlh\""));
+        assert!(html.ends_with("</body></html>\n"));
+    }
+}
@@ -0,0 +1,448 @@
+use crate::highlight::{self, Language, TokenKind};
+use crate::parser::{Document, Event, ParseError, Tag, TagEnd};
+use crate::renderer::{self, RenderError, Renderer};
+use crate::text_writer::{self, DEFAULT_WIDTH};
+use std::cell::RefCell;
+use std::env;
+use std::fmt;
+use std::io;
+
+// Environment variable holding the terminal width
+const COLUMNS_VAR: &str = "COLUMNS";
+
+// Layout
+const NEWLINE: char = '\n';
+const CODE_INDENT: &str = "    ";
+const TAB: char = '\t';
+
+// SGR (Select Graphic Rendition) sequences and parameters
+const SGR_START: &str = "\x1b[";
+const SGR_END: char = 'm';
+const SGR_SEPARATOR: char = ';';
+const SGR_RESET: &str = "\x1b[0m";
+const SGR_RESET_PARAM: &str = "0";
+const SGR_BOLD: &str = "1";
+const SGR_DIM: &str = "2";
+const SGR_ITALIC: &str = "3";
+const SGR_UNDERLINE: &str = "4";
+
+// Heading colours by level (h1, h2, h3 and deeper)
+const SGR_HEADINGS: [&str; 3] = ["1;4;35", "1;36", "1;33"];
+
+// OSC 8 hyperlinks: ESC ] 8 ; ; url ESC \ text ESC ] 8 ; ; ESC \
+const OSC8_START: &str = "\x1b]8;;";
+const OSC8_END: &str = "\x1b\\";
+const URL_SPACE_ESCAPE: &str = "%20";
+const SCHEME_SEPARATOR: &str = "://";
+
+// Links whose target isn't a full URL also show it, dimmed
+const LINK_URL_OPEN: &str = " [";
+const LINK_URL_CLOSE: char = ']';
+const IMAGE_OPEN: &str = "[Image: ";
+const IMAGE_CLOSE: char = ']';
+
+/// Terminal width: the width of the terminal on stdout, else `$COLUMNS`
+/// (when stdout isn't a terminal), else 80 columns
+pub fn terminal_width() -> usize {
+    let tty_width = terminal_size::terminal_size()
+        .map(|(terminal_size::Width(columns), _)| usize::from(columns))
+        .filter(|&columns| columns > 0);
+
+    tty_width
+        .or_else(|| {
+            env::var(COLUMNS_VAR)
+                .ok()
+                .and_then(|columns| columns.trim().parse().ok())
+                .filter(|&columns| columns > 0)
+        })
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+/// Terminal writer: text styled with ANSI escape sequences, for reading
+/// pages in a shell
+///
+/// - Headings in bold colour (underlined for level 1)
+/// - Bold and italic text
+/// - Links as OSC 8 hyperlinks, underlined; targets that aren't full URLs
+///   (routes, relative paths) are also shown dimmed in brackets
+/// - Images as a dimmed `[Image: alt text]` hyperlinked to the image
+/// - Code blocks indented, highlighted for languages `highlight` knows
+/// - Paragraphs wrapped at the configured width (escape sequences take no
+///   columns)
+///
+/// Control characters in the document are dropped, so page text can't
+/// smuggle its own escape sequences to the terminal.
+///
+/// # Example
+/// ```
+/// use wtf::parser::MarkdownParser;
+/// use wtf::term_writer::TermWriter;
+///
+/// let doc = MarkdownParser::parse("plain **bold**").unwrap();
+/// let text = TermWriter::new().write_term(&doc).unwrap();
+/// assert_eq!(text, "plain \x1b[0;1mbold\x1b[0m\n");
+/// ```
+pub struct TermWriter {
+    /// Wrap column (0 disables wrapping)
+    width: usize,
+    state: RefCell<TermState>,
+}
+
+/// The block being collected, and the styles open at its end
+#[derive(Default)]
+struct TermState {
+    block: String,
+    /// URL of each open link
+    links: Vec<String>,
+    heading: Option<u8>,
+    bold: usize,
+    italic: usize,
+    started: bool,
+}
+
+impl TermState {
+    /// Writes the SGR sequence for the current styles (a reset, then every
+    /// style still open, so closing one style never cancels another)
+    fn push_style(&mut self) {
+        let mut params = vec![SGR_RESET_PARAM];
+        if let Some(level) = self.heading {
+            let index = usize::from(level.max(1) - 1).min(SGR_HEADINGS.len() - 1);
+            params.push(SGR_HEADINGS[index]);
+        }
+        if self.bold > 0 {
+            params.push(SGR_BOLD);
+        }
+        if self.italic > 0 {
+            params.push(SGR_ITALIC);
+        }
+        if !self.links.is_empty() {
+            params.push(SGR_UNDERLINE);
+        }
+        push_sgr(&mut self.block, &params);
+    }
+}
+
+impl TermWriter {
+    /// Creates a writer that wraps at the terminal width (see
+    /// `terminal_width`)
+    pub fn new() -> Self {
+        Self {
+            width: terminal_width(),
+            state: RefCell::new(TermState::default()),
+        }
+    }
+
+    /// Sets the column paragraphs are wrapped at (0 disables wrapping)
+    pub fn with_width(mut self, width: usize) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Converts a parsed markdown document to styled terminal text
+    ///
+    /// # Returns
+    /// * `Ok(String)` - The text, ending in a newline unless it's empty
+    /// * `Err(RenderError)` - If writing fails
+    pub fn write_term(&self, document: &Document) -> Result<String, RenderError> {
+        renderer::render_to_string(self, document)
+    }
+
+    /// Streams the styled text for a document into a writer
+    ///
+    /// # Returns
+    /// * `Err(RenderError)` - If the writer returns an error
+    pub fn write_term_to<W: io::Write>(
+        &self,
+        document: &Document,
+        writer: W,
+    ) -> Result<(), RenderError> {
+        renderer::render_to_io(self, document, writer)
+    }
+
+    /// Renders an event stream (e.g. `MarkdownParser::events`) to styled
+    /// terminal text
+    ///
+    /// # Returns
+    /// * `Ok(String)` - The same text as `write_term` on the events' tree
+    /// * `Err(RenderError)` - For the first parse error in the stream
+    pub fn write_term_events<'a, I>(&self, events: I) -> Result<String, RenderError>
+    where
+        I: IntoIterator<Item = Result<Event<'a>, ParseError>>,
+    {
+        renderer::render_events_to_string(self, events)
+    }
+
+    /// Writes a finished block, separated from the previous one by a blank
+    /// line
+    fn write_block(&self, out: &mut dyn fmt::Write, text: &str) -> fmt::Result {
+        let mut state = self.state.borrow_mut();
+        if state.started {
+            out.write_char(NEWLINE)?;
+        }
+        state.started = true;
+        out.write_str(text)?;
+        out.write_char(NEWLINE)
+    }
+
+    /// Takes the collected block, ending any styling it uses
+    fn take_block(&self) -> String {
+        let mut block = std::mem::take(&mut self.state.borrow_mut().block);
+        if block.contains(SGR_START) && !block.ends_with(SGR_RESET) {
+            block.push_str(SGR_RESET);
+        }
+        block
+    }
+}
+
+impl Default for TermWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer for TermWriter {
+    type Error = RenderError;
+
+    fn begin(&self, _out: &mut dyn fmt::Write) -> Result<(), RenderError> {
+        self.state.replace(TermState::default());
+        Ok(())
+    }
+
+    fn start(&self, _out: &mut dyn fmt::Write, tag: &Tag) -> Result<(), RenderError> {
+        let mut state = self.state.borrow_mut();
+        match tag {
+            Tag::Section(_) => return Ok(()),
+            Tag::Paragraph => {
+                state.block.clear();
+                return Ok(());
+            }
+            Tag::Heading(level) => {
+                state.block.clear();
+                state.heading = Some(*level);
+            }
+            Tag::Bold => state.bold += 1,
+            Tag::Italic => state.italic += 1,
+            Tag::Link(url) => {
+                let url = sanitize(url);
+                push_hyperlink(&mut state.block, &url);
+                state.links.push(url);
+            }
+        }
+        state.push_style();
+        Ok(())
+    }
+
+    fn end(&self, out: &mut dyn fmt::Write, tag: TagEnd) -> Result<(), RenderError> {
+        let block = match tag {
+            TagEnd::Section(_) => return Ok(()),
+            TagEnd::Heading(_) => {
+                self.state.borrow_mut().heading = None;
+                self.take_block()
+            }
+            TagEnd::Paragraph => text_writer::wrap(&self.take_block(), self.width),
+            TagEnd::Bold => {
+                let mut state = self.state.borrow_mut();
+                state.bold = state.bold.saturating_sub(1);
+                state.push_style();
+                return Ok(());
+            }
+            TagEnd::Italic => {
+                let mut state = self.state.borrow_mut();
+                state.italic = state.italic.saturating_sub(1);
+                state.push_style();
+                return Ok(());
+            }
+            TagEnd::Link => {
+                let mut state = self.state.borrow_mut();
+                state.block.push_str(OSC8_START);
+                state.block.push_str(OSC8_END);
+                if let Some(url) = state.links.pop()
+                    && !url.contains(SCHEME_SEPARATOR)
+                {
+                    push_sgr(&mut state.block, &[SGR_RESET_PARAM, SGR_DIM]);
+                    state.block.push_str(LINK_URL_OPEN);
+                    state.block.push_str(&url);
+                    state.block.push(LINK_URL_CLOSE);
+                }
+                state.push_style();
+                return Ok(());
+            }
+        };
+        Ok(self.write_block(out, &block)?)
+    }
+
+    fn text(&self, _out: &mut dyn fmt::Write, text: &str) -> Result<(), RenderError> {
+        self.state.borrow_mut().block.push_str(&sanitize(text));
+        Ok(())
+    }
+
+    fn line_break(&self, _out: &mut dyn fmt::Write) -> Result<(), RenderError> {
+        self.state.borrow_mut().block.push(NEWLINE);
+        Ok(())
+    }
+
+    fn image(
+        &self,
+        out: &mut dyn fmt::Write,
+        alt_text: &str,
+        url: &str,
+    ) -> Result<(), RenderError> {
+        let label = sanitize(if alt_text.is_empty() { url } else { alt_text });
+        let mut block = String::new();
+        push_hyperlink(&mut block, &sanitize(url));
+        push_sgr(&mut block, &[SGR_RESET_PARAM, SGR_DIM]);
+        block.push_str(IMAGE_OPEN);
+        block.push_str(&label);
+        block.push(IMAGE_CLOSE);
+        block.push_str(SGR_RESET);
+        block.push_str(OSC8_START);
+        block.push_str(OSC8_END);
+        Ok(self.write_block(out, &block)?)
+    }
+
+    fn code_block(
+        &self,
+        out: &mut dyn fmt::Write,
+        info: &str,
+        code: &str,
+    ) -> Result<(), RenderError> {
+        let code = sanitize(code);
+        let mut styled = String::with_capacity(code.len());
+        match Language::from_info(info) {
+            Some(language) => {
+                for token in highlight::highlight(language, &code) {
+                    match token.kind {
+                        Some(kind) => {
+                            push_sgr(&mut styled, &[SGR_RESET_PARAM, token_style(kind)]);
+                            styled.push_str(token.text);
+                            styled.push_str(SGR_RESET);
+                        }
+                        None => styled.push_str(token.text),
+                    }
+                }
+            }
+            None => styled.push_str(&code),
+        }
+
+        // Indent every line with something on it (styles carry over lines)
+        let indented: Vec<String> = styled
+            .split(NEWLINE)
+            .map(|line| {
+                if text_writer::display_width(line) == 0 {
+                    line.to_string()
+                } else {
+                    format!("{}{}", CODE_INDENT, line)
+                }
+            })
+            .collect();
+        Ok(self.write_block(out, &indented.join("\n"))?)
+    }
+}
+
+/// SGR parameters for a highlighted token
+fn token_style(kind: TokenKind) -> &'static str {
+    match kind {
+        TokenKind::Keyword => "35",
+        TokenKind::Type => "36",
+        TokenKind::Function | TokenKind::Macro | TokenKind::Key => "34",
+        TokenKind::String => "32",
+        TokenKind::Number | TokenKind::Literal => "33",
+        TokenKind::Comment => "2;3",
+        TokenKind::Variable => "36",
+        TokenKind::Attribute => "2;35",
+        TokenKind::Section => "1;35",
+    }
+}
+
+/// Appends `ESC [ params m`
+fn push_sgr(out: &mut String, params: &[&str]) {
+    out.push_str(SGR_START);
+    for (i, param) in params.iter().enumerate() {
+        if i > 0 {
+            out.push(SGR_SEPARATOR);
+        }
+        out.push_str(param);
+    }
+    out.push(SGR_END);
+}
+
+/// Opens an OSC 8 hyperlink (spaces are escaped so wrapping can't split it)
+fn push_hyperlink(out: &mut String, url: &str) {
+    out.push_str(OSC8_START);
+    out.push_str(&url.replace(' ', URL_SPACE_ESCAPE));
+    out.push_str(OSC8_END);
+}
+
+/// Drops control characters other than newlines and tabs
+fn sanitize(text: &str) -> String {
+    text.chars()
+        .filter(|&ch| !ch.is_control() || ch == NEWLINE || ch == TAB)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::MarkdownParser;
+
+    fn term(source: &str) -> String {
+        TermWriter::new()
+            .with_width(80)
+            .write_term(&MarkdownParser::parse(source).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_headings_and_emphasis() {
+        assert_eq!(
+            term("# Title\n## Sub **b *bi* b**"),
+            "\x1b[0;1;4;35mTitle\x1b[0m\n\n\
+             \x1b[0;1;36mSub \x1b[0;1;36;1mb \x1b[0;1;36;1;3mbi\x1b[0;1;36;1m b\x1b[0;1;36m\x1b[0m\n"
+        );
+        assert_eq!(term("#### Deep"), "\x1b[0;1;33mDeep\x1b[0m\n");
+        assert_eq!(term("plain"), "plain\n");
+    }
+
+    #[test]
+    fn test_links() {
+        assert_eq!(
+            term("[site](https://example.com) [home](/)"),
+            "\x1b]8;;https://example.com\x1b\\\x1b[0;4msite\x1b]8;;\x1b\\\x1b[0m \
+             \x1b]8;;/\x1b\\\x1b[0;4mhome\x1b]8;;\x1b\\\x1b[0;2m [/]\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn test_wrapping_ignores_escapes() {
+        let writer = TermWriter::new().with_width(10);
+        let doc = MarkdownParser::parse("**aaaa** bbbb cccc").unwrap();
+        assert_eq!(
+            writer.write_term(&doc).unwrap(),
+            "\x1b[0;1maaaa\x1b[0m bbbb\ncccc\x1b[0m\n"
+        );
+        assert_eq!(text_writer::display_width("\x1b[0;1mab\x1b]8;;u\x1b\\c"), 3);
+    }
+
+    #[test]
+    fn test_control_characters_dropped() {
+        assert_eq!(term("a\x1b\\[31mb\x07c"), "a[31mbc\n");
+    }
+
+    #[test]
+    fn test_images_and_code() {
+        assert_eq!(
+            term("![A cat](cat.png)"),
+            "\x1b]8;;cat.png\x1b\\\x1b[0;2m[Image: A cat]\x1b[0m\x1b]8;;\x1b\\\n"
+        );
+        assert_eq!(
+            term("```rust\nlet x = 1;\n\n// end\n```"),
+            "    \x1b[0;35mlet\x1b[0m x = \x1b[0;33m1\x1b[0m;\n\n    \x1b[0;2;3m// end\x1b[0m\n"
+        );
+        assert_eq!(term("```\nplain\n```"), "    plain\n");
+    }
+
+    #[test]
+    fn test_events_render_like_tree() {
+        renderer::assert_events_render_like_tree(&TermWriter::new(), renderer::EVENTS_FIXTURE);
+    }
+}
@@ -0,0 +1,358 @@
+use crate::parser::{Document, Event, ParseError, Tag, TagEnd};
+use crate::renderer::{self, RenderError, Renderer};
+use std::cell::RefCell;
+use std::fmt;
+use std::io;
+
+// Layout
+pub(crate) const DEFAULT_WIDTH: usize = 80;
+const NEWLINE: char = '\n';
+const SPACE: char = ' ';
+const CODE_INDENT: &str = "    ";
+
+// Terminal escape sequences (written by `TermWriter`, zero columns wide)
+const ESC: char = '\x1b';
+const CSI_START: char = '[';
+const OSC_START: char = ']';
+const BEL: char = '\x07';
+const ST_END: char = '\\';
+
+// Heading underlines (level 1, deeper levels)
+const UNDERLINE_TITLE: char = '=';
+const UNDERLINE_SUBTITLE: char = '-';
+
+// Links and images
+const LINK_URL_OPEN: &str = " [";
+const LINK_URL_CLOSE: char = ']';
+const IMAGE_OPEN: &str = "[Image: ";
+const IMAGE_CLOSE: char = ']';
+
+/// Plain-text writer for search indexing, link previews and email digests
+///
+/// Produces readable text with no markup:
+/// - Headings underlined with `=` (level 1) or `-` (deeper levels)
+/// - Paragraphs word-wrapped at the configured width, keeping line breaks
+/// - Bold and italic as plain text; links followed by their URL in
+///   brackets (`docs [https://...]`) unless the text is the URL
+/// - Images as `[Image: alt text]`
+/// - Code blocks indented by four spaces, never wrapped
+/// - One blank line between blocks, a single trailing newline
+///
+/// # Example
+/// ```
+/// use wtf::parser::MarkdownParser;
+/// use wtf::text_writer::TextWriter;
+///
+/// let doc = MarkdownParser::parse("# Hi\nSee [docs](https://example.com)").unwrap();
+/// let text = TextWriter::new().write_text(&doc).unwrap();
+/// assert_eq!(text, "Hi\n==\n\nSee docs [https://example.com]\n");
+/// ```
+pub struct TextWriter {
+    /// Wrap column (0 disables wrapping)
+    width: usize,
+    state: RefCell<TextState>,
+}
+
+/// The block being collected (blocks are written whole so they can be
+/// wrapped), and whether one has been written yet
+#[derive(Default)]
+struct TextState {
+    block: String,
+    /// URL and start offset in `block` of each open link
+    links: Vec<(String, usize)>,
+    started: bool,
+}
+
+impl TextWriter {
+    /// Creates a writer that wraps at 80 columns
+    pub fn new() -> Self {
+        Self {
+            width: DEFAULT_WIDTH,
+            state: RefCell::new(TextState::default()),
+        }
+    }
+
+    /// Sets the column paragraphs are wrapped at (0 disables wrapping)
+    ///
+    /// Words longer than the width are kept whole on a line of their own.
+    pub fn with_width(mut self, width: usize) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Converts a parsed markdown document to plain text
+    ///
+    /// # Returns
+    /// * `Ok(String)` - The text, ending in a newline unless it's empty
+    /// * `Err(RenderError)` - If writing fails
+    pub fn write_text(&self, document: &Document) -> Result<String, RenderError> {
+        renderer::render_to_string(self, document)
+    }
+
+    /// Streams the text for a document into a writer
+    ///
+    /// # Returns
+    /// * `Err(RenderError)` - If the writer returns an error
+    pub fn write_text_to<W: io::Write>(
+        &self,
+        document: &Document,
+        writer: W,
+    ) -> Result<(), RenderError> {
+        renderer::render_to_io(self, document, writer)
+    }
+
+    /// Renders an event stream (e.g. `MarkdownParser::events`) to plain text
+    ///
+    /// # Returns
+    /// * `Ok(String)` - The same text as `write_text` on the events' tree
+    /// * `Err(RenderError)` - For the first parse error in the stream
+    pub fn write_text_events<'a, I>(&self, events: I) -> Result<String, RenderError>
+    where
+        I: IntoIterator<Item = Result<Event<'a>, ParseError>>,
+    {
+        renderer::render_events_to_string(self, events)
+    }
+
+    /// Writes a finished block, separated from the previous one by a blank
+    /// line
+    fn write_block(&self, out: &mut dyn fmt::Write, text: &str) -> fmt::Result {
+        let mut state = self.state.borrow_mut();
+        if state.started {
+            out.write_char(NEWLINE)?;
+        }
+        state.started = true;
+        out.write_str(text)?;
+        out.write_char(NEWLINE)
+    }
+}
+
+impl Default for TextWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer for TextWriter {
+    type Error = RenderError;
+
+    fn begin(&self, _out: &mut dyn fmt::Write) -> Result<(), RenderError> {
+        self.state.replace(TextState::default());
+        Ok(())
+    }
+
+    fn start(&self, _out: &mut dyn fmt::Write, tag: &Tag) -> Result<(), RenderError> {
+        let mut state = self.state.borrow_mut();
+        match tag {
+            Tag::Heading(_) | Tag::Paragraph => state.block.clear(),
+            Tag::Link(url) => {
+                let start = state.block.len();
+                state.links.push((url.to_string(), start));
+            }
+            Tag::Section(_) | Tag::Bold | Tag::Italic => {}
+        }
+        Ok(())
+    }
+
+    fn end(&self, out: &mut dyn fmt::Write, tag: TagEnd) -> Result<(), RenderError> {
+        let block = match tag {
+            TagEnd::Heading(level) => {
+                let title = std::mem::take(&mut self.state.borrow_mut().block);
+                let underline = if level == 1 {
+                    UNDERLINE_TITLE
+                } else {
+                    UNDERLINE_SUBTITLE
+                };
+                let underline: String =
+                    std::iter::repeat_n(underline, title.chars().count()).collect();
+                format!("{}{}{}", title, NEWLINE, underline)
+            }
+            TagEnd::Paragraph => wrap(
+                &std::mem::take(&mut self.state.borrow_mut().block),
+                self.width,
+            ),
+            TagEnd::Link => {
+                let mut state = self.state.borrow_mut();
+                if let Some((url, start)) = state.links.pop()
+                    && state.block[start..] != url
+                {
+                    state.block.push_str(LINK_URL_OPEN);
+                    state.block.push_str(&url);
+                    state.block.push(LINK_URL_CLOSE);
+                }
+                return Ok(());
+            }
+            TagEnd::Section(_) | TagEnd::Bold | TagEnd::Italic => return Ok(()),
+        };
+        Ok(self.write_block(out, &block)?)
+    }
+
+    fn text(&self, _out: &mut dyn fmt::Write, text: &str) -> Result<(), RenderError> {
+        self.state.borrow_mut().block.push_str(text);
+        Ok(())
+    }
+
+    fn line_break(&self, _out: &mut dyn fmt::Write) -> Result<(), RenderError> {
+        self.state.borrow_mut().block.push(NEWLINE);
+        Ok(())
+    }
+
+    fn image(
+        &self,
+        out: &mut dyn fmt::Write,
+        alt_text: &str,
+        url: &str,
+    ) -> Result<(), RenderError> {
+        let label = if alt_text.is_empty() { url } else { alt_text };
+        Ok(self.write_block(out, &format!("{}{}{}", IMAGE_OPEN, label, IMAGE_CLOSE))?)
+    }
+
+    fn code_block(
+        &self,
+        out: &mut dyn fmt::Write,
+        _info: &str,
+        code: &str,
+    ) -> Result<(), RenderError> {
+        // Blank lines stay empty rather than ending in the indent
+        let indented: Vec<String> = code
+            .split(NEWLINE)
+            .map(|line| {
+                if line.is_empty() {
+                    String::new()
+                } else {
+                    format!("{}{}", CODE_INDENT, line)
+                }
+            })
+            .collect();
+        Ok(self.write_block(out, &indented.join("\n"))?)
+    }
+}
+
+/// Word-wraps text at `width` columns, keeping its line breaks
+///
+/// Runs of whitespace between words collapse to one space. A width of 0
+/// only does that, without wrapping. Escape sequences don't count towards
+/// the width, so styled terminal text wraps like the plain text.
+pub(crate) fn wrap(text: &str, width: usize) -> String {
+    let mut wrapped = String::with_capacity(text.len());
+    for (i, line) in text.split(NEWLINE).enumerate() {
+        if i > 0 {
+            wrapped.push(NEWLINE);
+        }
+        let mut column = 0;
+        for word in line.split_whitespace() {
+            let len = display_width(word);
+            if column > 0 && width > 0 && column + 1 + len > width {
+                wrapped.push(NEWLINE);
+                column = 0;
+            } else if column > 0 {
+                wrapped.push(SPACE);
+                column += 1;
+            }
+            wrapped.push_str(word);
+            column += len;
+        }
+    }
+    wrapped
+}
+
+/// Number of columns text takes, skipping ANSI escape sequences (CSI
+/// `ESC [ ... m` and OSC `ESC ] ... ESC \`)
+pub(crate) fn display_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch != ESC {
+            width += 1;
+            continue;
+        }
+        match chars.next() {
+            Some(CSI_START) => {
+                for ch in chars.by_ref() {
+                    if ('@'..='~').contains(&ch) {
+                        break;
+                    }
+                }
+            }
+            Some(OSC_START) => {
+                while let Some(ch) = chars.next() {
+                    if ch == BEL || (ch == ESC && chars.next() == Some(ST_END)) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    width
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::MarkdownParser;
+
+    fn text(source: &str) -> String {
+        TextWriter::new()
+            .write_text(&MarkdownParser::parse(source).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_blocks() {
+        assert_eq!(
+            text(
+                "Intro *with* **style**\nsecond line\n# Title\n## Sub\n![A cat](cat.png)\n![](bare.png)\n```sh\nls\n\ncd /\n```"
+            ),
+            "Intro with style\nsecond line\n\nTitle\n=====\n\nSub\n---\n\n\
+             [Image: A cat]\n\n[Image: bare.png]\n\n    ls\n\n    cd /\n"
+        );
+        assert_eq!(text(""), "");
+    }
+
+    #[test]
+    fn test_links() {
+        assert_eq!(
+            text("[docs](https://example.com) and [https://x.org](https://x.org)"),
+            "docs [https://example.com] and https://x.org\n"
+        );
+        assert_eq!(text("# [Home](/)"), "Home [/]\n========\n");
+    }
+
+    #[test]
+    fn test_wrapping() {
+        let source = "one two three four five six seven\nshort\n\nunbreakable-long-word x";
+        let writer = TextWriter::new().with_width(10);
+        assert_eq!(
+            writer
+                .write_text(&MarkdownParser::parse(source).unwrap())
+                .unwrap(),
+            "one two\nthree four\nfive six\nseven\nshort\n\nunbreakable-long-word\nx\n"
+        );
+
+        let writer = TextWriter::new().with_width(0);
+        let long = "word ".repeat(40);
+        assert_eq!(
+            writer
+                .write_text(&MarkdownParser::parse(&long).unwrap())
+                .unwrap(),
+            format!("{}\n", long.trim_end())
+        );
+    }
+
+    #[test]
+    fn test_events_render_like_tree() {
+        renderer::assert_events_render_like_tree(&TextWriter::new(), renderer::EVENTS_FIXTURE);
+    }
+
+    #[test]
+    fn test_write_text_to_matches_write_text() {
+        let doc = MarkdownParser::parse("# A\nb").unwrap();
+        let writer = TextWriter::new();
+        let mut bytes = Vec::new();
+        writer.write_text_to(&doc, &mut bytes).unwrap();
+        assert_eq!(
+            String::from_utf8(bytes).unwrap(),
+            writer.write_text(&doc).unwrap()
+        );
+    }
+}
@@ -0,0 +1,391 @@
+use crate::front_matter::FrontMatter;
+use crate::parser::{Document, InlineNode, ParseError};
+use crate::visit::{self, VisitorMut};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Constants
+const VARIABLE_OPEN: &str = "{{";
+const VARIABLE_CLOSE: &str = "}}";
+const ESCAPE_CHAR: char = '\\';
+const SITE_PREFIX: &str = "site.";
+const PAGE_PREFIX: &str = "page.";
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Site-wide variables file in the content root (`key: value` lines)
+pub const SITE_CONFIG_FILENAME: &str = "_site.conf";
+
+/// Built-in page variable: last modification date of the page (YYYY-MM-DD)
+pub const PAGE_UPDATED: &str = "page.updated";
+
+/// Built-in page variable: URL path the page is served at
+pub const PAGE_URL: &str = "page.url";
+
+// ============================================================================
+// ERROR HANDLING
+// ============================================================================
+
+/// Errors from loading `_site.conf`
+#[derive(Debug, Clone, PartialEq)]
+pub enum SiteConfigError {
+    IoError { path: PathBuf, source: String },
+    ParseError { path: PathBuf, source: ParseError },
+}
+
+impl fmt::Display for SiteConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SiteConfigError::IoError { path, source } => {
+                write!(f, "IO error reading {}: {}", path.display(), source)
+            }
+            SiteConfigError::ParseError { path, source } => {
+                write!(f, "Invalid {}: {}", path.display(), source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SiteConfigError {}
+
+// ============================================================================
+// VARIABLES
+// ============================================================================
+
+/// Named values substituted into page text as `{{ name }}`
+///
+/// Variables are namespaced by where they come from:
+/// - `site.*` - from `_site.conf` in the content root
+/// - `page.*` - from the page's front matter, plus `page.updated` and `page.url`
+///
+/// Substitution is plain text replacement over `InlineNode::Text` nodes, so
+/// no code is ever executed and values can't inject markdown or HTML.
+/// Referencing an undefined variable is an error; `\{{` produces a literal `{{`.
+#[derive(Debug, Clone, Default)]
+pub struct Variables {
+    values: HashMap<String, String>,
+}
+
+impl Variables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets a variable, replacing any previous value
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.values.insert(name.to_string(), value.to_string());
+    }
+
+    /// Sets a variable (builder style)
+    pub fn with(mut self, name: &str, value: &str) -> Self {
+        self.set(name, value);
+        self
+    }
+
+    /// Returns the value of a variable, if defined
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    /// Adds every entry of a site config as `site.<key>`
+    pub fn with_site_config(mut self, config: &FrontMatter) -> Self {
+        for (key, value) in config.entries() {
+            self.set(&format!("{}{}", SITE_PREFIX, key), value);
+        }
+        self
+    }
+
+    /// Adds every front matter entry as `page.<key>`
+    pub fn with_front_matter(mut self, front_matter: &FrontMatter) -> Self {
+        for (key, value) in front_matter.entries() {
+            self.set(&format!("{}{}", PAGE_PREFIX, key), value);
+        }
+        self
+    }
+
+    /// Substitutes all `{{ name }}` references in a string
+    ///
+    /// # Returns
+    /// * `Ok(String)` - Text with every reference replaced
+    /// * `Err(ParseError)` - For undefined variables or malformed references
+    pub fn substitute_text(&self, text: &str) -> Result<String, ParseError> {
+        let mut output = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(open) = rest.find(VARIABLE_OPEN) {
+            // "\{{" is an escaped literal "{{"
+            if rest[..open].ends_with(ESCAPE_CHAR) {
+                output.push_str(&rest[..open - ESCAPE_CHAR.len_utf8()]);
+                output.push_str(VARIABLE_OPEN);
+                rest = &rest[open + VARIABLE_OPEN.len()..];
+                continue;
+            }
+
+            output.push_str(&rest[..open]);
+            let after_open = &rest[open + VARIABLE_OPEN.len()..];
+
+            let Some(close) = after_open.find(VARIABLE_CLOSE) else {
+                return Err(ParseError::InvalidVariable {
+                    expression: rest[open..].to_string(),
+                    reason: format!("missing closing '{}'", VARIABLE_CLOSE),
+                });
+            };
+
+            let name = after_open[..close].trim();
+            if !is_variable_name(name) {
+                return Err(ParseError::InvalidVariable {
+                    expression: format!(
+                        "{}{}{}",
+                        VARIABLE_OPEN,
+                        &after_open[..close],
+                        VARIABLE_CLOSE
+                    ),
+                    reason: "names may only contain letters, digits, '_', '-' and '.'".to_string(),
+                });
+            }
+
+            let value = self
+                .get(name)
+                .ok_or_else(|| ParseError::UndefinedVariable {
+                    name: name.to_string(),
+                })?;
+
+            output.push_str(value);
+            rest = &after_open[close + VARIABLE_CLOSE.len()..];
+        }
+
+        output.push_str(rest);
+        Ok(output)
+    }
+
+    /// Substitutes variables in every text node of a document, in place
+    ///
+    /// Stops at the first error; the document may then be partially substituted.
+    pub fn substitute_document(&self, document: &mut Document) -> Result<(), ParseError> {
+        let mut pass = SubstitutionPass {
+            variables: self,
+            error: None,
+        };
+        pass.visit_document_mut(document);
+
+        match pass.error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
+
+/// `VisitorMut` pass substituting variables into text nodes
+struct SubstitutionPass<'a> {
+    variables: &'a Variables,
+    error: Option<ParseError>,
+}
+
+impl VisitorMut for SubstitutionPass<'_> {
+    fn visit_inline_mut(&mut self, node: &mut InlineNode) {
+        if self.error.is_some() {
+            return;
+        }
+
+        if let InlineNode::Text(text) = node
+            && text.contains(VARIABLE_OPEN)
+        {
+            match self.variables.substitute_text(text) {
+                Ok(substituted) => *text = substituted.into(),
+                Err(error) => self.error = Some(error),
+            }
+        }
+        visit::walk_inline_mut(self, node);
+    }
+}
+
+/// Checks whether a string is a valid variable name (`site.name`, `page.updated`)
+fn is_variable_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|ch| ch.is_alphanumeric() || matches!(ch, '_' | '-' | '.'))
+}
+
+/// Reads the site config (`_site.conf`) of a content root
+///
+/// # Returns
+/// * `Ok(FrontMatter)` - The config's entries (none without a `_site.conf`)
+/// * `Err(SiteConfigError)` - If the file can't be read or parsed
+pub fn load_site_config(content_root: &Path) -> Result<FrontMatter, SiteConfigError> {
+    let path = content_root.join(SITE_CONFIG_FILENAME);
+    if !path.is_file() {
+        return Ok(FrontMatter::new());
+    }
+
+    let text = fs::read_to_string(&path).map_err(|e| SiteConfigError::IoError {
+        path: path.clone(),
+        source: e.to_string(),
+    })?;
+    FrontMatter::parse(&text).map_err(|source| SiteConfigError::ParseError { path, source })
+}
+
+/// Formats a timestamp as an ISO 8601 date (YYYY-MM-DD, UTC)
+pub fn format_date(time: SystemTime) -> String {
+    let days = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() / SECONDS_PER_DAY)
+        .unwrap_or(0) as i64;
+
+    // Civil-from-days (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{BlockNode, MarkdownParser};
+    use std::time::Duration;
+
+    #[test]
+    fn test_substitute_text() {
+        let variables = Variables::new()
+            .with("site.name", "Handbook")
+            .with("page.owner", "Ops");
+        assert_eq!(
+            variables
+                .substitute_text("{{ site.name }} by {{page.owner}}")
+                .unwrap(),
+            "Handbook by Ops"
+        );
+    }
+
+    #[test]
+    fn test_escaped_braces() {
+        let variables = Variables::new();
+        assert_eq!(
+            variables
+                .substitute_text("Use \\{{ name }} literally")
+                .unwrap(),
+            "Use {{ name }} literally"
+        );
+    }
+
+    #[test]
+    fn test_undefined_variable() {
+        let result = Variables::new().substitute_text("Hi {{ site.missing }}");
+        assert_eq!(
+            result,
+            Err(ParseError::UndefinedVariable {
+                name: "site.missing".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_invalid_variable() {
+        let variables = Variables::new();
+        assert!(matches!(
+            variables.substitute_text("{{ 1 + 1 }}"),
+            Err(ParseError::InvalidVariable { .. })
+        ));
+        assert!(matches!(
+            variables.substitute_text("{{ site.name"),
+            Err(ParseError::InvalidVariable { .. })
+        ));
+        assert!(matches!(
+            variables.substitute_text("{{}}"),
+            Err(ParseError::InvalidVariable { .. })
+        ));
+    }
+
+    #[test]
+    fn test_site_config_and_front_matter_namespaces() {
+        let site = FrontMatter::parse("name: Handbook").unwrap();
+        let page = FrontMatter::parse("owner: Ops").unwrap();
+        let variables = Variables::new()
+            .with_site_config(&site)
+            .with_front_matter(&page);
+        assert_eq!(variables.get("site.name"), Some("Handbook"));
+        assert_eq!(variables.get("page.owner"), Some("Ops"));
+        assert_eq!(variables.get("name"), None);
+    }
+
+    #[test]
+    fn test_load_site_config() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        assert!(load_site_config(root).unwrap().is_empty());
+
+        std::fs::write(root.join(SITE_CONFIG_FILENAME), "name: Handbook\n").unwrap();
+        let config = load_site_config(root).unwrap();
+        assert_eq!(config.get("name"), Some("Handbook"));
+
+        std::fs::write(root.join(SITE_CONFIG_FILENAME), "no separator\n").unwrap();
+        assert!(matches!(
+            load_site_config(root),
+            Err(SiteConfigError::ParseError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_substitute_document() {
+        let mut doc = MarkdownParser::parse(
+            "# {{ site.name }}\n\n**{{ page.owner }}** [{{site.name}}](/{{x}})",
+        )
+        .unwrap();
+        let variables = Variables::new()
+            .with("site.name", "Handbook")
+            .with("page.owner", "Ops");
+        variables.substitute_document(&mut doc).unwrap();
+
+        let section = &doc.sections()[0];
+        assert_eq!(section.title(), &[InlineNode::Text("Handbook".into())]);
+        match &section.content()[0] {
+            BlockNode::Paragraph(inlines) => {
+                assert_eq!(
+                    inlines[0],
+                    InlineNode::Bold(vec![InlineNode::Text("Ops".into())])
+                );
+                // URLs are not substituted
+                assert_eq!(
+                    inlines[2],
+                    InlineNode::Link {
+                        text: vec![InlineNode::Text("Handbook".into())],
+                        url: "/{{x}}".into(),
+                    }
+                );
+            }
+            _ => panic!("Expected Paragraph"),
+        }
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date(UNIX_EPOCH), "1970-01-01");
+        assert_eq!(
+            format_date(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+            "2000-02-29"
+        );
+        assert_eq!(
+            format_date(UNIX_EPOCH + Duration::from_secs(1_792_281_600)),
+            "2026-10-18"
+        );
+    }
+}
@@ -0,0 +1,316 @@
+//! Visitor traits for walking and transforming a `Document`
+//!
+//! Implement `Visitor` to inspect a tree or `VisitorMut` to rewrite it in
+//! place. Every method has a default that recurses into the node's children
+//! through the matching `walk_*` function, so a pass only overrides the nodes
+//! it cares about. An override that still wants to reach the children calls
+//! the `walk_*` function itself.
+
+use crate::parser::{BlockNode, Document, InlineNode, Section, SectionMut};
+
+// ============================================================================
+// READ-ONLY VISITOR
+// ============================================================================
+
+/// Read-only traversal over a document tree
+///
+/// # Example
+/// ```
+/// use wtf::parser::{MarkdownParser, Section};
+/// use wtf::visit::{self, Visitor};
+///
+/// struct HeadingCounter(usize);
+///
+/// impl Visitor for HeadingCounter {
+///     fn visit_section(&mut self, section: &Section) {
+///         self.0 += 1;
+///         visit::walk_section(self, section);
+///     }
+/// }
+///
+/// let doc = MarkdownParser::parse("# A\n## B\n# C").unwrap();
+/// let mut counter = HeadingCounter(0);
+/// counter.visit_document(&doc);
+/// assert_eq!(counter.0, 3);
+/// ```
+pub trait Visitor {
+    fn visit_document(&mut self, document: &Document) {
+        walk_document(self, document);
+    }
+
+    fn visit_section(&mut self, section: &Section) {
+        walk_section(self, section);
+    }
+
+    fn visit_block(&mut self, block: &BlockNode) {
+        walk_block(self, block);
+    }
+
+    fn visit_inline(&mut self, node: &InlineNode) {
+        walk_inline(self, node);
+    }
+}
+
+/// Visits the preamble blocks, then every top-level section
+pub fn walk_document<V: Visitor + ?Sized>(visitor: &mut V, document: &Document) {
+    for block in document.content() {
+        visitor.visit_block(block);
+    }
+
+    for section in document.sections() {
+        visitor.visit_section(section);
+    }
+}
+
+/// Visits the title inlines, the content blocks, then every subsection
+pub fn walk_section<V: Visitor + ?Sized>(visitor: &mut V, section: &Section) {
+    for node in section.title() {
+        visitor.visit_inline(node);
+    }
+
+    for block in section.content() {
+        visitor.visit_block(block);
+    }
+
+    for subsection in section.subsections() {
+        visitor.visit_section(subsection);
+    }
+}
+
+/// Visits the inline children of a block (images and code blocks have none)
+pub fn walk_block<V: Visitor + ?Sized>(visitor: &mut V, block: &BlockNode) {
+    match block {
+        BlockNode::Paragraph(inlines) => {
+            for node in inlines {
+                visitor.visit_inline(node);
+            }
+        }
+        BlockNode::Image { .. } | BlockNode::CodeBlock { .. } => {}
+    }
+}
+
+/// Visits the children of bold, italic and link nodes
+pub fn walk_inline<V: Visitor + ?Sized>(visitor: &mut V, node: &InlineNode) {
+    match node {
+        InlineNode::Text(_) | InlineNode::LineBreak => {}
+        InlineNode::Bold(children) | InlineNode::Italic(children) => {
+            for child in children {
+                visitor.visit_inline(child);
+            }
+        }
+        InlineNode::Link { text, .. } => {
+            for child in text {
+                visitor.visit_inline(child);
+            }
+        }
+    }
+}
+
+// ============================================================================
+// MUTABLE VISITOR
+// ============================================================================
+
+/// In-place transformation of a document tree
+///
+/// Besides the per-node hooks, `visit_blocks_mut` and `visit_inlines_mut`
+/// receive whole lists, so a pass can drop, insert or merge nodes (e.g. strip
+/// every image). Sections arrive as `SectionMut` handles, which add or
+/// remove subsections only through the level-validating methods.
+///
+/// # Example
+/// ```
+/// use wtf::parser::{InlineNode, MarkdownParser};
+/// use wtf::visit::{self, VisitorMut};
+///
+/// struct HttpsUpgrade;
+///
+/// impl VisitorMut for HttpsUpgrade {
+///     fn visit_inline_mut(&mut self, node: &mut InlineNode) {
+///         if let InlineNode::Link { url, .. } = node {
+///             if let Some(rest) = url.strip_prefix("http://") {
+///                 *url = format!("https://{}", rest).into();
+///             }
+///         }
+///         visit::walk_inline_mut(self, node);
+///     }
+/// }
+///
+/// let mut doc = MarkdownParser::parse("[site](http://example.com)").unwrap();
+/// HttpsUpgrade.visit_document_mut(&mut doc);
+/// ```
+pub trait VisitorMut {
+    fn visit_document_mut(&mut self, document: &mut Document) {
+        walk_document_mut(self, document);
+    }
+
+    fn visit_section_mut(&mut self, section: SectionMut) {
+        walk_section_mut(self, section);
+    }
+
+    fn visit_blocks_mut(&mut self, blocks: &mut Vec<BlockNode>) {
+        walk_blocks_mut(self, blocks);
+    }
+
+    fn visit_block_mut(&mut self, block: &mut BlockNode) {
+        walk_block_mut(self, block);
+    }
+
+    fn visit_inlines_mut(&mut self, nodes: &mut Vec<InlineNode>) {
+        walk_inlines_mut(self, nodes);
+    }
+
+    fn visit_inline_mut(&mut self, node: &mut InlineNode) {
+        walk_inline_mut(self, node);
+    }
+}
+
+/// Visits the preamble block list, then every top-level section
+pub fn walk_document_mut<V: VisitorMut + ?Sized>(visitor: &mut V, document: &mut Document) {
+    visitor.visit_blocks_mut(document.content_vec_mut());
+
+    for section in document.sections_iter_mut() {
+        visitor.visit_section_mut(section);
+    }
+}
+
+/// Visits the title, the content block list, then every subsection
+pub fn walk_section_mut<V: VisitorMut + ?Sized>(visitor: &mut V, mut section: SectionMut) {
+    visitor.visit_inlines_mut(section.title_mut());
+    visitor.visit_blocks_mut(section.content_vec_mut());
+
+    for subsection in section.subsections_iter_mut() {
+        visitor.visit_section_mut(subsection);
+    }
+}
+
+/// Visits each block in a list
+pub fn walk_blocks_mut<V: VisitorMut + ?Sized>(visitor: &mut V, blocks: &mut Vec<BlockNode>) {
+    for block in blocks {
+        visitor.visit_block_mut(block);
+    }
+}
+
+/// Visits the inline list of a paragraph (images and code blocks have none)
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(visitor: &mut V, block: &mut BlockNode) {
+    match block {
+        BlockNode::Paragraph(inlines) => visitor.visit_inlines_mut(inlines),
+        BlockNode::Image { .. } | BlockNode::CodeBlock { .. } => {}
+    }
+}
+
+/// Visits each inline node in a list
+pub fn walk_inlines_mut<V: VisitorMut + ?Sized>(visitor: &mut V, nodes: &mut Vec<InlineNode>) {
+    for node in nodes {
+        visitor.visit_inline_mut(node);
+    }
+}
+
+/// Visits the child lists of bold, italic and link nodes
+pub fn walk_inline_mut<V: VisitorMut + ?Sized>(visitor: &mut V, node: &mut InlineNode) {
+    match node {
+        InlineNode::Text(_) | InlineNode::LineBreak => {}
+        InlineNode::Bold(children) | InlineNode::Italic(children) => {
+            visitor.visit_inlines_mut(children)
+        }
+        InlineNode::Link { text, .. } => visitor.visit_inlines_mut(text),
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::MarkdownParser;
+
+    struct HeadingCollector {
+        headings: Vec<(u8, usize)>,
+    }
+
+    impl Visitor for HeadingCollector {
+        fn visit_section(&mut self, section: &Section) {
+            self.headings.push((section.level(), section.title().len()));
+            walk_section(self, section);
+        }
+    }
+
+    struct LinkCollector {
+        urls: Vec<String>,
+    }
+
+    impl Visitor for LinkCollector {
+        fn visit_inline(&mut self, node: &InlineNode) {
+            if let InlineNode::Link { url, .. } = node {
+                self.urls.push(url.to_string());
+            }
+            walk_inline(self, node);
+        }
+    }
+
+    struct ImageStripper;
+
+    impl VisitorMut for ImageStripper {
+        fn visit_blocks_mut(&mut self, blocks: &mut Vec<BlockNode>) {
+            blocks.retain(|block| !matches!(block, BlockNode::Image { .. }));
+            walk_blocks_mut(self, blocks);
+        }
+    }
+
+    struct UrlRewriter;
+
+    impl VisitorMut for UrlRewriter {
+        fn visit_inline_mut(&mut self, node: &mut InlineNode) {
+            if let InlineNode::Link { url, .. } = node {
+                *url = url.replace("old.example", "new.example").into();
+            }
+            walk_inline_mut(self, node);
+        }
+    }
+
+    #[test]
+    fn test_collect_headings_in_document_order() {
+        let doc = MarkdownParser::parse("# A\n## B\n### C\n## D\n# E").unwrap();
+        let mut collector = HeadingCollector {
+            headings: Vec::new(),
+        };
+        collector.visit_document(&doc);
+        assert_eq!(
+            collector.headings,
+            vec![(1, 1), (2, 1), (3, 1), (2, 1), (1, 1)]
+        );
+    }
+
+    #[test]
+    fn test_collect_nested_links() {
+        let doc = MarkdownParser::parse(
+            "[a](1)\n\n# [b](2)\n\n**[c](3)** and *[d](4)*\n\n![img](not-a-link)",
+        )
+        .unwrap();
+        let mut collector = LinkCollector { urls: Vec::new() };
+        collector.visit_document(&doc);
+        assert_eq!(collector.urls, vec!["1", "2", "3", "4"]);
+    }
+
+    #[test]
+    fn test_strip_images() {
+        let mut doc =
+            MarkdownParser::parse("![a](a.png)\n\nText\n\n# H\n![b](b.png)\n\nMore").unwrap();
+        ImageStripper.visit_document_mut(&mut doc);
+        assert_eq!(doc, MarkdownParser::parse("Text\n\n# H\nMore").unwrap());
+    }
+
+    #[test]
+    fn test_rewrite_urls() {
+        let mut doc =
+            MarkdownParser::parse("# [t](http://old.example/a)\n**[x](http://old.example/b)**")
+                .unwrap();
+        UrlRewriter.visit_document_mut(&mut doc);
+        assert_eq!(
+            doc,
+            MarkdownParser::parse("# [t](http://new.example/a)\n**[x](http://new.example/b)**")
+                .unwrap()
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use std::io::{self, Write};
+
+// Record signatures
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const END_OF_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+
+// Header fields
+const VERSION_NEEDED: u16 = 10;
+const VERSION_MADE_BY: u16 = 20;
+/// General purpose flag bit 11: names are UTF-8
+const FLAG_UTF8: u16 = 0x0800;
+const METHOD_STORED: u16 = 0;
+/// DOS date for 1980-01-01, the earliest a ZIP can record (time 00:00)
+const DOS_DATE: u16 = 0x0021;
+const DOS_TIME: u16 = 0;
+
+const CRC32_POLYNOMIAL: u32 = 0xEDB8_8320;
+
+/// Minimal ZIP archive writer storing entries uncompressed
+///
+/// Enough for EPUB packaging, which requires its `mimetype` entry stored
+/// (not deflated) and first in the archive. Archives over 4 GiB (ZIP64)
+/// aren't supported.
+pub(crate) struct ZipWriter<W: Write> {
+    inner: W,
+    /// Bytes written so far, i.e. the offset of the next record
+    offset: u32,
+    entries: Vec<CentralEntry>,
+}
+
+/// What the central directory records about each entry
+struct CentralEntry {
+    name: String,
+    crc: u32,
+    size: u32,
+    offset: u32,
+}
+
+impl<W: Write> ZipWriter<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        Self {
+            inner,
+            offset: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Adds a file stored without compression
+    ///
+    /// # Arguments
+    /// * `name` - Path inside the archive, `/`-separated
+    /// * `data` - The file's contents
+    pub(crate) fn add_stored(&mut self, name: &str, data: &[u8]) -> io::Result<()> {
+        let size = to_u32(data.len())?;
+        let crc = crc32(data);
+
+        let mut header = Vec::with_capacity(30 + name.len());
+        put_u32(&mut header, LOCAL_HEADER_SIGNATURE);
+        put_u16(&mut header, VERSION_NEEDED);
+        put_u16(&mut header, name_flags(name));
+        put_u16(&mut header, METHOD_STORED);
+        put_u16(&mut header, DOS_TIME);
+        put_u16(&mut header, DOS_DATE);
+        put_u32(&mut header, crc);
+        put_u32(&mut header, size); // compressed size
+        put_u32(&mut header, size);
+        put_u16(&mut header, to_u16(name.len())?);
+        put_u16(&mut header, 0); // extra field length
+        header.extend_from_slice(name.as_bytes());
+
+        self.inner.write_all(&header)?;
+        self.inner.write_all(data)?;
+
+        self.entries.push(CentralEntry {
+            name: name.to_string(),
+            crc,
+            size,
+            offset: self.offset,
+        });
+        self.offset = to_u32(self.offset as usize + header.len() + data.len())?;
+        Ok(())
+    }
+
+    /// Writes the central directory, completing the archive
+    ///
+    /// # Returns
+    /// * `Ok(W)` - The underlying writer
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        let mut directory = Vec::new();
+        for entry in &self.entries {
+            put_u32(&mut directory, CENTRAL_HEADER_SIGNATURE);
+            put_u16(&mut directory, VERSION_MADE_BY);
+            put_u16(&mut directory, VERSION_NEEDED);
+            put_u16(&mut directory, name_flags(&entry.name));
+            put_u16(&mut directory, METHOD_STORED);
+            put_u16(&mut directory, DOS_TIME);
+            put_u16(&mut directory, DOS_DATE);
+            put_u32(&mut directory, entry.crc);
+            put_u32(&mut directory, entry.size);
+            put_u32(&mut directory, entry.size);
+            put_u16(&mut directory, to_u16(entry.name.len())?);
+            put_u16(&mut directory, 0); // extra field length
+            put_u16(&mut directory, 0); // comment length
+            put_u16(&mut directory, 0); // disk number
+            put_u16(&mut directory, 0); // internal attributes
+            put_u32(&mut directory, 0); // external attributes
+            put_u32(&mut directory, entry.offset);
+            directory.extend_from_slice(entry.name.as_bytes());
+        }
+
+        let count = to_u16(self.entries.len())?;
+        let mut end = Vec::with_capacity(22);
+        put_u32(&mut end, END_OF_DIRECTORY_SIGNATURE);
+        put_u16(&mut end, 0); // this disk
+        put_u16(&mut end, 0); // disk with the directory
+        put_u16(&mut end, count);
+        put_u16(&mut end, count);
+        put_u32(&mut end, to_u32(directory.len())?);
+        put_u32(&mut end, self.offset);
+        put_u16(&mut end, 0); // comment length
+
+        self.inner.write_all(&directory)?;
+        self.inner.write_all(&end)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+/// CRC-32 (IEEE) checksum, as ZIP records for each entry
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ CRC32_POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn name_flags(name: &str) -> u16 {
+    if name.is_ascii() { 0 } else { FLAG_UTF8 }
+}
+
+fn put_u16(buffer: &mut Vec<u8>, value: u16) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn to_u16(value: usize) -> io::Result<u16> {
+    u16::try_from(value).map_err(|_| too_large())
+}
+
+fn to_u32(value: usize) -> io::Result<u32> {
+    u32::try_from(value).map_err(|_| too_large())
+}
+
+fn too_large() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "archive too large for ZIP without ZIP64",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16_at(bytes: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes([bytes[at], bytes[at + 1]])
+    }
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b"application/epub+zip"), 0x2CAB_616F);
+    }
+
+    #[test]
+    fn test_archive_layout() {
+        let mut zip = ZipWriter::new(Vec::new());
+        zip.add_stored("mimetype", b"application/epub+zip").unwrap();
+        zip.add_stored("a/é.txt", b"hi").unwrap();
+        let bytes = zip.finish().unwrap();
+
+        // First entry: local header, name and data at offset 0
+        assert_eq!(u32_at(&bytes, 0), LOCAL_HEADER_SIGNATURE);
+        assert_eq!(u16_at(&bytes, 8), METHOD_STORED);
+        assert_eq!(u32_at(&bytes, 14), crc32(b"application/epub+zip"));
+        assert_eq!(u16_at(&bytes, 28), 0);
+        assert_eq!(&bytes[30..38], b"mimetype");
+        assert_eq!(&bytes[38..58], b"application/epub+zip");
+
+        // Second entry follows with the UTF-8 name flag set
+        assert_eq!(u32_at(&bytes, 58), LOCAL_HEADER_SIGNATURE);
+        assert_eq!(u16_at(&bytes, 64), FLAG_UTF8);
+
+        // End record points at a two-entry directory ending where it starts
+        let end = bytes.len() - 22;
+        assert_eq!(u32_at(&bytes, end), END_OF_DIRECTORY_SIGNATURE);
+        assert_eq!(u16_at(&bytes, end + 10), 2);
+        let directory_size = u32_at(&bytes, end + 12) as usize;
+        let directory_offset = u32_at(&bytes, end + 16) as usize;
+        assert_eq!(directory_offset + directory_size, end);
+        assert_eq!(u32_at(&bytes, directory_offset), CENTRAL_HEADER_SIGNATURE);
+        assert_eq!(u32_at(&bytes, directory_offset + 42), 0);
+    }
+}
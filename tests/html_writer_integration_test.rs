@@ -216,3 +216,31 @@ The end.
     // Verify no newlines (minified)
     assert!(!html.contains('\n'));
 }
+
+#[test]
+fn test_render_programmatically_built_document() {
+    use wtf::parser::{BlockNode, Document, InlineNode, Section};
+
+    let text = |value: &str| vec![InlineNode::Text(value.to_string().into())];
+
+    let mut api = Section::new(1, text("API")).unwrap();
+    for name in ["parse", "write_html"] {
+        api.push_subsection(
+            Section::new(2, text(name))
+                .unwrap()
+                .with_block(BlockNode::Paragraph(text("Docs"))),
+        )
+        .unwrap();
+    }
+
+    let mut doc = Document::new();
+    doc.push_section(api).unwrap();
+    doc.insert_block(0, BlockNode::Paragraph(text("Generated")))
+        .unwrap();
+
+    let html = HtmlWriter::new().write_html(&doc).unwrap();
+    assert_eq!(
+        html,
+        "<p>Generated</p><h1>API</h1><h2>parse</h2><p>Docs</p><h2>write_html</h2><p>Docs</p>"
+    );
+}
@@ -0,0 +1,132 @@
+use std::io::BufReader;
+use wtf::parser::{MarkdownParser, TextEdit};
+
+/// Lines that cover every block kind, nesting changes, multi-line images
+/// and code fences
+const LINES: &[&str] = &[
+    "",
+    "",
+    "text",
+    "more *text*",
+    "**bold** [link](u)",
+    "# One",
+    "## Two",
+    "### Three",
+    "#",
+    "![alt](a.png)",
+    "![multi",
+    "line](b.png) tail",
+    "\\# escaped",
+    "**unclosed",
+    "```rust",
+    "```",
+    "let x = 1; # y",
+];
+
+/// Fragments inserted by random edits
+const FRAGMENTS: &[&str] = &[
+    "", "\n", "\n\n", "#", "# ", "## H\n", "x", "*", "**", "[", "](v)", "![", "\\", "é", "`", "```",
+];
+
+/// Small deterministic generator so failures are reproducible
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> usize {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (self.0 >> 33) as usize
+    }
+}
+
+fn random_source(rng: &mut Lcg) -> String {
+    let count = rng.next() % 12;
+    let lines: Vec<&str> = (0..count)
+        .map(|_| LINES[rng.next() % LINES.len()])
+        .collect();
+    let mut source = lines.join("\n");
+    if rng.next().is_multiple_of(2) {
+        source.push('\n');
+    }
+    source
+}
+
+/// Picks a random char boundary in `text`
+fn random_boundary(rng: &mut Lcg, text: &str) -> usize {
+    let boundaries: Vec<usize> = (0..=text.len())
+        .filter(|&i| text.is_char_boundary(i))
+        .collect();
+    boundaries[rng.next() % boundaries.len()]
+}
+
+#[test]
+fn test_reparse_matches_full_parse() {
+    let mut rng = Lcg(7);
+    let mut partial = 0;
+
+    for _ in 0..50_000 {
+        let source = random_source(&mut rng);
+        let Ok(mut doc) = MarkdownParser::parse(&source).map(|doc| doc.into_owned()) else {
+            continue;
+        };
+
+        // A few edits in a row, each applied to the previous result
+        let mut text = source;
+        for _ in 0..4 {
+            let a = random_boundary(&mut rng, &text);
+            let b = random_boundary(&mut rng, &text);
+            let range = a.min(b)..a.max(b);
+            let replacement = FRAGMENTS[rng.next() % FRAGMENTS.len()];
+
+            let mut edited = text.clone();
+            edited.replace_range(range.clone(), replacement);
+            let edit = TextEdit::new(range, replacement);
+
+            let before = doc.clone();
+            let expected = MarkdownParser::parse(&edited);
+            let result = MarkdownParser::reparse(&mut doc, &edited, &edit);
+
+            match expected {
+                Ok(expected) => {
+                    let reparsed =
+                        result.unwrap_or_else(|e| panic!("{:?} -> {:?}: {}", text, edited, e));
+                    if reparsed != (0..edited.len()) {
+                        partial += 1;
+                    }
+                    assert_eq!(doc, expected, "{:?} -> {:?}", text, edited);
+                    assert_eq!(doc.spans(), expected.spans(), "{:?} -> {:?}", text, edited);
+                    text = edited;
+                }
+                Err(error) => {
+                    assert_eq!(result, Err(error), "{:?} -> {:?}", text, edited);
+                    assert_eq!(doc, before);
+                    assert_eq!(doc.spans(), before.spans());
+                }
+            }
+        }
+    }
+
+    assert!(partial > 10_000);
+}
+
+#[test]
+fn test_parse_reader_matches_parse() {
+    let mut rng = Lcg(11);
+
+    for _ in 0..50_000 {
+        let source = random_source(&mut rng);
+        let expected = MarkdownParser::parse(&source);
+        let streamed = MarkdownParser::parse_reader(BufReader::with_capacity(3, source.as_bytes()));
+
+        match expected {
+            Ok(expected) => {
+                let streamed = streamed.unwrap_or_else(|e| panic!("{:?}: {}", source, e));
+                assert_eq!(streamed, expected, "{:?}", source);
+                assert_eq!(streamed.spans(), expected.spans(), "{:?}", source);
+            }
+            Err(error) => assert_eq!(streamed, Err(error), "{:?}", source),
+        }
+    }
+}
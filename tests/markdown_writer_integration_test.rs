@@ -0,0 +1,142 @@
+use wtf::markdown_writer::{MarkdownWriter, format_source};
+use wtf::parser::{BlockNode, Document, InlineNode, MarkdownParser, Section};
+
+/// Characters that exercise every piece of syntax the parser knows
+const ALPHABET: &[char] = &[
+    'a', 'b', ' ', '\n', '#', '*', '[', ']', '(', ')', '!', '\\', '{', '}', '`',
+];
+
+/// Small deterministic generator so failures are reproducible
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> usize {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (self.0 >> 33) as usize
+    }
+}
+
+#[test]
+fn test_roundtrip_random_sources() {
+    let writer = MarkdownWriter::new();
+    let mut rng = Lcg(42);
+    let mut parsed = 0;
+
+    for _ in 0..200_000 {
+        let len = rng.next() % 24;
+        let source: String = (0..len)
+            .map(|_| ALPHABET[rng.next() % ALPHABET.len()])
+            .collect();
+
+        let Ok(doc) = MarkdownParser::parse(&source) else {
+            continue;
+        };
+        parsed += 1;
+
+        let markdown = writer
+            .write_markdown(&doc)
+            .unwrap_or_else(|e| panic!("{:?}: {}", source, e));
+        assert_eq!(
+            MarkdownParser::parse(&markdown).as_ref(),
+            Ok(&doc),
+            "source {:?} wrote {:?}",
+            source,
+            markdown
+        );
+        // Canonical output is a fixed point
+        assert_eq!(writer.write_markdown(&doc).unwrap(), markdown);
+    }
+
+    assert!(parsed > 1_000);
+}
+
+/// Builds a random inline tree, including shapes the parser never produces
+fn random_inlines(rng: &mut Lcg, depth: usize) -> Vec<InlineNode<'static>> {
+    let count = rng.next() % 4;
+    (0..count)
+        .map(|_| match rng.next() % if depth == 0 { 2 } else { 5 } {
+            0 => {
+                let len = 1 + rng.next() % 4;
+                InlineNode::Text(
+                    (0..len)
+                        .map(|_| ALPHABET[rng.next() % ALPHABET.len()])
+                        .collect(),
+                )
+            }
+            1 => InlineNode::LineBreak,
+            2 => InlineNode::Bold(random_inlines(rng, depth - 1)),
+            3 => InlineNode::Italic(random_inlines(rng, depth - 1)),
+            _ => InlineNode::Link {
+                text: random_inlines(rng, depth - 1),
+                url: "u".into(),
+            },
+        })
+        .collect()
+}
+
+/// Drops the spaces at either end of a heading title, as the writer does
+fn trimmed(mut title: Vec<InlineNode<'static>>) -> Vec<InlineNode<'static>> {
+    while let Some(InlineNode::Text(text)) = title.first_mut() {
+        *text = text.trim_start_matches([' ', '\t']).to_string().into();
+        if !text.is_empty() {
+            break;
+        }
+        title.remove(0);
+    }
+    while let Some(InlineNode::Text(text)) = title.last_mut() {
+        *text = text.trim_end_matches([' ', '\t']).to_string().into();
+        if !text.is_empty() {
+            break;
+        }
+        title.pop();
+    }
+    title
+}
+
+#[test]
+fn test_written_trees_parse_back_identically() {
+    let writer = MarkdownWriter::new();
+    let mut rng = Lcg(7);
+    let mut written = 0;
+
+    for _ in 0..100_000 {
+        let paragraph = random_inlines(&mut rng, 3);
+        let title = random_inlines(&mut rng, 3);
+        let build = |title| {
+            Document::from_parts(
+                vec![BlockNode::Paragraph(paragraph.clone())],
+                vec![Section::new(1, title).unwrap()],
+            )
+            .unwrap()
+        };
+        let doc = build(title.clone());
+
+        // Either the tree is rejected, or it reads back exactly (but for
+        // the heading, which is written trimmed)
+        if let Ok(markdown) = writer.write_markdown(&doc) {
+            written += 1;
+            assert_eq!(
+                MarkdownParser::parse(&markdown).as_ref(),
+                Ok(&build(trimmed(title))),
+                "wrote {:?}",
+                markdown
+            );
+        }
+    }
+
+    assert!(written > 1_000);
+}
+
+#[test]
+fn test_format_source_is_idempotent() {
+    let source = "---\ntitle: Notes\n---\n\n\n#   Notes  \nSome *text*\nand more\n\n\n\n## Next\n![x](y.png)";
+    let formatted = format_source(source).unwrap();
+    assert_eq!(
+        formatted,
+        "---\ntitle: Notes\n---\n# Notes\n\nSome *text*\nand more\n\n## Next\n\n![x](y.png)\n"
+    );
+    assert_eq!(format_source(&formatted).unwrap(), formatted);
+}
@@ -0,0 +1,73 @@
+#![cfg(feature = "serde")]
+
+use serde_json::json;
+use wtf::parser::{Document, MarkdownParser};
+
+#[test]
+fn test_json_schema() {
+    let doc =
+        MarkdownParser::parse("Intro\n\n# Title\n**Bold** [*link*](url)\nnext\n\n![alt](img.png)")
+            .unwrap();
+    let value = serde_json::to_value(&doc).unwrap();
+
+    assert_eq!(
+        value,
+        json!({
+            "content": [
+                { "type": "paragraph", "value": [{ "type": "text", "value": "Intro" }] }
+            ],
+            "sections": [{
+                "level": 1,
+                "title": [{ "type": "text", "value": "Title" }],
+                "content": [
+                    { "type": "paragraph", "value": [
+                        { "type": "bold", "value": [{ "type": "text", "value": "Bold" }] },
+                        { "type": "text", "value": " " },
+                        { "type": "link", "value": {
+                            "text": [{ "type": "italic", "value": [{ "type": "text", "value": "link" }] }],
+                            "url": "url"
+                        }},
+                        { "type": "line_break" },
+                        { "type": "text", "value": "next" }
+                    ]},
+                    { "type": "image", "value": { "alt_text": "alt", "url": "img.png" } }
+                ],
+                "subsections": []
+            }]
+        })
+    );
+}
+
+#[test]
+fn test_json_round_trip() {
+    let doc = MarkdownParser::parse("# H1\n## H2\nText *with* **style**\n### H3\n[a](b)").unwrap();
+    let json = serde_json::to_string(&doc).unwrap();
+    let parsed: Document = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, doc);
+}
+
+#[test]
+fn test_json_rejects_invalid_levels() {
+    let section = |level: u8, subsections: serde_json::Value| json!({ "level": level, "title": [], "content": [], "subsections": subsections });
+
+    let level_zero = json!({ "content": [], "sections": [section(0, json!([]))] });
+    let error = serde_json::from_value::<Document>(level_zero).unwrap_err();
+    assert!(
+        error.to_string().contains("Invalid heading level: 0"),
+        "{}",
+        error
+    );
+
+    let shallow_subsection =
+        json!({ "content": [], "sections": [section(3, json!([section(1, json!([]))]))] });
+    let error = serde_json::from_value::<Document>(shallow_subsection).unwrap_err();
+    assert!(
+        error.to_string().contains("deeper than its parent"),
+        "{}",
+        error
+    );
+
+    let valid = json!({ "content": [], "sections": [section(2, json!([section(3, json!([]))]))] });
+    let doc = serde_json::from_value::<Document>(valid).unwrap();
+    assert_eq!(doc.sections()[0].subsections()[0].level(), 3);
+}